@@ -0,0 +1,174 @@
+/* Tipi condivisi per i gruppi cifrati end-to-end. Il server non vede mai chiavi private né testo in
+   chiaro: pubblica le chiavi pubbliche degli utenti (key directory), inoltra le sender key cifrate per
+   ciascun destinatario e salva il contenuto dei messaggi così come arriva.
+
+   Schema (sender keys): ogni membro genera per il gruppo una chiave simmetrica propria (sender key),
+   la cifra con la chiave pubblica di ogni altro membro e la distribuisce tramite il server; i suoi
+   messaggi sono cifrati con quella chiave e Message.content contiene un EncryptedContent codificato.
+   Qui ci sono solo i formati: le primitive crittografiche sono a carico dei client (native o WASM).
+   Chiavi, nonce e ciphertext viaggiano in base64 standard. */
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Algoritmo delle chiavi pubbliche della key directory (scambio di chiavi per le sender key).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum KeyAlgorithm {
+    #[serde(rename = "x25519")]
+    X25519,
+}
+
+impl KeyAlgorithm {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            KeyAlgorithm::X25519 => "x25519",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "x25519" => Some(KeyAlgorithm::X25519),
+            _ => None,
+        }
+    }
+}
+
+/// Algoritmo con cui il contenuto di un messaggio è cifrato con la sender key del mittente.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ContentAlgorithm {
+    #[serde(rename = "xchacha20poly1305")]
+    XChaCha20Poly1305,
+    #[serde(rename = "aes256gcm")]
+    Aes256Gcm,
+}
+
+impl ContentAlgorithm {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ContentAlgorithm::XChaCha20Poly1305 => "xchacha20poly1305",
+            ContentAlgorithm::Aes256Gcm => "aes256gcm",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "xchacha20poly1305" => Some(ContentAlgorithm::XChaCha20Poly1305),
+            "aes256gcm" => Some(ContentAlgorithm::Aes256Gcm),
+            _ => None,
+        }
+    }
+}
+
+/// Chiave pubblica corrente di un utente nella key directory.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PublicKey {
+    pub user_id: String,
+    /// Identificativo scelto dal client, cambia ad ogni rotazione della chiave
+    pub key_id: String,
+    pub alg: KeyAlgorithm,
+    /// Chiave pubblica in base64
+    pub public_key: String,
+    pub created_at: String, // RFC3339 UTC
+}
+
+/// Sender key di un membro, cifrata per un singolo destinatario.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SenderKey {
+    pub group_id: String,
+    /// Membro che cifra i propri messaggi con questa chiave
+    pub sender_id: String,
+    pub sender_key_id: String,
+    pub recipient_id: String,
+    /// key_id della chiave pubblica del destinatario usata per cifrarla
+    pub recipient_key_id: String,
+    /// Sender key cifrata per il destinatario, in base64
+    pub sealed_key: String,
+    pub created_at: String, // RFC3339 UTC
+}
+
+/// Prefisso di Message.content per i messaggi cifrati.
+pub const ENCRYPTED_CONTENT_PREFIX: &str = "e2ee:";
+
+/// Contenuto cifrato di un messaggio, codificato in Message.content come
+/// "e2ee:<alg>:<sender_key_id>:<nonce>:<ciphertext>".
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EncryptedContent {
+    pub alg: ContentAlgorithm,
+    /// Sender key (del mittente) con cui è cifrato il messaggio
+    pub sender_key_id: String,
+    /// Nonce in base64
+    pub nonce: String,
+    /// Ciphertext (con tag di autenticazione) in base64
+    pub ciphertext: String,
+}
+
+/// Errore di decodifica di un EncryptedContent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidEncryptedContent(pub String);
+
+impl fmt::Display for InvalidEncryptedContent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid encrypted content: {}", self.0)
+    }
+}
+
+impl std::error::Error for InvalidEncryptedContent {}
+
+impl EncryptedContent {
+    /// Stringa da mettere in Message.content / SendMessage.content.
+    pub fn encode(&self) -> String {
+        format!(
+            "{}{}:{}:{}:{}",
+            ENCRYPTED_CONTENT_PREFIX,
+            self.alg.as_str(),
+            self.sender_key_id,
+            self.nonce,
+            self.ciphertext
+        )
+    }
+
+    /// Decodifica il contenuto di un messaggio cifrato.
+    pub fn parse(content: &str) -> Result<Self, InvalidEncryptedContent> {
+        let invalid = |reason: &str| InvalidEncryptedContent(reason.to_string());
+        let rest = content.strip_prefix(ENCRYPTED_CONTENT_PREFIX).ok_or_else(|| invalid("missing e2ee: prefix"))?;
+        let parts: Vec<&str> = rest.split(':').collect();
+        let [alg, sender_key_id, nonce, ciphertext] = parts[..] else {
+            return Err(invalid("expected alg:senderKeyId:nonce:ciphertext"));
+        };
+        let alg = ContentAlgorithm::parse(alg).ok_or_else(|| invalid("unknown algorithm"))?;
+        if !is_key_id(sender_key_id) {
+            return Err(invalid("invalid sender key id"));
+        }
+        for (name, value) in [("nonce", nonce), ("ciphertext", ciphertext)] {
+            if !is_base64(value) {
+                return Err(InvalidEncryptedContent(format!("{} is not base64", name)));
+            }
+        }
+        Ok(Self {
+            alg,
+            sender_key_id: sender_key_id.to_string(),
+            nonce: nonce.to_string(),
+            ciphertext: ciphertext.to_string(),
+        })
+    }
+}
+
+/// true se il contenuto di un messaggio è nel formato cifrato (senza validarlo).
+pub fn is_encrypted(content: &str) -> bool {
+    content.starts_with(ENCRYPTED_CONTENT_PREFIX)
+}
+
+/// Identificativi di chiave ammessi: non vuoti, al più 64 caratteri tra alfanumerici, '-' e '_'.
+pub fn is_key_id(s: &str) -> bool {
+    !s.is_empty() && s.len() <= 64 && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Controllo sintattico del base64 standard (con padding).
+pub fn is_base64(s: &str) -> bool {
+    let data = s.trim_end_matches('=');
+    !s.is_empty()
+        && s.len().is_multiple_of(4)
+        && s.len() - data.len() <= 2
+        && data.chars().all(|c| c.is_ascii_alphanumeric() || c == '+' || c == '/')
+}
@@ -6,9 +6,51 @@ use serde::{Deserialize, Serialize};
 pub struct Error {
     /// Codice messaggio
     pub code: String,
-    
+
     pub message: String,
-    
+
     #[serde(skip_serializing_if = "Option::is_none")]
     pub details: Option<serde_json::Value>,
 }
+
+impl Error {
+    /// Crea un errore senza dettagli.
+    pub fn new(code: &str, message: impl Into<String>) -> Self {
+        Self { code: code.to_string(), message: message.into(), details: None }
+    }
+
+    /// Aggiunge `details.requestId`, l'identificativo con cui la richiesta compare nei log del server
+    /// (lo stesso dell'header X-Request-Id). Eventuali altri dettagli oggetto vengono mantenuti.
+    pub fn with_request_id(mut self, request_id: &str) -> Self {
+        let id = serde_json::Value::String(request_id.to_string());
+        match &mut self.details {
+            Some(serde_json::Value::Object(map)) => {
+                map.insert("requestId".to_string(), id);
+            }
+            Some(_) => {}
+            None => self.details = Some(serde_json::json!({ "requestId": id })),
+        }
+        self
+    }
+
+    /// Identificativo della richiesta che ha generato l'errore, se presente.
+    pub fn request_id(&self) -> Option<&str> {
+        self.details.as_ref()?.get("requestId")?.as_str()
+    }
+}
+
+/// Header HTTP con cui client e server si scambiano l'identificativo della richiesta.
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Codici di errore usati dal server nel campo `code`.
+pub mod codes {
+    pub const BAD_REQUEST: &str = "BAD_REQUEST";
+    pub const UNAUTHORIZED: &str = "UNAUTHORIZED";
+    pub const FORBIDDEN: &str = "FORBIDDEN";
+    pub const NOT_FOUND: &str = "NOT_FOUND";
+    /// Troppe richieste: `details.retryAfter` indica dopo quanti secondi riprovare
+    pub const RATE_LIMITED: &str = "RATE_LIMITED";
+    /// Account bloccato temporaneamente dopo troppi login falliti (`details.retryAfter` come sopra)
+    pub const ACCOUNT_LOCKED: &str = "ACCOUNT_LOCKED";
+    pub const INTERNAL: &str = "INTERNAL";
+}
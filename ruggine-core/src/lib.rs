@@ -1,17 +1,34 @@
 //! ruggine-core: tipi condivisi tra client e server (modelli, DTO HTTP, messaggi WS, errori).
 //! Niente I/O o dipendenze non compatibili con WASM.
 
+pub mod e2ee;
 pub mod models;
 pub mod protocol;
 pub mod error;
 pub mod utils;
 
 // Re-export utili per ridurre i percorsi nei crate client/server
+pub use e2ee::{ContentAlgorithm, EncryptedContent, KeyAlgorithm, PublicKey, SenderKey};
 pub use error::Error;
-pub use models::{group::Group, message::Message, user::User};
-pub use protocol::ws::{Ack, AckStatus, SendMessage, WsMessage};
+pub use models::{
+    group::{Group, PinPolicy, RetentionPolicy},
+    message::Message,
+    pin::Pin,
+    reaction::Reaction,
+    scheduled::ScheduledMessage,
+    webhook::{DeliveryStatus, Webhook, WebhookDelivery, WebhookEvent, WebhookPayload},
+    user::User,
+};
+pub use protocol::ws::{
+    encode_since, parse_since, Ack, AckStatus, CaughtUp, HistoryPruned, Mention, MessageExpired, PinChanged, PinCommand, ReactionChanged,
+    ReactionCommand, ResyncRequired, SendMessage, ServerGoingAway, WsMessage, SINCE_PARAM,
+};
 pub use protocol::http::{
-    CreateGroupRequest, CreateGroupResponse, ListGroupsResponse, ListMessagesResponse,
-    LoginRequest, LoginResponse, RegisterRequest, RegisterResponse,
+    BackupResponse, ComponentHealth, CreateGroupRequest, CreateGroupResponse, CreateWebhookRequest, CreateWebhookResponse,
+    DistributeSenderKeyRequest, ExportedMember, GroupExport,
+    HealthResponse, HealthStatus,
+    ListGroupsResponse, ListMentionsResponse, ListMessagesResponse, ListPinsResponse, ListPublicKeysResponse, ListScheduledResponse,
+    ListSenderKeysResponse, ListWebhookDeliveriesResponse, ListWebhooksResponse, LoginRequest, LoginResponse, MuteGroupRequest, AddMemberRequest, PublicKeyResponse, PublishKeyRequest, RegisterRequest,
+    RegisterResponse, SealedSenderKey, UpdateGroupRequest, UpdateGroupResponse, UpdateWebhookRequest, GROUP_EXPORT_VERSION,
 };
-pub use utils::{new_client_msg_id, now_timestamp};
+pub use utils::{new_client_msg_id, now_timestamp, parse_mentions, timestamp_from_unix, unix_from_timestamp};
@@ -7,4 +7,58 @@ pub struct Group {
     pub group_id: String,
     pub name: String,
     pub created_at: String, // RFC3339 UTC
+    /// Chi può fissare messaggi nel gruppo (configurabile dagli admin)
+    #[serde(default)]
+    pub pin_policy: PinPolicy,
+    /// Gruppo cifrato end-to-end: il contenuto dei messaggi è un EncryptedContent (vedi e2ee)
+    #[serde(default)]
+    pub encrypted: bool,
+    /// Limiti di conservazione dei messaggi (configurabili dagli admin)
+    #[serde(default)]
+    pub retention: RetentionPolicy,
+    /// Durata di default in secondi dei messaggi inviati senza ttl; assente = messaggi non effimeri
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message_ttl_secs: Option<u64>,
+}
+
+/// Per quanto tempo e quanti messaggi un gruppo conserva: i messaggi oltre i limiti vengono
+/// eliminati periodicamente dal server. Un limite assente usa il default del server.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RetentionPolicy {
+    /// Età massima dei messaggi in secondi
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_age_secs: Option<u64>,
+    /// Numero massimo di messaggi conservati (i più recenti)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_count: Option<u64>,
+}
+
+/// Chi può fissare/rimuovere messaggi fissati in un gruppo.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PinPolicy {
+    /// Qualsiasi membro
+    #[default]
+    Members,
+    /// Solo gli admin del gruppo
+    Admins,
+}
+
+impl PinPolicy {
+    /// Rappresentazione testuale usata anche per la persistenza.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PinPolicy::Members => "members",
+            PinPolicy::Admins => "admins",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "members" => Some(PinPolicy::Members),
+            "admins" => Some(PinPolicy::Admins),
+            _ => None,
+        }
+    }
 }
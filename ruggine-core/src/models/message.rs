@@ -1,5 +1,7 @@
 use serde::{Deserialize, Serialize};
 
+use crate::models::Reaction;
+
 /// Messaggio persistito dal server e notificato via WS.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -9,4 +11,16 @@ pub struct Message {
     pub sender_id: String,
     pub content: String,
     pub created_at: String, // RFC3339 UTC
+    /// Numero progressivo del messaggio nel suo gruppo (1, 2, ...), assegnato dal server
+    #[serde(default)]
+    pub seq: u64,
+    /// Reazioni aggregate, calcolate per il destinatario del payload
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub reactions: Vec<Reaction>,
+    /// user_id degli utenti menzionati con @username nel contenuto
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub mentions: Vec<String>,
+    /// Messaggio effimero: istante (RFC3339 UTC) in cui il server lo elimina
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<String>,
 }
@@ -1,8 +1,16 @@
 pub mod user;
 pub mod group;
 pub mod message;
+pub mod pin;
+pub mod reaction;
+pub mod scheduled;
+pub mod webhook;
 
 // Re-export per comodità
 pub use user::User;
-pub use group::Group;
+pub use group::{Group, PinPolicy, RetentionPolicy};
 pub use message::Message;
+pub use pin::Pin;
+pub use reaction::Reaction;
+pub use scheduled::ScheduledMessage;
+pub use webhook::{DeliveryStatus, Webhook, WebhookDelivery, WebhookEvent, WebhookPayload};
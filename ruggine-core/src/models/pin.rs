@@ -0,0 +1,13 @@
+use serde::{Deserialize, Serialize};
+
+use crate::models::Message;
+
+/// Messaggio fissato in un gruppo.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Pin {
+    pub message: Message,
+    /// user_id di chi ha fissato il messaggio
+    pub pinned_by: String,
+    pub pinned_at: String, // RFC3339 UTC
+}
@@ -0,0 +1,12 @@
+use serde::{Deserialize, Serialize};
+
+/// Reazioni aggregate per una singola emoji su un messaggio.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Reaction {
+    pub emoji: String,
+    /// Numero di utenti che hanno reagito con questa emoji
+    pub count: u32,
+    /// true se l'utente che riceve il payload è tra quelli che hanno reagito
+    pub reacted_by_me: bool,
+}
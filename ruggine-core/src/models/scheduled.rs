@@ -0,0 +1,17 @@
+use serde::{Deserialize, Serialize};
+
+/// Messaggio programmato: trattenuto dal server e pubblicato all'istante send_at.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScheduledMessage {
+    /// message_id che il messaggio avrà una volta pubblicato
+    pub message_id: String,
+    pub group_id: String,
+    pub sender_id: String,
+    pub content: String,
+    pub send_at: String,    // RFC3339 UTC
+    pub created_at: String, // RFC3339 UTC, quando è stato programmato
+    /// ttl_secs richiesto con sendMessage, applicato alla pubblicazione
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ttl_secs: Option<u64>,
+}
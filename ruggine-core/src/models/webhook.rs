@@ -0,0 +1,138 @@
+use serde::{Deserialize, Serialize};
+
+use crate::models::{Message, User};
+
+/// Webhook in uscita di un gruppo: riceve una POST JSON firmata per ogni evento a cui è iscritto.
+/// Il segreto usato per la firma è mostrato solo alla creazione.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Webhook {
+    pub webhook_id: String,
+    pub group_id: String,
+    pub url: String,
+    pub events: Vec<WebhookEvent>,
+    /// false se disabilitato da un admin o dal server dopo troppi fallimenti consecutivi
+    pub enabled: bool,
+    /// user_id dell'admin che lo ha registrato
+    pub created_by: String,
+    pub created_at: String, // RFC3339 UTC
+    /// Tentativi di consegna falliti dall'ultimo andato a buon fine
+    #[serde(default)]
+    pub consecutive_failures: u32,
+}
+
+/// Evento di gruppo a cui un webhook può iscriversi.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum WebhookEvent {
+    /// Nuovo messaggio pubblicato nel gruppo (anche programmato)
+    #[serde(rename = "message.created")]
+    MessageCreated,
+    /// Utente entrato nel gruppo (alla creazione o aggiunto da un admin)
+    #[serde(rename = "member.joined")]
+    MemberJoined,
+    /// Utente uscito dal gruppo o rimosso da un admin
+    #[serde(rename = "member.left")]
+    MemberLeft,
+}
+
+impl WebhookEvent {
+    /// Rappresentazione testuale usata sul wire e per la persistenza.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WebhookEvent::MessageCreated => "message.created",
+            WebhookEvent::MemberJoined => "member.joined",
+            WebhookEvent::MemberLeft => "member.left",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "message.created" => Some(WebhookEvent::MessageCreated),
+            "member.joined" => Some(WebhookEvent::MemberJoined),
+            "member.left" => Some(WebhookEvent::MemberLeft),
+            _ => None,
+        }
+    }
+}
+
+/// Stato di una consegna di un webhook.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DeliveryStatus {
+    /// In coda per il primo tentativo o per un nuovo tentativo
+    Pending,
+    Delivered,
+    /// Tentativi esauriti, webhook disabilitato o messaggio (o utente) non più disponibile
+    Failed,
+}
+
+impl DeliveryStatus {
+    /// Rappresentazione testuale usata anche per la persistenza.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DeliveryStatus::Pending => "pending",
+            DeliveryStatus::Delivered => "delivered",
+            DeliveryStatus::Failed => "failed",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "pending" => Some(DeliveryStatus::Pending),
+            "delivered" => Some(DeliveryStatus::Delivered),
+            "failed" => Some(DeliveryStatus::Failed),
+            _ => None,
+        }
+    }
+}
+
+/// Voce del registro delle consegne di un webhook.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebhookDelivery {
+    pub delivery_id: String,
+    pub webhook_id: String,
+    pub group_id: String,
+    pub event: WebhookEvent,
+    /// Messaggio a cui si riferisce l'evento, per gli eventi message.*
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message_id: Option<String>,
+    /// Utente a cui si riferisce l'evento, per gli eventi member.*
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_id: Option<String>,
+    pub status: DeliveryStatus,
+    /// Tentativi di consegna eseguiti
+    pub attempts: u32,
+    /// Prossimo tentativo (RFC3339 UTC), solo se status = pending
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_attempt_at: Option<String>,
+    /// Codice HTTP dell'ultima risposta ricevuta
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_status_code: Option<u16>,
+    /// Errore dell'ultimo tentativo non riuscito
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+    pub created_at: String, // RFC3339 UTC
+    /// Quando la consegna è riuscita o è stata abbandonata (RFC3339 UTC)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub finished_at: Option<String>,
+}
+
+/// Corpo JSON delle POST inviate ai webhook; la firma HMAC-SHA256 del corpo, calcolata con il
+/// segreto del webhook, è nell'header X-Ruggine-Signature come "sha256=<hex>".
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebhookPayload {
+    pub delivery_id: String,
+    pub webhook_id: String,
+    pub event: WebhookEvent,
+    pub group_id: String,
+    /// Quando si è verificato l'evento (RFC3339 UTC); non cambia tra un tentativo e l'altro
+    pub created_at: String,
+    /// Presente per gli eventi message.*
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<Message>,
+    /// Utente entrato o uscito, presente per gli eventi member.*
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub member: Option<User>,
+}
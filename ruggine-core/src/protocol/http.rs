@@ -1,6 +1,11 @@
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
-use crate::models::{Group, Message, User};
+use crate::e2ee::{KeyAlgorithm, PublicKey, SenderKey};
+use crate::models::{
+    Group, Message, Pin, PinPolicy, RetentionPolicy, ScheduledMessage, User, Webhook, WebhookDelivery, WebhookEvent,
+};
+use crate::protocol::ws::Mention;
 /*
     http dto for http requests
 */
@@ -48,6 +53,9 @@ pub struct CreateGroupRequest {
     pub name: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub members: Option<Vec<String>>, // userId UUID
+    /// Gruppo cifrato end-to-end; non modificabile dopo la creazione
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub encrypted: bool,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -56,9 +64,214 @@ pub struct CreateGroupResponse {
     pub group: Group,
 }
 
+// Update group settings (admin only); absent fields are left unchanged
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateGroupRequest {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pin_policy: Option<PinPolicy>,
+    /// Sostituisce i limiti di conservazione del gruppo (campi assenti: default del server)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retention: Option<RetentionPolicy>,
+    /// Nuova durata di default dei messaggi in secondi; 0 la rimuove (messaggi non effimeri)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message_ttl_secs: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateGroupResponse {
+    pub group: Group,
+}
+
 // List messages (with before=timestamp & limit handled as query params, not in body)
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ListMessagesResponse {
     pub messages: Vec<Message>,
 }
+
+// Mentions inbox (with before=timestamp & limit as query params, like messages)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListMentionsResponse {
+    pub mentions: Vec<Mention>,
+}
+
+// Pinned messages of a group, most recently pinned first
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListPinsResponse {
+    pub pins: Vec<Pin>,
+}
+
+// Caller's scheduled messages not yet published (GET /api/scheduled), earliest send_at first
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListScheduledResponse {
+    pub scheduled: Vec<ScheduledMessage>,
+}
+
+// Mute a group for the authenticated member (PUT /api/groups/{id}/mute): message, reaction and pin
+// pushes stop, mention events (and reactions/pins on messages mentioning the member) still arrive
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MuteGroupRequest {
+    pub muted: bool,
+}
+
+// Add a user to the group, admins only (POST /api/groups/{id}/members); members leave or are removed
+// with DELETE /api/groups/{id}/members/{user_id}
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AddMemberRequest {
+    pub user_id: String,
+}
+
+// Outgoing webhooks of a group, managed by its admins (POST /api/groups/{id}/webhooks)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateWebhookRequest {
+    /// URL http(s) che riceve le POST
+    pub url: String,
+    /// Eventi a cui iscriversi (assente = tutti)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub events: Option<Vec<WebhookEvent>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateWebhookResponse {
+    pub webhook: Webhook,
+    /// Segreto per verificare la firma delle POST: non viene più mostrato
+    pub secret: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListWebhooksResponse {
+    pub webhooks: Vec<Webhook>,
+}
+
+// PATCH /api/groups/{id}/webhooks/{webhook_id}: re-enabling also resets the failure counter
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateWebhookRequest {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub enabled: Option<bool>,
+}
+
+// Delivery log of a webhook, most recent first (limit as query param)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListWebhookDeliveriesResponse {
+    pub deliveries: Vec<WebhookDelivery>,
+}
+
+// Key directory: publish own public key (PUT /api/keys), replacing the previous one
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PublishKeyRequest {
+    pub key_id: String,
+    pub alg: KeyAlgorithm,
+    pub public_key: String, // base64
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PublicKeyResponse {
+    pub key: PublicKey,
+}
+
+// Public keys of the members of a group that have published one
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListPublicKeysResponse {
+    pub keys: Vec<PublicKey>,
+}
+
+// Distribution of the caller's sender key for a group, sealed for each recipient
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DistributeSenderKeyRequest {
+    pub sender_key_id: String,
+    pub keys: Vec<SealedSenderKey>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SealedSenderKey {
+    pub recipient_id: String,
+    pub recipient_key_id: String,
+    pub sealed_key: String, // base64
+}
+
+// Sender keys of a group addressed to the caller, oldest first
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListSenderKeysResponse {
+    pub sender_keys: Vec<SenderKey>,
+}
+
+// Online database backup (POST /api/admin/backup)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupResponse {
+    /// Nome del file creato nella backup_dir del server
+    pub file: String,
+    pub size_bytes: u64,
+    pub created_at: String, // RFC3339 UTC
+}
+
+/// Versione del formato di GroupExport, da incrementare se cambia in modo incompatibile.
+pub const GROUP_EXPORT_VERSION: u32 = 1;
+
+// Full history of a group for archival (GET /api/groups/:group_id/export)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GroupExport {
+    pub format_version: u32,
+    pub exported_at: String, // RFC3339 UTC
+    pub group: Group,
+    pub members: Vec<ExportedMember>,
+    /// Tutti i messaggi in ordine di seq, con reazioni (reactedByMe sempre false) e menzioni
+    pub messages: Vec<Message>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportedMember {
+    pub user: User,
+    /// "admin" oppure "member"
+    pub role: String,
+}
+
+// Health (GET /health/live e /health/ready)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum HealthStatus {
+    Ok,
+    Fail,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ComponentHealth {
+    pub status: HealthStatus,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HealthResponse {
+    /// Fail se almeno un componente è in errore
+    pub status: HealthStatus,
+    /// Versione del server (CARGO_PKG_VERSION)
+    pub version: String,
+    /// Stato dei singoli controlli (vuoto per la liveness)
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub components: BTreeMap<String, ComponentHealth>,
+}
@@ -2,8 +2,15 @@ pub mod ws;
 pub mod http;
 
 // Re-export comodi
-pub use ws::{Ack, AckStatus, SendMessage, WsMessage};
+pub use ws::{
+    encode_since, parse_since, Ack, AckStatus, CaughtUp, HistoryPruned, Mention, MessageExpired, PinChanged, PinCommand, ReactionChanged,
+    ReactionCommand, ResyncRequired, SendMessage, ServerGoingAway, WsMessage, SINCE_PARAM,
+};
 pub use http::{
     RegisterRequest, RegisterResponse, LoginRequest, LoginResponse, ListGroupsResponse,
-    CreateGroupRequest, CreateGroupResponse, ListMessagesResponse,
+    CreateGroupRequest, CreateGroupResponse, ListMessagesResponse, ListMentionsResponse, ListPinsResponse,
+    UpdateGroupRequest, UpdateGroupResponse, MuteGroupRequest, AddMemberRequest, HealthStatus, ComponentHealth, HealthResponse,
+    PublishKeyRequest, PublicKeyResponse, ListPublicKeysResponse, DistributeSenderKeyRequest, SealedSenderKey,
+    ListSenderKeysResponse, ListScheduledResponse, BackupResponse, GroupExport, ExportedMember, GROUP_EXPORT_VERSION,
+    CreateWebhookRequest, CreateWebhookResponse, ListWebhooksResponse, UpdateWebhookRequest, ListWebhookDeliveriesResponse,
 };
@@ -4,10 +4,22 @@
     Message -> message from server
     Ack -> ack sent from the server in response to a request from client (for example in response to a SendMessage)
     Error -> for errors not related to a command
+    AddReaction / RemoveReaction -> reaction commands from client (answered with an Ack)
+    ReactionChanged -> event from server when the reactions of a message change
+    Mention -> event from server sent only to the users mentioned in a message
+    PinMessage / UnpinMessage -> pin commands from client (answered with an Ack)
+    PinChanged -> event from server when a message of a group is pinned or unpinned
+    ServerGoingAway -> last message from server before it closes the socket because it is shutting down
+    ResyncRequired -> during catch-up, too many messages were missed in a group: the client must refetch its history
+    CaughtUp -> end of catch-up on (re)connect, live events follow
+    SenderKey -> event from server when a member of an encrypted group distributes a sender key to this user
+    HistoryPruned -> event from server when old messages of a group were deleted by its retention policy
+    MessageExpired -> event from server when ephemeral messages of a group reached their expiry and were deleted
 */
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
-use crate::{error::Error, models::Message};
+use crate::{e2ee::SenderKey, error::Error, models::{Message, Pin, Reaction}};
 
 /// Messaggio WS con envelope { type, payload }.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -25,6 +37,45 @@ pub enum WsMessage {
     /// Server → Client: errore fuori banda.
     #[serde(rename = "error")]
     Error(Error),
+    /// Client → Server: aggiunge una reazione ad un messaggio.
+    #[serde(rename = "addReaction")]
+    AddReaction(ReactionCommand),
+    /// Client → Server: rimuove una propria reazione da un messaggio.
+    #[serde(rename = "removeReaction")]
+    RemoveReaction(ReactionCommand),
+    /// Server → Client: le reazioni di un messaggio sono cambiate.
+    #[serde(rename = "reactionChanged")]
+    ReactionChanged(ReactionChanged),
+    /// Server → Client: l'utente è stato menzionato in un messaggio.
+    #[serde(rename = "mention")]
+    Mention(Mention),
+    /// Client → Server: fissa un messaggio nel suo gruppo.
+    #[serde(rename = "pinMessage")]
+    PinMessage(PinCommand),
+    /// Client → Server: rimuove un messaggio dai fissati.
+    #[serde(rename = "unpinMessage")]
+    UnpinMessage(PinCommand),
+    /// Server → Client: un messaggio è stato fissato o rimosso dai fissati.
+    #[serde(rename = "pinChanged")]
+    PinChanged(PinChanged),
+    /// Server → Client: il server si sta arrestando e chiuderà la connessione.
+    #[serde(rename = "serverGoingAway")]
+    ServerGoingAway(ServerGoingAway),
+    /// Server → Client: messaggi persi troppo numerosi per essere reinviati, ricaricare lo storico.
+    #[serde(rename = "resyncRequired")]
+    ResyncRequired(ResyncRequired),
+    /// Server → Client: fine del recupero alla connessione, da qui in poi solo eventi live.
+    #[serde(rename = "caughtUp")]
+    CaughtUp(CaughtUp),
+    /// Server → Client: nuova sender key cifrata per questo utente in un gruppo cifrato.
+    #[serde(rename = "senderKey")]
+    SenderKey(SenderKey),
+    /// Server → Client: i messaggi più vecchi di un gruppo sono stati eliminati dalla retention.
+    #[serde(rename = "historyPruned")]
+    HistoryPruned(HistoryPruned),
+    /// Server → Client: messaggi effimeri di un gruppo scaduti ed eliminati.
+    #[serde(rename = "messageExpired")]
+    MessageExpired(MessageExpired),
 }
 
 /// Payload per l'intento di invio messaggio (C→S).
@@ -36,6 +87,12 @@ pub struct SendMessage {
     pub content: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub sent_at: Option<String>, // RFC3339 (opzionale)
+    /// Messaggio effimero: secondi dopo i quali il server lo elimina (assente = default del gruppo)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ttl_secs: Option<u64>,
+    /// Messaggio programmato: istante (RFC3339) in cui il server lo pubblica (assente = subito)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub send_at: Option<String>,
 }
 
 /// Stato dell'acknowledgement.
@@ -65,7 +122,137 @@ pub struct Ack {
     pub group_id: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub content: Option<String>,
+    /// Presente se il messaggio è stato programmato: non è ancora stato pubblicato
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub send_at: Option<String>,
     /// Presente se status = error
     #[serde(skip_serializing_if = "Option::is_none")]
     pub error: Option<Error>,
 }
+
+/// Payload per aggiungere/rimuovere una reazione (C→S).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReactionCommand {
+    pub client_msg_id: String,
+    pub message_id: String,
+    pub emoji: String,
+}
+
+/// Evento di modifica delle reazioni di un messaggio (S→C).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReactionChanged {
+    pub message_id: String,
+    pub group_id: String,
+    /// Utente che ha aggiunto o rimosso la reazione
+    pub user_id: String,
+    pub emoji: String,
+    /// true se la reazione è stata aggiunta, false se rimossa
+    pub added: bool,
+    /// Stato aggregato aggiornato, calcolato per il destinatario
+    pub reactions: Vec<Reaction>,
+}
+
+/// Notifica di menzione (S→C), usata anche come elemento dell'inbox delle menzioni.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Mention {
+    pub message: Message,
+    /// Nome del gruppo, per mostrare la notifica senza un'altra richiesta
+    pub group_name: String,
+}
+
+/// Payload per fissare/rimuovere un messaggio fissato (C→S).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PinCommand {
+    pub client_msg_id: String,
+    pub message_id: String,
+}
+
+/// Evento di modifica dei messaggi fissati di un gruppo (S→C).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PinChanged {
+    pub group_id: String,
+    pub message_id: String,
+    /// Utente che ha fissato o rimosso il messaggio
+    pub user_id: String,
+    pub pinned: bool,
+    /// Presente se pinned = true, per mostrare il banner senza ricaricare i fissati
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pin: Option<Pin>,
+}
+
+/// Avviso di arresto del server (S→C): il socket verrà chiuso subito dopo.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerGoingAway {
+    pub reason: String,
+    /// Attesa suggerita prima di riconnettersi (i client dovrebbero aggiungere un ritardo casuale)
+    pub reconnect_after_ms: u64,
+}
+
+/// Parametro della query string di /ws con l'ultimo seq visto per ogni gruppo.
+pub const SINCE_PARAM: &str = "since";
+
+/// Codifica l'ultimo seq visto per gruppo come "group_id:seq,group_id:seq".
+pub fn encode_since(last_seen: &BTreeMap<String, u64>) -> String {
+    last_seen
+        .iter()
+        .map(|(group_id, seq)| format!("{}:{}", group_id, seq))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Inverso di encode_since; la stringa vuota indica nessun gruppo.
+pub fn parse_since(s: &str) -> Result<BTreeMap<String, u64>, String> {
+    s.split(',')
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let (group_id, seq) = part.rsplit_once(':').ok_or_else(|| format!("missing seq in {:?}", part))?;
+            if group_id.is_empty() {
+                return Err(format!("missing group id in {:?}", part));
+            }
+            let seq = seq.parse().map_err(|_| format!("invalid seq in {:?}", part))?;
+            Ok((group_id.to_string(), seq))
+        })
+        .collect()
+}
+
+/// Recupero impossibile per un gruppo (S→C): i messaggi dopo last_seen_seq superano il limite
+/// di reinvio, il client deve ricaricare lo storico via HTTP.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResyncRequired {
+    pub group_id: String,
+    pub last_seen_seq: u64,
+    pub latest_seq: u64,
+}
+
+/// Fine del recupero (S→C): seq più recente di ogni gruppo richiesto nel parametro since.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CaughtUp {
+    pub latest_seq: BTreeMap<String, u64>,
+}
+
+/// Messaggi eliminati dalla retention (S→C): tutti i messaggi del gruppo con seq fino a up_to_seq
+/// non esistono più e vanno tolti dalla cronologia mostrata.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryPruned {
+    pub group_id: String,
+    pub up_to_seq: u64,
+    /// Messaggi eliminati in questo passaggio
+    pub deleted: u64,
+}
+
+/// Messaggi effimeri scaduti (S→C): sono stati eliminati e vanno tolti dalla cronologia mostrata.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MessageExpired {
+    pub group_id: String,
+    pub message_ids: Vec<String>,
+}
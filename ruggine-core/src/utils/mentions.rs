@@ -0,0 +1,31 @@
+/// Estrae gli username menzionati con `@username` da un testo, senza duplicati e nell'ordine di comparsa.
+/// Una menzione inizia con '@' a inizio testo o dopo un carattere che non fa parte di un nome
+/// (così "mario@example.com" non è una menzione) e prosegue con lettere, cifre, '_', '-' o '.';
+/// un '.' finale viene scartato perché di solito chiude la frase.
+pub fn parse_mentions(content: &str) -> Vec<String> {
+    let is_name_char = |c: char| c.is_alphanumeric() || matches!(c, '_' | '-' | '.');
+    let mut out: Vec<String> = Vec::new();
+    let mut prev: Option<char> = None;
+    let mut chars = content.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        if c == '@' && !prev.is_some_and(is_name_char) {
+            let start = i + 1;
+            let mut end = start;
+            while let Some(&(j, n)) = chars.peek() {
+                if !is_name_char(n) {
+                    break;
+                }
+                end = j + n.len_utf8();
+                chars.next();
+            }
+            let name = content[start..end].trim_end_matches('.');
+            if !name.is_empty() && !out.iter().any(|m| m == name) {
+                out.push(name.to_string());
+            }
+            prev = content[..end].chars().next_back();
+            continue;
+        }
+        prev = Some(c);
+    }
+    out
+}
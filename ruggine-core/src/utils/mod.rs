@@ -1,5 +1,7 @@
 mod ids;
+mod mentions;
 mod time;
 
 pub use ids::new_client_msg_id;
-pub use time::now_timestamp;
+pub use mentions::parse_mentions;
+pub use time::{now_timestamp, timestamp_from_unix, unix_from_timestamp};
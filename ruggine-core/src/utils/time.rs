@@ -5,3 +5,14 @@ pub fn now_timestamp() -> String {
     let now = OffsetDateTime::now_utc();
     now.format(&Rfc3339).expect("error formatting timestamp")
 }
+
+/// Formatta come RFC3339 UTC un istante espresso in secondi unix (fuori scala: l'epoch).
+pub fn timestamp_from_unix(secs: i64) -> String {
+    let at = OffsetDateTime::from_unix_timestamp(secs).unwrap_or(OffsetDateTime::UNIX_EPOCH);
+    at.format(&Rfc3339).expect("error formatting timestamp")
+}
+
+/// Secondi unix di un timestamp RFC3339; None se non è leggibile.
+pub fn unix_from_timestamp(s: &str) -> Option<i64> {
+    OffsetDateTime::parse(s, &Rfc3339).ok().map(|at| at.unix_timestamp())
+}
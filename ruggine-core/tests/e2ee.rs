@@ -0,0 +1,66 @@
+use ruggine_core::e2ee::{is_base64, is_encrypted, ContentAlgorithm, EncryptedContent, SenderKey};
+use ruggine_core::WsMessage;
+
+fn sample() -> EncryptedContent {
+    EncryptedContent {
+        alg: ContentAlgorithm::XChaCha20Poly1305,
+        sender_key_id: "sk-1".to_string(),
+        nonce: "AAECAwQFBgcICQoLDA0ODxAREhMUFRYX".to_string(),
+        ciphertext: "c2VncmV0bw==".to_string(),
+    }
+}
+
+// Test che verifica la codifica del contenuto cifrato in Message.content e la sua decodifica
+#[test]
+fn encrypted_content_roundtrip() {
+    let content = sample().encode();
+    assert_eq!(content, "e2ee:xchacha20poly1305:sk-1:AAECAwQFBgcICQoLDA0ODxAREhMUFRYX:c2VncmV0bw==");
+    assert!(is_encrypted(&content));
+    assert_eq!(EncryptedContent::parse(&content), Ok(sample()));
+
+    let aes = EncryptedContent { alg: ContentAlgorithm::Aes256Gcm, ..sample() };
+    assert_eq!(EncryptedContent::parse(&aes.encode()), Ok(aes));
+}
+
+// Test che verifica il rifiuto di testo in chiaro, algoritmi sconosciuti e campi malformati
+#[test]
+fn malformed_encrypted_content_is_rejected() {
+    for content in [
+        "ciao a tutti",
+        "e2ee:rot13:sk-1:AAAA:AAAA",
+        "e2ee:aes256gcm:sk-1:AAAA",
+        "e2ee:aes256gcm:sk-1:AAAA:AAAA:AAAA",
+        "e2ee:aes256gcm::AAAA:AAAA",
+        "e2ee:aes256gcm:sk 1:AAAA:AAAA",
+        "e2ee:aes256gcm:sk-1:not base64:AAAA",
+        "e2ee:aes256gcm:sk-1:AAAA:AAA",
+    ] {
+        assert!(EncryptedContent::parse(content).is_err(), "{}", content);
+    }
+    assert!(!is_encrypted("ciao e2ee:"));
+    assert!(is_base64("AA=="));
+    assert!(!is_base64("A==="));
+    assert!(!is_base64(""));
+}
+
+// Test che verifica il formato JSON dell'evento senderKey
+#[test]
+fn ws_sender_key_roundtrip() {
+    let key = SenderKey {
+        group_id: "g1".to_string(),
+        sender_id: "u1".to_string(),
+        sender_key_id: "sk-1".to_string(),
+        recipient_id: "u2".to_string(),
+        recipient_key_id: "k-2".to_string(),
+        sealed_key: "AAAA".to_string(),
+        created_at: "2025-11-02T10:00:00Z".to_string(),
+    };
+    let msg = WsMessage::SenderKey(key);
+    let v: serde_json::Value = serde_json::to_value(&msg).unwrap();
+    assert_eq!(v["type"], "senderKey");
+    assert_eq!(v["payload"]["senderKeyId"], "sk-1");
+    assert_eq!(v["payload"]["recipientKeyId"], "k-2");
+    assert_eq!(v["payload"]["sealedKey"], "AAAA");
+    let back: WsMessage = serde_json::from_value(v).unwrap();
+    assert_eq!(back, msg);
+}
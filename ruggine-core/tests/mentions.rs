@@ -0,0 +1,19 @@
+use ruggine_core::parse_mentions;
+
+// Test che verifica l'estrazione delle menzioni: ordine, duplicati e punteggiatura finale
+#[test]
+fn parses_mentions_in_order_without_duplicates() {
+    assert_eq!(
+        parse_mentions("@alice ciao, hai visto @bob.rossi? @alice."),
+        vec!["alice".to_string(), "bob.rossi".to_string()]
+    );
+    assert_eq!(parse_mentions("(@carla_99) e @dario-x!"), vec!["carla_99".to_string(), "dario-x".to_string()]);
+}
+
+// Test che verifica che indirizzi email e '@' isolati non vengano scambiati per menzioni
+#[test]
+fn ignores_emails_and_lone_at_signs() {
+    assert!(parse_mentions("scrivi a mario@example.com").is_empty());
+    assert!(parse_mentions("@ nessuno @. qui").is_empty());
+    assert!(parse_mentions("").is_empty());
+}
@@ -1,5 +1,6 @@
 use ruggine_core::*;
 use serde_json::{self as json, Value};
+use std::collections::BTreeMap;
 
 fn parse(json_str: &str) -> Value {
     json::from_str(json_str).expect("valid json")
@@ -18,6 +19,8 @@ fn ws_send_message_roundtrip() {
         group_id: "22222222-2222-4222-8222-222222222222".to_string(),
         content: "ciao".to_string(),
         sent_at: Some("2025-11-02T10:20:30Z".to_string()),
+        ttl_secs: None,
+        send_at: None,
     };
     let msg = WsMessage::SendMessage(sm.clone());
     // serializzazione in una stringa json
@@ -50,6 +53,8 @@ fn ws_send_message_omits_optional_sent_at() {
         group_id: "22222222-2222-4222-8222-222222222222".to_string(),
         content: "ciao".to_string(),
         sent_at: None,
+        ttl_secs: None,
+        send_at: None,
     };
     let msg = WsMessage::SendMessage(sm.clone());
 
@@ -78,6 +83,10 @@ fn ws_message_roundtrip() {
         sender_id: "44444444-4444-4444-8444-444444444444".to_string(),
         content: "hello".to_string(),
         created_at: "2025-11-02T10:20:35Z".to_string(),
+        seq: 7,
+        reactions: vec![],
+        mentions: vec![],
+        expires_at: None,
     };
     let msg = WsMessage::Message(m.clone());
 
@@ -109,6 +118,7 @@ fn ws_ack_ok_roundtrip() {
         created_at: Some("2025-11-02T10:20:35Z".to_string()),
         group_id: Some("22222222-2222-4222-8222-222222222222".to_string()),
         content: Some("hello".to_string()),
+        send_at: None,
         error: None,
     };
     let msg = WsMessage::Ack(ack.clone());
@@ -145,6 +155,7 @@ fn ws_ack_error_roundtrip() {
         created_at: None,
         group_id: None,
         content: None,
+        send_at: None,
         error: Some(err.clone()),
     };
     let msg = WsMessage::Ack(ack.clone());
@@ -199,6 +210,10 @@ fn http_create_group_response_roundtrip() {
         group_id: "aaaaaaaa-aaaa-4aaa-8aaa-aaaaaaaaaaaa".to_string(),
         name: "general".to_string(),
         created_at: "2025-11-02T10:00:00Z".to_string(),
+        pin_policy: PinPolicy::Members,
+        encrypted: false,
+        retention: RetentionPolicy::default(),
+        message_ttl_secs: None,
     };
     let resp = CreateGroupResponse { group: group.clone() };
 
@@ -208,6 +223,8 @@ fn http_create_group_response_roundtrip() {
     assert_eq!(v["group"]["groupId"], group.group_id);
     assert_eq!(v["group"]["name"], group.name);
     assert_eq!(v["group"]["createdAt"], group.created_at);
+    assert_eq!(v["group"]["pinPolicy"], "members");
+    assert_eq!(v["group"]["encrypted"], false);
 
     let back: CreateGroupResponse = json::from_str(&s).expect("deserialize");
     assert_eq!(back.group, group);
@@ -227,6 +244,10 @@ fn http_list_messages_response_roundtrip() {
         sender_id: "cccccccc-cccc-4ccc-8ccc-cccccccccccc".to_string(),
         content: "hi".to_string(),
         created_at: "2025-11-02T10:01:00Z".to_string(),
+        seq: 1,
+        reactions: vec![],
+        mentions: vec![],
+        expires_at: None,
     };
     let m2 = Message {
         message_id: "dddddddd-dddd-4ddd-8ddd-dddddddddddd".to_string(),
@@ -234,6 +255,10 @@ fn http_list_messages_response_roundtrip() {
         sender_id: "eeeeeeee-eeee-4eee-8eee-eeeeeeeeeeee".to_string(),
         content: "there".to_string(),
         created_at: "2025-11-02T10:02:00Z".to_string(),
+        seq: 2,
+        reactions: vec![],
+        mentions: vec![],
+        expires_at: None,
     };
     let resp = ListMessagesResponse { messages: vec![m1.clone(), m2.clone()] };
 
@@ -275,3 +300,477 @@ fn ws_error_envelope_roundtrip() {
         _ => panic!("expected Error envelope"),
     }
 }
+
+/*
+    Obiettivo test: verificare che i comandi addReaction/removeReaction e l'evento reactionChanged
+    usino il tag atteso e i campi in camelCase, e che le reazioni di un Message vengano serializzate
+    solo se presenti.
+*/
+#[test]
+fn ws_reactions_roundtrip() {
+    let cmd = ReactionCommand {
+        client_msg_id: "11111111-1111-4111-8111-111111111111".to_string(),
+        message_id: "33333333-3333-4333-8333-333333333333".to_string(),
+        emoji: "👍".to_string(),
+    };
+    let s = json::to_string(&WsMessage::RemoveReaction(cmd.clone())).expect("serialize");
+    let v = parse(&s);
+    assert_eq!(v["type"], "removeReaction");
+    assert_eq!(v["payload"]["messageId"], cmd.message_id);
+    assert_eq!(v["payload"]["emoji"], "👍");
+    match json::from_str(&s).expect("deserialize") {
+        WsMessage::RemoveReaction(back) => assert_eq!(back, cmd),
+        _ => panic!("expected RemoveReaction"),
+    }
+
+    let changed = ReactionChanged {
+        message_id: cmd.message_id.clone(),
+        group_id: "22222222-2222-4222-8222-222222222222".to_string(),
+        user_id: "44444444-4444-4444-8444-444444444444".to_string(),
+        emoji: "👍".to_string(),
+        added: true,
+        reactions: vec![Reaction { emoji: "👍".to_string(), count: 2, reacted_by_me: true }],
+    };
+    let s = json::to_string(&WsMessage::ReactionChanged(changed.clone())).expect("serialize");
+    let v = parse(&s);
+    assert_eq!(v["type"], "reactionChanged");
+    assert_eq!(v["payload"]["reactions"][0]["count"], 2);
+    assert_eq!(v["payload"]["reactions"][0]["reactedByMe"], true);
+    match json::from_str(&s).expect("deserialize") {
+        WsMessage::ReactionChanged(back) => assert_eq!(back, changed),
+        _ => panic!("expected ReactionChanged"),
+    }
+
+    // un messaggio senza reazioni non serializza il campo, e lo ritrova vuoto in deserializzazione
+    let m: Message = json::from_value(json::json!({
+        "messageId": "m", "groupId": "g", "senderId": "s", "content": "c", "createdAt": "2025-11-02T10:00:00Z"
+    })).expect("deserialize");
+    assert!(m.reactions.is_empty());
+    assert!(parse(&json::to_string(&m).unwrap())["reactions"].is_null());
+}
+
+/*
+    Obiettivo test: verificare che l'evento mention contenga il messaggio completo (con le menzioni)
+    e il nome del gruppo, e che sia deserializzabile nello stesso valore.
+*/
+#[test]
+fn ws_mention_roundtrip() {
+    let mention = Mention {
+        message: Message {
+            message_id: "33333333-3333-4333-8333-333333333333".to_string(),
+            group_id: "22222222-2222-4222-8222-222222222222".to_string(),
+            sender_id: "44444444-4444-4444-8444-444444444444".to_string(),
+            content: "@bob guarda qui".to_string(),
+            created_at: "2025-11-02T10:20:35Z".to_string(),
+            seq: 1,
+            reactions: vec![],
+            mentions: vec!["55555555-5555-4555-8555-555555555555".to_string()],
+            expires_at: None,
+        },
+        group_name: "general".to_string(),
+    };
+    let s = json::to_string(&WsMessage::Mention(mention.clone())).expect("serialize");
+    let v = parse(&s);
+
+    assert_eq!(v["type"], "mention");
+    assert_eq!(v["payload"]["groupName"], "general");
+    assert_eq!(v["payload"]["message"]["mentions"][0], mention.message.mentions[0]);
+
+    match json::from_str(&s).expect("deserialize") {
+        WsMessage::Mention(back) => assert_eq!(back, mention),
+        _ => panic!("expected Mention"),
+    }
+}
+
+/*
+    Obiettivo test: verificare l'evento pinChanged (con e senza pin) e che un Group senza pinPolicy
+    (formato precedente) venga deserializzato con la policy di default.
+*/
+#[test]
+fn ws_pin_changed_roundtrip() {
+    let pin = Pin {
+        message: Message {
+            message_id: "33333333-3333-4333-8333-333333333333".to_string(),
+            group_id: "22222222-2222-4222-8222-222222222222".to_string(),
+            sender_id: "44444444-4444-4444-8444-444444444444".to_string(),
+            content: "regole del gruppo".to_string(),
+            created_at: "2025-11-02T10:20:35Z".to_string(),
+            seq: 1,
+            reactions: vec![],
+            mentions: vec![],
+            expires_at: None,
+        },
+        pinned_by: "44444444-4444-4444-8444-444444444444".to_string(),
+        pinned_at: "2025-11-02T10:30:00Z".to_string(),
+    };
+    let changed = PinChanged {
+        group_id: pin.message.group_id.clone(),
+        message_id: pin.message.message_id.clone(),
+        user_id: pin.pinned_by.clone(),
+        pinned: true,
+        pin: Some(pin.clone()),
+    };
+    let s = json::to_string(&WsMessage::PinChanged(changed.clone())).expect("serialize");
+    let v = parse(&s);
+    assert_eq!(v["type"], "pinChanged");
+    assert_eq!(v["payload"]["pin"]["pinnedBy"], pin.pinned_by);
+    assert_eq!(v["payload"]["pin"]["message"]["content"], "regole del gruppo");
+    match json::from_str(&s).expect("deserialize") {
+        WsMessage::PinChanged(back) => assert_eq!(back, changed),
+        _ => panic!("expected PinChanged"),
+    }
+
+    let unpinned = PinChanged { pinned: false, pin: None, ..changed };
+    let v = parse(&json::to_string(&WsMessage::PinChanged(unpinned)).expect("serialize"));
+    assert!(v["payload"]["pin"].is_null());
+
+    let g: Group = json::from_str(r#"{"groupId":"g","name":"n","createdAt":"2025-11-02T10:00:00Z"}"#).expect("deserialize");
+    assert_eq!(g.pin_policy, PinPolicy::Members);
+}
+
+/*
+    Obiettivo test: verificare il formato dell'avviso serverGoingAway con il suggerimento di riconnessione.
+*/
+#[test]
+fn ws_server_going_away_roundtrip() {
+    let notice = ServerGoingAway { reason: "shutdown".to_string(), reconnect_after_ms: 2000 };
+    let s = json::to_string(&WsMessage::ServerGoingAway(notice.clone())).expect("serialize");
+    let v = parse(&s);
+    assert_eq!(v["type"], "serverGoingAway");
+    assert_eq!(v["payload"]["reconnectAfterMs"], 2000);
+    match json::from_str(&s).expect("deserialize") {
+        WsMessage::ServerGoingAway(back) => assert_eq!(back, notice),
+        _ => panic!("expected ServerGoingAway"),
+    }
+}
+
+/*
+    Obiettivo test: verificare che with_request_id aggiunga details.requestId mantenendo
+    gli altri dettagli già presenti, e che request_id lo rilegga.
+*/
+#[test]
+fn error_request_id_in_details() {
+    let plain = Error::new("NOT_FOUND", "group not found").with_request_id("req-1");
+    assert_eq!(plain.request_id(), Some("req-1"));
+    assert_eq!(parse(&json::to_string(&plain).expect("serialize"))["details"]["requestId"], "req-1");
+
+    let limited = Error { details: Some(json::json!({"retryAfter": 3})), ..Error::new("RATE_LIMITED", "slow down") }
+        .with_request_id("req-2");
+    let details = limited.details.clone().expect("details");
+    assert_eq!(details["retryAfter"], 3);
+    assert_eq!(details["requestId"], "req-2");
+    assert_eq!(Error::new("INTERNAL", "boom").request_id(), None);
+}
+
+/*
+    Obiettivo test: verificare i messaggi del recupero alla riconnessione (resyncRequired, caughtUp)
+    e la codifica del parametro since, compreso il rifiuto dei valori malformati.
+*/
+#[test]
+fn ws_catch_up_roundtrip_and_since_param() {
+    let resync = ResyncRequired { group_id: "g1".to_string(), last_seen_seq: 3, latest_seq: 900 };
+    let s = json::to_string(&WsMessage::ResyncRequired(resync.clone())).expect("serialize");
+    let v = parse(&s);
+    assert_eq!(v["type"], "resyncRequired");
+    assert_eq!(v["payload"]["lastSeenSeq"], 3);
+    assert_eq!(json::from_str::<WsMessage>(&s).expect("deserialize"), WsMessage::ResyncRequired(resync));
+
+    let latest: BTreeMap<String, u64> = [("g1".to_string(), 900), ("g2".to_string(), 0)].into();
+    let caught_up = WsMessage::CaughtUp(CaughtUp { latest_seq: latest.clone() });
+    let s = json::to_string(&caught_up).expect("serialize");
+    assert_eq!(parse(&s)["payload"]["latestSeq"]["g1"], 900);
+    assert_eq!(json::from_str::<WsMessage>(&s).expect("deserialize"), caught_up);
+
+    assert_eq!(encode_since(&latest), "g1:900,g2:0");
+    assert_eq!(parse_since(&encode_since(&latest)), Ok(latest));
+    assert_eq!(parse_since(""), Ok(BTreeMap::new()));
+    assert!(parse_since("g1").is_err());
+    assert!(parse_since("g1:x").is_err());
+    assert!(parse_since(":4").is_err());
+
+    // i messaggi senza seq (client o server precedenti) valgono 0
+    let m: Message = json::from_str(
+        r#"{"messageId":"m","groupId":"g","senderId":"u","content":"c","createdAt":"2025-11-02T10:00:00Z"}"#,
+    )
+    .expect("deserialize");
+    assert_eq!(m.seq, 0);
+}
+
+/*
+    Obiettivo test:
+    verificare che GroupExport venga serializzato con i nomi campo in camelCase, la versione del formato,
+    i membri con il loro ruolo e i messaggi completi
+    verificare che lo stesso JSON sia deserializzabile di nuovo nello stesso valore Rust
+*/
+#[test]
+fn http_group_export_roundtrip() {
+    let group = Group {
+        group_id: "aaaaaaaa-aaaa-4aaa-8aaa-aaaaaaaaaaaa".to_string(),
+        name: "general".to_string(),
+        created_at: "2025-11-02T10:00:00Z".to_string(),
+        pin_policy: PinPolicy::Admins,
+        encrypted: false,
+        retention: RetentionPolicy::default(),
+        message_ttl_secs: None,
+    };
+    let alice = User {
+        user_id: "cccccccc-cccc-4ccc-8ccc-cccccccccccc".to_string(),
+        username: "alice".to_string(),
+        created_at: "2025-11-01T09:00:00Z".to_string(),
+    };
+    let message = Message {
+        message_id: "bbbbbbbb-bbbb-4bbb-8bbb-bbbbbbbbbbbb".to_string(),
+        group_id: group.group_id.clone(),
+        sender_id: alice.user_id.clone(),
+        content: "hi @alice".to_string(),
+        created_at: "2025-11-02T10:01:00Z".to_string(),
+        seq: 1,
+        reactions: vec![Reaction { emoji: "👍".to_string(), count: 2, reacted_by_me: false }],
+        mentions: vec![alice.user_id.clone()],
+        expires_at: None,
+    };
+    let export = GroupExport {
+        format_version: GROUP_EXPORT_VERSION,
+        exported_at: "2025-11-03T00:00:00Z".to_string(),
+        group: group.clone(),
+        members: vec![ExportedMember { user: alice.clone(), role: "admin".to_string() }],
+        messages: vec![message.clone()],
+    };
+
+    let s = json::to_string(&export).expect("serialize");
+    let v = parse(&s);
+
+    assert_eq!(v["formatVersion"], 1);
+    assert_eq!(v["exportedAt"], "2025-11-03T00:00:00Z");
+    assert_eq!(v["group"]["pinPolicy"], "admins");
+    assert_eq!(v["members"][0]["user"]["username"], "alice");
+    assert_eq!(v["members"][0]["role"], "admin");
+    assert_eq!(v["messages"][0]["seq"], 1);
+    assert_eq!(v["messages"][0]["reactions"][0]["count"], 2);
+
+    let back: GroupExport = json::from_str(&s).expect("deserialize");
+    assert_eq!(back, export);
+}
+
+/*
+    Obiettivo test: verificare l'evento historyPruned e i limiti di conservazione del gruppo:
+    limiti assenti omessi dal JSON e ricavati di default da un gruppo che non li riporta.
+*/
+#[test]
+fn ws_history_pruned_and_retention_roundtrip() {
+    let pruned = HistoryPruned { group_id: "g1".to_string(), up_to_seq: 42, deleted: 40 };
+    let s = json::to_string(&WsMessage::HistoryPruned(pruned.clone())).expect("serialize");
+    let v = parse(&s);
+    assert_eq!(v["type"], "historyPruned");
+    assert_eq!(v["payload"]["upToSeq"], 42);
+    match json::from_str(&s).expect("deserialize") {
+        WsMessage::HistoryPruned(back) => assert_eq!(back, pruned),
+        _ => panic!("expected HistoryPruned"),
+    }
+
+    let req = UpdateGroupRequest {
+        name: None,
+        pin_policy: None,
+        retention: Some(RetentionPolicy { max_age_secs: None, max_count: Some(1000) }),
+        message_ttl_secs: None,
+    };
+    let v = parse(&json::to_string(&req).expect("serialize"));
+    assert_eq!(v, json::json!({"retention": {"maxCount": 1000}}));
+    let g: Group = json::from_str(r#"{"groupId":"g","name":"n","createdAt":"2025-11-02T10:00:00Z"}"#).expect("deserialize");
+    assert_eq!(g.retention, RetentionPolicy::default());
+}
+
+/*
+    Obiettivo test: verificare i messaggi effimeri sul wire: ttlSecs in sendMessage e expiresAt nel
+    messaggio solo se presenti, l'evento messageExpired e la durata di default del gruppo.
+*/
+#[test]
+fn ws_ephemeral_messages_roundtrip() {
+    let sm = SendMessage {
+        client_msg_id: "c1".to_string(),
+        group_id: "g1".to_string(),
+        content: "presto sparirà".to_string(),
+        sent_at: None,
+        ttl_secs: Some(30),
+        send_at: None,
+    };
+    let v = parse(&json::to_string(&WsMessage::SendMessage(sm.clone())).expect("serialize"));
+    assert_eq!(v["payload"]["ttlSecs"], 30);
+    let back: SendMessage = json::from_str(r#"{"clientMsgId":"c1","groupId":"g1","content":"x"}"#).expect("deserialize");
+    assert_eq!(back.ttl_secs, None);
+
+    let msg = Message {
+        message_id: "m1".to_string(),
+        group_id: "g1".to_string(),
+        sender_id: "u1".to_string(),
+        content: "presto sparirà".to_string(),
+        created_at: "2025-11-02T10:00:00Z".to_string(),
+        seq: 1,
+        reactions: vec![],
+        mentions: vec![],
+        expires_at: Some(timestamp_from_unix(unix_from_timestamp("2025-11-02T10:00:30Z").unwrap())),
+    };
+    let v = parse(&json::to_string(&msg).expect("serialize"));
+    assert_eq!(v["expiresAt"], "2025-11-02T10:00:30Z");
+    let plain = Message { expires_at: None, ..msg };
+    assert!(parse(&json::to_string(&plain).expect("serialize")).get("expiresAt").is_none());
+
+    let expired = MessageExpired { group_id: "g1".to_string(), message_ids: vec!["m1".to_string()] };
+    let s = json::to_string(&WsMessage::MessageExpired(expired.clone())).expect("serialize");
+    let v = parse(&s);
+    assert_eq!(v["type"], "messageExpired");
+    assert_eq!(v["payload"]["messageIds"][0], "m1");
+    match json::from_str(&s).expect("deserialize") {
+        WsMessage::MessageExpired(back) => assert_eq!(back, expired),
+        _ => panic!("expected MessageExpired"),
+    }
+
+    let req = UpdateGroupRequest { name: None, pin_policy: None, retention: None, message_ttl_secs: Some(0) };
+    assert_eq!(parse(&json::to_string(&req).expect("serialize")), json::json!({"messageTtlSecs": 0}));
+}
+
+/*
+    Obiettivo test: verificare i messaggi programmati sul wire: sendAt in sendMessage e nell'ack solo
+    se presenti, e la lista dei messaggi programmati restituita da GET /api/scheduled.
+*/
+#[test]
+fn ws_scheduled_messages_roundtrip() {
+    let sm = SendMessage {
+        client_msg_id: "c1".to_string(),
+        group_id: "g1".to_string(),
+        content: "più tardi".to_string(),
+        sent_at: None,
+        ttl_secs: None,
+        send_at: Some("2025-11-02T18:00:00Z".to_string()),
+    };
+    let v = parse(&json::to_string(&WsMessage::SendMessage(sm.clone())).expect("serialize"));
+    assert_eq!(v["payload"]["sendAt"], "2025-11-02T18:00:00Z");
+    let back: SendMessage = json::from_str(r#"{"clientMsgId":"c1","groupId":"g1","content":"x"}"#).expect("deserialize");
+    assert_eq!(back.send_at, None);
+
+    let ack = Ack {
+        in_reply_to: "c1".to_string(),
+        status: AckStatus::Ok,
+        message_id: Some("m1".to_string()),
+        created_at: None,
+        group_id: Some("g1".to_string()),
+        content: Some("più tardi".to_string()),
+        send_at: sm.send_at.clone(),
+        error: None,
+    };
+    let v = parse(&json::to_string(&WsMessage::Ack(ack.clone())).expect("serialize"));
+    assert_eq!(v["payload"]["sendAt"], "2025-11-02T18:00:00Z");
+    assert!(v["payload"].get("createdAt").is_none());
+    let immediate = Ack { send_at: None, ..ack };
+    assert!(parse(&json::to_string(&immediate).expect("serialize")).get("sendAt").is_none());
+
+    let list = ListScheduledResponse {
+        scheduled: vec![ScheduledMessage {
+            message_id: "m1".to_string(),
+            group_id: "g1".to_string(),
+            sender_id: "u1".to_string(),
+            content: "più tardi".to_string(),
+            send_at: "2025-11-02T18:00:00Z".to_string(),
+            created_at: "2025-11-02T10:00:00Z".to_string(),
+            ttl_secs: None,
+        }],
+    };
+    let s = json::to_string(&list).expect("serialize");
+    let v = parse(&s);
+    assert_eq!(v["scheduled"][0]["sendAt"], "2025-11-02T18:00:00Z");
+    assert!(v["scheduled"][0].get("ttlSecs").is_none());
+    assert_eq!(json::from_str::<ListScheduledResponse>(&s).expect("deserialize"), list);
+}
+
+/*
+    Obiettivo test: Verificare il formato dei webhook: eventi come "message.created" e "member.joined",
+    richiesta di creazione con events opzionale, stato delle consegne in camelCase con i campi assenti
+    omessi, e il corpo inviato ai webhook con il messaggio o con il membro.
+*/
+#[test]
+fn http_webhooks_roundtrip() {
+    let req: CreateWebhookRequest = json::from_str(r#"{"url":"https://example.com/hook"}"#).expect("deserialize");
+    assert_eq!(req.events, None);
+    let events = vec![WebhookEvent::MessageCreated, WebhookEvent::MemberJoined, WebhookEvent::MemberLeft];
+    let req = CreateWebhookRequest { events: Some(events.clone()), ..req };
+    let v = parse(&json::to_string(&req).expect("serialize"));
+    assert_eq!(v["events"], json::json!(["message.created", "member.joined", "member.left"]));
+    for event in events {
+        assert_eq!(WebhookEvent::parse(event.as_str()), Some(event));
+    }
+    assert!(json::from_str::<WebhookEvent>(r#""member.banned""#).is_err());
+
+    let webhook = Webhook {
+        webhook_id: "w1".to_string(),
+        group_id: "g1".to_string(),
+        url: "https://example.com/hook".to_string(),
+        events: vec![WebhookEvent::MessageCreated],
+        enabled: true,
+        created_by: "u1".to_string(),
+        created_at: "2025-11-02T10:00:00Z".to_string(),
+        consecutive_failures: 0,
+    };
+    let created = CreateWebhookResponse { webhook: webhook.clone(), secret: "s3cr3t".to_string() };
+    let v = parse(&json::to_string(&created).expect("serialize"));
+    assert_eq!((v["webhook"]["webhookId"].as_str(), v["secret"].as_str()), (Some("w1"), Some("s3cr3t")));
+    assert_eq!(v["webhook"]["consecutiveFailures"], 0);
+    let back: CreateWebhookResponse = json::from_value(v).expect("deserialize");
+    assert_eq!(back, created);
+
+    let delivery = WebhookDelivery {
+        delivery_id: "d1".to_string(),
+        webhook_id: "w1".to_string(),
+        group_id: "g1".to_string(),
+        event: WebhookEvent::MessageCreated,
+        message_id: Some("m1".to_string()),
+        user_id: None,
+        status: DeliveryStatus::Pending,
+        attempts: 1,
+        next_attempt_at: Some("2025-11-02T10:00:10Z".to_string()),
+        last_status_code: Some(500),
+        last_error: Some("HTTP 500".to_string()),
+        created_at: "2025-11-02T10:00:00Z".to_string(),
+        finished_at: None,
+    };
+    let list = ListWebhookDeliveriesResponse { deliveries: vec![delivery] };
+    let v = parse(&json::to_string(&list).expect("serialize"));
+    assert_eq!(v["deliveries"][0]["status"], "pending");
+    assert_eq!(v["deliveries"][0]["lastStatusCode"], 500);
+    assert!(v["deliveries"][0].get("finishedAt").is_none());
+    assert!(v["deliveries"][0].get("userId").is_none());
+    let back: ListWebhookDeliveriesResponse = json::from_value(v).expect("deserialize");
+    assert_eq!(back, list);
+
+    let payload = WebhookPayload {
+        delivery_id: "d1".to_string(),
+        webhook_id: "w1".to_string(),
+        event: WebhookEvent::MessageCreated,
+        group_id: "g1".to_string(),
+        created_at: "2025-11-02T10:00:00Z".to_string(),
+        message: Some(Message {
+            message_id: "m1".to_string(),
+            group_id: "g1".to_string(),
+            sender_id: "u1".to_string(),
+            content: "ciao".to_string(),
+            created_at: "2025-11-02T10:00:00Z".to_string(),
+            seq: 1,
+            reactions: Vec::new(),
+            mentions: Vec::new(),
+            expires_at: None,
+        }),
+        member: None,
+    };
+    let v = parse(&json::to_string(&payload).expect("serialize"));
+    assert_eq!((v["event"].as_str(), v["message"]["content"].as_str()), (Some("message.created"), Some("ciao")));
+    assert!(v.get("member").is_none());
+    let back: WebhookPayload = json::from_value(v).expect("deserialize");
+    assert_eq!(back, payload);
+
+    let member = User { user_id: "u2".to_string(), username: "bob".to_string(), created_at: "2025-11-01T10:00:00Z".to_string() };
+    let payload = WebhookPayload { event: WebhookEvent::MemberJoined, message: None, member: Some(member), ..payload };
+    let v = parse(&json::to_string(&payload).expect("serialize"));
+    assert_eq!((v["event"].as_str(), v["member"]["userId"].as_str()), (Some("member.joined"), Some("u2")));
+    assert!(v.get("message").is_none());
+    let back: WebhookPayload = json::from_value(v).expect("deserialize");
+    assert_eq!(back, payload);
+}
@@ -0,0 +1,21 @@
+// Con la feature embed-web-client il binario incorpora ruggine-client-web/dist: se manca la build del
+// client include_dir! fallirebbe con un errore poco chiaro, per cui lo controlliamo qui.
+use std::path::Path;
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    if std::env::var_os("CARGO_FEATURE_EMBED_WEB_CLIENT").is_none() {
+        return;
+    }
+    let manifest_dir = std::env::var("CARGO_MANIFEST_DIR").expect("CARGO_MANIFEST_DIR is set by cargo");
+    let dist = Path::new(&manifest_dir).join("../ruggine-client-web/dist");
+    println!("cargo:rerun-if-changed={}", dist.display());
+    if !dist.join("index.html").is_file() {
+        eprintln!(
+            "error: the embed-web-client feature needs the web client build in {}: run `trunk build --release` \
+             in ruggine-client-web first, or build without the feature",
+            dist.display()
+        );
+        std::process::exit(1);
+    }
+}
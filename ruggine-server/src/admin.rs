@@ -0,0 +1,192 @@
+/* Comandi di amministrazione del binario (`ruggine-server create-user ...`): lavorano sullo stesso
+   database_url del server attraverso lo Store, anche mentre il server è in esecuzione. Gli utenti
+   sono indicati per username. Revoche e disabilitazioni hanno effetto subito perché ogni richiesta
+   rilegge il token dal database; le connessioni WebSocket già aperte restano attive fino alla
+   riconnessione. */
+use anyhow::{bail, Context};
+use clap::Subcommand;
+use ruggine_core::{utils::now_timestamp, User};
+use std::io::{BufRead, Write};
+use std::path::PathBuf;
+use uuid::Uuid;
+
+use crate::backup;
+use crate::import::{self, ImportFormat, ImportOptions};
+use crate::db::{hash_password, unix_now};
+use crate::store::Store;
+
+/// Comandi che operano sul database.
+#[derive(Debug, Clone, PartialEq, Eq, Subcommand)]
+pub enum AdminCommand {
+    /// Crea un utente (la password è letta da stdin se --password manca)
+    CreateUser {
+        username: String,
+        #[arg(long)]
+        password: Option<String>,
+    },
+    /// Imposta una nuova password e chiude la sessione dell'utente (password da stdin se --password manca)
+    ResetPassword {
+        username: String,
+        #[arg(long)]
+        password: Option<String>,
+    },
+    /// Elenca gli utenti (username, user_id, creazione, stato, sessione) separati da tabulazioni
+    ListUsers,
+    /// Impedisce il login all'utente e ne chiude la sessione
+    DisableUser { username: String },
+    /// Riabilita un utente disabilitato
+    EnableUser { username: String },
+    /// Elenca i gruppi (group_id, nome, creazione, membri, messaggi) separati da tabulazioni
+    ListGroups,
+    /// Elimina un gruppo con messaggi, membership, inviti e chiavi
+    DeleteGroup { group_id: String },
+    /// Esporta in JSON gruppo, membri e tutti i messaggi (su stdout o nel file indicato)
+    ExportGroup {
+        group_id: String,
+        #[arg(long, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+    /// Importa lo storico da un export Slack (ZIP) o Matrix (JSON); con --dry-run stampa solo il resoconto
+    Import {
+        file: PathBuf,
+        #[arg(long, value_enum)]
+        format: ImportFormat,
+        #[arg(long)]
+        dry_run: bool,
+        /// Utente esistente da aggiungere come admin a ogni gruppo importato
+        #[arg(long, value_name = "USERNAME")]
+        admin: Option<String>,
+    },
+    /// Chiude la sessione dell'utente indicato, o di tutti con --all
+    RevokeSessions {
+        #[arg(required_unless_present = "all")]
+        username: Option<String>,
+        #[arg(long, conflicts_with = "username")]
+        all: bool,
+    },
+    /// Stampa i conteggi di utenti, sessioni, gruppi, messaggi e inviti
+    Stats,
+}
+
+/// Esegue il comando sullo store; `input` fornisce le password non passate come argomento,
+/// l'esito è scritto su `out`.
+pub async fn run(store: &dyn Store, command: AdminCommand, input: impl BufRead, mut out: impl Write) -> anyhow::Result<()> {
+    match command {
+        AdminCommand::CreateUser { username, password } => {
+            let password = password_or_read(password, input)?;
+            let user = create_user(store, &username, &password).await?;
+            writeln!(out, "created user {} ({})", user.username, user.user_id)?;
+        }
+        AdminCommand::ResetPassword { username, password } => {
+            let password = password_or_read(password, input)?;
+            if password.is_empty() {
+                bail!("password must not be empty");
+            }
+            let user = find_user(store, &username).await?;
+            store.set_password_hash(&user.user_id, &hash_password(&password)).await?;
+            store.revoke_sessions(Some(&user.user_id)).await?;
+            writeln!(out, "password of {} reset", user.username)?;
+        }
+        AdminCommand::ListUsers => {
+            writeln!(out, "username\tuser_id\tcreated_at\tstatus\tsession")?;
+            for u in store.list_users(unix_now()).await? {
+                let status = if u.disabled { "disabled" } else { "active" };
+                let session = if u.active_session { "yes" } else { "no" };
+                writeln!(out, "{}\t{}\t{}\t{}\t{}", u.user.username, u.user.user_id, u.user.created_at, status, session)?;
+            }
+        }
+        AdminCommand::DisableUser { username } => {
+            let user = find_user(store, &username).await?;
+            store.set_user_disabled(&user.user_id, true).await?;
+            store.revoke_sessions(Some(&user.user_id)).await?;
+            writeln!(out, "user {} disabled", user.username)?;
+        }
+        AdminCommand::EnableUser { username } => {
+            let user = find_user(store, &username).await?;
+            store.set_user_disabled(&user.user_id, false).await?;
+            writeln!(out, "user {} enabled", user.username)?;
+        }
+        AdminCommand::ListGroups => {
+            writeln!(out, "group_id\tname\tcreated_at\tmembers\tmessages")?;
+            for g in store.list_groups().await? {
+                writeln!(out, "{}\t{}\t{}\t{}\t{}", g.group.group_id, g.group.name, g.group.created_at, g.members, g.messages)?;
+            }
+        }
+        AdminCommand::DeleteGroup { group_id } => {
+            if !store.delete_group(&group_id).await? {
+                bail!("group {:?} not found", group_id);
+            }
+            writeln!(out, "group {} deleted", group_id)?;
+        }
+        AdminCommand::ExportGroup { group_id, output } => {
+            let export = backup::export_group(store, &group_id).await?.with_context(|| format!("group {:?} not found", group_id))?;
+            let json = serde_json::to_string_pretty(&export)?;
+            match output {
+                Some(path) => {
+                    std::fs::write(&path, json).with_context(|| format!("write export {:?}", path))?;
+                    writeln!(out, "exported {} messages of group {} to {}", export.messages.len(), group_id, path.display())?;
+                }
+                None => writeln!(out, "{}", json)?,
+            }
+        }
+        AdminCommand::Import { file, format, dry_run, admin } => {
+            let export = import::read(format, &file)?;
+            let report = import::import(store, &export, &ImportOptions { dry_run, admin }).await?;
+            write!(out, "{}", report)?;
+        }
+        AdminCommand::RevokeSessions { username, .. } => {
+            let revoked = match username {
+                Some(username) => {
+                    let user = find_user(store, &username).await?;
+                    store.revoke_sessions(Some(&user.user_id)).await?
+                }
+                None => store.revoke_sessions(None).await?,
+            };
+            writeln!(out, "revoked {} sessions", revoked)?;
+        }
+        AdminCommand::Stats => {
+            let stats = store.stats(unix_now()).await?;
+            writeln!(out, "users: {}", stats.users)?;
+            writeln!(out, "disabled users: {}", stats.disabled_users)?;
+            writeln!(out, "active sessions: {}", stats.active_sessions)?;
+            writeln!(out, "groups: {}", stats.groups)?;
+            writeln!(out, "messages: {}", stats.messages)?;
+            writeln!(out, "pending invites: {}", stats.pending_invites)?;
+        }
+    }
+    Ok(())
+}
+
+/// Crea un utente senza sessione, con le stesse regole della registrazione HTTP.
+pub async fn create_user(store: &dyn Store, username: &str, password: &str) -> anyhow::Result<User> {
+    if username.trim().is_empty() {
+        bail!("username must not be empty");
+    }
+    if password.is_empty() {
+        bail!("password must not be empty");
+    }
+    if store.username_exists(username).await? {
+        bail!("username {:?} already exists", username);
+    }
+    let user = User { user_id: Uuid::new_v4().to_string(), username: username.to_string(), created_at: now_timestamp() };
+    store.create_user(&user, &hash_password(password)).await.context("create user")?;
+    Ok(user)
+}
+
+async fn find_user(store: &dyn Store, username: &str) -> anyhow::Result<User> {
+    match store.credentials(username).await? {
+        Some(creds) => Ok(creds.user),
+        None => bail!("user {:?} not found", username),
+    }
+}
+
+// La password viene dalla prima riga dell'input (senza il fine riga), per non lasciarla nella
+// lista dei processi o nella history della shell
+fn password_or_read(password: Option<String>, mut input: impl BufRead) -> anyhow::Result<String> {
+    if let Some(password) = password {
+        return Ok(password);
+    }
+    let mut line = String::new();
+    input.read_line(&mut line).context("read password from stdin")?;
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
@@ -0,0 +1,189 @@
+/* Backup, ripristino ed esportazione dei dati. Il backup è online: il backend scrive una copia coerente
+   del database (VACUUM INTO per SQLite) in un file con data e ora nel nome dentro backup_dir, mentre il
+   server continua a servire richieste. Il ripristino sostituisce il file del database (a server fermo)
+   dopo aver verificato integrità e versione dello schema della copia; la copia precedente resta accanto
+   con suffisso .pre-restore-<timestamp>. Gli upload non fanno parte del backup. L'esportazione di un
+   gruppo produce un GroupExport JSON (gruppo, membri e tutti i messaggi) per l'archiviazione. */
+use anyhow::{bail, Context};
+use ruggine_core::{now_timestamp, ExportedMember, GroupExport, Message, GROUP_EXPORT_VERSION};
+use sqlx::sqlite::SqliteConnectOptions;
+use sqlx::{ConnectOptions, Connection};
+use std::path::{Path, PathBuf};
+
+use crate::config::Config;
+use crate::store::{self, Store, StoreResult};
+use crate::{db, is_memory_url, sqlite_path_from, SCHEMA_VERSION};
+
+/// Prefisso ed estensione dei file di backup: ruggine-<AAAAMMGGTHHMMSSZ>.db
+pub const BACKUP_PREFIX: &str = "ruggine-";
+pub const BACKUP_EXTENSION: &str = "db";
+
+// Messaggi letti per volta durante l'esportazione
+const EXPORT_PAGE: i64 = 500;
+
+/// Backup appena creato.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Backup {
+    pub path: PathBuf,
+    pub size_bytes: u64,
+    pub created_at: String,
+}
+
+/// Esito di un ripristino.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Restored {
+    pub database: PathBuf,
+    /// Versione dello schema della copia ripristinata, prima delle migrazioni
+    pub schema_version: i64,
+    /// Dove è stato spostato il database sostituito, se esisteva
+    pub previous: Option<PathBuf>,
+}
+
+// "2026-10-18T12:34:56.789Z" -> "20261018T123456Z"
+fn compact_timestamp(timestamp: &str) -> String {
+    let seconds = timestamp.split(['.', 'Z', '+']).next().unwrap_or(timestamp);
+    format!("{}Z", seconds.replace(['-', ':'], ""))
+}
+
+/// Scrive un backup del database in `dir` (creata se manca) con un nome che non esiste ancora.
+pub async fn create(store: &dyn Store, dir: &Path) -> anyhow::Result<Backup> {
+    std::fs::create_dir_all(dir).with_context(|| format!("create backup dir {:?}", dir))?;
+    let dir = std::path::absolute(dir).with_context(|| format!("resolve backup dir {:?}", dir))?;
+    let created_at = now_timestamp();
+    let stem = format!("{}{}", BACKUP_PREFIX, compact_timestamp(&created_at));
+    // più backup nello stesso secondo ricevono un suffisso numerico
+    let path = (0..)
+        .map(|n| match n {
+            0 => dir.join(format!("{}.{}", stem, BACKUP_EXTENSION)),
+            n => dir.join(format!("{}-{}.{}", stem, n, BACKUP_EXTENSION)),
+        })
+        .find(|p| !p.exists())
+        .expect("unbounded candidates");
+    store.backup_to(&path).await?;
+    let size_bytes = std::fs::metadata(&path).with_context(|| format!("stat backup {:?}", path))?.len();
+    tracing::info!(path = ?path, size_bytes, "database backup written");
+    Ok(Backup { path, size_bytes, created_at })
+}
+
+/// Controlla che `file` sia un database Ruggine integro con uno schema non più recente di quello del
+/// server e ne ritorna la versione. Il file è aperto in sola lettura.
+pub async fn validate_backup(file: &Path) -> anyhow::Result<i64> {
+    if !file.is_file() {
+        bail!("backup {:?} not found", file);
+    }
+    let mut conn = SqliteConnectOptions::new()
+        .filename(file)
+        .read_only(true)
+        .connect()
+        .await
+        .with_context(|| format!("open backup {:?}", file))?;
+    let checked = async {
+        let integrity: String = sqlx::query_scalar("PRAGMA integrity_check").fetch_one(&mut conn).await?;
+        let version: i64 = sqlx::query_scalar("PRAGMA user_version").fetch_one(&mut conn).await?;
+        let tables: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name IN ('users', 'groups', 'messages')",
+        )
+        .fetch_one(&mut conn)
+        .await?;
+        Ok::<_, sqlx::Error>((integrity, version, tables))
+    }
+    .await
+    .with_context(|| format!("read backup {:?} (not a sqlite database?)", file));
+    let _ = conn.close().await;
+    let (integrity, version, tables) = checked?;
+    if integrity != "ok" {
+        bail!("backup {:?} failed the integrity check: {}", file, integrity);
+    }
+    if tables < 3 {
+        bail!("backup {:?} is not a ruggine database", file);
+    }
+    if version > SCHEMA_VERSION {
+        bail!(
+            "backup {:?} has schema version {}, newer than the {} supported by this server",
+            file,
+            version,
+            SCHEMA_VERSION
+        );
+    }
+    Ok(version)
+}
+
+/// Sostituisce il database SQLite di `config` con la copia `file` e lo porta allo schema corrente.
+/// Il server non deve essere in esecuzione sullo stesso database.
+pub async fn restore(config: &Config, file: &Path) -> anyhow::Result<Restored> {
+    if store::is_postgres_url(&config.database_url) {
+        bail!("restore is only supported for sqlite databases: use pg_restore for postgres");
+    }
+    let Some(database) = sqlite_path_from(&config.database_url).filter(|_| !is_memory_url(&config.database_url)) else {
+        bail!("restore needs a database file, not an in-memory database");
+    };
+    let schema_version = validate_backup(file).await?;
+
+    // la copia viene prima scritta accanto al database, poi sostituita con una rename
+    let staging = with_suffix(&database, ".restore-tmp");
+    std::fs::copy(file, &staging).with_context(|| format!("copy backup to {:?}", staging))?;
+    let previous = if database.exists() {
+        let previous = with_suffix(&database, &format!(".pre-restore-{}", compact_timestamp(&now_timestamp())));
+        std::fs::rename(&database, &previous).with_context(|| format!("move {:?} to {:?}", database, previous))?;
+        // file del journal WAL del database sostituito: non valgono per la copia ripristinata
+        for ext in ["-wal", "-shm"] {
+            let journal = with_suffix(&database, ext);
+            if journal.exists() {
+                std::fs::rename(&journal, with_suffix(&previous, ext))
+                    .with_context(|| format!("move {:?}", journal))?;
+            }
+        }
+        Some(previous)
+    } else {
+        None
+    };
+    std::fs::rename(&staging, &database).with_context(|| format!("move {:?} to {:?}", staging, database))?;
+
+    let store = store::open(config).await.context("migrate restored database")?;
+    store.close().await;
+    tracing::info!(database = ?database, from = ?file, schema_version, "database restored");
+    Ok(Restored { database, schema_version, previous })
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+/// Esporta il gruppo con membri e messaggi (in ordine di seq, con reazioni e menzioni);
+/// None se il gruppo non esiste.
+pub async fn export_group(store: &dyn Store, group_id: &str) -> StoreResult<Option<GroupExport>> {
+    let Some(group) = store.find_group(group_id).await? else {
+        return Ok(None);
+    };
+    let mut members = Vec::new();
+    for user_id in store.member_ids(group_id).await? {
+        let (Some(user), Some(role)) = (store.find_user(&user_id).await?, store.member_role(group_id, &user_id).await?)
+        else {
+            continue;
+        };
+        members.push(ExportedMember { user, role: role.as_str().to_string() });
+    }
+    members.sort_by(|a, b| a.user.username.cmp(&b.user.username));
+
+    let mut messages: Vec<Message> = Vec::new();
+    loop {
+        let after = messages.last().map(|m| m.seq).unwrap_or(0);
+        let mut page = store.messages_after(group_id, after, EXPORT_PAGE).await?;
+        let last_page = (page.len() as i64) < EXPORT_PAGE;
+        // nessun destinatario: reactedByMe resta false
+        db::hydrate(store, &mut page, "").await?;
+        messages.append(&mut page);
+        if last_page {
+            break;
+        }
+    }
+    Ok(Some(GroupExport {
+        format_version: GROUP_EXPORT_VERSION,
+        exported_at: now_timestamp(),
+        group,
+        members,
+        messages,
+    }))
+}
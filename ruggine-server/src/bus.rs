@@ -0,0 +1,222 @@
+/* Bus per il fan-out degli eventi WS. Ogni istanza consegna gli eventi solo alle connessioni del
+   proprio Hub: con più istanze dietro un load balancer il bus porta l'evento alle altre, ognuna delle
+   quali lo consegna ai destinatari connessi localmente. Così ogni connessione riceve l'evento una
+   volta sola, qualunque sia l'istanza che lo ha generato. */
+use anyhow::Context;
+use ruggine_core::WsMessage;
+use serde::{Deserialize, Serialize};
+use sqlx::{
+    postgres::{PgListener, PgPoolOptions},
+    PgPool,
+};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use tokio::task::JoinHandle;
+
+use crate::config::Config;
+use crate::hub::Hub;
+
+pub trait Bus: Send + Sync {
+    /// Nome dell'implementazione ("local", "postgres").
+    fn name(&self) -> &'static str;
+    /// Hub con le connessioni di questa istanza.
+    fn hub(&self) -> &Arc<Hub>;
+    /// Consegna l'evento a tutte le connessioni dei destinatari, su qualunque istanza.
+    fn publish(&self, recipients: &[String], event: &WsMessage);
+}
+
+/// Bus di un'istanza singola: consegna direttamente all'Hub locale.
+pub struct LocalBus {
+    hub: Arc<Hub>,
+}
+
+impl LocalBus {
+    pub fn new(hub: Arc<Hub>) -> Self {
+        Self { hub }
+    }
+}
+
+impl Bus for LocalBus {
+    fn name(&self) -> &'static str {
+        "local"
+    }
+
+    fn hub(&self) -> &Arc<Hub> {
+        &self.hub
+    }
+
+    fn publish(&self, recipients: &[String], event: &WsMessage) {
+        self.hub.send_to_users(recipients, event);
+    }
+}
+
+/// Canale NOTIFY usato tra le istanze.
+pub const CHANNEL: &str = "ruggine_fanout";
+
+// Il payload di NOTIFY è limitato a 8000 byte: oltre questa soglia l'evento viene salvato
+// nella tabella fanout_payloads e la notifica porta solo il suo id.
+const MAX_INLINE_PAYLOAD: usize = 7000;
+
+// Gli eventi salvati servono solo finché le altre istanze li hanno letti
+const STORED_PAYLOAD_TTL_SECS: i64 = 300;
+
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Envelope {
+    recipients: Vec<String>,
+    event: WsMessage,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Notice {
+    /// Istanza che ha generato l'evento (e lo ha già consegnato localmente)
+    origin: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    envelope: Option<Envelope>,
+    /// id in fanout_payloads per gli eventi troppo grandi per NOTIFY
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    stored: Option<i64>,
+}
+
+/// Bus tra più istanze basato su LISTEN/NOTIFY di PostgreSQL. L'evento è consegnato subito alle
+/// connessioni locali e poi notificato alle altre istanze, che ignorano le notifiche generate da sé.
+pub struct PostgresBus {
+    hub: Arc<Hub>,
+    outbox: UnboundedSender<Envelope>,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl PostgresBus {
+    pub async fn connect(url: &str, hub: Arc<Hub>) -> anyhow::Result<Self> {
+        // una connessione resta al listener, le altre servono per NOTIFY e per i payload salvati
+        let pool = PgPoolOptions::new().max_connections(3).connect(url).await.context("connect bus to postgres")?;
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS fanout_payloads (
+                id         BIGSERIAL PRIMARY KEY,
+                payload    TEXT NOT NULL,
+                created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+            )",
+        )
+        .execute(&pool)
+        .await
+        .context("create fanout_payloads")?;
+        let mut listener = PgListener::connect_with(&pool).await.context("open bus listener")?;
+        listener.listen(CHANNEL).await.context("listen on bus channel")?;
+
+        let node_id = uuid::Uuid::new_v4().to_string();
+        let (outbox, rx) = unbounded_channel();
+        let tasks = vec![
+            tokio::spawn(receive_loop(listener, pool.clone(), node_id.clone(), hub.clone())),
+            tokio::spawn(publish_loop(rx, pool, node_id)),
+        ];
+        Ok(Self { hub, outbox, tasks })
+    }
+}
+
+impl Bus for PostgresBus {
+    fn name(&self) -> &'static str {
+        "postgres"
+    }
+
+    fn hub(&self) -> &Arc<Hub> {
+        &self.hub
+    }
+
+    fn publish(&self, recipients: &[String], event: &WsMessage) {
+        if recipients.is_empty() {
+            return;
+        }
+        self.hub.send_to_users(recipients, event);
+        let envelope = Envelope { recipients: recipients.to_vec(), event: event.clone() };
+        if self.outbox.send(envelope).is_err() {
+            tracing::warn!("fan-out bus stopped, event delivered only to local connections");
+        }
+    }
+}
+
+impl Drop for PostgresBus {
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+}
+
+// Notifica gli eventi alle altre istanze nell'ordine in cui sono stati pubblicati
+async fn publish_loop(mut rx: UnboundedReceiver<Envelope>, pool: PgPool, node_id: String) {
+    while let Some(envelope) = rx.recv().await {
+        if let Err(e) = notify(&pool, &node_id, envelope).await {
+            tracing::warn!(error = %e, "cannot publish event on the fan-out bus");
+        }
+    }
+}
+
+async fn notify(pool: &PgPool, node_id: &str, envelope: Envelope) -> anyhow::Result<()> {
+    let inline = Notice { origin: node_id.to_string(), envelope: Some(envelope), stored: None };
+    let mut payload = serde_json::to_string(&inline)?;
+    if payload.len() > MAX_INLINE_PAYLOAD {
+        sqlx::query("DELETE FROM fanout_payloads WHERE created_at < now() - make_interval(secs => $1)")
+            .bind(STORED_PAYLOAD_TTL_SECS as f64)
+            .execute(pool)
+            .await?;
+        let id: i64 = sqlx::query_scalar("INSERT INTO fanout_payloads (payload) VALUES ($1) RETURNING id")
+            .bind(serde_json::to_string(&inline.envelope)?)
+            .fetch_one(pool)
+            .await?;
+        payload = serde_json::to_string(&Notice { origin: inline.origin, envelope: None, stored: Some(id) })?;
+    }
+    sqlx::query("SELECT pg_notify($1, $2)").bind(CHANNEL).bind(payload).execute(pool).await?;
+    Ok(())
+}
+
+// Consegna alle connessioni locali gli eventi notificati dalle altre istanze. In caso di errore
+// PgListener si riconnette alla chiamata successiva; le notifiche perse nel frattempo non tornano.
+async fn receive_loop(mut listener: PgListener, pool: PgPool, node_id: String, hub: Arc<Hub>) {
+    loop {
+        let notification = match listener.recv().await {
+            Ok(n) => n,
+            Err(e) => {
+                tracing::warn!(error = %e, "fan-out bus listener error, reconnecting");
+                tokio::time::sleep(RECONNECT_DELAY).await;
+                continue;
+            }
+        };
+        match envelope_of(&pool, &node_id, notification.payload()).await {
+            Ok(Some(envelope)) => hub.send_to_users(&envelope.recipients, &envelope.event),
+            Ok(None) => {}
+            Err(e) => tracing::warn!(error = %e, "invalid fan-out bus notification"),
+        }
+    }
+}
+
+// Evento contenuto nella notifica, None se l'ha generata questa istanza
+async fn envelope_of(pool: &PgPool, node_id: &str, payload: &str) -> anyhow::Result<Option<Envelope>> {
+    let notice: Notice = serde_json::from_str(payload)?;
+    if notice.origin == node_id {
+        return Ok(None);
+    }
+    match (notice.envelope, notice.stored) {
+        (Some(envelope), _) => Ok(Some(envelope)),
+        (None, Some(id)) => {
+            let stored: String = sqlx::query_scalar("SELECT payload FROM fanout_payloads WHERE id = $1")
+                .bind(id)
+                .fetch_one(pool)
+                .await
+                .with_context(|| format!("load stored payload {}", id))?;
+            Ok(Some(serde_json::from_str(&stored)?))
+        }
+        (None, None) => anyhow::bail!("notification without event"),
+    }
+}
+
+/// Bus indicato da bus_url: PostgresBus se impostato, altrimenti LocalBus.
+pub async fn connect(config: &Config, hub: Arc<Hub>) -> anyhow::Result<Arc<dyn Bus>> {
+    Ok(match &config.bus_url {
+        Some(url) => Arc::new(PostgresBus::connect(url, hub).await?),
+        None => Arc::new(LocalBus::new(hub)),
+    })
+}
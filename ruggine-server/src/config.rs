@@ -0,0 +1,640 @@
+/* Configurazione del server a livelli: valori di default, poi file TOML, poi variabili d'ambiente,
+   infine i flag da riga di comando (ogni livello sovrascrive i precedenti). */
+use anyhow::{bail, Context};
+use clap::{Args, ValueEnum};
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+
+use crate::ephemeral::EphemeralConfig;
+use crate::ratelimit::RateLimitConfig;
+use crate::retention::RetentionConfig;
+use crate::scheduled::ScheduledConfig;
+use crate::webhooks::WebhooksConfig;
+
+/// Content-Security-Policy di default: solo risorse della stessa origine, WebSocket verso qualsiasi host
+/// (il client può parlare con un server su un'altra origine) e lo script di bootstrap inline di Trunk.
+pub const DEFAULT_CONTENT_SECURITY_POLICY: &str = "default-src 'self'; \
+     script-src 'self' 'unsafe-inline' 'wasm-unsafe-eval'; style-src 'self' 'unsafe-inline'; \
+     img-src 'self' data: blob:; connect-src 'self' http: https: ws: wss:; object-src 'none'; base-uri 'self'";
+
+/// File letto se non viene indicato nulla con --config o RUGGINE_CONFIG (ignorato se non esiste).
+pub const DEFAULT_CONFIG_FILE: &str = "ruggine.toml";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Indirizzo host:porta su cui ascoltare
+    pub bind_addr: String,
+    /// Certificato TLS (catena PEM); insieme a tls_key_file attiva https:// e wss:// su bind_addr
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tls_cert_file: Option<PathBuf>,
+    /// Chiave privata TLS (PEM: PKCS#8, RSA o EC)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tls_key_file: Option<PathBuf>,
+    /// Intervallo con cui i file di certificato e chiave vengono ricontrollati per il ricaricamento a caldo
+    pub tls_reload_interval_secs: u64,
+    /// Indirizzo host:porta di un listener in chiaro che reindirizza tutte le richieste a https://
+    /// (solo con TLS attivo)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tls_redirect_addr: Option<String>,
+    /// Percorso del file SQLite (anche nella forma sqlite://...) oppure URL postgres://...;
+    /// "sqlite::memory:" per un database in memoria (perso all'arresto)
+    pub database_url: String,
+    /// Numero massimo di connessioni nel pool del database
+    pub db_pool_size: u32,
+    /// Bus per il fan-out degli eventi WS tra più istanze (postgres://..., via LISTEN/NOTIFY);
+    /// se assente gli eventi sono consegnati solo alle connessioni di questo processo
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bus_url: Option<String>,
+    /// File con le chiavi per la cifratura a riposo del contenuto dei messaggi e degli upload
+    /// (righe "<key_id> <base64>", la prima è quella attiva); se assente i dati sono salvati in chiaro
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub encryption_key_file: Option<PathBuf>,
+    /// File con il token (prima riga) richiesto come `Authorization: Bearer` dalle API di
+    /// amministrazione (/api/admin/...); se assente le API di amministrazione sono disattivate
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub admin_token_file: Option<PathBuf>,
+    /// Directory per i file caricati dagli utenti
+    pub upload_dir: PathBuf,
+    /// Directory in cui vengono scritti i backup del database (comando backup e POST /api/admin/backup)
+    pub backup_dir: PathBuf,
+    /// Spazio libero minimo (MiB) sui dischi di database e upload perché /health/ready risponda ok
+    pub min_free_disk_mb: u64,
+    /// Origini ammesse dal browser (es. "http://localhost:8080"); "*" le ammette tutte
+    pub cors_origins: Vec<String>,
+    /// Consente richieste cross-origin con credenziali (cookie, Authorization gestito dal browser);
+    /// non compatibile con l'origine "*"
+    pub cors_allow_credentials: bool,
+    /// Content-Security-Policy inviata con ogni risposta (senza frame-ancestors, vedi sotto);
+    /// il default ammette lo script di avvio inline generato da Trunk e la compilazione del WASM
+    pub content_security_policy: String,
+    /// Origini che possono incorporare le pagine in un frame (direttiva frame-ancestors);
+    /// "'none'" (default) vieta l'incorporamento
+    pub frame_ancestors: Vec<String>,
+    /// Directory con la build di ruggine-client-web (dist/ di `trunk build`) da servire sulla stessa
+    /// origine delle API; se assente il server espone solo le API
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub web_dist_dir: Option<PathBuf>,
+    /// Filtro dei log in sintassi EnvFilter (es. "info" oppure "ruggine_server=debug,sqlx=warn")
+    pub log_level: String,
+    /// Formato dei log: testo leggibile oppure una riga JSON per evento
+    pub log_format: LogFormat,
+    /// Durata dei token di sessione in secondi
+    pub token_lifetime_secs: u64,
+    /// Attesa massima, all'arresto, per la chiusura delle sessioni WS
+    pub shutdown_timeout_secs: u64,
+    /// Messaggi persi per gruppo che una connessione WS può recuperare alla riconnessione;
+    /// oltre questa soglia il client riceve resyncRequired e deve ricaricare la cronologia
+    pub max_replay_messages: u32,
+    pub rate_limits: RateLimitConfig,
+    /// Limiti di conservazione dei messaggi di default e pulizia periodica
+    pub retention: RetentionConfig,
+    /// Durata massima dei messaggi effimeri ed eliminazione periodica di quelli scaduti
+    pub ephemeral: EphemeralConfig,
+    /// Limiti dei messaggi programmati e frequenza con cui vengono pubblicati
+    pub scheduled: ScheduledConfig,
+    /// Tentativi, attese e limiti dei webhook in uscita dei gruppi
+    pub webhooks: WebhooksConfig,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            bind_addr: "127.0.0.1:3000".to_string(),
+            tls_cert_file: None,
+            tls_key_file: None,
+            tls_reload_interval_secs: 10,
+            tls_redirect_addr: None,
+            database_url: "ruggine.db".to_string(),
+            db_pool_size: 10,
+            bus_url: None,
+            encryption_key_file: None,
+            admin_token_file: None,
+            upload_dir: PathBuf::from("uploads"),
+            backup_dir: PathBuf::from("backups"),
+            min_free_disk_mb: 100,
+            cors_origins: Vec::new(),
+            cors_allow_credentials: false,
+            content_security_policy: DEFAULT_CONTENT_SECURITY_POLICY.to_string(),
+            frame_ancestors: vec!["'none'".to_string()],
+            web_dist_dir: None,
+            // le singole query di sqlx sono già coperte dagli span di db.rs: a info sarebbero troppo verbose
+            log_level: "info,sqlx::query=warn".to_string(),
+            log_format: LogFormat::Text,
+            token_lifetime_secs: 30 * 24 * 60 * 60,
+            shutdown_timeout_secs: 10,
+            max_replay_messages: 500,
+            rate_limits: RateLimitConfig::default(),
+            retention: RetentionConfig::default(),
+            ephemeral: EphemeralConfig::default(),
+            scheduled: ScheduledConfig::default(),
+            webhooks: WebhooksConfig::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+impl std::str::FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            other => Err(format!("unknown log format {:?} (expected \"text\" or \"json\")", other)),
+        }
+    }
+}
+
+/// Flag da riga di comando che sovrascrivono file e ambiente.
+#[derive(Debug, Clone, Default, Args)]
+pub struct ConfigArgs {
+    /// File di configurazione TOML (default: $RUGGINE_CONFIG, poi ./ruggine.toml se esiste)
+    #[arg(long, value_name = "FILE")]
+    pub config: Option<PathBuf>,
+    /// Indirizzo host:porta su cui ascoltare
+    #[arg(long, value_name = "ADDR")]
+    pub bind_addr: Option<String>,
+    /// Certificato TLS (PEM)
+    #[arg(long, value_name = "FILE")]
+    pub tls_cert_file: Option<PathBuf>,
+    /// Chiave privata TLS (PEM)
+    #[arg(long, value_name = "FILE")]
+    pub tls_key_file: Option<PathBuf>,
+    /// Intervallo di controllo dei file TLS per il ricaricamento a caldo
+    #[arg(long, value_name = "SECS")]
+    pub tls_reload_interval_secs: Option<u64>,
+    /// Indirizzo del listener in chiaro che reindirizza a https://
+    #[arg(long, value_name = "ADDR")]
+    pub tls_redirect_addr: Option<String>,
+    /// Percorso o URL del database (SQLite, sqlite::memory: o postgres://...)
+    #[arg(long, value_name = "URL")]
+    pub database_url: Option<String>,
+    /// Numero massimo di connessioni nel pool
+    #[arg(long, value_name = "N")]
+    pub db_pool_size: Option<u32>,
+    /// URL del bus di fan-out tra istanze (postgres://...)
+    #[arg(long, value_name = "URL")]
+    pub bus_url: Option<String>,
+    /// File delle chiavi per la cifratura a riposo
+    #[arg(long, value_name = "FILE")]
+    pub encryption_key_file: Option<PathBuf>,
+    /// File con il token delle API di amministrazione
+    #[arg(long, value_name = "FILE")]
+    pub admin_token_file: Option<PathBuf>,
+    /// Directory per i file caricati
+    #[arg(long, value_name = "DIR")]
+    pub upload_dir: Option<PathBuf>,
+    /// Directory dei backup del database
+    #[arg(long, value_name = "DIR")]
+    pub backup_dir: Option<PathBuf>,
+    /// Spazio libero minimo (MiB) richiesto dalla readiness
+    #[arg(long, value_name = "MIB")]
+    pub min_free_disk_mb: Option<u64>,
+    /// Origine CORS ammessa (ripetibile); sostituisce l'elenco del file
+    #[arg(long = "cors-origin", value_name = "ORIGIN")]
+    pub cors_origins: Vec<String>,
+    /// Ammette richieste cross-origin con credenziali
+    #[arg(long)]
+    pub cors_allow_credentials: bool,
+    /// Content-Security-Policy delle risposte
+    #[arg(long, value_name = "POLICY")]
+    pub content_security_policy: Option<String>,
+    /// Origine che può incorporare le pagine in un frame (ripetibile); sostituisce l'elenco del file
+    #[arg(long = "frame-ancestor", value_name = "SOURCE")]
+    pub frame_ancestors: Vec<String>,
+    /// Directory della build del client web da servire
+    #[arg(long, value_name = "DIR")]
+    pub web_dist_dir: Option<PathBuf>,
+    /// Filtro dei log (sintassi EnvFilter)
+    #[arg(long, value_name = "FILTER")]
+    pub log_level: Option<String>,
+    /// Formato dei log
+    #[arg(long, value_enum, value_name = "FORMAT")]
+    pub log_format: Option<LogFormat>,
+    /// Durata dei token di sessione in secondi
+    #[arg(long, value_name = "SECS")]
+    pub token_lifetime_secs: Option<u64>,
+    /// Attesa massima per la chiusura delle sessioni WS all'arresto
+    #[arg(long, value_name = "SECS")]
+    pub shutdown_timeout_secs: Option<u64>,
+    /// Messaggi per gruppo recuperabili alla riconnessione WS
+    #[arg(long, value_name = "N")]
+    pub max_replay_messages: Option<u32>,
+    /// Richieste consecutive di register/login per IP
+    #[arg(long, value_name = "N")]
+    pub auth_burst: Option<u32>,
+    /// Richieste di register/login recuperate al secondo per IP
+    #[arg(long, value_name = "RATE")]
+    pub auth_refill_per_sec: Option<f64>,
+    /// Comandi WS consecutivi per utente
+    #[arg(long, value_name = "N")]
+    pub ws_burst: Option<u32>,
+    /// Comandi WS recuperati al secondo per utente
+    #[arg(long, value_name = "RATE")]
+    pub ws_refill_per_sec: Option<f64>,
+    /// Età massima di default dei messaggi in secondi
+    #[arg(long, value_name = "SECS")]
+    pub retention_max_age_secs: Option<u64>,
+    /// Messaggi conservati di default per gruppo
+    #[arg(long, value_name = "N")]
+    pub retention_max_count: Option<u64>,
+    /// Intervallo tra due passaggi di pulizia dei messaggi scaduti
+    #[arg(long, value_name = "SECS")]
+    pub retention_interval_secs: Option<u64>,
+}
+
+impl Config {
+    /// Costruisce la configurazione effettiva a partire dai flag e dalle variabili d'ambiente del processo.
+    pub fn load(args: &ConfigArgs) -> anyhow::Result<Self> {
+        Self::load_with_env(args, |k| std::env::var(k).ok())
+    }
+
+    /// Come `load`, ma le variabili d'ambiente sono lette tramite `env` (utile nei test).
+    pub fn load_with_env(args: &ConfigArgs, env: impl Fn(&str) -> Option<String>) -> anyhow::Result<Self> {
+        let explicit = args.config.clone().or_else(|| env("RUGGINE_CONFIG").map(PathBuf::from));
+        let mut config = match explicit {
+            Some(path) => Self::from_file(&path)?,
+            None if Path::new(DEFAULT_CONFIG_FILE).exists() => Self::from_file(Path::new(DEFAULT_CONFIG_FILE))?,
+            None => Self::default(),
+        };
+        config.apply_env(&env)?;
+        config.apply_args(args);
+        Ok(config)
+    }
+
+    pub fn from_file(path: &Path) -> anyhow::Result<Self> {
+        let text = std::fs::read_to_string(path).with_context(|| format!("read config file {:?}", path))?;
+        toml::from_str(&text).with_context(|| format!("parse config file {:?}", path))
+    }
+
+    // Variabili RUGGINE_*; DATABASE_URL e BIND_ADDR restano accettate per compatibilità
+    // (le RUGGINE_* hanno la precedenza).
+    fn apply_env(&mut self, env: &impl Fn(&str) -> Option<String>) -> anyhow::Result<()> {
+        fn parsed<T: std::str::FromStr>(name: &str, value: String) -> anyhow::Result<T>
+        where
+            T::Err: std::fmt::Display,
+        {
+            value.trim().parse().map_err(|e| anyhow::anyhow!("invalid {}={:?}: {}", name, value, e))
+        }
+
+        if let Some(v) = env("RUGGINE_BIND_ADDR").or_else(|| env("BIND_ADDR")) {
+            self.bind_addr = v;
+        }
+        if let Some(v) = env("RUGGINE_TLS_CERT_FILE") {
+            self.tls_cert_file = Some(PathBuf::from(v)).filter(|p| !p.as_os_str().is_empty());
+        }
+        if let Some(v) = env("RUGGINE_TLS_KEY_FILE") {
+            self.tls_key_file = Some(PathBuf::from(v)).filter(|p| !p.as_os_str().is_empty());
+        }
+        if let Some(v) = env("RUGGINE_TLS_RELOAD_INTERVAL_SECS") {
+            self.tls_reload_interval_secs = parsed("RUGGINE_TLS_RELOAD_INTERVAL_SECS", v)?;
+        }
+        if let Some(v) = env("RUGGINE_TLS_REDIRECT_ADDR") {
+            self.tls_redirect_addr = Some(v).filter(|v| !v.trim().is_empty());
+        }
+        if let Some(v) = env("RUGGINE_DATABASE_URL").or_else(|| env("DATABASE_URL")) {
+            self.database_url = v;
+        }
+        if let Some(v) = env("RUGGINE_DB_POOL_SIZE") {
+            self.db_pool_size = parsed("RUGGINE_DB_POOL_SIZE", v)?;
+        }
+        if let Some(v) = env("RUGGINE_BUS_URL") {
+            self.bus_url = Some(v).filter(|v| !v.trim().is_empty());
+        }
+        if let Some(v) = env("RUGGINE_ENCRYPTION_KEY_FILE") {
+            self.encryption_key_file = Some(PathBuf::from(v)).filter(|p| !p.as_os_str().is_empty());
+        }
+        if let Some(v) = env("RUGGINE_ADMIN_TOKEN_FILE") {
+            self.admin_token_file = Some(PathBuf::from(v)).filter(|p| !p.as_os_str().is_empty());
+        }
+        if let Some(v) = env("RUGGINE_UPLOAD_DIR") {
+            self.upload_dir = PathBuf::from(v);
+        }
+        if let Some(v) = env("RUGGINE_BACKUP_DIR") {
+            self.backup_dir = PathBuf::from(v);
+        }
+        if let Some(v) = env("RUGGINE_MIN_FREE_DISK_MB") {
+            self.min_free_disk_mb = parsed("RUGGINE_MIN_FREE_DISK_MB", v)?;
+        }
+        if let Some(v) = env("RUGGINE_CORS_ORIGINS") {
+            self.cors_origins = v.split(',').map(str::trim).filter(|o| !o.is_empty()).map(String::from).collect();
+        }
+        if let Some(v) = env("RUGGINE_CORS_ALLOW_CREDENTIALS") {
+            self.cors_allow_credentials = parsed("RUGGINE_CORS_ALLOW_CREDENTIALS", v)?;
+        }
+        if let Some(v) = env("RUGGINE_CONTENT_SECURITY_POLICY") {
+            self.content_security_policy = v;
+        }
+        if let Some(v) = env("RUGGINE_FRAME_ANCESTORS") {
+            self.frame_ancestors = v.split(',').map(str::trim).filter(|o| !o.is_empty()).map(String::from).collect();
+        }
+        if let Some(v) = env("RUGGINE_WEB_DIST_DIR") {
+            self.web_dist_dir = Some(PathBuf::from(v)).filter(|p| !p.as_os_str().is_empty());
+        }
+        if let Some(v) = env("RUGGINE_LOG_LEVEL") {
+            self.log_level = v;
+        }
+        if let Some(v) = env("RUGGINE_LOG_FORMAT") {
+            self.log_format = parsed("RUGGINE_LOG_FORMAT", v)?;
+        }
+        if let Some(v) = env("RUGGINE_TOKEN_LIFETIME_SECS") {
+            self.token_lifetime_secs = parsed("RUGGINE_TOKEN_LIFETIME_SECS", v)?;
+        }
+        if let Some(v) = env("RUGGINE_SHUTDOWN_TIMEOUT_SECS") {
+            self.shutdown_timeout_secs = parsed("RUGGINE_SHUTDOWN_TIMEOUT_SECS", v)?;
+        }
+        if let Some(v) = env("RUGGINE_MAX_REPLAY_MESSAGES") {
+            self.max_replay_messages = parsed("RUGGINE_MAX_REPLAY_MESSAGES", v)?;
+        }
+
+        let limits = &mut self.rate_limits;
+        if let Some(v) = env("RUGGINE_AUTH_BURST") {
+            limits.auth_per_ip.burst = parsed("RUGGINE_AUTH_BURST", v)?;
+        }
+        if let Some(v) = env("RUGGINE_AUTH_REFILL_PER_SEC") {
+            limits.auth_per_ip.refill_per_sec = parsed("RUGGINE_AUTH_REFILL_PER_SEC", v)?;
+        }
+        if let Some(v) = env("RUGGINE_WS_BURST") {
+            limits.ws_per_user.burst = parsed("RUGGINE_WS_BURST", v)?;
+        }
+        if let Some(v) = env("RUGGINE_WS_REFILL_PER_SEC") {
+            limits.ws_per_user.refill_per_sec = parsed("RUGGINE_WS_REFILL_PER_SEC", v)?;
+        }
+        if let Some(v) = env("RUGGINE_LOGIN_MAX_FAILURES") {
+            limits.login_lockout.max_failures = parsed("RUGGINE_LOGIN_MAX_FAILURES", v)?;
+        }
+        if let Some(v) = env("RUGGINE_LOGIN_WINDOW_SECS") {
+            limits.login_lockout.window_secs = parsed("RUGGINE_LOGIN_WINDOW_SECS", v)?;
+        }
+        if let Some(v) = env("RUGGINE_LOGIN_LOCK_SECS") {
+            limits.login_lockout.lock_secs = parsed("RUGGINE_LOGIN_LOCK_SECS", v)?;
+        }
+
+        let retention = &mut self.retention;
+        // vuote = nessun limite di default
+        if let Some(v) = env("RUGGINE_RETENTION_MAX_AGE_SECS") {
+            retention.max_age_secs =
+                if v.trim().is_empty() { None } else { Some(parsed("RUGGINE_RETENTION_MAX_AGE_SECS", v)?) };
+        }
+        if let Some(v) = env("RUGGINE_RETENTION_MAX_COUNT") {
+            retention.max_count =
+                if v.trim().is_empty() { None } else { Some(parsed("RUGGINE_RETENTION_MAX_COUNT", v)?) };
+        }
+        if let Some(v) = env("RUGGINE_RETENTION_INTERVAL_SECS") {
+            retention.interval_secs = parsed("RUGGINE_RETENTION_INTERVAL_SECS", v)?;
+        }
+        if let Some(v) = env("RUGGINE_RETENTION_BATCH_SIZE") {
+            retention.batch_size = parsed("RUGGINE_RETENTION_BATCH_SIZE", v)?;
+        }
+
+        let ephemeral = &mut self.ephemeral;
+        if let Some(v) = env("RUGGINE_EPHEMERAL_MAX_TTL_SECS") {
+            ephemeral.max_ttl_secs = parsed("RUGGINE_EPHEMERAL_MAX_TTL_SECS", v)?;
+        }
+        if let Some(v) = env("RUGGINE_EPHEMERAL_INTERVAL_SECS") {
+            ephemeral.interval_secs = parsed("RUGGINE_EPHEMERAL_INTERVAL_SECS", v)?;
+        }
+        if let Some(v) = env("RUGGINE_EPHEMERAL_BATCH_SIZE") {
+            ephemeral.batch_size = parsed("RUGGINE_EPHEMERAL_BATCH_SIZE", v)?;
+        }
+
+        let scheduled = &mut self.scheduled;
+        if let Some(v) = env("RUGGINE_SCHEDULED_MAX_DELAY_SECS") {
+            scheduled.max_delay_secs = parsed("RUGGINE_SCHEDULED_MAX_DELAY_SECS", v)?;
+        }
+        if let Some(v) = env("RUGGINE_SCHEDULED_MAX_PENDING_PER_USER") {
+            scheduled.max_pending_per_user = parsed("RUGGINE_SCHEDULED_MAX_PENDING_PER_USER", v)?;
+        }
+        if let Some(v) = env("RUGGINE_SCHEDULED_INTERVAL_SECS") {
+            scheduled.interval_secs = parsed("RUGGINE_SCHEDULED_INTERVAL_SECS", v)?;
+        }
+        if let Some(v) = env("RUGGINE_SCHEDULED_BATCH_SIZE") {
+            scheduled.batch_size = parsed("RUGGINE_SCHEDULED_BATCH_SIZE", v)?;
+        }
+
+        let webhooks = &mut self.webhooks;
+        if let Some(v) = env("RUGGINE_WEBHOOKS_TIMEOUT_SECS") {
+            webhooks.timeout_secs = parsed("RUGGINE_WEBHOOKS_TIMEOUT_SECS", v)?;
+        }
+        if let Some(v) = env("RUGGINE_WEBHOOKS_MAX_ATTEMPTS") {
+            webhooks.max_attempts = parsed("RUGGINE_WEBHOOKS_MAX_ATTEMPTS", v)?;
+        }
+        if let Some(v) = env("RUGGINE_WEBHOOKS_RETRY_BASE_SECS") {
+            webhooks.retry_base_secs = parsed("RUGGINE_WEBHOOKS_RETRY_BASE_SECS", v)?;
+        }
+        if let Some(v) = env("RUGGINE_WEBHOOKS_RETRY_MAX_SECS") {
+            webhooks.retry_max_secs = parsed("RUGGINE_WEBHOOKS_RETRY_MAX_SECS", v)?;
+        }
+        if let Some(v) = env("RUGGINE_WEBHOOKS_DISABLE_AFTER_FAILURES") {
+            webhooks.disable_after_failures = parsed("RUGGINE_WEBHOOKS_DISABLE_AFTER_FAILURES", v)?;
+        }
+        if let Some(v) = env("RUGGINE_WEBHOOKS_MAX_PER_GROUP") {
+            webhooks.max_per_group = parsed("RUGGINE_WEBHOOKS_MAX_PER_GROUP", v)?;
+        }
+        if let Some(v) = env("RUGGINE_WEBHOOKS_INTERVAL_SECS") {
+            webhooks.interval_secs = parsed("RUGGINE_WEBHOOKS_INTERVAL_SECS", v)?;
+        }
+        if let Some(v) = env("RUGGINE_WEBHOOKS_BATCH_SIZE") {
+            webhooks.batch_size = parsed("RUGGINE_WEBHOOKS_BATCH_SIZE", v)?;
+        }
+        if let Some(v) = env("RUGGINE_WEBHOOKS_LOG_RETENTION_SECS") {
+            webhooks.log_retention_secs = parsed("RUGGINE_WEBHOOKS_LOG_RETENTION_SECS", v)?;
+        }
+        if let Some(v) = env("RUGGINE_WEBHOOKS_ALLOW_PRIVATE_TARGETS") {
+            webhooks.allow_private_targets = parsed("RUGGINE_WEBHOOKS_ALLOW_PRIVATE_TARGETS", v)?;
+        }
+        Ok(())
+    }
+
+    fn apply_args(&mut self, args: &ConfigArgs) {
+        if let Some(v) = &args.bind_addr {
+            self.bind_addr = v.clone();
+        }
+        if let Some(v) = &args.tls_cert_file {
+            self.tls_cert_file = Some(v.clone());
+        }
+        if let Some(v) = &args.tls_key_file {
+            self.tls_key_file = Some(v.clone());
+        }
+        if let Some(v) = args.tls_reload_interval_secs {
+            self.tls_reload_interval_secs = v;
+        }
+        if let Some(v) = &args.tls_redirect_addr {
+            self.tls_redirect_addr = Some(v.clone());
+        }
+        if let Some(v) = &args.database_url {
+            self.database_url = v.clone();
+        }
+        if let Some(v) = args.db_pool_size {
+            self.db_pool_size = v;
+        }
+        if let Some(v) = &args.bus_url {
+            self.bus_url = Some(v.clone());
+        }
+        if let Some(v) = &args.encryption_key_file {
+            self.encryption_key_file = Some(v.clone());
+        }
+        if let Some(v) = &args.admin_token_file {
+            self.admin_token_file = Some(v.clone());
+        }
+        if let Some(v) = &args.upload_dir {
+            self.upload_dir = v.clone();
+        }
+        if let Some(v) = &args.backup_dir {
+            self.backup_dir = v.clone();
+        }
+        if let Some(v) = args.min_free_disk_mb {
+            self.min_free_disk_mb = v;
+        }
+        if !args.cors_origins.is_empty() {
+            self.cors_origins = args.cors_origins.clone();
+        }
+        if args.cors_allow_credentials {
+            self.cors_allow_credentials = true;
+        }
+        if let Some(v) = &args.content_security_policy {
+            self.content_security_policy = v.clone();
+        }
+        if !args.frame_ancestors.is_empty() {
+            self.frame_ancestors = args.frame_ancestors.clone();
+        }
+        if let Some(v) = &args.web_dist_dir {
+            self.web_dist_dir = Some(v.clone());
+        }
+        if let Some(v) = &args.log_level {
+            self.log_level = v.clone();
+        }
+        if let Some(v) = args.log_format {
+            self.log_format = v;
+        }
+        if let Some(v) = args.token_lifetime_secs {
+            self.token_lifetime_secs = v;
+        }
+        if let Some(v) = args.shutdown_timeout_secs {
+            self.shutdown_timeout_secs = v;
+        }
+        if let Some(v) = args.max_replay_messages {
+            self.max_replay_messages = v;
+        }
+        if let Some(v) = args.auth_burst {
+            self.rate_limits.auth_per_ip.burst = v;
+        }
+        if let Some(v) = args.auth_refill_per_sec {
+            self.rate_limits.auth_per_ip.refill_per_sec = v;
+        }
+        if let Some(v) = args.ws_burst {
+            self.rate_limits.ws_per_user.burst = v;
+        }
+        if let Some(v) = args.ws_refill_per_sec {
+            self.rate_limits.ws_per_user.refill_per_sec = v;
+        }
+        if let Some(v) = args.retention_max_age_secs {
+            self.retention.max_age_secs = Some(v);
+        }
+        if let Some(v) = args.retention_max_count {
+            self.retention.max_count = Some(v);
+        }
+        if let Some(v) = args.retention_interval_secs {
+            self.retention.interval_secs = v;
+        }
+    }
+
+    /// Verifica la configurazione; in caso di problemi l'errore li elenca tutti, uno per riga.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        let mut problems = Vec::new();
+        if self.bind_addr.parse::<SocketAddr>().is_err() {
+            problems.push(format!("bind_addr {:?} is not a valid host:port address", self.bind_addr));
+        }
+        match (&self.tls_cert_file, &self.tls_key_file) {
+            (Some(_), None) => problems.push("tls_cert_file requires tls_key_file".to_string()),
+            (None, Some(_)) => problems.push("tls_key_file requires tls_cert_file".to_string()),
+            _ => {}
+        }
+        if self.tls_reload_interval_secs == 0 {
+            problems.push("tls_reload_interval_secs must be positive".to_string());
+        }
+        if let Some(addr) = &self.tls_redirect_addr {
+            if !self.tls_enabled() {
+                problems.push("tls_redirect_addr requires tls_cert_file and tls_key_file".to_string());
+            }
+            if addr.parse::<SocketAddr>().is_err() {
+                problems.push(format!("tls_redirect_addr {:?} is not a valid host:port address", addr));
+            }
+        }
+        if self.database_url.trim().is_empty() {
+            problems.push("database_url must not be empty".to_string());
+        }
+        if self.db_pool_size == 0 {
+            problems.push("db_pool_size must be at least 1".to_string());
+        }
+        if let Some(url) = self.bus_url.as_ref().filter(|u| !crate::store::is_postgres_url(u)) {
+            problems.push(format!("bus_url {:?} must be a postgres:// or postgresql:// url", url));
+        }
+        if let Some(path) = self.admin_token_file.as_ref().filter(|p| !p.is_file()) {
+            problems.push(format!("admin_token_file {:?} is not a readable file", path));
+        }
+        if self.upload_dir.as_os_str().is_empty() {
+            problems.push("upload_dir must not be empty".to_string());
+        }
+        if self.backup_dir.as_os_str().is_empty() {
+            problems.push("backup_dir must not be empty".to_string());
+        }
+        for origin in &self.cors_origins {
+            if origin != "*" && !(origin.starts_with("http://") || origin.starts_with("https://")) {
+                problems.push(format!("cors origin {:?} must be \"*\" or start with http:// or https://", origin));
+            }
+        }
+        if self.cors_allow_credentials && self.cors_origins.iter().any(|o| o == "*") {
+            problems.push("cors_allow_credentials cannot be used with the \"*\" cors origin".to_string());
+        }
+        if axum::http::HeaderValue::from_str(&self.content_security_policy).is_err() {
+            problems.push("content_security_policy must be a single line of visible ASCII".to_string());
+        }
+        if self.content_security_policy.contains("frame-ancestors") {
+            problems.push("content_security_policy must not contain frame-ancestors (use frame_ancestors)".to_string());
+        }
+        for source in &self.frame_ancestors {
+            if source.is_empty() || source.contains([';', ',']) || source.contains(char::is_whitespace) {
+                problems.push(format!("frame ancestor {:?} must be a single CSP source", source));
+            }
+        }
+        if let Some(dir) = self.web_dist_dir.as_ref().filter(|d| !d.join(crate::web::INDEX_FILE).is_file()) {
+            problems.push(format!("web_dist_dir {:?} does not contain {}", dir, crate::web::INDEX_FILE));
+        }
+        if let Err(e) = tracing_subscriber::EnvFilter::try_new(&self.log_level) {
+            problems.push(format!("log_level {:?} is not a valid filter: {}", self.log_level, e));
+        }
+        if self.token_lifetime_secs == 0 {
+            problems.push("token_lifetime_secs must be positive".to_string());
+        }
+        problems.extend(self.rate_limits.problems());
+        problems.extend(self.retention.problems());
+        problems.extend(self.ephemeral.problems());
+        problems.extend(self.scheduled.problems());
+        problems.extend(self.webhooks.problems());
+
+        if !problems.is_empty() {
+            bail!("invalid configuration:\n  - {}", problems.join("\n  - "));
+        }
+        Ok(())
+    }
+
+    /// true se il server deve servire https:// e wss:// (certificato e chiave configurati).
+    pub fn tls_enabled(&self) -> bool {
+        self.tls_cert_file.is_some() && self.tls_key_file.is_some()
+    }
+
+    /// Configurazione effettiva in formato TOML (per --print-config).
+    pub fn to_toml(&self) -> anyhow::Result<String> {
+        toml::to_string_pretty(self).context("serialize config")
+    }
+}
@@ -1,26 +1,88 @@
-use axum::{extract::Extension, http::StatusCode, Json};
-use ruggine_core::{protocol::http::{RegisterRequest, RegisterResponse, LoginRequest, LoginResponse}, models::User, utils::now_timestamp};
+use axum::{
+    extract::{ConnectInfo, Extension, Path, Query},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use ruggine_core::{
+    e2ee::{is_base64, is_key_id, PublicKey, SenderKey},
+    error::codes,
+    protocol::http::{
+        AddMemberRequest, BackupResponse, CreateGroupRequest, CreateGroupResponse, CreateWebhookRequest, CreateWebhookResponse, GroupExport,
+        DistributeSenderKeyRequest, ListGroupsResponse, ListMentionsResponse, ListMessagesResponse, ListPinsResponse,
+        ListPublicKeysResponse, ListScheduledResponse, ListSenderKeysResponse, ListWebhookDeliveriesResponse,
+        ListWebhooksResponse, LoginRequest, LoginResponse, MuteGroupRequest, PublicKeyResponse, PublishKeyRequest, RegisterRequest,
+        RegisterResponse, UpdateGroupRequest, UpdateGroupResponse, UpdateWebhookRequest,
+    },
+    models::{Group, PinPolicy, RetentionPolicy, User, Webhook, WebhookEvent},
+    utils::now_timestamp,
+    WsMessage,
+};
+use serde::Deserialize;
 use sha2::{Digest, Sha256};
-use sqlx::Row;
+use std::net::SocketAddr;
 use std::sync::Arc;
 use uuid::Uuid;
 
-use crate::AppState;
+use crate::metrics::LoginOutcome;
+use crate::store::{Role, WebhookRecord};
+use crate::{backup, db, mentions, pins, ratelimit, retention, telemetry, webhooks, AppState};
+
+/// Estrae il token dall'header `Authorization: Bearer <token>` e ritorna l'user_id corrispondente.
+pub async fn authenticate(state: &AppState, headers: &HeaderMap) -> Result<String, (StatusCode, String)> {
+    let token = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .ok_or((StatusCode::UNAUTHORIZED, "missing bearer token".to_string()))?;
+    let user_id = db::user_id_for_token(state.store.as_ref(), token)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("db error: {}", e)))?
+        .ok_or((StatusCode::UNAUTHORIZED, "invalid token".to_string()))?;
+    telemetry::record_user(&user_id);
+    Ok(user_id)
+}
+
+/// Verifica il token delle API di amministrazione (`Authorization: Bearer`, confrontato con la prima riga
+/// di admin_token_file, riletto ad ogni richiesta così da poterlo cambiare senza riavvio).
+pub async fn authenticate_admin(state: &AppState, headers: &HeaderMap) -> Result<(), (StatusCode, String)> {
+    let Some(path) = &state.config.admin_token_file else {
+        return Err((StatusCode::FORBIDDEN, "admin api disabled (admin_token_file not configured)".to_string()));
+    };
+    let token = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .ok_or((StatusCode::UNAUTHORIZED, "missing bearer token".to_string()))?;
+    let expected = tokio::fs::read_to_string(path)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("read admin token: {}", e)))?;
+    let expected = expected.lines().next().unwrap_or_default().trim();
+    // confronto fra digest: il tempo non dipende dal prefisso in comune
+    if expected.is_empty() || Sha256::digest(token.as_bytes()) != Sha256::digest(expected.as_bytes()) {
+        return Err((StatusCode::UNAUTHORIZED, "invalid admin token".to_string()));
+    }
+    Ok(())
+}
+
+// Scadenza (secondi unix) di un token emesso adesso, secondo token_lifetime_secs
+fn token_expiry(state: &AppState) -> i64 {
+    db::unix_now().saturating_add(i64::try_from(state.config.token_lifetime_secs).unwrap_or(i64::MAX))
+}
 
 /// Handler per POST /api/register
 pub async fn register(
     Extension(state): Extension<Arc<AppState>>,
     Json(req): Json<RegisterRequest>,
 ) -> Result<(StatusCode, Json<RegisterResponse>), (StatusCode, String)> {
-    // controllo se lo username esiste già:
-    // query_scalar Makes a SQL query that is mapped to a single concrete type
-    let existing: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM users WHERE username = ?")
-        .bind(&req.username)    // bind imposta il parametro della query
-        .fetch_one(&state.pool)     // fetch_one esegue la query usando il pool asincrono e si aspetta che la query ritorni esattamente una riga
+    // controllo se lo username esiste già
+    let existing = state
+        .store
+        .username_exists(&req.username)
         .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("db error: {}", e)))?; // se la query fallisce mappo l'errore in (StatusCode, string)
-    if existing > 0 {
-        /* Se il risultato è maggiore di 0 allora lo username esiste già */
+    if existing {
+        /* lo username esiste già */
         return Err((StatusCode::CONFLICT, "username already exists".to_string()));
     }
 
@@ -28,71 +90,756 @@ pub async fn register(
     let user_id = Uuid::new_v4().to_string();
     let token = Uuid::new_v4().to_string();
     // hash semplice della password
-    let mut hasher = Sha256::new();
-    hasher.update(req.password.as_bytes());
-    let password_hash = format!("{:x}", hasher.finalize());
+    let password_hash = db::hash_password(&req.password);
     let created_at = now_timestamp();
 
-    // inserisci
-    sqlx::query("INSERT INTO users (user_id, username, password_hash, token, created_at) VALUES (?, ?, ?, ?, ?)")
-        .bind(&user_id)
-        .bind(&req.username)
-        .bind(&password_hash)
-        .bind(&token)
-        .bind(&created_at)
-        /* execute esegue la query, non ritorna righe ma il risultato dell'esecuzione della query */
-        .execute(&state.pool)
-        .await
-        /* se l'INSERT fallisce map_err converte l'errore in 500 ed esce dall'handler con l'operatore ? */
+    // inserisci l'utente e il suo primo token
+    let user = User { user_id: user_id.clone(), username: req.username.clone(), created_at };
+    /* se l'INSERT fallisce map_err converte l'errore in 500 ed esce dall'handler con l'operatore ? */
+    state
+        .store
+        .create_user(&user, &password_hash)
+        .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("db insert error: {}", e)))?;
+    state
+        .store
+        .set_token(&user_id, &token, Some(token_expiry(&state)))
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("db update error: {}", e)))?;
 
+    telemetry::record_user(&user_id);
+    tracing::info!(username = %req.username, "user registered");
     /* creazione della risposta */
-    let user = User { user_id: user_id.clone(), username: req.username.clone(), created_at };
     let resp = RegisterResponse { user, token };
     Ok((StatusCode::CREATED, Json(resp)))
 }
 
-/// Handler per POST /api/login
+/// Handler per POST /api/login.
+/// Dopo troppi tentativi falliti da un IP l'account resta bloccato per un po' per quell'IP (429 con codice
+/// ACCOUNT_LOCKED); dagli altri IP si può continuare ad accedere.
 pub async fn login(
+    connect_info: Option<ConnectInfo<SocketAddr>>,
     Extension(state): Extension<Arc<AppState>>,
     Json(req): Json<LoginRequest>,
-) -> Result<Json<LoginResponse>, (StatusCode, String)> {
+) -> Response {
+    // come in limit_auth_by_ip: senza ConnectInfo tutti i client contano come uno solo
+    let ip = connect_info.map(|ConnectInfo(addr)| addr.ip().to_string()).unwrap_or_default();
+    if let Some(left) = state.limits.lockout.locked_for(&req.username, &ip) {
+        tracing::warn!(username = %req.username, "login rejected: account locked");
+        state.metrics.record_login(LoginOutcome::Locked);
+        return ratelimit::too_many_requests(codes::ACCOUNT_LOCKED, "account temporarily locked", left);
+    }
+    match verify_login(&state, &req).await {
+        Ok(resp) => {
+            telemetry::record_user(&resp.user.user_id);
+            tracing::info!(username = %req.username, "login succeeded");
+            state.metrics.record_login(LoginOutcome::Success);
+            state.limits.lockout.record_success(&req.username, &ip);
+            Json(resp).into_response()
+        }
+        Err((status, msg)) => {
+            // utente inesistente e password errata contano allo stesso modo
+            if status == StatusCode::UNAUTHORIZED || status == StatusCode::NOT_FOUND {
+                tracing::warn!(username = %req.username, "login failed");
+                state.metrics.record_login(LoginOutcome::Failure);
+                state.limits.lockout.record_failure(&req.username, &ip);
+            }
+            (status, msg).into_response()
+        }
+    }
+}
+
+// Verifica le credenziali e ruota il token dell'utente
+async fn verify_login(state: &AppState, req: &LoginRequest) -> Result<LoginResponse, (StatusCode, String)> {
     // cerca utente
-    let row = sqlx::query("SELECT user_id, password_hash, created_at FROM users WHERE username = ?")
-        .bind(&req.username) // passa parametro alla query
-        .fetch_optional(&state.pool)    // esegue la query ritornando un option<Row>
+    let creds = state
+        .store
+        .credentials(&req.username)
         .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("db error: {}", e)))?; // se fallisce mappa l'errore in 500 internal server error
-    let row = match row {
-        Some(r) => r,
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("db error: {}", e)))? // se fallisce mappa l'errore in 500 internal server error
         /* nessun utente trovato con quello username */
-        None => return Err((StatusCode::NOT_FOUND, "user not found".to_string())),
-    };
-    // cerco di ottenere i vari parametri dall'utente restituito perché row è di tipo Some(Row)
-    let user_id: String = row.try_get("user_id").map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("db get error: {}", e)))?;
-    let stored_hash: String = row.try_get("password_hash").map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("db get error: {}", e)))?;
-    let created_at: String = row.try_get("created_at").map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("db get error: {}", e)))?;
+        .ok_or((StatusCode::NOT_FOUND, "user not found".to_string()))?;
 
     // Calcolo hash sulla password fornita e confronto dell'hash preso dal db
-    let mut hasher = Sha256::new();
-    hasher.update(req.password.as_bytes());
-    let password_hash = format!("{:x}", hasher.finalize());
-    if password_hash != stored_hash {
+    if db::hash_password(&req.password) != creds.password_hash {
         /* se non coincidono ritorno UNAUTHORIZED */
         return Err((StatusCode::UNAUTHORIZED, "invalid credentials".to_string()));
     }
+    // account disabilitato con `ruggine-server disable-user`
+    if creds.disabled {
+        return Err((StatusCode::FORBIDDEN, "account disabled".to_string()));
+    }
 
     // genera token nuovo e aggiorna
     let token = Uuid::new_v4().to_string();
-    sqlx::query("UPDATE users SET token = ? WHERE user_id = ?")
-        .bind(&token)
-        .bind(&user_id)
-        /* execute esegue l'UPDATE che non ritorna righe; eventuali errori DB sono mappati in 500. */
-        .execute(&state.pool)
+    /* eventuali errori DB sono mappati in 500 */
+    state
+        .store
+        .set_token(&creds.user.user_id, &token, Some(token_expiry(state)))
         .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("db update error: {}", e)))?;
 
-    let user = User { user_id, username: req.username.clone(), created_at };
-    let resp = LoginResponse { token, user };
-    Ok(Json(resp))
+    let resp = LoginResponse { token, user: creds.user };
+    Ok(resp)
+}
+
+/// Handler per POST /api/groups: crea il gruppo e iscrive il creatore più gli eventuali membri indicati.
+pub async fn create_group(
+    Extension(state): Extension<Arc<AppState>>,
+    headers: HeaderMap,
+    Json(req): Json<CreateGroupRequest>,
+) -> Result<(StatusCode, Json<CreateGroupResponse>), (StatusCode, String)> {
+    let user_id = authenticate(&state, &headers).await?;
+    let name = req.name.trim();
+    if name.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "group name must not be empty".to_string()));
+    }
+
+    // il creatore è sempre membro; i duplicati vengono ignorati
+    let mut members = vec![user_id.clone()];
+    for m in req.members.unwrap_or_default() {
+        if !members.contains(&m) {
+            members.push(m);
+        }
+    }
+    for m in &members[1..] {
+        let exists = state
+            .store
+            .find_user(m)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("db error: {}", e)))?
+            .is_some();
+        if !exists {
+            return Err((StatusCode::BAD_REQUEST, format!("unknown member {}", m)));
+        }
+    }
+
+    let group = Group {
+        group_id: Uuid::new_v4().to_string(),
+        name: name.to_string(),
+        created_at: now_timestamp(),
+        pin_policy: PinPolicy::default(),
+        encrypted: req.encrypted,
+        retention: RetentionPolicy::default(),
+        message_ttl_secs: None,
+    };
+    // il creatore (primo della lista) è admin del gruppo
+    let members: Vec<(String, Role)> = members
+        .into_iter()
+        .enumerate()
+        .map(|(i, m)| (m, if i == 0 { Role::Admin } else { Role::Member }))
+        .collect();
+    /* gruppo e membership vengono inseriti nella stessa transazione */
+    state
+        .store
+        .create_group(&group, &members)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("db insert error: {}", e)))?;
+    for (member, _) in &members {
+        notify_member(&state, &group.group_id, WebhookEvent::MemberJoined, member).await;
+    }
+
+    Ok((StatusCode::CREATED, Json(CreateGroupResponse { group })))
+}
+
+/// Handler per POST /api/groups/{id}/members: aggiunge un utente al gruppo come membro semplice, solo
+/// per gli admin del gruppo.
+pub async fn add_member(
+    Extension(state): Extension<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(group_id): Path<String>,
+    Json(req): Json<AddMemberRequest>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let user_id = authenticate(&state, &headers).await?;
+    require_group_admin(&state, &group_id, &user_id, "add members").await?;
+    let exists = state
+        .store
+        .find_user(&req.user_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("db error: {}", e)))?
+        .is_some();
+    if !exists {
+        return Err((StatusCode::BAD_REQUEST, format!("unknown member {}", req.user_id)));
+    }
+    let added = state.store.add_member(&group_id, &req.user_id, Role::Member, &now_timestamp())
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("db error: {}", e)))?;
+    if !added {
+        return Err((StatusCode::CONFLICT, "already a member of the group".to_string()));
+    }
+    notify_member(&state, &group_id, WebhookEvent::MemberJoined, &req.user_id).await;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Handler per DELETE /api/groups/{id}/members/{user_id}: un membro può uscire dal gruppo, gli admin
+/// possono rimuovere chiunque. Il gruppo deve restare con almeno un admin (409 altrimenti).
+pub async fn remove_member(
+    Extension(state): Extension<Arc<AppState>>,
+    headers: HeaderMap,
+    Path((group_id, member_id)): Path<(String, String)>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let user_id = authenticate(&state, &headers).await?;
+    if member_id == user_id {
+        require_member(&state, &group_id, &user_id).await?;
+    } else {
+        require_group_admin(&state, &group_id, &user_id, "remove members").await?;
+    }
+    let admins = state.store.admin_ids(&group_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("db error: {}", e)))?;
+    if admins == [member_id.as_str()] {
+        return Err((StatusCode::CONFLICT, "the group must keep at least one admin".to_string()));
+    }
+    let removed = state.store.remove_member(&group_id, &member_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("db error: {}", e)))?;
+    if !removed {
+        return Err((StatusCode::NOT_FOUND, "not a member of the group".to_string()));
+    }
+    notify_member(&state, &group_id, WebhookEvent::MemberLeft, &member_id).await;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// la membership è già salvata: un problema con la coda dei webhook non deve far fallire la richiesta
+async fn notify_member(state: &AppState, group_id: &str, event: WebhookEvent, user_id: &str) {
+    if let Err(e) = webhooks::enqueue_member(state, group_id, event, user_id).await {
+        tracing::warn!(error = %e, group_id, user_id, event = event.as_str(), "webhook deliveries not enqueued");
+    }
+}
+
+/// Handler per GET /api/groups: gruppi di cui l'utente autenticato è membro.
+pub async fn list_groups(
+    Extension(state): Extension<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<Json<ListGroupsResponse>, (StatusCode, String)> {
+    let user_id = authenticate(&state, &headers).await?;
+    let groups = state
+        .store
+        .groups_for_user(&user_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("db error: {}", e)))?;
+    Ok(Json(ListGroupsResponse { groups }))
+}
+
+/// Handler per PATCH /api/groups/{id}: modifica nome, pin_policy, retention e/o durata di default dei
+/// messaggi, solo per gli admin del gruppo.
+pub async fn update_group(
+    Extension(state): Extension<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(group_id): Path<String>,
+    Json(req): Json<UpdateGroupRequest>,
+) -> Result<Json<UpdateGroupResponse>, (StatusCode, String)> {
+    let user_id = authenticate(&state, &headers).await?;
+    let mut group = state.store.find_group(&group_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("db error: {}", e)))?
+        .ok_or((StatusCode::NOT_FOUND, "group not found".to_string()))?;
+    let role = state.store.member_role(&group_id, &user_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("db error: {}", e)))?;
+    if role != Some(Role::Admin) {
+        return Err((StatusCode::FORBIDDEN, "only group admins can change group settings".to_string()));
+    }
+
+    if let Some(name) = req.name {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err((StatusCode::BAD_REQUEST, "group name must not be empty".to_string()));
+        }
+        group.name = name.to_string();
+    }
+    if let Some(policy) = req.pin_policy {
+        group.pin_policy = policy;
+    }
+    if let Some(limits) = req.retention {
+        retention::validate_policy(&limits).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+        group.retention = limits;
+    }
+    if let Some(ttl) = req.message_ttl_secs {
+        group.message_ttl_secs = if ttl == 0 {
+            None
+        } else {
+            state.config.ephemeral.validate_ttl(ttl).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+            Some(ttl)
+        };
+    }
+    state
+        .store
+        .update_group(&group)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("db update error: {}", e)))?;
+    Ok(Json(UpdateGroupResponse { group }))
+}
+
+/// Handler per GET /api/groups/{id}/pins: messaggi fissati del gruppo, dal più recente.
+pub async fn list_pins(
+    Extension(state): Extension<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(group_id): Path<String>,
+) -> Result<Json<ListPinsResponse>, (StatusCode, String)> {
+    let user_id = authenticate(&state, &headers).await?;
+    let member = state.store.is_member(&group_id, &user_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("db error: {}", e)))?;
+    if !member {
+        return Err((StatusCode::FORBIDDEN, "not a member of the group".to_string()));
+    }
+    let pins = pins::list(state.store.as_ref(), &group_id, &user_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("db error: {}", e)))?;
+    Ok(Json(ListPinsResponse { pins }))
+}
+
+/// Handler per PUT /api/groups/{id}/mute: silenzia o riattiva il gruppo per l'utente autenticato. Un
+/// gruppo silenziato non invia più sul WebSocket i nuovi messaggi né le reazioni e i pin, ma le menzioni
+/// (e reazioni e pin sui messaggi in cui l'utente è menzionato) arrivano comunque.
+pub async fn mute_group(
+    Extension(state): Extension<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(group_id): Path<String>,
+    Json(req): Json<MuteGroupRequest>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let user_id = authenticate(&state, &headers).await?;
+    let updated = state.store.set_muted(&group_id, &user_id, req.muted)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("db error: {}", e)))?;
+    if !updated {
+        return Err((StatusCode::FORBIDDEN, "not a member of the group".to_string()));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Handler per GET /api/groups/{id}/export: storico completo del gruppo (membri e messaggi) come
+/// allegato JSON, solo per gli admin del gruppo.
+pub async fn export_group(
+    Extension(state): Extension<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(group_id): Path<String>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let user_id = authenticate(&state, &headers).await?;
+    let role = state.store.member_role(&group_id, &user_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("db error: {}", e)))?;
+    match role {
+        Some(Role::Admin) => {}
+        Some(Role::Member) => return Err((StatusCode::FORBIDDEN, "only group admins can export the group".to_string())),
+        None => return Err((StatusCode::FORBIDDEN, "not a member of the group".to_string())),
+    }
+    let export: GroupExport = backup::export_group(state.store.as_ref(), &group_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("db error: {}", e)))?
+        .ok_or((StatusCode::NOT_FOUND, "group not found".to_string()))?;
+    tracing::info!(group_id = %group_id, messages = export.messages.len(), "group exported");
+    let disposition = format!("attachment; filename=\"ruggine-group-{}.json\"", group_id);
+    Ok(([(header::CONTENT_DISPOSITION, disposition)], Json(export)))
+}
+
+/// Handler per POST /api/admin/backup: backup online del database in backup_dir.
+pub async fn create_backup(
+    Extension(state): Extension<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<(StatusCode, Json<BackupResponse>), (StatusCode, String)> {
+    authenticate_admin(&state, &headers).await?;
+    if state.store.backend() != "sqlite" {
+        return Err((StatusCode::NOT_IMPLEMENTED, "online backup is only supported for sqlite databases".to_string()));
+    }
+    let created = backup::create(state.store.as_ref(), &state.config.backup_dir)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("backup failed: {:#}", e)))?;
+    let file = created.path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+    Ok((StatusCode::CREATED, Json(BackupResponse { file, size_bytes: created.size_bytes, created_at: created.created_at })))
+}
+
+/// Query string di GET /api/groups/{id}/messages e GET /api/mentions
+#[derive(Debug, Deserialize)]
+pub struct ListMessagesQuery {
+    /// Restituisce solo messaggi creati prima di questo timestamp RFC3339
+    pub before: Option<String>,
+    pub limit: Option<i64>,
+}
+
+/// Handler per GET /api/groups/{id}/messages: pagina di storico in ordine cronologico,
+/// con le reazioni aggregate dal punto di vista del richiedente.
+pub async fn list_messages(
+    Extension(state): Extension<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(group_id): Path<String>,
+    Query(q): Query<ListMessagesQuery>,
+) -> Result<Json<ListMessagesResponse>, (StatusCode, String)> {
+    let user_id = authenticate(&state, &headers).await?;
+    let member = state.store.is_member(&group_id, &user_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("db error: {}", e)))?;
+    if !member {
+        return Err((StatusCode::FORBIDDEN, "not a member of the group".to_string()));
+    }
+
+    let limit = q.limit.unwrap_or(50).clamp(1, 200);
+    // pagina in ordine cronologico
+    let mut messages = state
+        .store
+        .list_messages(&group_id, q.before.as_deref(), limit)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("db error: {}", e)))?;
+    db::hydrate(state.store.as_ref(), &mut messages, &user_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("db error: {}", e)))?;
+    Ok(Json(ListMessagesResponse { messages }))
+}
+
+/// Handler per GET /api/mentions: inbox delle menzioni dell'utente autenticato, dalla più recente.
+pub async fn list_mentions(
+    Extension(state): Extension<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(q): Query<ListMessagesQuery>,
+) -> Result<Json<ListMentionsResponse>, (StatusCode, String)> {
+    let user_id = authenticate(&state, &headers).await?;
+    let limit = q.limit.unwrap_or(50).clamp(1, 200);
+    let mentions = mentions::inbox(state.store.as_ref(), &user_id, q.before.as_deref(), limit)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("db error: {}", e)))?;
+    Ok(Json(ListMentionsResponse { mentions }))
+}
+
+/// Handler per GET /api/scheduled: messaggi programmati dall'utente e non ancora pubblicati,
+/// dal primo da inviare.
+pub async fn list_scheduled(
+    Extension(state): Extension<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<Json<ListScheduledResponse>, (StatusCode, String)> {
+    let user_id = authenticate(&state, &headers).await?;
+    let scheduled = state.store.scheduled_for_sender(&user_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("db error: {}", e)))?;
+    Ok(Json(ListScheduledResponse { scheduled }))
+}
+
+/// Handler per DELETE /api/scheduled/:message_id: annulla un messaggio programmato dall'utente.
+/// 404 se non esiste, è di un altro utente o è già stato pubblicato.
+pub async fn cancel_scheduled(
+    Extension(state): Extension<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(message_id): Path<String>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let user_id = authenticate(&state, &headers).await?;
+    let cancelled = state.store.cancel_scheduled(&message_id, &user_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("db error: {}", e)))?;
+    if !cancelled {
+        return Err((StatusCode::NOT_FOUND, "scheduled message not found".to_string()));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// 404 se il gruppo non esiste, 403 se l'utente non ne è admin
+async fn require_group_admin(
+    state: &AppState,
+    group_id: &str,
+    user_id: &str,
+    action: &str,
+) -> Result<(), (StatusCode, String)> {
+    state.store.find_group(group_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("db error: {}", e)))?
+        .ok_or((StatusCode::NOT_FOUND, "group not found".to_string()))?;
+    let role = state.store.member_role(group_id, user_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("db error: {}", e)))?;
+    match role {
+        Some(Role::Admin) => Ok(()),
+        Some(Role::Member) => Err((StatusCode::FORBIDDEN, format!("only group admins can {}", action))),
+        None => Err((StatusCode::FORBIDDEN, "not a member of the group".to_string())),
+    }
+}
+
+// Webhook del gruppo indicato nel path; 404 anche se appartiene a un altro gruppo
+async fn group_webhook(state: &AppState, group_id: &str, webhook_id: &str) -> Result<Webhook, (StatusCode, String)> {
+    state.store.find_webhook(webhook_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("db error: {}", e)))?
+        .map(|r| r.webhook)
+        .filter(|w| w.group_id == group_id)
+        .ok_or((StatusCode::NOT_FOUND, "webhook not found".to_string()))
+}
+
+/// Handler per GET /api/groups/{id}/webhooks: webhook del gruppo (senza segreti), solo per gli admin.
+pub async fn list_webhooks(
+    Extension(state): Extension<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(group_id): Path<String>,
+) -> Result<Json<ListWebhooksResponse>, (StatusCode, String)> {
+    let user_id = authenticate(&state, &headers).await?;
+    require_group_admin(&state, &group_id, &user_id, "manage webhooks").await?;
+    let webhooks = state.store.webhooks_for_group(&group_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("db error: {}", e)))?
+        .into_iter()
+        .map(|r| r.webhook)
+        .collect();
+    Ok(Json(ListWebhooksResponse { webhooks }))
+}
+
+/// Handler per POST /api/groups/{id}/webhooks: registra un webhook del gruppo, solo per gli admin.
+/// La risposta contiene il segreto di firma, che non viene più mostrato.
+pub async fn create_webhook(
+    Extension(state): Extension<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(group_id): Path<String>,
+    Json(req): Json<CreateWebhookRequest>,
+) -> Result<(StatusCode, Json<CreateWebhookResponse>), (StatusCode, String)> {
+    let user_id = authenticate(&state, &headers).await?;
+    require_group_admin(&state, &group_id, &user_id, "manage webhooks").await?;
+    let url = req.url.trim();
+    webhooks::validate_url(url, &state.config.webhooks).await.map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    let mut events = req.events.unwrap_or_else(|| vec![WebhookEvent::MessageCreated]);
+    events.sort();
+    events.dedup();
+    if events.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "events must not be empty".to_string()));
+    }
+    let existing = state.store.webhooks_for_group(&group_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("db error: {}", e)))?
+        .len();
+    if existing >= state.config.webhooks.max_per_group as usize {
+        return Err((StatusCode::BAD_REQUEST, "too many webhooks in the group".to_string()));
+    }
+
+    let secret = webhooks::new_secret().map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("{:#}", e)))?;
+    let webhook = Webhook {
+        webhook_id: Uuid::new_v4().to_string(),
+        group_id,
+        url: url.to_string(),
+        events,
+        enabled: true,
+        created_by: user_id,
+        created_at: now_timestamp(),
+        consecutive_failures: 0,
+    };
+    let record = WebhookRecord { webhook: webhook.clone(), secret: secret.clone() };
+    state.store.create_webhook(&record)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("db insert error: {}", e)))?;
+    tracing::info!(webhook_id = %webhook.webhook_id, group_id = %webhook.group_id, "webhook created");
+    Ok((StatusCode::CREATED, Json(CreateWebhookResponse { webhook, secret })))
+}
+
+/// Handler per PATCH /api/groups/{id}/webhooks/{webhook_id}: abilita o disabilita il webhook, solo per
+/// gli admin. Riabilitarlo azzera i fallimenti consecutivi; disabilitarlo fa fallire le consegne in coda.
+pub async fn update_webhook(
+    Extension(state): Extension<Arc<AppState>>,
+    headers: HeaderMap,
+    Path((group_id, webhook_id)): Path<(String, String)>,
+    Json(req): Json<UpdateWebhookRequest>,
+) -> Result<Json<Webhook>, (StatusCode, String)> {
+    let user_id = authenticate(&state, &headers).await?;
+    require_group_admin(&state, &group_id, &user_id, "manage webhooks").await?;
+    group_webhook(&state, &group_id, &webhook_id).await?;
+    if let Some(enabled) = req.enabled {
+        let now = time::OffsetDateTime::now_utc().unix_timestamp();
+        state.store.set_webhook_enabled(&webhook_id, enabled, now)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("db update error: {}", e)))?;
+        tracing::info!(webhook_id = %webhook_id, enabled, "webhook updated");
+    }
+    Ok(Json(group_webhook(&state, &group_id, &webhook_id).await?))
+}
+
+/// Handler per DELETE /api/groups/{id}/webhooks/{webhook_id}: elimina il webhook e il suo registro
+/// delle consegne, solo per gli admin.
+pub async fn delete_webhook(
+    Extension(state): Extension<Arc<AppState>>,
+    headers: HeaderMap,
+    Path((group_id, webhook_id)): Path<(String, String)>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let user_id = authenticate(&state, &headers).await?;
+    require_group_admin(&state, &group_id, &user_id, "manage webhooks").await?;
+    group_webhook(&state, &group_id, &webhook_id).await?;
+    let deleted = state.store.delete_webhook(&webhook_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("db error: {}", e)))?;
+    if !deleted {
+        return Err((StatusCode::NOT_FOUND, "webhook not found".to_string()));
+    }
+    tracing::info!(webhook_id = %webhook_id, group_id = %group_id, "webhook deleted");
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Query string di GET /api/groups/{id}/webhooks/{webhook_id}/deliveries
+#[derive(Debug, Deserialize)]
+pub struct ListDeliveriesQuery {
+    pub limit: Option<i64>,
+}
+
+/// Handler per GET /api/groups/{id}/webhooks/{webhook_id}/deliveries: registro delle consegne del
+/// webhook, dalla più recente, solo per gli admin.
+pub async fn list_webhook_deliveries(
+    Extension(state): Extension<Arc<AppState>>,
+    headers: HeaderMap,
+    Path((group_id, webhook_id)): Path<(String, String)>,
+    Query(q): Query<ListDeliveriesQuery>,
+) -> Result<Json<ListWebhookDeliveriesResponse>, (StatusCode, String)> {
+    let user_id = authenticate(&state, &headers).await?;
+    require_group_admin(&state, &group_id, &user_id, "manage webhooks").await?;
+    group_webhook(&state, &group_id, &webhook_id).await?;
+    let limit = q.limit.unwrap_or(50).clamp(1, 200);
+    let deliveries = state.store.deliveries_for_webhook(&webhook_id, limit)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("db error: {}", e)))?;
+    Ok(Json(ListWebhookDeliveriesResponse { deliveries }))
+}
+
+// Lunghezza massima in caratteri base64 di chiavi pubbliche e sender key cifrate
+const MAX_KEY_LEN: usize = 1024;
+
+// 403 se l'utente non è membro del gruppo
+async fn require_member(state: &AppState, group_id: &str, user_id: &str) -> Result<(), (StatusCode, String)> {
+    let member = state.store.is_member(group_id, user_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("db error: {}", e)))?;
+    if !member {
+        return Err((StatusCode::FORBIDDEN, "not a member of the group".to_string()));
+    }
+    Ok(())
+}
+
+/// Handler per PUT /api/keys: pubblica la chiave pubblica dell'utente autenticato nella key directory,
+/// sostituendo la precedente.
+pub async fn publish_key(
+    Extension(state): Extension<Arc<AppState>>,
+    headers: HeaderMap,
+    Json(req): Json<PublishKeyRequest>,
+) -> Result<Json<PublicKeyResponse>, (StatusCode, String)> {
+    let user_id = authenticate(&state, &headers).await?;
+    if !is_key_id(&req.key_id) {
+        return Err((StatusCode::BAD_REQUEST, "keyId must be 1-64 characters among letters, digits, - and _".to_string()));
+    }
+    if req.public_key.len() > MAX_KEY_LEN || !is_base64(&req.public_key) {
+        return Err((StatusCode::BAD_REQUEST, "publicKey must be base64".to_string()));
+    }
+    let key = PublicKey {
+        user_id,
+        key_id: req.key_id,
+        alg: req.alg,
+        public_key: req.public_key,
+        created_at: now_timestamp(),
+    };
+    state
+        .store
+        .put_public_key(&key)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("db insert error: {}", e)))?;
+    Ok(Json(PublicKeyResponse { key }))
+}
+
+/// Handler per GET /api/keys/{user_id}: chiave pubblica corrente di un utente.
+pub async fn get_key(
+    Extension(state): Extension<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(user_id): Path<String>,
+) -> Result<Json<PublicKeyResponse>, (StatusCode, String)> {
+    authenticate(&state, &headers).await?;
+    let key = state
+        .store
+        .public_key(&user_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("db error: {}", e)))?
+        .ok_or((StatusCode::NOT_FOUND, "no public key for user".to_string()))?;
+    Ok(Json(PublicKeyResponse { key }))
+}
+
+/// Handler per GET /api/groups/{id}/keys: chiavi pubbliche dei membri, per cifrare la propria sender key.
+pub async fn list_group_keys(
+    Extension(state): Extension<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(group_id): Path<String>,
+) -> Result<Json<ListPublicKeysResponse>, (StatusCode, String)> {
+    let user_id = authenticate(&state, &headers).await?;
+    require_member(&state, &group_id, &user_id).await?;
+    let keys = state
+        .store
+        .group_public_keys(&group_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("db error: {}", e)))?;
+    Ok(Json(ListPublicKeysResponse { keys }))
+}
+
+/// Handler per POST /api/groups/{id}/sender-keys: distribuisce la sender key del richiedente,
+/// già cifrata per ciascun destinatario. Ogni destinatario deve essere membro e la chiave deve essere
+/// cifrata con la sua chiave pubblica corrente (409 altrimenti, il client deve ricaricare le chiavi).
+/// I destinatari connessi ricevono l'evento senderKey.
+pub async fn distribute_sender_key(
+    Extension(state): Extension<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(group_id): Path<String>,
+    Json(req): Json<DistributeSenderKeyRequest>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let user_id = authenticate(&state, &headers).await?;
+    let group = state.store.find_group(&group_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("db error: {}", e)))?
+        .ok_or((StatusCode::NOT_FOUND, "group not found".to_string()))?;
+    require_member(&state, &group_id, &user_id).await?;
+    if !group.encrypted {
+        return Err((StatusCode::BAD_REQUEST, "group is not end-to-end encrypted".to_string()));
+    }
+    if !is_key_id(&req.sender_key_id) {
+        return Err((StatusCode::BAD_REQUEST, "senderKeyId must be 1-64 characters among letters, digits, - and _".to_string()));
+    }
+    if req.keys.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "keys must not be empty".to_string()));
+    }
+
+    let created_at = now_timestamp();
+    let mut keys = Vec::with_capacity(req.keys.len());
+    for sealed in req.keys {
+        if sealed.sealed_key.len() > MAX_KEY_LEN || !is_base64(&sealed.sealed_key) {
+            return Err((StatusCode::BAD_REQUEST, "sealedKey must be base64".to_string()));
+        }
+        require_member(&state, &group_id, &sealed.recipient_id)
+            .await
+            .map_err(|_| (StatusCode::BAD_REQUEST, format!("recipient {} is not a member", sealed.recipient_id)))?;
+        let current = state.store.public_key(&sealed.recipient_id)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("db error: {}", e)))?;
+        if current.map(|k| k.key_id).as_deref() != Some(sealed.recipient_key_id.as_str()) {
+            return Err((StatusCode::CONFLICT, format!("stale public key for recipient {}", sealed.recipient_id)));
+        }
+        keys.push(SenderKey {
+            group_id: group_id.clone(),
+            sender_id: user_id.clone(),
+            sender_key_id: req.sender_key_id.clone(),
+            recipient_id: sealed.recipient_id,
+            recipient_key_id: sealed.recipient_key_id,
+            sealed_key: sealed.sealed_key,
+            created_at: created_at.clone(),
+        });
+    }
+    state
+        .store
+        .put_sender_keys(&keys)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("db insert error: {}", e)))?;
+
+    for key in keys {
+        let recipient = [key.recipient_id.clone()];
+        state.bus.publish(&recipient, &WsMessage::SenderKey(key));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Handler per GET /api/groups/{id}/sender-keys: sender key del gruppo destinate al richiedente.
+pub async fn list_sender_keys(
+    Extension(state): Extension<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(group_id): Path<String>,
+) -> Result<Json<ListSenderKeysResponse>, (StatusCode, String)> {
+    let user_id = authenticate(&state, &headers).await?;
+    require_member(&state, &group_id, &user_id).await?;
+    let sender_keys = state
+        .store
+        .sender_keys_for(&group_id, &user_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("db error: {}", e)))?;
+    Ok(Json(ListSenderKeysResponse { sender_keys }))
 }
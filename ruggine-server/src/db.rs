@@ -0,0 +1,38 @@
+/* Funzioni di supporto condivise tra gli handler HTTP (controllers) e le sessioni WS (ws),
+   costruite sopra lo Store */
+use ruggine_core::Message;
+use sha2::{Digest, Sha256};
+
+use crate::store::{Store, StoreResult};
+use crate::{mentions, reactions};
+
+/// Istante corrente in secondi unix, usato per le scadenze.
+pub fn unix_now() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+/// Hash salvato per una password (SHA-256 in esadecimale), usato da registrazione, login e comandi
+/// di amministrazione.
+pub fn hash_password(password: &str) -> String {
+    format!("{:x}", Sha256::digest(password.as_bytes()))
+}
+
+/// Ritorna l'user_id associato al token di sessione, se esiste e non è scaduto.
+pub async fn user_id_for_token(store: &dyn Store, token: &str) -> StoreResult<Option<String>> {
+    store.user_id_for_token(token, unix_now()).await
+}
+
+/// Completa i messaggi letti dal DB con reazioni (dal punto di vista di `viewer`) e menzioni.
+#[tracing::instrument(level = "debug", skip(store, messages), fields(count = messages.len()), err)]
+pub async fn hydrate(store: &dyn Store, messages: &mut [Message], viewer: &str) -> StoreResult<()> {
+    reactions::attach(store, messages, viewer).await?;
+    mentions::attach(store, messages).await
+}
+
+/// Nome del gruppo, se esiste.
+pub async fn group_name(store: &dyn Store, group_id: &str) -> StoreResult<Option<String>> {
+    Ok(store.find_group(group_id).await?.map(|g| g.name))
+}
@@ -0,0 +1,213 @@
+/* Cifratura a riposo: il contenuto dei messaggi viene cifrato con AES-256-GCM prima di arrivare al
+   database. Gli allegati sotto upload_dir sono fuori dallo scope: il server non li legge né li
+   scrive, quindi restano in chiaro e rotate-key non li tocca. Le chiavi sono lette da un file
+   indicato da encryption_key_file, una per riga nella forma "<key_id> <base64>": la prima
+   è quella attiva (usata per cifrare), le successive servono solo a decifrare i dati non ancora
+   ricifrati. Per ruotare la chiave si aggiunge una nuova prima riga, si esegue `rotate-key`
+   e poi si possono togliere le righe vecchie. */
+use anyhow::{bail, Context};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+use ring::rand::{SecureRandom, SystemRandom};
+use std::path::Path;
+
+use crate::store::Store;
+
+/// Prefisso dei campi cifrati salvati nel database: "enc:v1:<key_id>:<base64(nonce || ciphertext)>".
+pub const FIELD_PREFIX: &str = "enc:v1:";
+
+/// Lunghezza in byte delle chiavi AES-256.
+pub const KEY_LEN: usize = 32;
+
+// Messaggi ricifrati per ogni pagina letta durante la rotazione
+const ROTATION_BATCH: i64 = 500;
+
+/// Chiavi di cifratura a riposo; la prima è quella attiva.
+pub struct Keyring {
+    keys: Vec<(String, LessSafeKey)>,
+    rng: SystemRandom,
+}
+
+impl std::fmt::Debug for Keyring {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let ids: Vec<&str> = self.keys.iter().map(|(id, _)| id.as_str()).collect();
+        f.debug_struct("Keyring").field("key_ids", &ids).finish()
+    }
+}
+
+impl Keyring {
+    /// Legge il file delle chiavi (righe vuote e righe che iniziano con '#' sono ignorate).
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let text = std::fs::read_to_string(path).with_context(|| format!("read encryption key file {:?}", path))?;
+        Self::parse(&text).with_context(|| format!("invalid encryption key file {:?}", path))
+    }
+
+    pub fn parse(text: &str) -> anyhow::Result<Self> {
+        let mut keys: Vec<(String, LessSafeKey)> = Vec::new();
+        for (n, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some((id, encoded)) = line.split_once(char::is_whitespace) else {
+                bail!("line {}: expected \"<key_id> <base64 key>\"", n + 1);
+            };
+            if !ruggine_core::e2ee::is_key_id(id) {
+                bail!("line {}: invalid key id {:?}", n + 1, id);
+            }
+            if keys.iter().any(|(existing, _)| existing == id) {
+                bail!("line {}: duplicate key id {:?}", n + 1, id);
+            }
+            let bytes = STANDARD.decode(encoded.trim()).with_context(|| format!("line {}: key is not base64", n + 1))?;
+            if bytes.len() != KEY_LEN {
+                bail!("line {}: key must be {} bytes, got {}", n + 1, KEY_LEN, bytes.len());
+            }
+            let key = UnboundKey::new(&AES_256_GCM, &bytes).map_err(|_| anyhow::anyhow!("line {}: invalid key", n + 1))?;
+            keys.push((id.to_string(), LessSafeKey::new(key)));
+        }
+        if keys.is_empty() {
+            bail!("no keys");
+        }
+        Ok(Self { keys, rng: SystemRandom::new() })
+    }
+
+    /// Riga per il file delle chiavi con una nuova chiave casuale.
+    pub fn generate_key_line() -> anyhow::Result<String> {
+        let mut bytes = [0u8; KEY_LEN];
+        SystemRandom::new().fill(&mut bytes).map_err(|_| anyhow::anyhow!("random generator failure"))?;
+        let id = format!("key-{}", &uuid::Uuid::new_v4().simple().to_string()[..12]);
+        Ok(format!("{} {}", id, STANDARD.encode(bytes)))
+    }
+
+    pub fn active_key_id(&self) -> &str {
+        &self.keys[0].0
+    }
+
+    fn key(&self, id: &str) -> anyhow::Result<&LessSafeKey> {
+        self.keys
+            .iter()
+            .find(|(k, _)| k == id)
+            .map(|(_, key)| key)
+            .with_context(|| format!("unknown encryption key {:?}", id))
+    }
+
+    // nonce || ciphertext || tag
+    fn seal_bytes(&self, key: &LessSafeKey, plaintext: &[u8], aad: &[u8]) -> anyhow::Result<Vec<u8>> {
+        let mut nonce = [0u8; NONCE_LEN];
+        self.rng.fill(&mut nonce).map_err(|_| anyhow::anyhow!("random generator failure"))?;
+        let mut in_out = plaintext.to_vec();
+        key.seal_in_place_append_tag(Nonce::assume_unique_for_key(nonce), Aad::from(aad), &mut in_out)
+            .map_err(|_| anyhow::anyhow!("encryption failure"))?;
+        let mut out = nonce.to_vec();
+        out.extend_from_slice(&in_out);
+        Ok(out)
+    }
+
+    fn open_bytes(key: &LessSafeKey, sealed: &[u8], aad: &[u8]) -> anyhow::Result<Vec<u8>> {
+        if sealed.len() < NONCE_LEN {
+            bail!("ciphertext too short");
+        }
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        let nonce = Nonce::try_assume_unique_for_key(nonce).map_err(|_| anyhow::anyhow!("invalid nonce"))?;
+        let mut in_out = ciphertext.to_vec();
+        let plaintext = key
+            .open_in_place(nonce, Aad::from(aad), &mut in_out)
+            .map_err(|_| anyhow::anyhow!("decryption failure (wrong key or tampered data)"))?;
+        Ok(plaintext.to_vec())
+    }
+
+    /// Cifra il valore di un campo con la chiave attiva. `aad` lega il valore alla sua riga
+    /// (es. il message_id): spostato su un'altra riga non si decifra più.
+    pub fn encrypt_field(&self, plaintext: &str, aad: &str) -> anyhow::Result<String> {
+        let (id, key) = &self.keys[0];
+        let sealed = self.seal_bytes(key, plaintext.as_bytes(), aad.as_bytes())?;
+        Ok(format!("{}{}:{}", FIELD_PREFIX, id, STANDARD.encode(sealed)))
+    }
+
+    /// Inverso di encrypt_field; i valori senza prefisso (salvati prima di attivare la cifratura)
+    /// sono ritornati così come sono.
+    pub fn decrypt_field(&self, stored: &str, aad: &str) -> anyhow::Result<String> {
+        let Some(rest) = stored.strip_prefix(FIELD_PREFIX) else {
+            return Ok(stored.to_string());
+        };
+        let (id, encoded) = rest.split_once(':').context("malformed encrypted field")?;
+        let sealed = STANDARD.decode(encoded).context("malformed encrypted field")?;
+        let plaintext = Self::open_bytes(self.key(id)?, &sealed, aad.as_bytes())?;
+        String::from_utf8(plaintext).context("decrypted field is not utf-8")
+    }
+}
+
+/// Chiave con cui è cifrato un campo, None se il valore è in chiaro.
+pub fn field_key_id(stored: &str) -> Option<&str> {
+    stored.strip_prefix(FIELD_PREFIX)?.split_once(':').map(|(id, _)| id)
+}
+
+/// Esito di una rotazione della chiave: campi ricifrati con la chiave attiva, compresi quelli che
+/// erano in chiaro.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RotationReport {
+    /// Contenuto dei messaggi
+    pub messages: u64,
+    /// Contenuto dei messaggi programmati non ancora pubblicati
+    pub scheduled: u64,
+    /// Segreti di firma dei webhook
+    pub webhook_secrets: u64,
+}
+
+// Tabelle con campi cifrati a riposo; l'id della riga è anche l'AAD del campo
+#[derive(Debug, Clone, Copy)]
+enum Table {
+    Messages,
+    Scheduled,
+    WebhookSecrets,
+}
+
+impl Table {
+    async fn page(self, store: &dyn Store, after: Option<&str>) -> sqlx::Result<Vec<(String, String)>> {
+        match self {
+            Table::Messages => store.raw_contents(after, ROTATION_BATCH).await,
+            Table::Scheduled => store.raw_scheduled_contents(after, ROTATION_BATCH).await,
+            Table::WebhookSecrets => store.raw_webhook_secrets(after, ROTATION_BATCH).await,
+        }
+    }
+
+    async fn update(self, store: &dyn Store, id: &str, value: &str) -> sqlx::Result<()> {
+        match self {
+            Table::Messages => store.set_raw_content(id, value).await,
+            Table::Scheduled => store.set_raw_scheduled_content(id, value).await,
+            Table::WebhookSecrets => store.set_raw_webhook_secret(id, value).await,
+        }
+    }
+}
+
+/// Ricifra con la chiave attiva i messaggi, i messaggi programmati e i segreti dei webhook cifrati
+/// con altre chiavi o in chiaro; gli allegati sotto upload_dir sono fuori dallo scope e restano come sono.
+/// `store` deve essere il backend senza cifratura (i contenuti vanno letti come salvati).
+/// Può essere ripetuta: i dati già cifrati con la chiave attiva non vengono toccati.
+pub async fn rotate(store: &dyn Store, keyring: &Keyring) -> anyhow::Result<RotationReport> {
+    Ok(RotationReport {
+        messages: rotate_table(store, keyring, Table::Messages).await?,
+        scheduled: rotate_table(store, keyring, Table::Scheduled).await?,
+        webhook_secrets: rotate_table(store, keyring, Table::WebhookSecrets).await?,
+    })
+}
+
+async fn rotate_table(store: &dyn Store, keyring: &Keyring, table: Table) -> anyhow::Result<u64> {
+    let mut rotated = 0;
+    let mut after: Option<String> = None;
+    loop {
+        let page = table.page(store, after.as_deref()).await.with_context(|| format!("read {:?}", table))?;
+        let Some((last, _)) = page.last() else { break };
+        after = Some(last.clone());
+        for (id, stored) in page {
+            if field_key_id(&stored) == Some(keyring.active_key_id()) {
+                continue;
+            }
+            let plaintext = keyring.decrypt_field(&stored, &id).with_context(|| format!("decrypt {:?} {}", table, id))?;
+            let value = keyring.encrypt_field(&plaintext, &id)?;
+            table.update(store, &id, &value).await.with_context(|| format!("update {:?} {}", table, id))?;
+            rotated += 1;
+        }
+    }
+    Ok(rotated)
+}
@@ -0,0 +1,108 @@
+/* Messaggi effimeri: sendMessage può indicare ttl_secs, altrimenti vale il message_ttl_secs del gruppo
+   (se impostato). La scadenza è salvata con il messaggio (expires_at), quindi sopravvive ai riavvii;
+   le letture del DB ignorano già i messaggi scaduti, mentre un task in background li elimina ogni
+   interval_secs, dal primo a scadere e a lotti di batch_size (con reazioni, menzioni e pin), e avvisa i
+   membri con messageExpired. Con più istanze ogni messaggio è eliminato, e notificato, da una sola. */
+use ruggine_core::{timestamp_from_unix, MessageExpired, WsMessage};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use time::OffsetDateTime;
+
+use crate::store::StoreResult;
+use crate::AppState;
+
+/// Sezione [ephemeral] della configurazione.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EphemeralConfig {
+    /// Durata massima accettata per un messaggio effimero (ttl del messaggio o default del gruppo)
+    pub max_ttl_secs: u64,
+    /// Intervallo tra due controlli dei messaggi scaduti
+    pub interval_secs: u64,
+    /// Messaggi eliminati per transazione
+    pub batch_size: u32,
+}
+
+impl Default for EphemeralConfig {
+    fn default() -> Self {
+        Self { max_ttl_secs: 30 * 24 * 60 * 60, interval_secs: 1, batch_size: 500 }
+    }
+}
+
+impl EphemeralConfig {
+    /// Controlla una durata chiesta da un client: deve essere positiva e non oltre max_ttl_secs.
+    pub fn validate_ttl(&self, ttl_secs: u64) -> Result<(), String> {
+        if ttl_secs == 0 {
+            return Err("ttl must be positive".to_string());
+        }
+        if ttl_secs > self.max_ttl_secs {
+            return Err(format!("ttl must be at most {} seconds", self.max_ttl_secs));
+        }
+        Ok(())
+    }
+
+    pub fn problems(&self) -> Vec<String> {
+        let mut out = Vec::new();
+        if self.max_ttl_secs == 0 {
+            out.push("ephemeral.max_ttl_secs must be positive".to_string());
+        }
+        if self.interval_secs == 0 {
+            out.push("ephemeral.interval_secs must be positive".to_string());
+        }
+        if self.batch_size == 0 {
+            out.push("ephemeral.batch_size must be at least 1".to_string());
+        }
+        out
+    }
+}
+
+/// Scadenza (RFC3339) di un messaggio inviato ora con durata `ttl_secs`; arrotondata al secondo
+/// successivo, così il messaggio resta visibile almeno per tutta la durata.
+pub fn expires_at(ttl_secs: u64) -> String {
+    let now = OffsetDateTime::now_utc();
+    let secs = now.unix_timestamp() + i64::from(now.nanosecond() > 0);
+    timestamp_from_unix(secs.saturating_add(i64::try_from(ttl_secs).unwrap_or(i64::MAX)))
+}
+
+/// Elimina tutti i messaggi scaduti e avvisa i membri dei loro gruppi; ritorna il numero di messaggi eliminati.
+pub async fn run_once(state: &AppState) -> StoreResult<u64> {
+    let batch = i64::from(state.config.ephemeral.batch_size.max(1));
+    let now = OffsetDateTime::now_utc().unix_timestamp();
+    let mut total = 0;
+    loop {
+        let expired = state.store.purge_expired(now, batch).await?;
+        let n = expired.len();
+        total += n as u64;
+        let mut by_group: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for (group_id, message_id) in expired {
+            by_group.entry(group_id).or_default().push(message_id);
+        }
+        for (group_id, message_ids) in by_group {
+            tracing::debug!(group_id = %group_id, count = message_ids.len(), "ephemeral messages expired");
+            let members = state.store.member_ids(&group_id).await?;
+            state.bus.publish(&members, &WsMessage::MessageExpired(MessageExpired { group_id, message_ids }));
+        }
+        if n < batch as usize {
+            break;
+        }
+    }
+    Ok(total)
+}
+
+/// Avvia l'eliminazione periodica dei messaggi scaduti, che termina all'arresto del server.
+pub fn spawn(state: Arc<AppState>) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticks = tokio::time::interval(std::time::Duration::from_secs(state.config.ephemeral.interval_secs));
+        ticks.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            tokio::select! {
+                _ = ticks.tick() => {}
+                _ = state.shutdown.triggered() => return,
+            }
+            if let Err(e) = run_once(&state).await {
+                tracing::warn!(error = %e, "ephemeral purge failed");
+            }
+        }
+    })
+}
@@ -0,0 +1,100 @@
+/* Endpoint di salute: /health/live risponde finché il processo serve richieste, /health/ready
+   verifica che il server possa davvero lavorare (database interrogabile, schema aggiornato,
+   spazio su disco sufficiente, arresto non in corso). */
+use axum::{extract::Extension, http::StatusCode, Json};
+use ruggine_core::{ComponentHealth, HealthResponse, HealthStatus};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::{AppState, SCHEMA_VERSION};
+
+/// Versione del server riportata nelle risposte.
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+// Tempo massimo per i controlli sul database: oltre, il pool è considerato non disponibile
+const DB_CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+const MIB: u64 = 1024 * 1024;
+
+fn ok(detail: impl Into<String>) -> ComponentHealth {
+    ComponentHealth { status: HealthStatus::Ok, detail: Some(detail.into()) }
+}
+
+fn fail(detail: impl Into<String>) -> ComponentHealth {
+    ComponentHealth { status: HealthStatus::Fail, detail: Some(detail.into()) }
+}
+
+/// Handler per GET /health/live
+pub async fn live() -> Json<HealthResponse> {
+    Json(HealthResponse { status: HealthStatus::Ok, version: VERSION.to_string(), components: BTreeMap::new() })
+}
+
+/// Handler per GET /health/ready: 200 se tutti i componenti sono ok, altrimenti 503 con il dettaglio.
+pub async fn ready(Extension(state): Extension<Arc<AppState>>) -> (StatusCode, Json<HealthResponse>) {
+    let report = readiness(&state).await;
+    let status = match report.status {
+        HealthStatus::Ok => StatusCode::OK,
+        HealthStatus::Fail => StatusCode::SERVICE_UNAVAILABLE,
+    };
+    (status, Json(report))
+}
+
+/// Esegue tutti i controlli di readiness.
+pub async fn readiness(state: &AppState) -> HealthResponse {
+    let min_free = state.config.min_free_disk_mb.saturating_mul(MIB);
+    let mut components = BTreeMap::new();
+    components.insert("database".to_string(), check_query(state).await);
+    components.insert("migrations".to_string(), check_schema(state).await);
+    components.insert(
+        "diskDatabase".to_string(),
+        match state.store.data_dir().await {
+            Ok(Some(dir)) => check_disk(&dir, min_free),
+            Ok(None) if state.store.backend() == "sqlite" => ok("in-memory database"),
+            Ok(None) => ok(format!("remote {} database", state.store.backend())),
+            Err(e) => fail(format!("cannot locate database file: {}", e)),
+        },
+    );
+    components.insert("diskUploads".to_string(), check_disk(&state.config.upload_dir, min_free));
+    components.insert(
+        "shutdown".to_string(),
+        if state.shutdown.is_triggered() { fail("server is shutting down") } else { ok("running") },
+    );
+
+    let status = if components.values().all(|c| c.status == HealthStatus::Ok) {
+        HealthStatus::Ok
+    } else {
+        HealthStatus::Fail
+    };
+    HealthResponse { status, version: VERSION.to_string(), components }
+}
+
+async fn check_query(state: &AppState) -> ComponentHealth {
+    match tokio::time::timeout(DB_CHECK_TIMEOUT, state.store.ping()).await {
+        Ok(Ok(())) => ok("query ok"),
+        Ok(Err(e)) => fail(format!("query failed: {}", e)),
+        Err(_) => fail("query timed out"),
+    }
+}
+
+async fn check_schema(state: &AppState) -> ComponentHealth {
+    match tokio::time::timeout(DB_CHECK_TIMEOUT, state.store.schema_version()).await {
+        Ok(Ok(v)) if v == SCHEMA_VERSION => ok(format!("schema version {}", v)),
+        Ok(Ok(v)) => fail(format!("schema version {}, expected {}", v, SCHEMA_VERSION)),
+        Ok(Err(e)) => fail(format!("cannot read schema version: {}", e)),
+        Err(_) => fail("schema check timed out"),
+    }
+}
+
+// Spazio libero sul filesystem che contiene `dir`; se la directory non esiste ancora
+// si controlla l'antenato più vicino, dove verrà creata.
+fn check_disk(dir: &Path, min_free: u64) -> ComponentHealth {
+    let existing = dir.ancestors().find(|p| p.exists()).unwrap_or_else(|| Path::new("."));
+    let existing = if existing.as_os_str().is_empty() { Path::new(".") } else { existing };
+    match fs2::available_space(existing) {
+        Ok(free) if free >= min_free => ok(format!("{} MiB free", free / MIB)),
+        Ok(free) => fail(format!("{} MiB free, at least {} MiB required", free / MIB, min_free / MIB)),
+        Err(e) => fail(format!("cannot read free space of {:?}: {}", existing, e)),
+    }
+}
@@ -0,0 +1,71 @@
+use ruggine_core::WsMessage;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+
+/// Connessione registrata: (id connessione, coda verso il writer del socket).
+type Conn = (u64, UnboundedSender<WsMessage>);
+
+/// Registro delle connessioni WS aperte, indicizzate per utente.
+/// Un utente può avere più connessioni (es. più schede del browser): ogni evento va a tutte.
+#[derive(Default)]
+pub struct Hub {
+    next_id: AtomicU64,
+    conns: Mutex<HashMap<String, Vec<Conn>>>,
+}
+
+impl Hub {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registra una nuova connessione per l'utente.
+    /// Ritorna l'id della connessione, il sender per rispondere su questa sola connessione
+    /// e il receiver da cui la sessione legge i messaggi da scrivere sul socket.
+    pub fn register(&self, user_id: &str) -> (u64, UnboundedSender<WsMessage>, UnboundedReceiver<WsMessage>) {
+        let conn_id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (tx, rx) = unbounded_channel();
+        self.conns
+            .lock()
+            .expect("hub lock poisoned")
+            .entry(user_id.to_string())
+            .or_default()
+            .push((conn_id, tx.clone()));
+        (conn_id, tx, rx)
+    }
+
+    /// Rimuove la connessione; se era l'ultima dell'utente rimuove anche la voce dell'utente.
+    pub fn unregister(&self, user_id: &str, conn_id: u64) {
+        let mut conns = self.conns.lock().expect("hub lock poisoned");
+        if let Some(list) = conns.get_mut(user_id) {
+            list.retain(|(id, _)| *id != conn_id);
+            if list.is_empty() {
+                conns.remove(user_id);
+            }
+        }
+    }
+
+    /// Invia un messaggio a tutte le connessioni aperte dell'utente (nessun effetto se offline).
+    pub fn send_to_user(&self, user_id: &str, msg: WsMessage) {
+        let conns = self.conns.lock().expect("hub lock poisoned");
+        if let Some(list) = conns.get(user_id) {
+            for (_, tx) in list {
+                // se il receiver è già chiuso la sessione sta terminando: ignoriamo l'errore
+                let _ = tx.send(msg.clone());
+            }
+        }
+    }
+
+    /// Invia lo stesso messaggio a più utenti.
+    pub fn send_to_users<'a>(&self, user_ids: impl IntoIterator<Item = &'a String>, msg: &WsMessage) {
+        for user_id in user_ids {
+            self.send_to_user(user_id, msg.clone());
+        }
+    }
+
+    /// Numero di connessioni aperte in totale.
+    pub fn connection_count(&self) -> usize {
+        self.conns.lock().expect("hub lock poisoned").values().map(Vec::len).sum()
+    }
+}
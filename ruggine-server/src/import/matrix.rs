@@ -0,0 +1,117 @@
+/* Lettura dell'export JSON di una stanza Matrix fatto da Element: room_name, room_creator e la lista
+   degli eventi in "messages". Diventano messaggi gli m.room.message di testo (m.text, m.notice,
+   m.emote); i membri sono quelli con membership "join" nell'ultimo m.room.member di ciascuno, o tutti
+   i mittenti se l'export non contiene eventi di membership. Lo username è la parte locale dell'id
+   Matrix (@alice:example.org -> alice). */
+use anyhow::Context;
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::path::Path;
+
+use super::{sanitize_username, Export, SourceChannel, SourceMessage, SourceUser};
+
+#[derive(Debug, Deserialize)]
+struct RoomExport {
+    #[serde(default)]
+    room_id: Option<String>,
+    room_name: String,
+    #[serde(default)]
+    room_creator: Option<String>,
+    #[serde(default)]
+    messages: Vec<Event>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Event {
+    #[serde(rename = "type")]
+    kind: String,
+    sender: String,
+    origin_server_ts: i64,
+    #[serde(default)]
+    event_id: Option<String>,
+    #[serde(default)]
+    state_key: Option<String>,
+    #[serde(default)]
+    content: serde_json::Value,
+}
+
+pub fn read(path: &Path) -> anyhow::Result<Export> {
+    let raw = std::fs::read(path).with_context(|| format!("open matrix export {:?}", path))?;
+    let room: RoomExport = serde_json::from_slice(&raw).with_context(|| format!("parse matrix export {:?}", path))?;
+
+    let mut users: Vec<SourceUser> = Vec::new();
+    let mut add_user = |mxid: &str| {
+        if !users.iter().any(|u| u.key == mxid) {
+            users.push(SourceUser { key: mxid.to_string(), username: sanitize_username(localpart(mxid)) });
+        }
+    };
+    // ultima membership di ogni utente, nell'ordine di ingresso
+    let mut membership: Vec<(String, bool)> = Vec::new();
+    let mut messages = Vec::new();
+    let mut skipped = 0;
+    for (i, event) in room.messages.iter().enumerate() {
+        match event.kind.as_str() {
+            "m.room.member" => {
+                let Some(member) = event.state_key.as_deref() else {
+                    skipped += 1;
+                    continue;
+                };
+                let joined = event.content["membership"].as_str() == Some("join");
+                match membership.iter_mut().find(|(m, _)| m == member) {
+                    Some(entry) => entry.1 = joined,
+                    None => membership.push((member.to_string(), joined)),
+                }
+                add_user(member);
+                skipped += 1;
+            }
+            "m.room.message" => {
+                let body = event.content["body"].as_str();
+                let content = match (event.content["msgtype"].as_str(), body) {
+                    (Some("m.text" | "m.notice"), Some(body)) => body.to_string(),
+                    (Some("m.emote"), Some(body)) => format!("* {} {}", localpart(&event.sender), body),
+                    _ => {
+                        // allegati e messaggi cancellati (redacted) non hanno un testo da importare
+                        skipped += 1;
+                        continue;
+                    }
+                };
+                add_user(&event.sender);
+                messages.push(SourceMessage {
+                    key: event.event_id.clone().unwrap_or_else(|| format!("{}:{}", event.origin_server_ts, i)),
+                    sender: event.sender.clone(),
+                    content,
+                    timestamp_ms: event.origin_server_ts,
+                });
+            }
+            _ => skipped += 1,
+        }
+    }
+    if let Some(creator) = &room.room_creator {
+        add_user(creator);
+    }
+
+    let created_ms = room.messages.iter().find(|e| e.kind == "m.room.create").map(|e| e.origin_server_ts);
+    let members: Vec<String> = membership.into_iter().filter(|(_, joined)| *joined).map(|(m, _)| m).collect();
+    let senders: HashSet<&str> = messages.iter().map(|m: &SourceMessage| m.sender.as_str()).collect();
+    // gli utenti che non hanno scritto né fanno parte della stanza non servono
+    users.retain(|u| senders.contains(u.key.as_str()) || members.contains(&u.key) || room.room_creator.as_ref() == Some(&u.key));
+    let key = format!("matrix:{}", room.room_id.clone().unwrap_or_else(|| room.room_name.clone()));
+    Ok(Export {
+        users,
+        channels: vec![SourceChannel {
+            key,
+            name: room.room_name,
+            created_ms,
+            creator: room.room_creator,
+            members,
+            messages,
+            skipped,
+        }],
+    })
+}
+
+/// "@alice:example.org" -> "alice"
+pub fn localpart(mxid: &str) -> &str {
+    let id = mxid.strip_prefix('@').unwrap_or(mxid);
+    id.split_once(':').map(|(local, _)| local).unwrap_or(id)
+}
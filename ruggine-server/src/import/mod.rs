@@ -0,0 +1,335 @@
+/* Importazione dello storico da altri strumenti di chat: un export di workspace Slack (ZIP) o di una
+   stanza Matrix (JSON di Element) viene letto in un modello intermedio comune (utenti, canali,
+   messaggi) e poi scritto nello Store come utenti, gruppi, membership e messaggi, con i timestamp e
+   i mittenti originali. Gli utenti sono associati per username agli account Ruggine esistenti; quelli
+   nuovi vengono creati senza password (va impostata con reset-password). Gli id sono derivati dagli
+   id di origine, quindi reimportare lo stesso export salta i canali già importati. Con dry_run viene
+   solo calcolato il resoconto, senza scrivere nulla. */
+use anyhow::{bail, Context};
+use clap::ValueEnum;
+use ruggine_core::{now_timestamp, parse_mentions, Group, Message, PinPolicy, RetentionPolicy, User};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::Path;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+use crate::mentions;
+use crate::store::{Role, Store};
+
+pub mod matrix;
+pub mod slack;
+
+/// Hash salvato per gli utenti importati: nessuna password ha questo SHA-256, il login è impossibile
+/// finché un amministratore non ne imposta una.
+pub const NO_PASSWORD_HASH: &str = "!imported";
+
+/// Formato del file da importare.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ImportFormat {
+    /// ZIP dell'export di un workspace Slack
+    Slack,
+    /// JSON dell'export di una stanza Matrix (Element)
+    Matrix,
+}
+
+/// Contenuto di un export, indipendente dal formato di origine.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Export {
+    pub users: Vec<SourceUser>,
+    pub channels: Vec<SourceChannel>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceUser {
+    /// Id nel sistema di origine (es. "U024BE7LH" o "@alice:matrix.org")
+    pub key: String,
+    /// Username proposto per Ruggine
+    pub username: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceChannel {
+    pub key: String,
+    pub name: String,
+    /// Creazione in millisecondi unix, se nota
+    pub created_ms: Option<i64>,
+    /// Chiave dell'utente che diventa admin del gruppo (il creatore del canale)
+    pub creator: Option<String>,
+    /// Chiavi degli utenti membri
+    pub members: Vec<String>,
+    pub messages: Vec<SourceMessage>,
+    /// Eventi ignorati perché non sono messaggi (ingressi, cambi di argomento, messaggi di bot, ...)
+    pub skipped: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceMessage {
+    pub key: String,
+    /// Chiave dell'utente mittente
+    pub sender: String,
+    pub content: String,
+    pub timestamp_ms: i64,
+}
+
+/// Legge il file nel formato indicato.
+pub fn read(format: ImportFormat, path: &Path) -> anyhow::Result<Export> {
+    match format {
+        ImportFormat::Slack => slack::read(path),
+        ImportFormat::Matrix => matrix::read(path),
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ImportOptions {
+    /// Calcola solo il resoconto senza scrivere
+    pub dry_run: bool,
+    /// Username di un utente Ruggine esistente da aggiungere come admin a ogni gruppo importato
+    pub admin: Option<String>,
+}
+
+/// Resoconto dell'importazione (o di cosa farebbe, con dry_run).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ImportReport {
+    pub dry_run: bool,
+    /// Username degli utenti creati (senza password)
+    pub users_created: Vec<String>,
+    /// Username degli utenti associati ad account esistenti
+    pub users_matched: Vec<String>,
+    pub groups: Vec<GroupReport>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GroupReport {
+    pub name: String,
+    pub group_id: String,
+    /// false se il canale era già stato importato ed è stato saltato
+    pub imported: bool,
+    pub members: usize,
+    pub messages: usize,
+    pub skipped: usize,
+    pub first_message: Option<String>,
+    pub last_message: Option<String>,
+}
+
+impl ImportReport {
+    pub fn messages(&self) -> usize {
+        self.groups.iter().filter(|g| g.imported).map(|g| g.messages).sum()
+    }
+}
+
+impl fmt::Display for ImportReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.dry_run {
+            writeln!(f, "dry run: nothing was written")?;
+        }
+        let verb = if self.dry_run { "would import" } else { "imported" };
+        writeln!(
+            f,
+            "users: {} new, {} matched to existing accounts",
+            self.users_created.len(),
+            self.users_matched.len()
+        )?;
+        for name in &self.users_created {
+            writeln!(f, "  + {}", name)?;
+        }
+        for name in &self.users_matched {
+            writeln!(f, "  = {}", name)?;
+        }
+        if !self.users_created.is_empty() {
+            writeln!(f, "  new users have no password: set one with reset-password")?;
+        }
+        writeln!(f, "groups: {}", self.groups.len())?;
+        for g in &self.groups {
+            if !g.imported {
+                writeln!(f, "  {} ({}): already imported, skipped", g.name, g.group_id)?;
+                continue;
+            }
+            write!(f, "  {} ({}): {} {} messages, {} members", g.name, g.group_id, verb, g.messages, g.members)?;
+            if let (Some(first), Some(last)) = (&g.first_message, &g.last_message) {
+                write!(f, ", {} .. {}", first, last)?;
+            }
+            if g.skipped > 0 {
+                write!(f, ", {} non-message events skipped", g.skipped)?;
+            }
+            writeln!(f)?;
+        }
+        writeln!(f, "messages: {}", self.messages())
+    }
+}
+
+/// Id Ruggine stabile per un oggetto dell'export: stesso export, stessi id.
+pub fn import_id(kind: &str, key: &str) -> String {
+    let digest = Sha256::digest(format!("ruggine-import:{}:{}", kind, key).as_bytes());
+    let hex = format!("{:x}", digest);
+    format!("{}-{}-{}-{}-{}", &hex[..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..32])
+}
+
+/// Username valido per Ruggine (e riconosciuto dalle menzioni): i caratteri diversi da lettere,
+/// cifre, '_', '-' e '.' diventano '_'.
+pub fn sanitize_username(name: &str) -> String {
+    let cleaned: String =
+        name.chars().map(|c| if c.is_alphanumeric() || matches!(c, '_' | '-' | '.') { c } else { '_' }).collect();
+    if cleaned.is_empty() { "user".to_string() } else { cleaned }
+}
+
+/// Timestamp RFC3339 UTC (come now_timestamp) da millisecondi unix.
+pub fn timestamp_from_ms(ms: i64) -> anyhow::Result<String> {
+    let at = OffsetDateTime::from_unix_timestamp_nanos(i128::from(ms) * 1_000_000)
+        .with_context(|| format!("timestamp {} out of range", ms))?;
+    Ok(at.format(&Rfc3339)?)
+}
+
+/// Scrive l'export nello store (o, con dry_run, calcola soltanto il resoconto).
+/// Un canale che fallisce a metà viene eliminato, così può essere reimportato.
+pub async fn import(store: &dyn Store, export: &Export, options: &ImportOptions) -> anyhow::Result<ImportReport> {
+    let mut report = ImportReport { dry_run: options.dry_run, ..Default::default() };
+    let extra_admin = match &options.admin {
+        Some(username) => match store.credentials(username).await? {
+            Some(creds) => Some(creds.user.user_id),
+            None => bail!("admin user {:?} not found", username),
+        },
+        None => None,
+    };
+
+    // utenti: username univoci nell'export, poi associazione agli account esistenti
+    let mut user_ids: HashMap<&str, String> = HashMap::new();
+    let mut usernames: HashMap<String, String> = HashMap::new();
+    let mut taken: HashSet<String> = HashSet::new();
+    for source in &export.users {
+        if user_ids.contains_key(source.key.as_str()) {
+            continue;
+        }
+        let username = (1..)
+            .map(|n| if n == 1 { source.username.clone() } else { format!("{}-{}", source.username, n) })
+            .find(|u| !taken.contains(u))
+            .expect("unbounded candidates");
+        taken.insert(username.clone());
+        let user_id = match store.credentials(&username).await? {
+            Some(creds) => {
+                report.users_matched.push(username.clone());
+                creds.user.user_id
+            }
+            None => {
+                let user = User {
+                    user_id: import_id("user", &source.key),
+                    username: username.clone(),
+                    created_at: now_timestamp(),
+                };
+                if !options.dry_run {
+                    store.create_user(&user, NO_PASSWORD_HASH).await.with_context(|| format!("create user {}", username))?;
+                }
+                report.users_created.push(username.clone());
+                user.user_id
+            }
+        };
+        usernames.insert(user_id.clone(), username);
+        user_ids.insert(&source.key, user_id);
+    }
+
+    for channel in &export.channels {
+        let group_id = import_id("group", &channel.key);
+        let mut messages: Vec<&SourceMessage> =
+            channel.messages.iter().filter(|m| user_ids.contains_key(m.sender.as_str())).collect();
+        messages.sort_by_key(|m| m.timestamp_ms);
+        // membri: quelli del canale, più gli utenti che vi hanno scritto se l'export non li elenca
+        let mut members: Vec<(String, Role)> = Vec::new();
+        let keys = if channel.members.is_empty() {
+            messages.iter().map(|m| m.sender.clone()).collect()
+        } else {
+            channel.members.clone()
+        };
+        for key in keys {
+            if let Some(user_id) = user_ids.get(key.as_str())
+                && !members.iter().any(|(id, _)| id == user_id)
+            {
+                members.push((user_id.clone(), Role::Member));
+            }
+        }
+        let creator = channel.creator.as_ref().and_then(|c| user_ids.get(c.as_str()));
+        match members.iter_mut().find(|(id, _)| Some(id) == creator) {
+            Some(member) => member.1 = Role::Admin,
+            None => {
+                if let Some(first) = members.first_mut() {
+                    first.1 = Role::Admin;
+                }
+            }
+        }
+        if let Some(admin) = &extra_admin {
+            members.retain(|(id, _)| id != admin);
+            members.push((admin.clone(), Role::Admin));
+        }
+
+        let mut group_report = GroupReport {
+            name: channel.name.clone(),
+            group_id: group_id.clone(),
+            imported: true,
+            members: members.len(),
+            messages: messages.len(),
+            skipped: channel.skipped + channel.messages.len() - messages.len(),
+            first_message: messages.first().map(|m| timestamp_from_ms(m.timestamp_ms)).transpose()?,
+            last_message: messages.last().map(|m| timestamp_from_ms(m.timestamp_ms)).transpose()?,
+        };
+        if store.find_group(&group_id).await?.is_some() {
+            group_report.imported = false;
+        } else if !options.dry_run {
+            let created_at = match channel.created_ms.or(messages.first().map(|m| m.timestamp_ms)) {
+                Some(ms) => timestamp_from_ms(ms)?,
+                None => now_timestamp(),
+            };
+            let group = Group {
+                group_id: group_id.clone(),
+                name: channel.name.clone(),
+                created_at,
+                pin_policy: PinPolicy::default(),
+                encrypted: false,
+                retention: RetentionPolicy::default(),
+                message_ttl_secs: None,
+            };
+            store.create_group(&group, &members).await.with_context(|| format!("create group {}", channel.name))?;
+            if let Err(e) = import_messages(store, &group_id, &messages, &user_ids).await {
+                // il gruppo parziale viene rimosso: un nuovo tentativo lo importerà da capo
+                let _ = store.delete_group(&group_id).await;
+                return Err(e.context(format!("import messages of {}", channel.name)));
+            }
+        }
+        report.groups.push(group_report);
+    }
+    tracing::info!(
+        dry_run = options.dry_run,
+        users = report.users_created.len(),
+        groups = report.groups.len(),
+        messages = report.messages(),
+        "history import finished"
+    );
+    Ok(report)
+}
+
+async fn import_messages(
+    store: &dyn Store,
+    group_id: &str,
+    messages: &[&SourceMessage],
+    user_ids: &HashMap<&str, String>,
+) -> anyhow::Result<()> {
+    for source in messages {
+        let sender_id = &user_ids[source.sender.as_str()];
+        let message = Message {
+            message_id: import_id("message", &format!("{}:{}", group_id, source.key)),
+            group_id: group_id.to_string(),
+            sender_id: sender_id.clone(),
+            content: source.content.clone(),
+            created_at: timestamp_from_ms(source.timestamp_ms)?,
+            seq: 0,
+            reactions: Vec::new(),
+            mentions: Vec::new(),
+            expires_at: None,
+        };
+        store.insert_message(&message).await?;
+        // menzioni come per i messaggi inviati via WS: @username dei membri, escluso il mittente
+        let mentioned = mentions::resolve(store, group_id, sender_id, &parse_mentions(&message.content)).await?;
+        if !mentioned.is_empty() {
+            store.add_mentions(&message.message_id, &mentioned).await?;
+        }
+    }
+    Ok(())
+}
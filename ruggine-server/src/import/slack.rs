@@ -0,0 +1,195 @@
+/* Lettura dell'export di un workspace Slack: uno ZIP con users.json, channels.json (pubblici),
+   groups.json (privati), mpims.json e dms.json (messaggi diretti) e, per ogni canale, una cartella con
+   un file <AAAA-MM-GG>.json di messaggi per giorno (la cartella ha il nome del canale, o l'id per i
+   messaggi diretti). Il markup di Slack (<@U123>, <#C123|nome>, <!here>, <url|testo>) viene riscritto
+   in testo semplice, le menzioni come @username. I bot e gli eventi che non sono messaggi sono esclusi. */
+use anyhow::Context;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Read, Seek};
+use std::path::Path;
+
+use super::{sanitize_username, Export, SourceChannel, SourceMessage, SourceUser};
+
+// Sottotipi di messaggio che portano testo scritto da un utente
+const MESSAGE_SUBTYPES: &[&str] = &["me_message", "thread_broadcast", "file_share"];
+
+#[derive(Debug, Deserialize)]
+struct SlackUser {
+    id: String,
+    name: String,
+    #[serde(default)]
+    is_bot: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct SlackChannel {
+    id: String,
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    created: Option<i64>,
+    #[serde(default)]
+    creator: Option<String>,
+    #[serde(default)]
+    members: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SlackMessage {
+    #[serde(rename = "type", default)]
+    kind: String,
+    #[serde(default)]
+    subtype: Option<String>,
+    #[serde(default)]
+    user: Option<String>,
+    #[serde(default)]
+    text: String,
+    ts: String,
+}
+
+pub fn read(path: &Path) -> anyhow::Result<Export> {
+    let file = File::open(path).with_context(|| format!("open slack export {:?}", path))?;
+    let mut zip = zip::ZipArchive::new(file).with_context(|| format!("read slack export {:?} (not a zip file?)", path))?;
+    // alcuni export hanno tutto dentro una cartella di primo livello
+    let root = zip
+        .file_names()
+        .filter_map(|n| n.strip_suffix("users.json"))
+        .filter(|prefix| prefix.is_empty() || prefix.ends_with('/'))
+        .min_by_key(|prefix| prefix.len())
+        .map(str::to_string)
+        .context("users.json not found in slack export")?;
+
+    let users: Vec<SlackUser> = read_json(&mut zip, &format!("{}users.json", root))?.unwrap_or_default();
+    let users: Vec<SlackUser> = users.into_iter().filter(|u| !u.is_bot && u.id != "USLACKBOT").collect();
+    let names: HashMap<&str, &str> = users.iter().map(|u| (u.id.as_str(), u.name.as_str())).collect();
+
+    // (canale, cartella dei messaggi)
+    let mut channels: Vec<(SlackChannel, String)> = Vec::new();
+    for list in ["channels.json", "groups.json", "mpims.json", "dms.json"] {
+        let found: Vec<SlackChannel> = read_json(&mut zip, &format!("{}{}", root, list))?.unwrap_or_default();
+        for mut channel in found {
+            let dir = match &channel.name {
+                Some(name) => name.clone(),
+                None => {
+                    let who: Vec<&str> = channel.members.iter().map(|m| names.get(m.as_str()).copied().unwrap_or(m.as_str())).collect();
+                    channel.name = Some(format!("dm-{}", who.join("-")));
+                    channel.id.clone()
+                }
+            };
+            channels.push((channel, dir));
+        }
+    }
+    let channel_names: HashMap<String, String> =
+        channels.iter().map(|(c, _)| (c.id.clone(), c.name.clone().unwrap_or_default())).collect();
+
+    let mut export = Export {
+        users: users.iter().map(|u| SourceUser { key: u.id.clone(), username: sanitize_username(&u.name) }).collect(),
+        channels: Vec::new(),
+    };
+    for (channel, dir) in channels {
+        let prefix = format!("{}{}/", root, dir);
+        let mut days: Vec<String> = zip
+            .file_names()
+            .filter(|n| n.strip_prefix(&prefix).is_some_and(|f| f.ends_with(".json") && !f.contains('/')))
+            .map(str::to_string)
+            .collect();
+        days.sort();
+        let mut messages = Vec::new();
+        let mut skipped = 0;
+        for day in days {
+            let found: Vec<SlackMessage> = read_json(&mut zip, &day)?.unwrap_or_default();
+            for m in found {
+                let is_text = m.kind == "message" && m.subtype.as_deref().is_none_or(|s| MESSAGE_SUBTYPES.contains(&s));
+                let (Some(sender), true) = (m.user, is_text) else {
+                    skipped += 1;
+                    continue;
+                };
+                let timestamp_ms = parse_ts(&m.ts).with_context(|| format!("invalid ts {:?} in {}", m.ts, day))?;
+                messages.push(SourceMessage {
+                    key: m.ts,
+                    sender,
+                    content: plain_text(&m.text, &names, &channel_names),
+                    timestamp_ms,
+                });
+            }
+        }
+        export.channels.push(SourceChannel {
+            key: format!("slack:{}", channel.id),
+            name: channel.name.unwrap_or(channel.id),
+            created_ms: channel.created.map(|s| s * 1000),
+            creator: channel.creator,
+            members: channel.members,
+            messages,
+            skipped,
+        });
+    }
+    Ok(export)
+}
+
+// Contenuto JSON di un file dello ZIP; None se il file manca
+fn read_json<R: Read + Seek, T: serde::de::DeserializeOwned>(
+    zip: &mut zip::ZipArchive<R>,
+    name: &str,
+) -> anyhow::Result<Option<T>> {
+    let mut entry = match zip.by_name(name) {
+        Ok(entry) => entry,
+        Err(zip::result::ZipError::FileNotFound) => return Ok(None),
+        Err(e) => return Err(e).with_context(|| format!("read {}", name)),
+    };
+    let mut raw = Vec::new();
+    entry.read_to_end(&mut raw).with_context(|| format!("read {}", name))?;
+    Ok(Some(serde_json::from_slice(&raw).with_context(|| format!("parse {}", name))?))
+}
+
+/// "1355517523.000005" (secondi e microsecondi) -> millisecondi unix.
+pub fn parse_ts(ts: &str) -> Option<i64> {
+    let (seconds, fraction) = ts.split_once('.').unwrap_or((ts, ""));
+    let seconds: i64 = seconds.parse().ok()?;
+    let millis: i64 = if fraction.is_empty() { 0 } else { format!("{:0<3}", &fraction[..fraction.len().min(3)]).parse().ok()? };
+    Some(seconds * 1000 + millis)
+}
+
+/// Riscrive il markup dei messaggi Slack in testo semplice.
+pub fn plain_text(text: &str, users: &HashMap<&str, &str>, channels: &HashMap<String, String>) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('<') {
+        let Some(len) = rest[start..].find('>') else {
+            break;
+        };
+        out.push_str(&rest[..start]);
+        let inner = &rest[start + 1..start + len];
+        let (target, label) = match inner.split_once('|') {
+            Some((target, label)) => (target, Some(label)),
+            None => (inner, None),
+        };
+        if let Some(id) = target.strip_prefix('@') {
+            let name = users.get(id).map(|n| sanitize_username(n)).or(label.map(str::to_string)).unwrap_or_else(|| id.to_string());
+            out.push('@');
+            out.push_str(&name);
+        } else if let Some(id) = target.strip_prefix('#') {
+            out.push('#');
+            out.push_str(channels.get(id).map(String::as_str).or(label).unwrap_or(id));
+        } else if let Some(special) = target.strip_prefix('!') {
+            // <!here>, <!channel>, <!everyone>, <!subteam^ID|@team>
+            match label {
+                Some(label) => out.push_str(label),
+                None => {
+                    out.push('@');
+                    out.push_str(special);
+                }
+            }
+        } else {
+            let target = target.strip_prefix("mailto:").unwrap_or(target);
+            match label {
+                Some(label) if label != target => out.push_str(&format!("{} ({})", label, target)),
+                _ => out.push_str(target),
+            }
+        }
+        rest = &rest[start + len + 1..];
+    }
+    out.push_str(rest);
+    out.replace("&lt;", "<").replace("&gt;", ">").replace("&amp;", "&")
+}
@@ -1,11 +1,69 @@
 use anyhow::Context;
 use axum::http::StatusCode;
-use sqlx::SqlitePool;
+use sqlx::{
+    sqlite::{SqliteConnectOptions, SqlitePoolOptions},
+    SqlitePool,
+};
+use std::str::FromStr;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::bus::{Bus, LocalBus};
+use crate::config::Config;
+use crate::hub::Hub;
+use crate::metrics::Metrics;
+use crate::ratelimit::RateLimits;
+use crate::shutdown::Shutdown;
+use crate::store::{SqliteStore, Store};
 
 #[derive(Clone)]
 pub struct AppState {
-    pub pool: SqlitePool,
+    /// Backend di persistenza (SQLite o PostgreSQL)
+    pub store: Arc<dyn Store>,
+    /// Configurazione effettiva con cui è stato avviato il server
+    pub config: Arc<Config>,
+    /// Connessioni WS aperte su questa istanza
+    pub hub: Arc<Hub>,
+    /// Fan-out degli eventi ai membri dei gruppi, anche connessi ad altre istanze
+    pub bus: Arc<dyn Bus>,
+    /// Limiti di frequenza per IP/utente e blocco dei login falliti
+    pub limits: Arc<RateLimits>,
+    /// Coordinamento dell'arresto controllato con le sessioni WS
+    pub shutdown: Arc<Shutdown>,
+    /// Metriche Prometheus esposte su /metrics
+    pub metrics: Arc<Metrics>,
+}
+
+impl AppState {
+    /// Stato con la configurazione di default su un pool SQLite.
+    pub fn new(pool: SqlitePool) -> Self {
+        Self::with_config(pool, Config::default())
+    }
+
+    pub fn with_config(pool: SqlitePool, config: Config) -> Self {
+        Self::with_store(Arc::new(SqliteStore::new(pool)), config)
+    }
+
+    /// Stato su un backend qualsiasi, già migrato.
+    pub fn with_store(store: Arc<dyn Store>, config: Config) -> Self {
+        let hub = Arc::new(Hub::new());
+        Self {
+            store,
+            bus: Arc::new(LocalBus::new(hub.clone())),
+            hub,
+            limits: Arc::new(RateLimits::new(config.rate_limits)),
+            shutdown: Arc::new(Shutdown::new()),
+            metrics: Arc::new(Metrics::new()),
+            config: Arc::new(config),
+        }
+    }
+
+    /// Sostituisce il bus di fan-out (di default LocalBus); l'Hub diventa quello del bus.
+    pub fn with_bus(mut self, bus: Arc<dyn Bus>) -> Self {
+        self.hub = bus.hub().clone();
+        self.bus = bus;
+        self
+    }
 }
 
 // Dato un percorso di file, restituisce un URL SQLite valido. Crea le directory genitrici se non esistono.
@@ -21,6 +79,7 @@ pub fn sqlite_url_for_path(p: &Path) -> anyhow::Result<String> {
     }
     std::fs::OpenOptions::new()
         .create(true)
+        .truncate(false)
         .write(true)
         .open(&abs)
         .with_context(|| format!("create/open sqlite file {:?}", abs))?;
@@ -32,28 +91,107 @@ pub fn sqlite_url_for_path(p: &Path) -> anyhow::Result<String> {
 /// Se non è impostata, usa "ruggine.db" nella directory corrente.
 pub fn build_sqlite_url() -> anyhow::Result<String> {
     let raw = std::env::var("DATABASE_URL").unwrap_or_else(|_| "ruggine.db".to_string());
-    if raw == "sqlite::memory:" {
-        return Ok(raw);
+    sqlite_url_from(&raw)
+}
+
+/// Crea un DB URL SQLite da un percorso di file o da un URL "sqlite://...".
+pub fn sqlite_url_from(raw: &str) -> anyhow::Result<String> {
+    match sqlite_path_from(raw) {
+        Some(path) => sqlite_url_for_path(&path),
+        None => Ok(SQLITE_MEMORY_URL.to_string()),
     }
-    // Rimuovi il prefisso "sqlite://" se presente, per ottenere il percorso del file. 
+}
+
+/// Percorso del file SQLite indicato da un percorso o da un URL "sqlite://..." (None per il DB in memoria).
+pub fn sqlite_path_from(raw: &str) -> Option<PathBuf> {
+    if is_memory_url(raw) {
+        return None;
+    }
+    // Rimuovi il prefisso "sqlite://" se presente, per ottenere il percorso del file.
     let path_part = if raw.starts_with("sqlite://") {
-        raw.trim_start_matches("sqlite:///")
-            .trim_start_matches("sqlite://")
-            .to_string()
+        raw.trim_start_matches("sqlite:///").trim_start_matches("sqlite://")
     } else {
         raw
     };
-    sqlite_url_for_path(&PathBuf::from(path_part))
+    Some(PathBuf::from(path_part))
+}
+
+/// URL del database SQLite in memoria.
+pub const SQLITE_MEMORY_URL: &str = "sqlite::memory:";
+
+/// true per gli URL che indicano il database in memoria ("sqlite::memory:" o ":memory:").
+pub fn is_memory_url(url: &str) -> bool {
+    url == SQLITE_MEMORY_URL || url == ":memory:"
 }
 
 // Connect to the database and return a connection pool.
 pub async fn connect_pool(db_url: &str) -> anyhow::Result<SqlitePool> {
+    if db_url == SQLITE_MEMORY_URL {
+        return connect_memory_pool(SqlitePoolOptions::new()).await;
+    }
     let pool = SqlitePool::connect(db_url)
         .await
         .with_context(|| format!("connect to sqlite via {}", db_url))?;
     Ok(pool)
 }
 
+// Come connect_pool, ma con un numero massimo di connessioni esplicito.
+pub async fn connect_pool_sized(db_url: &str, max_connections: u32) -> anyhow::Result<SqlitePool> {
+    if db_url == SQLITE_MEMORY_URL {
+        return connect_memory_pool(SqlitePoolOptions::new().max_connections(max_connections)).await;
+    }
+    let pool = SqlitePoolOptions::new()
+        .max_connections(max_connections)
+        .connect(db_url)
+        .await
+        .with_context(|| format!("connect to sqlite via {}", db_url))?;
+    Ok(pool)
+}
+
+/* Database in memoria condiviso da tutte le connessioni del pool: un nome univoco con cache condivisa
+   (ogni chiamata crea un database distinto) e connessioni mai chiuse per inattività o età, perché
+   SQLite elimina il database quando si chiude l'ultima connessione. */
+async fn connect_memory_pool(options: SqlitePoolOptions) -> anyhow::Result<SqlitePool> {
+    let name = format!("sqlite:file:ruggine-memory-{}?mode=memory&cache=shared", uuid::Uuid::new_v4().simple());
+    let connect = SqliteConnectOptions::from_str(&name).context("in-memory sqlite options")?;
+    let pool = options
+        .min_connections(1)
+        .idle_timeout(None)
+        .max_lifetime(None)
+        .connect_with(connect)
+        .await
+        .context("open in-memory sqlite database")?;
+    Ok(pool)
+}
+
+/// Versione dello schema prodotta da run_migrations (salvata in PRAGMA user_version);
+/// va incrementata ad ogni modifica dello schema.
+pub const SCHEMA_VERSION: i64 = 10;
+
+const WEBHOOK_DELIVERIES_TABLE: &str = r#"
+        CREATE TABLE IF NOT EXISTS webhook_deliveries (
+            delivery_id      TEXT PRIMARY KEY,
+            webhook_id       TEXT NOT NULL,
+            group_id         TEXT NOT NULL,
+            event            TEXT NOT NULL,
+            message_id       TEXT,
+            user_id          TEXT,
+            status           TEXT NOT NULL,
+            attempts         INTEGER NOT NULL DEFAULT 0,
+            next_attempt_at  INTEGER,
+            last_status_code INTEGER,
+            last_error       TEXT,
+            created_at       TEXT NOT NULL,
+            finished_at      INTEGER,
+            FOREIGN KEY(webhook_id) REFERENCES webhooks(webhook_id) ON DELETE CASCADE
+        );"#;
+
+const WEBHOOK_DELIVERIES_INDEXES: [&str; 3] = [
+    "CREATE INDEX IF NOT EXISTS webhook_deliveries_due ON webhook_deliveries(status, next_attempt_at);",
+    "CREATE INDEX IF NOT EXISTS webhook_deliveries_webhook ON webhook_deliveries(webhook_id);",
+    "CREATE INDEX IF NOT EXISTS webhook_deliveries_finished ON webhook_deliveries(finished_at) WHERE finished_at IS NOT NULL;",
+];
+
 // Esegue le migrazioni del database. Crea le tabelle se non esistono.
 pub async fn run_migrations(pool: &SqlitePool) -> anyhow::Result<()> {
     // Enable foreign keys (SQLite)
@@ -62,6 +200,11 @@ pub async fn run_migrations(pool: &SqlitePool) -> anyhow::Result<()> {
         .await
         .context("enable foreign_keys")?;
 
+    let previous: i64 = sqlx::query_scalar("PRAGMA user_version")
+        .fetch_one(pool)
+        .await
+        .context("read schema version")?;
+
     let stmts = [
         r#"
         CREATE TABLE IF NOT EXISTS users (
@@ -69,13 +212,20 @@ pub async fn run_migrations(pool: &SqlitePool) -> anyhow::Result<()> {
             username     TEXT NOT NULL UNIQUE,
             password_hash TEXT NOT NULL,
             token        TEXT,
-            created_at   TEXT NOT NULL
+            created_at   TEXT NOT NULL,
+            token_expires_at INTEGER
         );"#,
         r#"
         CREATE TABLE IF NOT EXISTS groups (
             group_id   TEXT PRIMARY KEY,
             name       TEXT NOT NULL,
-            created_at TEXT NOT NULL
+            created_at TEXT NOT NULL,
+            pin_policy TEXT NOT NULL DEFAULT 'members',
+            last_seq   INTEGER NOT NULL DEFAULT 0,
+            encrypted  INTEGER NOT NULL DEFAULT 0,
+            retention_max_age_secs INTEGER,
+            retention_max_count    INTEGER,
+            message_ttl_secs       INTEGER
         );"#,
         r#"
         CREATE TABLE IF NOT EXISTS messages (
@@ -84,6 +234,8 @@ pub async fn run_migrations(pool: &SqlitePool) -> anyhow::Result<()> {
             sender_id  TEXT NOT NULL,
             content    TEXT NOT NULL,
             created_at TEXT NOT NULL,
+            group_seq  INTEGER NOT NULL DEFAULT 0,
+            expires_at INTEGER,
             FOREIGN KEY(group_id) REFERENCES groups(group_id),
             FOREIGN KEY(sender_id) REFERENCES users(user_id)
         );"#,
@@ -93,6 +245,8 @@ pub async fn run_migrations(pool: &SqlitePool) -> anyhow::Result<()> {
             group_id      TEXT NOT NULL,
             user_id       TEXT NOT NULL,
             joined_at     TEXT NOT NULL,
+            role          TEXT NOT NULL DEFAULT 'member',
+            muted         INTEGER NOT NULL DEFAULT 0,
             FOREIGN KEY(group_id) REFERENCES groups(group_id),
             FOREIGN KEY(user_id)  REFERENCES users(user_id)
         );"#,
@@ -104,6 +258,93 @@ pub async fn run_migrations(pool: &SqlitePool) -> anyhow::Result<()> {
             created_at TEXT NOT NULL,
             FOREIGN KEY(group_id) REFERENCES groups(group_id)
         );"#,
+        r#"
+        CREATE TABLE IF NOT EXISTS reactions (
+            message_id TEXT NOT NULL,
+            user_id    TEXT NOT NULL,
+            emoji      TEXT NOT NULL,
+            created_at TEXT NOT NULL,
+            PRIMARY KEY(message_id, user_id, emoji),
+            FOREIGN KEY(message_id) REFERENCES messages(message_id) ON DELETE CASCADE,
+            FOREIGN KEY(user_id)    REFERENCES users(user_id)
+        );"#,
+        r#"
+        CREATE TABLE IF NOT EXISTS mentions (
+            message_id TEXT NOT NULL,
+            user_id    TEXT NOT NULL,
+            PRIMARY KEY(message_id, user_id),
+            FOREIGN KEY(message_id) REFERENCES messages(message_id) ON DELETE CASCADE,
+            FOREIGN KEY(user_id)    REFERENCES users(user_id)
+        );"#,
+        r#"
+        CREATE TABLE IF NOT EXISTS pinned_messages (
+            message_id TEXT PRIMARY KEY,
+            group_id   TEXT NOT NULL,
+            pinned_by  TEXT NOT NULL,
+            pinned_at  TEXT NOT NULL,
+            FOREIGN KEY(message_id) REFERENCES messages(message_id) ON DELETE CASCADE,
+            FOREIGN KEY(group_id)   REFERENCES groups(group_id),
+            FOREIGN KEY(pinned_by)  REFERENCES users(user_id)
+        );"#,
+        // key directory per i gruppi cifrati end-to-end: una chiave pubblica corrente per utente
+        r#"
+        CREATE TABLE IF NOT EXISTS public_keys (
+            user_id    TEXT PRIMARY KEY,
+            key_id     TEXT NOT NULL,
+            alg        TEXT NOT NULL,
+            public_key TEXT NOT NULL,
+            created_at TEXT NOT NULL,
+            FOREIGN KEY(user_id) REFERENCES users(user_id)
+        );"#,
+        r#"
+        CREATE TABLE IF NOT EXISTS sender_keys (
+            group_id         TEXT NOT NULL,
+            sender_id        TEXT NOT NULL,
+            sender_key_id    TEXT NOT NULL,
+            recipient_id     TEXT NOT NULL,
+            recipient_key_id TEXT NOT NULL,
+            sealed_key       TEXT NOT NULL,
+            created_at       TEXT NOT NULL,
+            PRIMARY KEY(group_id, sender_id, sender_key_id, recipient_id),
+            FOREIGN KEY(group_id)     REFERENCES groups(group_id),
+            FOREIGN KEY(sender_id)    REFERENCES users(user_id),
+            FOREIGN KEY(recipient_id) REFERENCES users(user_id)
+        );"#,
+        // messaggi programmati (versione 7): send_at in secondi unix, spostati in messages alla pubblicazione
+        r#"
+        CREATE TABLE IF NOT EXISTS scheduled_messages (
+            message_id TEXT PRIMARY KEY,
+            group_id   TEXT NOT NULL,
+            sender_id  TEXT NOT NULL,
+            content    TEXT NOT NULL,
+            send_at    INTEGER NOT NULL,
+            ttl_secs   INTEGER,
+            created_at TEXT NOT NULL,
+            FOREIGN KEY(group_id)  REFERENCES groups(group_id),
+            FOREIGN KEY(sender_id) REFERENCES users(user_id)
+        );"#,
+        "CREATE INDEX IF NOT EXISTS scheduled_messages_send_at ON scheduled_messages(send_at);",
+        // webhook in uscita dei gruppi e coda delle consegne (versione 8); events separati da virgole,
+        // istanti delle consegne in secondi unix
+        r#"
+        CREATE TABLE IF NOT EXISTS webhooks (
+            webhook_id TEXT PRIMARY KEY,
+            group_id   TEXT NOT NULL,
+            url        TEXT NOT NULL,
+            events     TEXT NOT NULL,
+            secret     TEXT NOT NULL,
+            enabled    INTEGER NOT NULL DEFAULT 1,
+            created_by TEXT NOT NULL,
+            created_at TEXT NOT NULL,
+            consecutive_failures INTEGER NOT NULL DEFAULT 0,
+            FOREIGN KEY(group_id)   REFERENCES groups(group_id),
+            FOREIGN KEY(created_by) REFERENCES users(user_id)
+        );"#,
+        WEBHOOK_DELIVERIES_TABLE,
+        "CREATE INDEX IF NOT EXISTS webhooks_group ON webhooks(group_id);",
+        WEBHOOK_DELIVERIES_INDEXES[0],
+        WEBHOOK_DELIVERIES_INDEXES[1],
+        WEBHOOK_DELIVERIES_INDEXES[2],
     ];
     // applica ogni statement di migrazione
     for s in &stmts {
@@ -112,11 +353,131 @@ pub async fn run_migrations(pool: &SqlitePool) -> anyhow::Result<()> {
             .await
             .with_context(|| format!("apply migration: {}", &s[..s.len().min(40)].replace('\n', " ")))?;
     }
+
+    // colonne aggiunte dopo la prima versione dello schema
+    ensure_column(pool, "groups", "pin_policy", "TEXT NOT NULL DEFAULT 'members'").await?;
+    ensure_column(pool, "memberships", "role", "TEXT NOT NULL DEFAULT 'member'").await?;
+    // scadenza del token in secondi unix; NULL per i token emessi prima dell'introduzione della scadenza
+    ensure_column(pool, "users", "token_expires_at", "INTEGER").await?;
+    // numerazione per gruppo dei messaggi (versione 2): groups.last_seq è l'ultimo seq assegnato
+    ensure_column(pool, "messages", "group_seq", "INTEGER NOT NULL DEFAULT 0").await?;
+    ensure_column(pool, "groups", "last_seq", "INTEGER NOT NULL DEFAULT 0").await?;
+    // gruppi cifrati end-to-end (versione 3)
+    ensure_column(pool, "groups", "encrypted", "INTEGER NOT NULL DEFAULT 0").await?;
+    // account disabilitati dai comandi di amministrazione (versione 4)
+    ensure_column(pool, "users", "disabled", "INTEGER NOT NULL DEFAULT 0").await?;
+    // limiti di conservazione per gruppo, NULL = default del server (versione 5)
+    ensure_column(pool, "groups", "retention_max_age_secs", "INTEGER").await?;
+    ensure_column(pool, "groups", "retention_max_count", "INTEGER").await?;
+    // messaggi effimeri: scadenza in secondi unix e durata di default per gruppo (versione 6)
+    ensure_column(pool, "messages", "expires_at", "INTEGER").await?;
+    ensure_column(pool, "groups", "message_ttl_secs", "INTEGER").await?;
+    // gruppi silenziati dal singolo membro (versione 9)
+    ensure_column(pool, "memberships", "muted", "INTEGER NOT NULL DEFAULT 0").await?;
+    // consegne degli eventi member.joined/member.left, senza messaggio (versione 10)
+    ensure_column(pool, "webhook_deliveries", "user_id", "TEXT").await?;
+    relax_delivery_message_id(pool).await?;
+    if previous < 2 {
+        // i messaggi esistenti (anche di DB anteriori a user_version) sono numerati nell'ordine di inserimento
+        sqlx::query(
+            "UPDATE messages SET group_seq = (SELECT COUNT(*) FROM messages m \
+             WHERE m.group_id = messages.group_id AND m.rowid <= messages.rowid)",
+        )
+        .execute(pool)
+        .await
+        .context("number existing messages")?;
+        sqlx::query("UPDATE groups SET last_seq = (SELECT COALESCE(MAX(group_seq), 0) FROM messages WHERE group_id = groups.group_id)")
+            .execute(pool)
+            .await
+            .context("initialize group sequences")?;
+    }
+    sqlx::query("CREATE UNIQUE INDEX IF NOT EXISTS messages_group_seq ON messages(group_id, group_seq)")
+        .execute(pool)
+        .await
+        .context("create messages_group_seq index")?;
+    sqlx::query("CREATE INDEX IF NOT EXISTS messages_expires_at ON messages(expires_at) WHERE expires_at IS NOT NULL")
+        .execute(pool)
+        .await
+        .context("create messages_expires_at index")?;
+
+    // PRAGMA non accetta parametri: il valore è una costante numerica
+    sqlx::query(&format!("PRAGMA user_version = {}", SCHEMA_VERSION))
+        .execute(pool)
+        .await
+        .context("record schema version")?;
+    Ok(())
+}
+
+// Nei DB creati con la versione 8 webhook_deliveries.message_id è NOT NULL: SQLite non permette di
+// togliere il vincolo con ALTER TABLE, per cui la tabella viene ricostruita copiando le righe.
+async fn relax_delivery_message_id(pool: &SqlitePool) -> anyhow::Result<()> {
+    let not_null: Option<bool> =
+        sqlx::query_scalar("SELECT \"notnull\" FROM pragma_table_info('webhook_deliveries') WHERE name = 'message_id'")
+            .fetch_optional(pool)
+            .await
+            .context("read webhook_deliveries.message_id")?;
+    if not_null != Some(true) {
+        return Ok(());
+    }
+    let columns = "delivery_id, webhook_id, group_id, event, message_id, user_id, status, attempts, \
+                   next_attempt_at, last_status_code, last_error, created_at, finished_at";
+    let mut tx = pool.begin().await?;
+    // gli indici seguono la tabella rinominata e vengono eliminati con lei
+    sqlx::query("ALTER TABLE webhook_deliveries RENAME TO webhook_deliveries_old").execute(&mut tx).await?;
+    sqlx::query(WEBHOOK_DELIVERIES_TABLE).execute(&mut tx).await?;
+    sqlx::query(&format!("INSERT INTO webhook_deliveries ({0}) SELECT {0} FROM webhook_deliveries_old", columns))
+        .execute(&mut tx)
+        .await?;
+    sqlx::query("DROP TABLE webhook_deliveries_old").execute(&mut tx).await?;
+    for index in WEBHOOK_DELIVERIES_INDEXES {
+        sqlx::query(index).execute(&mut tx).await?;
+    }
+    tx.commit().await.context("rebuild webhook_deliveries")?;
     Ok(())
 }
 
+// Aggiunge la colonna se manca: CREATE TABLE IF NOT EXISTS non modifica le tabelle di DB già esistenti.
+async fn ensure_column(pool: &SqlitePool, table: &str, column: &str, decl: &str) -> anyhow::Result<()> {
+    let columns: Vec<String> = sqlx::query_scalar(&format!("SELECT name FROM pragma_table_info('{}')", table))
+        .fetch_all(pool)
+        .await
+        .with_context(|| format!("read columns of {}", table))?;
+    if !columns.iter().any(|c| c == column) {
+        sqlx::query(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, decl))
+            .execute(pool)
+            .await
+            .with_context(|| format!("add column {}.{}", table, column))?;
+    }
+    Ok(())
+}
+
+pub mod admin;
+pub mod backup;
+pub mod bus;
+pub mod config;
 pub mod controllers;
+pub mod db;
+pub mod encryption;
+pub mod ephemeral;
+pub mod health;
+pub mod hub;
+pub mod import;
+pub mod mentions;
+pub mod metrics;
+pub mod pins;
+pub mod ratelimit;
+pub mod reactions;
+pub mod retention;
 pub mod routes;
+pub mod scheduled;
+pub mod security;
+pub mod shutdown;
+pub mod store;
+pub mod telemetry;
+pub mod tls;
+pub mod web;
+pub mod webhooks;
+pub mod ws;
 
 /// Controlla lo stato di salute del database tentando di acquisire una connessione dal pool.
 pub async fn health_with_pool(pool: &SqlitePool) -> StatusCode {
@@ -124,4 +485,130 @@ pub async fn health_with_pool(pool: &SqlitePool) -> StatusCode {
         Ok(_) => StatusCode::OK,
         Err(_) => StatusCode::SERVICE_UNAVAILABLE,
     }
+}
+
+/// Come health_with_pool, per un backend qualsiasi.
+pub async fn health_with_store(store: &dyn Store) -> StatusCode {
+    match store.ping().await {
+        Ok(()) => StatusCode::OK,
+        Err(_) => StatusCode::SERVICE_UNAVAILABLE,
+    }
+}
+
+/// Serve l'applicazione sul listener finché `signal` non si risolve, poi esegue l'arresto controllato:
+/// nessuna nuova connessione, richieste HTTP in corso completate, sessioni WS avvisate e chiuse
+/// (ognuna dopo aver terminato il comando in corso), infine chiusura del pool del database.
+pub async fn serve(
+    listener: tokio::net::TcpListener,
+    state: Arc<AppState>,
+    signal: impl std::future::Future<Output = ()> + Send + 'static,
+) -> anyhow::Result<()> {
+    let app = routes::router(state.clone());
+    let shutdown = state.shutdown.clone();
+    // con connect info gli handler conoscono l'indirizzo del client (usato dal rate limiting per IP)
+    axum::serve(listener, app.into_make_service_with_connect_info::<std::net::SocketAddr>())
+        .with_graceful_shutdown(async move {
+            signal.await;
+            tracing::info!("shutdown requested, draining connections");
+            shutdown.trigger();
+        })
+        .await
+        .context("server shutdown")?;
+    drain(&state).await;
+    Ok(())
+}
+
+/// Come serve, ma le connessioni sono cifrate con TLS (https:// e wss://) usando il certificato
+/// corrente di `cert`, che può essere ricaricato a caldo.
+pub async fn serve_tls(
+    listener: tokio::net::TcpListener,
+    state: Arc<AppState>,
+    cert: Arc<tls::ReloadingCert>,
+    signal: impl std::future::Future<Output = ()> + Send + 'static,
+) -> anyhow::Result<()> {
+    use tower::Service;
+
+    let app = routes::router(state.clone());
+    let acceptor = cert.acceptor();
+    // ogni connessione tiene un clone del sender: quando sono stati tutti rilasciati recv ritorna None
+    let (open_tx, mut open_rx) = tokio::sync::mpsc::channel::<()>(1);
+    tokio::pin!(signal);
+    loop {
+        let (stream, remote) = tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok(conn) => conn,
+                Err(e) => {
+                    // errori transitori (es. troppi file aperti): non fermano il server
+                    tracing::warn!(error = %e, "accept failed");
+                    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+                    continue;
+                }
+            },
+            _ = &mut signal => break,
+        };
+        let acceptor = acceptor.clone();
+        let app = app.clone();
+        let shutdown = state.shutdown.clone();
+        let open = open_tx.clone();
+        tokio::spawn(async move {
+            let _open = open;
+            let stream = match tokio::time::timeout(tls::HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await {
+                Ok(Ok(stream)) => stream,
+                Ok(Err(e)) => {
+                    tracing::debug!(%remote, error = %e, "tls handshake failed");
+                    return;
+                }
+                Err(_) => {
+                    tracing::debug!(%remote, "tls handshake timed out");
+                    return;
+                }
+            };
+            // come into_make_service_with_connect_info: gli handler vedono l'indirizzo del client
+            let service = hyper::service::service_fn(move |mut req: axum::extract::Request<hyper::body::Incoming>| {
+                req.extensions_mut().insert(axum::extract::ConnectInfo(remote));
+                app.clone().call(req)
+            });
+            let conn = hyper::server::conn::http1::Builder::new()
+                .serve_connection(hyper_util::rt::TokioIo::new(stream), service)
+                .with_upgrades();
+            tokio::pin!(conn);
+            let result = tokio::select! {
+                result = conn.as_mut() => result,
+                _ = shutdown.triggered() => {
+                    conn.as_mut().graceful_shutdown();
+                    conn.await
+                }
+            };
+            if let Err(e) = result {
+                tracing::debug!(%remote, error = %e, "connection error");
+            }
+        });
+    }
+
+    tracing::info!("shutdown requested, draining connections");
+    state.shutdown.trigger();
+    drop(listener);
+    drop(open_tx);
+    // come per le sessioni WS l'attesa è limitata: una richiesta bloccata non ferma l'arresto
+    let timeout = std::time::Duration::from_secs(state.config.shutdown_timeout_secs);
+    if tokio::time::timeout(timeout, open_rx.recv()).await.is_err() {
+        tracing::warn!(
+            connections = open_rx.sender_strong_count(),
+            "tls connections still open after shutdown timeout"
+        );
+    }
+    drain(&state).await;
+    Ok(())
+}
+
+// Attende la chiusura delle sessioni WS (al più shutdown_timeout_secs) e chiude il database
+async fn drain(state: &AppState) {
+    let timeout = std::time::Duration::from_secs(state.config.shutdown_timeout_secs);
+    if !state.shutdown.wait_sessions(timeout).await {
+        tracing::warn!(
+            sessions = state.shutdown.active_sessions(),
+            "websocket sessions still open after shutdown timeout"
+        );
+    }
+    state.store.close().await;
 }
\ No newline at end of file
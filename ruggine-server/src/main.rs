@@ -1,29 +1,126 @@
 use std::net::SocketAddr;
+use std::path::PathBuf;
 use std::sync::Arc;
 use anyhow::Context;
+use clap::{Parser, Subcommand};
 
 // ri-utilizziamo le funzioni e strutture definite in lib.rs
-use ruggine_server::{build_sqlite_url, connect_pool, run_migrations, AppState, routes};
+use ruggine_server::{
+    config::{Config, ConfigArgs},
+    admin, backup, bus, encryption, ephemeral, is_memory_url, retention, scheduled, serve, serve_tls, shutdown, store, telemetry, tls, web, webhooks,
+    AppState,
+};
+
+/// Server di chat Ruggine (HTTP + WebSocket).
+#[derive(Debug, Parser)]
+#[command(version, about)]
+struct Cli {
+    #[command(flatten)]
+    config: ConfigArgs,
+    /// Stampa la configurazione effettiva (TOML) ed esce
+    #[arg(long)]
+    print_config: bool,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+/// Comandi di amministrazione; senza comando il server si avvia.
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Stampa una nuova riga "<key_id> <base64>" per il file delle chiavi di cifratura a riposo
+    GenerateKey,
+    /// Ricifra con la chiave attiva (la prima del file encryption_key_file) messaggi, messaggi
+    /// programmati e segreti dei webhook cifrati con chiavi precedenti o ancora in chiaro; gli allegati
+    /// sotto upload_dir sono fuori dallo scope e non vengono toccati
+    RotateKey,
+    /// Scrive un backup online del database (anche a server avviato) in backup_dir
+    Backup {
+        /// Directory di destinazione al posto di backup_dir
+        #[arg(long, value_name = "DIR")]
+        dir: Option<PathBuf>,
+    },
+    /// Sostituisce il database con un backup, dopo averne verificato integrità e versione dello schema
+    /// (il server deve essere fermo)
+    Restore { file: PathBuf },
+    #[command(flatten)]
+    Admin(admin::AdminCommand),
+}
 
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    // Costruisci l'URL del database SQLite
-    let db_url = build_sqlite_url().context("build sqlite DATABASE_URL")?;
-    println!("Using DATABASE_URL = {}", db_url);
-    // Connetti al database
-    let pool = connect_pool(&db_url).await.context("connect to sqlite")?;
-    // Esegui le migrazioni del database
-    run_migrations(&pool).await.context("run migrations")?;
-    // Crea lo stato dell'applicazione condiviso
-    let state = Arc::new(AppState { pool });
-    // Configura le rotte dell'applicazione
-    let app = routes::router(state.clone());
-    // Ottieni l'indirizzo di binding dal env o usa il default
-    let bind = std::env::var("BIND_ADDR").unwrap_or_else(|_| "127.0.0.1:3000".to_string());
+    let cli = Cli::parse();
+    // Configurazione: default < file TOML < variabili d'ambiente < flag
+    let config = Config::load(&cli.config)?;
+    if cli.print_config {
+        print!("{}", config.to_toml()?);
+    }
+    config.validate()?;
+    if cli.print_config {
+        return Ok(());
+    }
+    match cli.command {
+        Some(Command::GenerateKey) => {
+            println!("{}", encryption::Keyring::generate_key_line()?);
+            return Ok(());
+        }
+        Some(Command::RotateKey) => return rotate_key(&config).await,
+        Some(Command::Backup { dir }) => return run_backup(&config, dir).await,
+        Some(Command::Restore { file }) => {
+            let restored = backup::restore(&config, &file).await?;
+            if let Some(previous) = &restored.previous {
+                println!("previous database moved to {}", previous.display());
+            }
+            println!(
+                "restored {} from {} (schema version {})",
+                restored.database.display(),
+                file.display(),
+                restored.schema_version
+            );
+            return Ok(());
+        }
+        Some(Command::Admin(command)) => return run_admin(&config, command).await,
+        None => {}
+    }
+    // Log strutturati (testo o JSON) filtrati secondo log_level
+    telemetry::init(&config);
+
+    // Connetti al database (SQLite o PostgreSQL secondo lo schema dell'URL) ed esegui le migrazioni
+    let store = store::connect(&config).await.context("open database")?;
+    tracing::info!(backend = store.backend(), "using database");
+    if is_memory_url(&config.database_url) {
+        tracing::warn!("using an in-memory database: all data is lost when the server stops");
+    }
+    match (&config.web_dist_dir, web::EMBEDDED) {
+        (Some(dir), _) => tracing::info!(dir = ?dir, "serving web client"),
+        (None, true) => tracing::info!("serving embedded web client"),
+        (None, false) => {}
+    }
+    std::fs::create_dir_all(&config.upload_dir)
+        .with_context(|| format!("create upload dir {:?}", config.upload_dir))?;
     // converte la stringa bind in un socketAddr -> il tipo della libreria standard che rappresenta host + porta
-    let addr: SocketAddr = bind.parse().context("parse BIND_ADDR")?;
-    println!("Listening on http://{}", addr);
+    // (già verificata da validate)
+    let addr: SocketAddr = config.bind_addr.parse().context("parse bind_addr")?;
+    // Crea lo stato dell'applicazione condiviso
+    let state = AppState::with_store(store, config);
+    // fan-out verso le altre istanze, se configurato
+    let bus = bus::connect(&state.config, state.hub.clone()).await.context("connect fan-out bus")?;
+    tracing::info!(bus = bus.name(), "fan-out bus ready");
+    let state = Arc::new(state.with_bus(bus));
+    // pulizia periodica dei messaggi oltre i limiti di conservazione
+    retention::spawn(state.clone());
+    // eliminazione dei messaggi effimeri scaduti
+    ephemeral::spawn(state.clone());
+    // pubblicazione dei messaggi programmati
+    scheduled::spawn(state.clone());
+    // invio delle consegne dei webhook in uscita
+    webhooks::spawn(state.clone());
+    // certificato TLS caricato prima del bind: se non è valido il server non parte
+    let cert = match (&state.config.tls_cert_file, &state.config.tls_key_file) {
+        (Some(cert), Some(key)) => Some(Arc::new(tls::ReloadingCert::load(cert, key)?)),
+        _ => None,
+    };
+    tracing::info!(%addr, tls = cert.is_some(), "listening");
     // Crea il listener TCP, un socket tcp e lo lega all'indirizzo addr
     /*
      *Note su comportamento: il TcpListener::bind crea il socket
@@ -35,11 +132,76 @@ async fn main() -> anyhow::Result<()> {
     // Avvia il server Axum
     /*
      *Cosa fa: avvia il server HTTP che accetta connessioni sul listener e instrada
-     * le richieste usando il Router/service creato (app.into_make_service()).
+     * le richieste usando il Router creato da routes::router; alla ricezione di SIGINT/SIGTERM
+     * esegue l'arresto controllato (vedi ruggine_server::serve) e chiude il pool del database.
      */
-    axum::serve(listener, app.into_make_service())
-        .await
-        .context("server shutdown")?;
+    match cert {
+        Some(cert) => {
+            let interval = std::time::Duration::from_secs(state.config.tls_reload_interval_secs);
+            cert.spawn_reloader(interval, state.shutdown.clone());
+            if let Some(redirect_addr) = &state.config.tls_redirect_addr {
+                let redirect = tokio::net::TcpListener::bind(redirect_addr)
+                    .await
+                    .with_context(|| format!("bind redirect listener {}", redirect_addr))?;
+                tracing::info!(addr = %redirect_addr, "redirecting http to https");
+                let https_port = listener.local_addr().context("local addr")?.port();
+                let shutdown = state.shutdown.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = tls::serve_redirect(redirect, https_port, shutdown).await {
+                        tracing::error!(error = %format!("{:#}", e), "redirect listener failed");
+                    }
+                });
+            }
+            serve_tls(listener, state, cert, shutdown::signal()).await?;
+        }
+        None => serve(listener, state, shutdown::signal()).await?,
+    }
+    tracing::info!("server stopped");
+
+    Ok(())
+}
+
+// Comando rotate-key: lavora sul backend senza cifratura per leggere i contenuti come sono salvati
+async fn rotate_key(config: &Config) -> anyhow::Result<()> {
+    let path = config
+        .encryption_key_file
+        .as_ref()
+        .context("rotate-key requires encryption_key_file (--encryption-key-file or RUGGINE_ENCRYPTION_KEY_FILE)")?;
+    let keyring = encryption::Keyring::load(path)?;
+    let store = store::open(config).await.context("open database")?;
+    let report = encryption::rotate(store.as_ref(), &keyring).await;
+    store.close().await;
+    let report = report?;
+    println!(
+        "re-encrypted {} messages, {} scheduled messages and {} webhook secrets with key {}",
+        report.messages,
+        report.scheduled,
+        report.webhook_secrets,
+        keyring.active_key_id()
+    );
+    Ok(())
+}
+
+// Comandi di amministrazione: stesso database (e stessa cifratura a riposo) del server
+async fn run_admin(config: &Config, command: admin::AdminCommand) -> anyhow::Result<()> {
+    if is_memory_url(&config.database_url) {
+        anyhow::bail!("admin commands need a persistent database_url, not an in-memory database");
+    }
+    let store = store::connect(config).await.context("open database")?;
+    let result = admin::run(store.as_ref(), command, std::io::stdin().lock(), std::io::stdout().lock()).await;
+    store.close().await;
+    result
+}
 
+// Comando backup: il backend senza cifratura basta, la copia contiene i dati come sono salvati
+async fn run_backup(config: &Config, dir: Option<PathBuf>) -> anyhow::Result<()> {
+    if is_memory_url(&config.database_url) {
+        anyhow::bail!("backup needs a persistent database_url, not an in-memory database");
+    }
+    let store = store::open(config).await.context("open database")?;
+    let created = backup::create(store.as_ref(), dir.as_ref().unwrap_or(&config.backup_dir)).await;
+    store.close().await;
+    let created = created?;
+    println!("wrote backup {} ({} bytes)", created.path.display(), created.size_bytes);
     Ok(())
-}
\ No newline at end of file
+}
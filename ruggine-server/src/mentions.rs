@@ -0,0 +1,44 @@
+/* Menzioni @username: risoluzione sugli utenti del gruppo, persistenza per messaggio e inbox */
+use ruggine_core::{Mention, Message};
+use std::collections::HashMap;
+
+use crate::db;
+use crate::store::{Store, StoreResult};
+
+/// Risolve gli username menzionati tra i membri del gruppo, escludendo il mittente.
+/// Ritorna gli user_id nello stesso ordine degli username; i nomi sconosciuti vengono ignorati.
+pub async fn resolve(store: &dyn Store, group_id: &str, sender_id: &str, usernames: &[String]) -> StoreResult<Vec<String>> {
+    if usernames.is_empty() {
+        return Ok(Vec::new());
+    }
+    let mut found: HashMap<String, String> =
+        store.find_members_by_username(group_id, sender_id, usernames).await?.into_iter().collect();
+    Ok(usernames.iter().filter_map(|n| found.remove(n)).collect())
+}
+
+/// Popola il campo mentions dei messaggi.
+pub async fn attach(store: &dyn Store, messages: &mut [Message]) -> StoreResult<()> {
+    let ids: Vec<String> = messages.iter().map(|m| m.message_id.clone()).collect();
+    let mut mentions: HashMap<String, Vec<String>> = HashMap::new();
+    for (message_id, user_id) in store.mentions_for(&ids).await? {
+        mentions.entry(message_id).or_default().push(user_id);
+    }
+    for m in messages.iter_mut() {
+        if let Some(users) = mentions.remove(&m.message_id) {
+            m.mentions = users;
+        }
+    }
+    Ok(())
+}
+
+/// Inbox delle menzioni dell'utente, dalla più recente, limitata ai gruppi di cui è ancora membro.
+pub async fn inbox(store: &dyn Store, user_id: &str, before: Option<&str>, limit: i64) -> StoreResult<Vec<Mention>> {
+    let (mut messages, names): (Vec<Message>, Vec<String>) =
+        store.mention_inbox(user_id, before, limit).await?.into_iter().unzip();
+    db::hydrate(store, &mut messages, user_id).await?;
+    Ok(messages
+        .into_iter()
+        .zip(names)
+        .map(|(message, group_name)| Mention { message, group_name })
+        .collect())
+}
@@ -0,0 +1,215 @@
+/* Metriche Prometheus esposte su GET /metrics: richieste HTTP per rotta e status, esiti dei login,
+   connessioni WS aperte, messaggi inviati, latenza del fan-out, latenza delle query al database e
+   occupazione del pool (le stesse connessioni che health_with_store usa per il ping). */
+use axum::{
+    extract::{MatchedPath, Request},
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Extension,
+};
+use prometheus::{
+    Encoder, HistogramOpts, HistogramTimer, HistogramVec, IntCounter, IntCounterVec, IntGauge, Opts,
+    Registry, TextEncoder,
+};
+use std::sync::{Arc, OnceLock};
+use std::time::Instant;
+
+use crate::AppState;
+
+const NAMESPACE: &str = "ruggine";
+
+// Bucket (secondi) per latenze tipicamente sotto il millisecondo, come query e fan-out in memoria
+const FAST_BUCKETS: &[f64] = &[0.0001, 0.00025, 0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 1.0];
+
+// Le query sono eseguite dai backend dello Store, che non conoscono l'AppState: l'istogramma è unico per processo
+// e viene registrato nel Registry di ogni AppState.
+fn db_query_seconds() -> &'static HistogramVec {
+    static HIST: OnceLock<HistogramVec> = OnceLock::new();
+    HIST.get_or_init(|| {
+        HistogramVec::new(
+            HistogramOpts::new("db_query_duration_seconds", "SQLite query latency by query")
+                .namespace(NAMESPACE)
+                .buckets(FAST_BUCKETS.to_vec()),
+            &["query"],
+        )
+        .expect("valid histogram")
+    })
+}
+
+/// Avvia la misura di una query; il tempo viene registrato quando il timer viene rilasciato.
+pub fn db_timer(query: &str) -> HistogramTimer {
+    db_query_seconds().with_label_values(&[query]).start_timer()
+}
+
+/// Esito di un tentativo di login, usato come label.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoginOutcome {
+    Success,
+    Failure,
+    Locked,
+}
+
+impl LoginOutcome {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LoginOutcome::Success => "success",
+            LoginOutcome::Failure => "failure",
+            LoginOutcome::Locked => "locked",
+        }
+    }
+}
+
+pub struct Metrics {
+    registry: Registry,
+    http_requests: IntCounterVec,
+    http_duration: HistogramVec,
+    logins: IntCounterVec,
+    messages_sent: IntCounter,
+    fanout_duration: HistogramVec,
+    ws_connections: IntGauge,
+    pool_connections: IntGauge,
+    pool_idle: IntGauge,
+    pool_max: IntGauge,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let registry = Registry::new();
+        let http_requests = IntCounterVec::new(
+            Opts::new("http_requests_total", "HTTP requests by route and status").namespace(NAMESPACE),
+            &["method", "route", "status"],
+        )
+        .expect("valid counter");
+        let http_duration = HistogramVec::new(
+            HistogramOpts::new("http_request_duration_seconds", "HTTP request latency by route").namespace(NAMESPACE),
+            &["method", "route"],
+        )
+        .expect("valid histogram");
+        let logins = IntCounterVec::new(
+            Opts::new("logins_total", "Login attempts by outcome").namespace(NAMESPACE),
+            &["outcome"],
+        )
+        .expect("valid counter");
+        let messages_sent = IntCounter::with_opts(
+            Opts::new("messages_sent_total", "Chat messages accepted and stored").namespace(NAMESPACE),
+        )
+        .expect("valid counter");
+        let fanout_duration = HistogramVec::new(
+            HistogramOpts::new("fanout_duration_seconds", "Time to enqueue an event for all recipients")
+                .namespace(NAMESPACE)
+                .buckets(FAST_BUCKETS.to_vec()),
+            &["event"],
+        )
+        .expect("valid histogram");
+        let ws_connections = IntGauge::with_opts(
+            Opts::new("ws_connections", "Open WebSocket connections").namespace(NAMESPACE),
+        )
+        .expect("valid gauge");
+        let pool_connections = IntGauge::with_opts(
+            Opts::new("db_pool_connections", "Connections currently held by the database pool").namespace(NAMESPACE),
+        )
+        .expect("valid gauge");
+        let pool_idle = IntGauge::with_opts(
+            Opts::new("db_pool_idle_connections", "Idle connections in the database pool").namespace(NAMESPACE),
+        )
+        .expect("valid gauge");
+        let pool_max = IntGauge::with_opts(
+            Opts::new("db_pool_max_connections", "Configured maximum size of the database pool").namespace(NAMESPACE),
+        )
+        .expect("valid gauge");
+
+        let collectors: [Box<dyn prometheus::core::Collector>; 10] = [
+            Box::new(http_requests.clone()),
+            Box::new(http_duration.clone()),
+            Box::new(logins.clone()),
+            Box::new(messages_sent.clone()),
+            Box::new(fanout_duration.clone()),
+            Box::new(ws_connections.clone()),
+            Box::new(pool_connections.clone()),
+            Box::new(pool_idle.clone()),
+            Box::new(pool_max.clone()),
+            Box::new(db_query_seconds().clone()),
+        ];
+        for c in collectors {
+            registry.register(c).expect("metric registered once");
+        }
+
+        Self {
+            registry,
+            http_requests,
+            http_duration,
+            logins,
+            messages_sent,
+            fanout_duration,
+            ws_connections,
+            pool_connections,
+            pool_idle,
+            pool_max,
+        }
+    }
+
+    pub fn record_login(&self, outcome: LoginOutcome) {
+        self.logins.with_label_values(&[outcome.as_str()]).inc();
+    }
+
+    pub fn record_message_sent(&self) {
+        self.messages_sent.inc();
+    }
+
+    /// Esegue `f` (l'invio di un evento a tutti i destinatari) misurandone la durata.
+    pub fn time_fanout<T>(&self, event: &str, f: impl FnOnce() -> T) -> T {
+        let _timer = self.fanout_duration.with_label_values(&[event]).start_timer();
+        f()
+    }
+
+    /// Aggiorna i gauge campionati (connessioni WS, pool) e serializza tutto nel formato testuale di Prometheus.
+    pub fn render(&self, state: &AppState) -> Result<String, prometheus::Error> {
+        self.ws_connections.set(state.hub.connection_count() as i64);
+        let pool = state.store.pool_stats();
+        self.pool_connections.set(i64::from(pool.size));
+        self.pool_idle.set(pool.idle as i64);
+        self.pool_max.set(i64::from(state.config.db_pool_size));
+
+        let mut buf = Vec::new();
+        TextEncoder::new().encode(&self.registry.gather(), &mut buf)?;
+        Ok(String::from_utf8_lossy(&buf).into_owned())
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Middleware: conta le richieste e ne misura la durata, etichettate con il pattern della rotta
+/// (es. "/api/groups/:group_id/messages") così che gli id non moltiplichino le serie.
+pub async fn track_http(Extension(state): Extension<Arc<AppState>>, req: Request, next: Next) -> Response {
+    let method = req.method().to_string();
+    let route = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|p| p.as_str().to_string())
+        .unwrap_or_else(|| "unmatched".to_string());
+    let started = Instant::now();
+    let response = next.run(req).await;
+    let metrics = &state.metrics;
+    metrics
+        .http_requests
+        .with_label_values(&[&method, &route, response.status().as_str()])
+        .inc();
+    metrics
+        .http_duration
+        .with_label_values(&[&method, &route])
+        .observe(started.elapsed().as_secs_f64());
+    response
+}
+
+/// Handler per GET /metrics
+pub async fn metrics_handler(Extension(state): Extension<Arc<AppState>>) -> Response {
+    match state.metrics.render(&state) {
+        Ok(body) => ([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], body).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("metrics error: {}", e)).into_response(),
+    }
+}
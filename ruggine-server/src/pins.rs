@@ -0,0 +1,29 @@
+/* Messaggi fissati: un messaggio può essere fissato una sola volta nel suo gruppo.
+   Il pin riferisce il messaggio per id, quindi resta valido se il contenuto cambia,
+   e viene cancellato a cascata insieme al messaggio. */
+use ruggine_core::{Group, Pin, PinPolicy};
+
+use crate::db;
+use crate::store::{Role, Store, StoreResult};
+
+/// true se l'utente può fissare/rimuovere messaggi nel gruppo secondo la sua pin_policy.
+pub async fn can_pin(store: &dyn Store, group: &Group, user_id: &str) -> StoreResult<bool> {
+    let role = store.member_role(&group.group_id, user_id).await?;
+    Ok(match (role, group.pin_policy) {
+        (None, _) => false,
+        (Some(_), PinPolicy::Members) => true,
+        (Some(role), PinPolicy::Admins) => role == Role::Admin,
+    })
+}
+
+/// Messaggi fissati del gruppo, dal più recente, completi di reazioni viste da `viewer`.
+pub async fn list(store: &dyn Store, group_id: &str, viewer: &str) -> StoreResult<Vec<Pin>> {
+    let records = store.pins_for_group(group_id).await?;
+    let mut messages: Vec<_> = records.iter().map(|r| r.message.clone()).collect();
+    db::hydrate(store, &mut messages, viewer).await?;
+    Ok(messages
+        .into_iter()
+        .zip(records)
+        .map(|(message, r)| Pin { message, pinned_by: r.pinned_by, pinned_at: r.pinned_at })
+        .collect())
+}
@@ -0,0 +1,239 @@
+/* Limitazione del traffico: token bucket in memoria per IP (register/login) e per utente (comandi WS),
+   più il blocco temporaneo di un account dopo troppi login falliti. */
+use axum::{
+    extract::{ConnectInfo, Extension, Request},
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use ruggine_core::{error::codes, Error};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::AppState;
+
+/// Oltre questo numero di chiavi tracciate i bucket tornati pieni vengono scartati.
+const MAX_TRACKED_KEYS: usize = 10_000;
+
+/// Parametri di un token bucket: `burst` richieste consecutive, poi `refill_per_sec` al secondo.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BucketConfig {
+    pub burst: u32,
+    pub refill_per_sec: f64,
+}
+
+/// Blocco dell'account per un IP dopo `max_failures` login falliti da quell'IP entro `window_secs`, per `lock_secs` secondi.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LockoutConfig {
+    pub max_failures: u32,
+    pub window_secs: u64,
+    pub lock_secs: u64,
+}
+
+/// Sezione [rate_limits] della configurazione.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RateLimitConfig {
+    /// Per IP, condiviso tra /api/register e /api/login
+    pub auth_per_ip: BucketConfig,
+    /// Per utente, su tutti i comandi WS delle sue connessioni
+    pub ws_per_user: BucketConfig,
+    pub login_lockout: LockoutConfig,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            auth_per_ip: BucketConfig { burst: 10, refill_per_sec: 0.5 },
+            ws_per_user: BucketConfig { burst: 20, refill_per_sec: 5.0 },
+            login_lockout: LockoutConfig { max_failures: 5, window_secs: 15 * 60, lock_secs: 15 * 60 },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Insieme di token bucket indicizzati per chiave (IP, user_id, ...).
+pub struct RateLimiter<K> {
+    config: BucketConfig,
+    buckets: Mutex<HashMap<K, Bucket>>,
+}
+
+impl<K: Eq + Hash + Clone> RateLimiter<K> {
+    pub fn new(config: BucketConfig) -> Self {
+        Self { config, buckets: Mutex::new(HashMap::new()) }
+    }
+
+    /// Consuma un token per la chiave; se non ce ne sono ritorna il tempo da attendere.
+    pub fn check(&self, key: &K) -> Result<(), Duration> {
+        let now = Instant::now();
+        let burst = f64::from(self.config.burst);
+        let mut buckets = self.buckets.lock().expect("rate limiter lock poisoned");
+        if buckets.len() >= MAX_TRACKED_KEYS {
+            let refill = self.config.refill_per_sec;
+            buckets.retain(|_, b| b.tokens + now.duration_since(b.updated).as_secs_f64() * refill < burst);
+        }
+        let bucket = buckets.entry(key.clone()).or_insert(Bucket { tokens: burst, updated: now });
+        let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.config.refill_per_sec).min(burst);
+        bucket.updated = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else if self.config.refill_per_sec > 0.0 {
+            Err(Duration::from_secs_f64((1.0 - bucket.tokens) / self.config.refill_per_sec))
+        } else {
+            Err(Duration::MAX)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Failures {
+    count: u32,
+    first_at: Instant,
+    locked_until: Option<Instant>,
+}
+
+/// Conteggio dei login falliti per coppia (username, IP del client): i tentativi sbagliati da un IP non
+/// bloccano il proprietario dell'account che accede da un altro.
+pub struct LoginLockout {
+    config: LockoutConfig,
+    failures: Mutex<HashMap<(String, String), Failures>>,
+}
+
+impl LoginLockout {
+    pub fn new(config: LockoutConfig) -> Self {
+        Self { config, failures: Mutex::new(HashMap::new()) }
+    }
+
+    /// Se l'account è bloccato per l'IP ritorna il tempo rimanente.
+    pub fn locked_for(&self, username: &str, ip: &str) -> Option<Duration> {
+        let now = Instant::now();
+        let failures = self.failures.lock().expect("lockout lock poisoned");
+        failures
+            .get(&(username.to_string(), ip.to_string()))
+            .and_then(|f| f.locked_until)
+            .filter(|until| *until > now)
+            .map(|until| until - now)
+    }
+
+    /// Registra un login fallito; ritorna la durata del blocco se questo tentativo lo fa scattare.
+    pub fn record_failure(&self, username: &str, ip: &str) -> Option<Duration> {
+        let now = Instant::now();
+        let mut failures = self.failures.lock().expect("lockout lock poisoned");
+        let window = Duration::from_secs(self.config.window_secs);
+        let lock_for = Duration::from_secs(self.config.lock_secs);
+        if failures.len() >= MAX_TRACKED_KEYS {
+            failures.retain(|_, f| f.locked_until.is_some_and(|u| u > now) || now.duration_since(f.first_at) < window);
+        }
+        let f = failures
+            .entry((username.to_string(), ip.to_string()))
+            .or_insert(Failures { count: 0, first_at: now, locked_until: None });
+        // finestra scaduta o blocco terminato: si ricomincia a contare
+        if now.duration_since(f.first_at) >= window || f.locked_until.is_some_and(|u| u <= now) {
+            *f = Failures { count: 0, first_at: now, locked_until: None };
+        }
+        f.count += 1;
+        if f.count >= self.config.max_failures {
+            f.locked_until = Some(now + lock_for);
+            return Some(lock_for);
+        }
+        None
+    }
+
+    /// Un login riuscito azzera i tentativi falliti dallo stesso IP.
+    pub fn record_success(&self, username: &str, ip: &str) {
+        self.failures.lock().expect("lockout lock poisoned").remove(&(username.to_string(), ip.to_string()));
+    }
+}
+
+/// Stato dei limiti condiviso dal server.
+pub struct RateLimits {
+    pub auth: RateLimiter<String>,
+    pub ws: RateLimiter<String>,
+    pub lockout: LoginLockout,
+}
+
+impl RateLimits {
+    pub fn new(config: RateLimitConfig) -> Self {
+        Self {
+            auth: RateLimiter::new(config.auth_per_ip),
+            ws: RateLimiter::new(config.ws_per_user),
+            lockout: LoginLockout::new(config.login_lockout),
+        }
+    }
+}
+
+/// Secondi interi da comunicare al client (arrotondati per eccesso, almeno 1).
+pub fn retry_after_secs(retry_after: Duration) -> u64 {
+    (retry_after.as_secs_f64().ceil() as u64).max(1)
+}
+
+/// Errore con codice e `retryAfter` (secondi) nei dettagli, usato sia in HTTP che negli Ack WS.
+pub fn limited_error(code: &str, message: &str, retry_after: Duration) -> Error {
+    Error {
+        details: Some(serde_json::json!({ "retryAfter": retry_after_secs(retry_after) })),
+        ..Error::new(code, message)
+    }
+}
+
+/// Risposta HTTP 429 con header Retry-After e corpo JSON ruggine_core::Error.
+pub fn too_many_requests(code: &str, message: &str, retry_after: Duration) -> Response {
+    let secs = retry_after_secs(retry_after);
+    (
+        StatusCode::TOO_MANY_REQUESTS,
+        [(header::RETRY_AFTER, secs.to_string())],
+        Json(limited_error(code, message, retry_after)),
+    )
+        .into_response()
+}
+
+/// Middleware per le rotte di autenticazione: un token per richiesta dal bucket dell'IP del client.
+/// Senza ConnectInfo (server avviato con `into_make_service`) tutti i client condividono un unico bucket.
+pub async fn limit_auth_by_ip(
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    Extension(state): Extension<Arc<AppState>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let key = connect_info.map(|ConnectInfo(addr)| addr.ip().to_string()).unwrap_or_default();
+    match state.limits.auth.check(&key) {
+        Ok(()) => next.run(req).await,
+        Err(retry_after) => too_many_requests(codes::RATE_LIMITED, "too many requests", retry_after),
+    }
+}
+
+impl RateLimitConfig {
+    /// Controlla che i limiti siano utilizzabili; ritorna la lista dei problemi trovati.
+    pub fn problems(&self) -> Vec<String> {
+        let mut out = Vec::new();
+        for (name, b) in [("auth_per_ip", &self.auth_per_ip), ("ws_per_user", &self.ws_per_user)] {
+            if b.burst == 0 {
+                out.push(format!("rate_limits.{}.burst must be at least 1", name));
+            }
+            if !(b.refill_per_sec.is_finite() && b.refill_per_sec > 0.0) {
+                out.push(format!("rate_limits.{}.refill_per_sec must be a positive number", name));
+            }
+        }
+        let l = &self.login_lockout;
+        if l.max_failures == 0 {
+            out.push("rate_limits.login_lockout.max_failures must be at least 1".to_string());
+        }
+        if l.window_secs == 0 || l.lock_secs == 0 {
+            out.push("rate_limits.login_lockout window_secs and lock_secs must be positive".to_string());
+        }
+        out
+    }
+}
@@ -0,0 +1,68 @@
+/* Persistenza e aggregazione delle reazioni emoji ai messaggi */
+use ruggine_core::{Message, Reaction};
+use std::collections::HashMap;
+
+use crate::store::{Store, StoreResult};
+
+/// Lunghezza massima (in caratteri) accettata per una emoji:
+/// alcune emoji composte (famiglie, bandiere, toni di pelle) occupano più code point.
+pub const MAX_EMOJI_CHARS: usize = 16;
+
+/// Verifica che la stringa sia utilizzabile come reazione.
+pub fn is_valid_emoji(emoji: &str) -> bool {
+    let n = emoji.chars().count();
+    n > 0 && n <= MAX_EMOJI_CHARS && !emoji.chars().any(char::is_whitespace)
+}
+
+/// Reazioni di un messaggio: per ogni emoji gli utenti che l'hanno usata,
+/// nell'ordine in cui ciascuna emoji è comparsa per la prima volta.
+#[derive(Debug, Default, Clone)]
+pub struct Tally(Vec<(String, Vec<String>)>);
+
+impl Tally {
+    fn push(&mut self, emoji: String, user_id: String) {
+        match self.0.iter_mut().find(|(e, _)| *e == emoji) {
+            Some((_, users)) => users.push(user_id),
+            None => self.0.push((emoji, vec![user_id])),
+        }
+    }
+
+    /// Vista aggregata per un destinatario specifico (calcola reacted_by_me).
+    pub fn view(&self, viewer: &str) -> Vec<Reaction> {
+        self.0
+            .iter()
+            .map(|(emoji, users)| Reaction {
+                emoji: emoji.clone(),
+                count: users.len() as u32,
+                reacted_by_me: users.iter().any(|u| u == viewer),
+            })
+            .collect()
+    }
+}
+
+/// Carica le reazioni di un insieme di messaggi, raggruppate per message_id.
+pub async fn load(store: &dyn Store, message_ids: &[String]) -> StoreResult<HashMap<String, Tally>> {
+    let mut out: HashMap<String, Tally> = HashMap::new();
+    for (message_id, user_id, emoji) in store.reactions_for(message_ids).await? {
+        out.entry(message_id).or_default().push(emoji, user_id);
+    }
+    Ok(out)
+}
+
+/// Reazioni di un singolo messaggio.
+pub async fn tally_for(store: &dyn Store, message_id: &str) -> StoreResult<Tally> {
+    let mut tallies = load(store, &[message_id.to_string()]).await?;
+    Ok(tallies.remove(message_id).unwrap_or_default())
+}
+
+/// Popola il campo reactions dei messaggi dal punto di vista di `viewer`.
+pub async fn attach(store: &dyn Store, messages: &mut [Message], viewer: &str) -> StoreResult<()> {
+    let ids: Vec<String> = messages.iter().map(|m| m.message_id.clone()).collect();
+    let tallies = load(store, &ids).await?;
+    for m in messages.iter_mut() {
+        if let Some(t) = tallies.get(&m.message_id) {
+            m.reactions = t.view(viewer);
+        }
+    }
+    Ok(())
+}
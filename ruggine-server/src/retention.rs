@@ -0,0 +1,158 @@
+/* Conservazione dei messaggi: ogni gruppo può limitare l'età e il numero dei messaggi conservati
+   (RetentionPolicy), i limiti non impostati valgono come i default del server (sezione [retention]).
+   Un task in background controlla i gruppi ogni interval_secs ed elimina i messaggi oltre i limiti,
+   dal più vecchio e a lotti di batch_size (ogni lotto in una transazione, insieme a reazioni, menzioni
+   e pin), poi avvisa i membri con historyPruned. L'età è quella di created_at, letta nell'ordine di
+   seq: la pulizia si ferma al primo messaggio ancora da conservare. Con più istanze ognuna esegue la
+   pulizia: un lotto già eliminato da un'altra istanza non produce un secondo avviso. */
+use ruggine_core::{HistoryPruned, RetentionPolicy, WsMessage};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+use crate::store::{Store, StoreResult};
+use crate::AppState;
+
+/// Sezione [retention] della configurazione.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RetentionConfig {
+    /// Età massima dei messaggi in secondi per i gruppi che non ne indicano una; assente = nessun limite
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_age_secs: Option<u64>,
+    /// Messaggi conservati per gruppo (i più recenti) se il gruppo non indica un limite
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_count: Option<u64>,
+    /// Intervallo tra due passaggi di pulizia (il primo avviene all'avvio)
+    pub interval_secs: u64,
+    /// Messaggi eliminati per transazione
+    pub batch_size: u32,
+}
+
+impl Default for RetentionConfig {
+    fn default() -> Self {
+        Self { max_age_secs: None, max_count: None, interval_secs: 60 * 60, batch_size: 500 }
+    }
+}
+
+impl RetentionConfig {
+    /// Limiti effettivi del gruppo: i suoi, o i default del server per quelli che non imposta.
+    pub fn effective(&self, group: &RetentionPolicy) -> RetentionPolicy {
+        RetentionPolicy {
+            max_age_secs: group.max_age_secs.or(self.max_age_secs),
+            max_count: group.max_count.or(self.max_count),
+        }
+    }
+
+    pub fn problems(&self) -> Vec<String> {
+        let mut out = Vec::new();
+        if self.max_age_secs == Some(0) || self.max_count == Some(0) {
+            out.push("retention max_age_secs and max_count must be positive when set".to_string());
+        }
+        if self.interval_secs == 0 {
+            out.push("retention.interval_secs must be positive".to_string());
+        }
+        if self.batch_size == 0 {
+            out.push("retention.batch_size must be at least 1".to_string());
+        }
+        out
+    }
+}
+
+/// Controlla i limiti chiesti per un gruppo: zero non è ammesso (si ometta il limite).
+pub fn validate_policy(policy: &RetentionPolicy) -> Result<(), String> {
+    if policy.max_age_secs == Some(0) || policy.max_count == Some(0) {
+        return Err("retention limits must be positive".to_string());
+    }
+    Ok(())
+}
+
+/// Elimina i messaggi del gruppo oltre i limiti di `policy` all'istante `now`; None se non c'era
+/// nulla da eliminare.
+pub async fn purge_group(
+    store: &dyn Store,
+    group_id: &str,
+    policy: RetentionPolicy,
+    now: OffsetDateTime,
+    batch_size: u32,
+) -> StoreResult<Option<HistoryPruned>> {
+    let batch = i64::from(batch_size.max(1));
+    let mut through = 0;
+    if let Some(keep) = policy.max_count
+        && let Some(seq) = store.seq_beyond_newest(group_id, keep).await?
+    {
+        through = seq;
+    }
+    // oltre il limite di età: durata fuori scala = nessun messaggio abbastanza vecchio
+    let cutoff = policy
+        .max_age_secs
+        .and_then(|secs| now.checked_sub(time::Duration::seconds(i64::try_from(secs).unwrap_or(i64::MAX))));
+    if let Some(cutoff) = cutoff {
+        loop {
+            let page = store.messages_after(group_id, through, batch).await?;
+            let expired = page.iter().take_while(|m| is_before(&m.created_at, cutoff)).count();
+            if let Some(last) = page[..expired].last() {
+                through = last.seq;
+            }
+            if expired < page.len() || page.len() < batch as usize {
+                break;
+            }
+        }
+    }
+    if through == 0 {
+        return Ok(None);
+    }
+    let mut deleted = 0;
+    loop {
+        let n = store.purge_messages(group_id, through, batch).await?;
+        deleted += n;
+        if n < batch as u64 {
+            break;
+        }
+    }
+    Ok((deleted > 0).then(|| HistoryPruned { group_id: group_id.to_string(), up_to_seq: through, deleted }))
+}
+
+// Timestamp non leggibili contano come recenti: non vengono mai eliminati per età
+fn is_before(created_at: &str, cutoff: OffsetDateTime) -> bool {
+    OffsetDateTime::parse(created_at, &Rfc3339).is_ok_and(|at| at < cutoff)
+}
+
+/// Un passaggio di pulizia su tutti i gruppi; ritorna il numero di messaggi eliminati.
+pub async fn run_once(state: &AppState) -> StoreResult<u64> {
+    let config = state.config.retention;
+    let now = OffsetDateTime::now_utc();
+    let mut total = 0;
+    for summary in state.store.list_groups().await? {
+        let policy = config.effective(&summary.group.retention);
+        if policy == RetentionPolicy::default() || summary.messages == 0 {
+            continue;
+        }
+        let group_id = &summary.group.group_id;
+        let Some(pruned) = purge_group(state.store.as_ref(), group_id, policy, now, config.batch_size).await? else {
+            continue;
+        };
+        tracing::info!(group_id = %group_id, deleted = pruned.deleted, up_to_seq = pruned.up_to_seq, "expired messages purged");
+        total += pruned.deleted;
+        let members = state.store.member_ids(group_id).await?;
+        state.bus.publish(&members, &WsMessage::HistoryPruned(pruned));
+    }
+    Ok(total)
+}
+
+/// Avvia la pulizia periodica, che termina all'arresto del server.
+pub fn spawn(state: Arc<AppState>) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticks = tokio::time::interval(std::time::Duration::from_secs(state.config.retention.interval_secs));
+        ticks.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            tokio::select! {
+                _ = ticks.tick() => {}
+                _ = state.shutdown.triggered() => return,
+            }
+            if let Err(e) = run_once(&state).await {
+                tracing::warn!(error = %e, "retention purge failed");
+            }
+        }
+    })
+}
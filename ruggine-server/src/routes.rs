@@ -1,15 +1,59 @@
-use axum::{routing::{get, post}, Router, Extension};
+use axum::{middleware, routing::{delete, get, patch, post, put}, Router, Extension};
 use std::sync::Arc;
 
-use crate::{AppState, health_with_pool};
-use crate::controllers;
+use crate::{AppState, health_with_store};
+use crate::{controllers, health, metrics, ratelimit, security, telemetry, web, ws};
 
 pub fn router(state: Arc<AppState>) -> Router {
+    let policy = Arc::new(security::SecurityPolicy::new(&state.config));
     Router::new()
         .route("/health", get(|Extension(state): Extension<Arc<AppState>>| async move {
-            health_with_pool(&state.pool).await
+            health_with_store(state.store.as_ref()).await
         }))
+        .route("/health/live", get(health::live))
+        .route("/health/ready", get(health::ready))
+        .route("/metrics", get(metrics::metrics_handler))
+        .merge(auth_routes())
+        .route("/api/groups", get(controllers::list_groups).post(controllers::create_group))
+        .route("/api/groups/:group_id", patch(controllers::update_group))
+        .route("/api/groups/:group_id/messages", get(controllers::list_messages))
+        .route("/api/groups/:group_id/pins", get(controllers::list_pins))
+        .route("/api/groups/:group_id/mute", put(controllers::mute_group))
+        .route("/api/groups/:group_id/members", post(controllers::add_member))
+        .route("/api/groups/:group_id/members/:user_id", delete(controllers::remove_member))
+        .route("/api/groups/:group_id/export", get(controllers::export_group))
+        .route("/api/groups/:group_id/keys", get(controllers::list_group_keys))
+        .route(
+            "/api/groups/:group_id/sender-keys",
+            get(controllers::list_sender_keys).post(controllers::distribute_sender_key),
+        )
+        .route("/api/groups/:group_id/webhooks", get(controllers::list_webhooks).post(controllers::create_webhook))
+        .route(
+            "/api/groups/:group_id/webhooks/:webhook_id",
+            patch(controllers::update_webhook).delete(controllers::delete_webhook),
+        )
+        .route("/api/groups/:group_id/webhooks/:webhook_id/deliveries", get(controllers::list_webhook_deliveries))
+        .route("/api/keys", put(controllers::publish_key))
+        .route("/api/keys/:user_id", get(controllers::get_key))
+        .route("/api/mentions", get(controllers::list_mentions))
+        .route("/api/scheduled", get(controllers::list_scheduled))
+        .route("/api/scheduled/:message_id", delete(controllers::cancel_scheduled))
+        .route("/api/admin/backup", post(controllers::create_backup))
+        .route("/ws", get(ws::ws_handler))
+        // tutto il resto: file del client web, se configurato
+        .fallback(web::serve_client)
+        .layer(middleware::from_fn(metrics::track_http))
+        .layer(Extension(state))
+        // più esterno: lo span della richiesta copre anche rate limiting ed estrazione dello stato
+        .layer(middleware::from_fn(telemetry::trace_request))
+        // CORS e header di sicurezza anche sulle risposte di errore e sulle preflight
+        .layer(middleware::from_fn_with_state(policy, security::apply))
+}
+
+// Rotte di autenticazione, limitate per IP del client
+fn auth_routes() -> Router {
+    Router::new()
         .route("/api/register", post(controllers::register))
         .route("/api/login", post(controllers::login))
-        .layer(Extension(state))
+        .route_layer(middleware::from_fn(ratelimit::limit_auth_by_ip))
 }
@@ -0,0 +1,132 @@
+/* Messaggi programmati: sendMessage con send_at nel futuro non pubblica subito il messaggio ma lo salva
+   in scheduled_messages (l'ack riporta sendAt); un task in background controlla ogni interval_secs i
+   messaggi arrivati al loro send_at e li pubblica come un normale invio, con il message_id già
+   comunicato al mittente e created_at uguale all'istante di pubblicazione. Il mittente può elencare e
+   annullare i propri messaggi programmati via HTTP. Essendo nel database sopravvivono ai riavvii (quelli
+   scaduti nel frattempo partono al primo controllo); con più istanze ognuno è pubblicato da una sola.
+   Se alla pubblicazione il mittente non è più membro del gruppo il messaggio viene scartato. */
+use ruggine_core::{utils::now_timestamp, Message};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+use crate::store::StoreResult;
+use crate::{ephemeral, ws, AppState};
+
+/// Sezione [scheduled] della configurazione.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ScheduledConfig {
+    /// Anticipo massimo con cui un messaggio può essere programmato
+    pub max_delay_secs: u64,
+    /// Messaggi programmati e non ancora pubblicati per utente
+    pub max_pending_per_user: u32,
+    /// Intervallo tra due controlli dei messaggi da pubblicare
+    pub interval_secs: u64,
+    /// Messaggi letti per ogni controllo del database
+    pub batch_size: u32,
+}
+
+impl Default for ScheduledConfig {
+    fn default() -> Self {
+        Self { max_delay_secs: 365 * 24 * 60 * 60, max_pending_per_user: 100, interval_secs: 1, batch_size: 100 }
+    }
+}
+
+impl ScheduledConfig {
+    /// Istante di pubblicazione (secondi unix) per un send_at chiesto da un client, arrotondato al secondo
+    /// successivo così il messaggio non parte prima; None se send_at non è nel futuro (il messaggio va
+    /// inviato subito).
+    pub fn send_at(&self, send_at: &str) -> Result<Option<i64>, String> {
+        let at = OffsetDateTime::parse(send_at, &Rfc3339).map_err(|_| format!("invalid sendAt {:?}", send_at))?;
+        let at = at.unix_timestamp() + i64::from(at.nanosecond() > 0);
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        if at <= now {
+            return Ok(None);
+        }
+        if (at - now) as u64 > self.max_delay_secs {
+            return Err(format!("sendAt must be at most {} seconds in the future", self.max_delay_secs));
+        }
+        Ok(Some(at))
+    }
+
+    pub fn problems(&self) -> Vec<String> {
+        let mut out = Vec::new();
+        if self.max_delay_secs == 0 {
+            out.push("scheduled.max_delay_secs must be positive".to_string());
+        }
+        if self.max_pending_per_user == 0 {
+            out.push("scheduled.max_pending_per_user must be at least 1".to_string());
+        }
+        if self.interval_secs == 0 {
+            out.push("scheduled.interval_secs must be positive".to_string());
+        }
+        if self.batch_size == 0 {
+            out.push("scheduled.batch_size must be at least 1".to_string());
+        }
+        out
+    }
+}
+
+/// Pubblica tutti i messaggi programmati arrivati al loro send_at; ritorna il numero di messaggi pubblicati.
+pub async fn run_once(state: &AppState) -> StoreResult<u64> {
+    let batch = i64::from(state.config.scheduled.batch_size.max(1));
+    let now = OffsetDateTime::now_utc().unix_timestamp();
+    let mut total = 0;
+    loop {
+        let due = state.store.due_scheduled(now, batch).await?;
+        for scheduled in &due {
+            let group = match state.store.find_group(&scheduled.group_id).await? {
+                Some(group) if state.store.is_member(&group.group_id, &scheduled.sender_id).await? => group,
+                _ => {
+                    tracing::warn!(
+                        message_id = %scheduled.message_id,
+                        group_id = %scheduled.group_id,
+                        "scheduled message dropped: sender is no longer a member"
+                    );
+                    state.store.cancel_scheduled(&scheduled.message_id, &scheduled.sender_id).await?;
+                    continue;
+                }
+            };
+            let mut message = Message {
+                message_id: scheduled.message_id.clone(),
+                group_id: scheduled.group_id.clone(),
+                sender_id: scheduled.sender_id.clone(),
+                content: scheduled.content.clone(),
+                created_at: now_timestamp(),
+                seq: 0,
+                reactions: Vec::new(),
+                mentions: Vec::new(),
+                expires_at: scheduled.ttl_secs.or(group.message_ttl_secs).map(ephemeral::expires_at),
+            };
+            // già pubblicato da un'altra istanza o annullato nel frattempo
+            let Some(seq) = state.store.publish_scheduled(&message).await? else { continue };
+            message.seq = seq;
+            tracing::debug!(message_id = %message.message_id, group_id = %message.group_id, "scheduled message published");
+            ws::announce(state, &mut message, group.encrypted).await?;
+            total += 1;
+        }
+        if due.len() < batch as usize {
+            break;
+        }
+    }
+    Ok(total)
+}
+
+/// Avvia la pubblicazione periodica dei messaggi programmati, che termina all'arresto del server.
+pub fn spawn(state: Arc<AppState>) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticks = tokio::time::interval(std::time::Duration::from_secs(state.config.scheduled.interval_secs));
+        ticks.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            tokio::select! {
+                _ = ticks.tick() => {}
+                _ = state.shutdown.triggered() => return,
+            }
+            if let Err(e) = run_once(&state).await {
+                tracing::warn!(error = %e, "scheduled messages publish failed");
+            }
+        }
+    })
+}
+
@@ -0,0 +1,141 @@
+/* CORS e header di sicurezza applicati a tutte le risposte. Il client web servito da Trunk
+   (http://localhost:8080) chiama le API su un'altra origine: le origini ammesse sono in cors_origins
+   ("*" le ammette tutte), le preflight OPTIONS ricevono la risposta direttamente da qui. Ogni risposta
+   porta Content-Security-Policy (con frame-ancestors), X-Content-Type-Options, Referrer-Policy e,
+   con TLS attivo, Strict-Transport-Security; gli header già impostati da un handler non vengono
+   sovrascritti. */
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderMap, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use ruggine_core::error::REQUEST_ID_HEADER;
+use std::sync::Arc;
+
+use crate::config::Config;
+
+/// Metodi ammessi nelle richieste cross-origin.
+pub const CORS_METHODS: &str = "GET, POST, PUT, PATCH, DELETE, OPTIONS";
+
+/// Header ammessi nelle richieste cross-origin se la preflight non ne indica.
+pub const CORS_HEADERS: &str = "authorization, content-type, x-request-id";
+
+/// Durata (secondi) per cui il browser può riusare l'esito di una preflight.
+pub const CORS_MAX_AGE_SECS: u64 = 600;
+
+// Un anno, come raccomandato per HSTS
+const HSTS_VALUE: &str = "max-age=31536000";
+
+/// Politica calcolata una volta dalla configurazione.
+#[derive(Debug, Clone)]
+pub struct SecurityPolicy {
+    cors_any: bool,
+    cors_origins: Vec<String>,
+    cors_credentials: bool,
+    csp: HeaderValue,
+    deny_framing: bool,
+    hsts: bool,
+}
+
+impl SecurityPolicy {
+    pub fn new(config: &Config) -> Self {
+        let mut csp = config.content_security_policy.trim().trim_end_matches(';').to_string();
+        if !config.frame_ancestors.is_empty() {
+            if !csp.is_empty() {
+                csp.push_str("; ");
+            }
+            csp.push_str("frame-ancestors ");
+            csp.push_str(&config.frame_ancestors.join(" "));
+        }
+        Self {
+            cors_any: config.cors_origins.iter().any(|o| o == "*"),
+            cors_origins: config.cors_origins.iter().map(|o| o.trim_end_matches('/').to_string()).collect(),
+            cors_credentials: config.cors_allow_credentials,
+            // validate garantisce che sia un valore di header valido
+            csp: HeaderValue::from_str(&csp).unwrap_or_else(|_| HeaderValue::from_static("default-src 'self'")),
+            deny_framing: config.frame_ancestors == ["'none'"],
+            hsts: config.tls_enabled(),
+        }
+    }
+
+    fn allows(&self, origin: &str) -> bool {
+        self.cors_any || self.cors_origins.iter().any(|o| o == origin)
+    }
+
+    // Header CORS per un'origine ammessa: con le credenziali l'origine va ripetuta, "*" non è accettato
+    fn cors_headers(&self, origin: &HeaderValue, headers: &mut HeaderMap) {
+        let allow_origin = if self.cors_any && !self.cors_credentials {
+            HeaderValue::from_static("*")
+        } else {
+            headers.append(header::VARY, HeaderValue::from_static("origin"));
+            origin.clone()
+        };
+        headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, allow_origin);
+        if self.cors_credentials {
+            headers.insert(header::ACCESS_CONTROL_ALLOW_CREDENTIALS, HeaderValue::from_static("true"));
+        }
+    }
+
+    fn security_headers(&self, headers: &mut HeaderMap) {
+        headers.entry(header::CONTENT_SECURITY_POLICY).or_insert_with(|| self.csp.clone());
+        headers.entry(header::X_CONTENT_TYPE_OPTIONS).or_insert(HeaderValue::from_static("nosniff"));
+        headers.entry(header::REFERRER_POLICY).or_insert(HeaderValue::from_static("no-referrer"));
+        if self.deny_framing {
+            // per i browser che non conoscono frame-ancestors
+            headers.entry(header::X_FRAME_OPTIONS).or_insert(HeaderValue::from_static("DENY"));
+        }
+        if self.hsts {
+            headers.entry(header::STRICT_TRANSPORT_SECURITY).or_insert(HeaderValue::from_static(HSTS_VALUE));
+        }
+    }
+}
+
+/// Middleware esterno a tutte le rotte: risponde alle preflight CORS e aggiunge gli header CORS e di
+/// sicurezza alle risposte.
+pub async fn apply(State(policy): State<Arc<SecurityPolicy>>, req: Request, next: Next) -> Response {
+    let origin = req
+        .headers()
+        .get(header::ORIGIN)
+        .filter(|o| o.to_str().is_ok_and(|o| policy.allows(o)))
+        .cloned();
+    let is_preflight =
+        req.method() == Method::OPTIONS && req.headers().contains_key(header::ACCESS_CONTROL_REQUEST_METHOD);
+
+    let mut response = if is_preflight {
+        preflight(&req, origin.is_some())
+    } else {
+        next.run(req).await
+    };
+    let headers = response.headers_mut();
+    if let Some(origin) = &origin {
+        policy.cors_headers(origin, headers);
+        if !is_preflight {
+            headers.insert(header::ACCESS_CONTROL_EXPOSE_HEADERS, HeaderValue::from_static(REQUEST_ID_HEADER));
+        }
+    }
+    policy.security_headers(headers);
+    response
+}
+
+// Le preflight da origini non ammesse sono rifiutate senza header CORS: il browser blocca la richiesta
+fn preflight(req: &Request, allowed: bool) -> Response {
+    if !allowed {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+    let requested_headers = req
+        .headers()
+        .get(header::ACCESS_CONTROL_REQUEST_HEADERS)
+        .cloned()
+        .unwrap_or(HeaderValue::from_static(CORS_HEADERS));
+    (
+        StatusCode::NO_CONTENT,
+        [
+            (header::ACCESS_CONTROL_ALLOW_METHODS, HeaderValue::from_static(CORS_METHODS)),
+            (header::ACCESS_CONTROL_ALLOW_HEADERS, requested_headers),
+            (header::ACCESS_CONTROL_MAX_AGE, HeaderValue::from(CORS_MAX_AGE_SECS)),
+            (header::VARY, HeaderValue::from_static("access-control-request-headers")),
+        ],
+    )
+        .into_response()
+}
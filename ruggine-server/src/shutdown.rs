@@ -0,0 +1,102 @@
+/* Arresto controllato: al segnale il server smette di accettare connessioni, ogni sessione WS
+   termina il comando in corso, invia l'avviso serverGoingAway e chiude il socket; solo quando
+   tutte le sessioni sono chiuse (o scade il timeout) il pool SQLite viene chiuso. */
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{watch, Notify};
+
+/// Suggerimento ai client su quanto attendere prima di riconnettersi.
+pub const RECONNECT_AFTER_MS: u64 = 2_000;
+
+pub struct Shutdown {
+    triggered: watch::Sender<bool>,
+    sessions: AtomicUsize,
+    idle: Notify,
+}
+
+impl Default for Shutdown {
+    fn default() -> Self {
+        Self { triggered: watch::channel(false).0, sessions: AtomicUsize::new(0), idle: Notify::new() }
+    }
+}
+
+impl Shutdown {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Avvia l'arresto: le sessioni WS in ascolto vengono svegliate.
+    pub fn trigger(&self) {
+        self.triggered.send_replace(true);
+    }
+
+    pub fn is_triggered(&self) -> bool {
+        *self.triggered.borrow()
+    }
+
+    /// Si risolve quando l'arresto è stato avviato (subito se lo è già).
+    pub async fn triggered(&self) {
+        let mut rx = self.triggered.subscribe();
+        // il sender vive quanto self, quindi wait_for non può fallire
+        let _ = rx.wait_for(|t| *t).await;
+    }
+
+    /// Registra una sessione attiva; la sessione conta finché la guardia non viene rilasciata.
+    pub fn track_session(self: &Arc<Self>) -> SessionGuard {
+        self.sessions.fetch_add(1, Ordering::SeqCst);
+        SessionGuard(self.clone())
+    }
+
+    pub fn active_sessions(&self) -> usize {
+        self.sessions.load(Ordering::SeqCst)
+    }
+
+    /// Attende la chiusura di tutte le sessioni; false se scade prima il timeout.
+    pub async fn wait_sessions(&self, timeout: Duration) -> bool {
+        tokio::time::timeout(timeout, async {
+            loop {
+                let notified = self.idle.notified();
+                if self.active_sessions() == 0 {
+                    return;
+                }
+                notified.await;
+            }
+        })
+        .await
+        .is_ok()
+    }
+}
+
+pub struct SessionGuard(Arc<Shutdown>);
+
+impl Drop for SessionGuard {
+    fn drop(&mut self) {
+        if self.0.sessions.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.0.idle.notify_waiters();
+        }
+    }
+}
+
+/// Si risolve alla ricezione di SIGINT (Ctrl+C) o, su unix, di SIGTERM.
+pub async fn signal() {
+    let ctrl_c = async {
+        let _ = tokio::signal::ctrl_c().await;
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut s) => {
+                s.recv().await;
+            }
+            Err(_) => std::future::pending::<()>().await,
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
@@ -0,0 +1,460 @@
+/* Backend che cifra a riposo il contenuto dei messaggi (anche di quelli programmati) e i segreti dei
+   webhook: avvolge un altro Store, cifra in scrittura e decifra in lettura (vedi crate::encryption).
+   Tutte le altre operazioni sono inoltrate così come sono; raw_contents, raw_scheduled_contents,
+   raw_webhook_secrets e i rispettivi set_raw_* lavorano sul valore salvato. */
+use async_trait::async_trait;
+use ruggine_core::{Group, Message, PublicKey, ScheduledMessage, SenderKey, User, WebhookDelivery};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use super::{
+    AdminStore, Credentials, DeliveryAttempt, GroupStore, GroupSummary, Invite, InviteStore, KeyStore, MembershipStore,
+    MentionStore, MessageStore, PinRecord, PinStore, PoolStats, ReactionStore, Role, ScheduleStore, SessionStore, Store,
+    StoreResult, StoreStats, UserStore, UserSummary, WebhookRecord, WebhookStore,
+};
+use crate::encryption::Keyring;
+
+pub struct EncryptedStore {
+    inner: Arc<dyn Store>,
+    keyring: Arc<Keyring>,
+}
+
+impl EncryptedStore {
+    pub fn new(inner: Arc<dyn Store>, keyring: Arc<Keyring>) -> Self {
+        Self { inner, keyring }
+    }
+
+    /// Backend avvolto, che restituisce i contenuti così come sono salvati.
+    pub fn inner(&self) -> &Arc<dyn Store> {
+        &self.inner
+    }
+
+    fn seal(&self, mut message: Message) -> StoreResult<Message> {
+        message.content = self
+            .keyring
+            .encrypt_field(&message.content, &message.message_id)
+            .map_err(|e| sqlx::Error::Protocol(format!("encrypt message content: {:#}", e)))?;
+        Ok(message)
+    }
+
+    fn open(&self, mut message: Message) -> StoreResult<Message> {
+        message.content = self
+            .keyring
+            .decrypt_field(&message.content, &message.message_id)
+            .map_err(|e| sqlx::Error::Decode(format!("message {}: {:#}", message.message_id, e).into()))?;
+        Ok(message)
+    }
+
+    fn open_all(&self, messages: Vec<Message>) -> StoreResult<Vec<Message>> {
+        messages.into_iter().map(|m| self.open(m)).collect()
+    }
+
+    // I messaggi programmati sono cifrati come lo saranno una volta pubblicati (stesso message_id)
+    fn seal_scheduled(&self, mut scheduled: ScheduledMessage) -> StoreResult<ScheduledMessage> {
+        scheduled.content = self
+            .keyring
+            .encrypt_field(&scheduled.content, &scheduled.message_id)
+            .map_err(|e| sqlx::Error::Protocol(format!("encrypt scheduled content: {:#}", e)))?;
+        Ok(scheduled)
+    }
+
+    fn open_scheduled(&self, scheduled: Vec<ScheduledMessage>) -> StoreResult<Vec<ScheduledMessage>> {
+        scheduled
+            .into_iter()
+            .map(|mut s| {
+                s.content = self
+                    .keyring
+                    .decrypt_field(&s.content, &s.message_id)
+                    .map_err(|e| sqlx::Error::Decode(format!("scheduled message {}: {:#}", s.message_id, e).into()))?;
+                Ok(s)
+            })
+            .collect()
+    }
+
+    fn open_webhook(&self, mut record: WebhookRecord) -> StoreResult<WebhookRecord> {
+        record.secret = self
+            .keyring
+            .decrypt_field(&record.secret, &record.webhook.webhook_id)
+            .map_err(|e| sqlx::Error::Decode(format!("webhook {}: {:#}", record.webhook.webhook_id, e).into()))?;
+        Ok(record)
+    }
+}
+
+#[async_trait]
+impl UserStore for EncryptedStore {
+    async fn create_user(&self, user: &User, password_hash: &str) -> StoreResult<()> {
+        self.inner.create_user(user, password_hash).await
+    }
+
+    async fn find_user(&self, user_id: &str) -> StoreResult<Option<User>> {
+        self.inner.find_user(user_id).await
+    }
+
+    async fn username_exists(&self, username: &str) -> StoreResult<bool> {
+        self.inner.username_exists(username).await
+    }
+
+    async fn credentials(&self, username: &str) -> StoreResult<Option<Credentials>> {
+        self.inner.credentials(username).await
+    }
+}
+
+#[async_trait]
+impl SessionStore for EncryptedStore {
+    async fn set_token(&self, user_id: &str, token: &str, expires_at: Option<i64>) -> StoreResult<()> {
+        self.inner.set_token(user_id, token, expires_at).await
+    }
+
+    async fn user_id_for_token(&self, token: &str, now: i64) -> StoreResult<Option<String>> {
+        self.inner.user_id_for_token(token, now).await
+    }
+}
+
+#[async_trait]
+impl GroupStore for EncryptedStore {
+    async fn create_group(&self, group: &Group, members: &[(String, Role)]) -> StoreResult<()> {
+        self.inner.create_group(group, members).await
+    }
+
+    async fn find_group(&self, group_id: &str) -> StoreResult<Option<Group>> {
+        self.inner.find_group(group_id).await
+    }
+
+    async fn update_group(&self, group: &Group) -> StoreResult<()> {
+        self.inner.update_group(group).await
+    }
+
+    async fn groups_for_user(&self, user_id: &str) -> StoreResult<Vec<Group>> {
+        self.inner.groups_for_user(user_id).await
+    }
+}
+
+#[async_trait]
+impl MembershipStore for EncryptedStore {
+    async fn member_role(&self, group_id: &str, user_id: &str) -> StoreResult<Option<Role>> {
+        self.inner.member_role(group_id, user_id).await
+    }
+
+    async fn member_ids(&self, group_id: &str) -> StoreResult<Vec<String>> {
+        self.inner.member_ids(group_id).await
+    }
+
+    async fn find_members_by_username(
+        &self,
+        group_id: &str,
+        exclude: &str,
+        usernames: &[String],
+    ) -> StoreResult<Vec<(String, String)>> {
+        self.inner.find_members_by_username(group_id, exclude, usernames).await
+    }
+
+    async fn set_muted(&self, group_id: &str, user_id: &str, muted: bool) -> StoreResult<bool> {
+        self.inner.set_muted(group_id, user_id, muted).await
+    }
+
+    async fn muted_member_ids(&self, group_id: &str) -> StoreResult<Vec<String>> {
+        self.inner.muted_member_ids(group_id).await
+    }
+
+    async fn admin_ids(&self, group_id: &str) -> StoreResult<Vec<String>> {
+        self.inner.admin_ids(group_id).await
+    }
+
+    async fn add_member(&self, group_id: &str, user_id: &str, role: Role, joined_at: &str) -> StoreResult<bool> {
+        self.inner.add_member(group_id, user_id, role, joined_at).await
+    }
+
+    async fn remove_member(&self, group_id: &str, user_id: &str) -> StoreResult<bool> {
+        self.inner.remove_member(group_id, user_id).await
+    }
+}
+
+#[async_trait]
+impl MessageStore for EncryptedStore {
+    async fn insert_message(&self, message: &Message) -> StoreResult<u64> {
+        self.inner.insert_message(&self.seal(message.clone())?).await
+    }
+
+    async fn find_message(&self, message_id: &str) -> StoreResult<Option<Message>> {
+        self.inner.find_message(message_id).await?.map(|m| self.open(m)).transpose()
+    }
+
+    async fn list_messages(&self, group_id: &str, before: Option<&str>, limit: i64) -> StoreResult<Vec<Message>> {
+        self.open_all(self.inner.list_messages(group_id, before, limit).await?)
+    }
+
+    async fn messages_after(&self, group_id: &str, after_seq: u64, limit: i64) -> StoreResult<Vec<Message>> {
+        self.open_all(self.inner.messages_after(group_id, after_seq, limit).await?)
+    }
+
+    async fn latest_seq(&self, group_id: &str) -> StoreResult<u64> {
+        self.inner.latest_seq(group_id).await
+    }
+
+    async fn seq_beyond_newest(&self, group_id: &str, keep: u64) -> StoreResult<Option<u64>> {
+        self.inner.seq_beyond_newest(group_id, keep).await
+    }
+
+    async fn purge_messages(&self, group_id: &str, through_seq: u64, limit: i64) -> StoreResult<u64> {
+        self.inner.purge_messages(group_id, through_seq, limit).await
+    }
+
+    async fn purge_expired(&self, now: i64, limit: i64) -> StoreResult<Vec<(String, String)>> {
+        self.inner.purge_expired(now, limit).await
+    }
+
+    async fn raw_contents(&self, after: Option<&str>, limit: i64) -> StoreResult<Vec<(String, String)>> {
+        self.inner.raw_contents(after, limit).await
+    }
+
+    async fn set_raw_content(&self, message_id: &str, content: &str) -> StoreResult<()> {
+        self.inner.set_raw_content(message_id, content).await
+    }
+}
+
+#[async_trait]
+impl ScheduleStore for EncryptedStore {
+    async fn schedule_message(&self, scheduled: &ScheduledMessage) -> StoreResult<()> {
+        self.inner.schedule_message(&self.seal_scheduled(scheduled.clone())?).await
+    }
+
+    async fn scheduled_for_sender(&self, sender_id: &str) -> StoreResult<Vec<ScheduledMessage>> {
+        self.open_scheduled(self.inner.scheduled_for_sender(sender_id).await?)
+    }
+
+    async fn cancel_scheduled(&self, message_id: &str, sender_id: &str) -> StoreResult<bool> {
+        self.inner.cancel_scheduled(message_id, sender_id).await
+    }
+
+    async fn due_scheduled(&self, now: i64, limit: i64) -> StoreResult<Vec<ScheduledMessage>> {
+        self.open_scheduled(self.inner.due_scheduled(now, limit).await?)
+    }
+
+    async fn publish_scheduled(&self, message: &Message) -> StoreResult<Option<u64>> {
+        self.inner.publish_scheduled(&self.seal(message.clone())?).await
+    }
+
+    async fn raw_scheduled_contents(&self, after: Option<&str>, limit: i64) -> StoreResult<Vec<(String, String)>> {
+        self.inner.raw_scheduled_contents(after, limit).await
+    }
+
+    async fn set_raw_scheduled_content(&self, message_id: &str, content: &str) -> StoreResult<()> {
+        self.inner.set_raw_scheduled_content(message_id, content).await
+    }
+}
+
+#[async_trait]
+impl WebhookStore for EncryptedStore {
+    async fn create_webhook(&self, record: &WebhookRecord) -> StoreResult<()> {
+        let mut sealed = record.clone();
+        sealed.secret = self
+            .keyring
+            .encrypt_field(&record.secret, &record.webhook.webhook_id)
+            .map_err(|e| sqlx::Error::Protocol(format!("encrypt webhook secret: {:#}", e)))?;
+        self.inner.create_webhook(&sealed).await
+    }
+
+    async fn find_webhook(&self, webhook_id: &str) -> StoreResult<Option<WebhookRecord>> {
+        self.inner.find_webhook(webhook_id).await?.map(|r| self.open_webhook(r)).transpose()
+    }
+
+    async fn webhooks_for_group(&self, group_id: &str) -> StoreResult<Vec<WebhookRecord>> {
+        self.inner.webhooks_for_group(group_id).await?.into_iter().map(|r| self.open_webhook(r)).collect()
+    }
+
+    async fn set_webhook_enabled(&self, webhook_id: &str, enabled: bool, now: i64) -> StoreResult<bool> {
+        self.inner.set_webhook_enabled(webhook_id, enabled, now).await
+    }
+
+    async fn delete_webhook(&self, webhook_id: &str) -> StoreResult<bool> {
+        self.inner.delete_webhook(webhook_id).await
+    }
+
+    async fn enqueue_deliveries(&self, deliveries: &[WebhookDelivery]) -> StoreResult<()> {
+        self.inner.enqueue_deliveries(deliveries).await
+    }
+
+    async fn claim_deliveries(&self, now: i64, lease_until: i64, limit: i64) -> StoreResult<Vec<WebhookDelivery>> {
+        self.inner.claim_deliveries(now, lease_until, limit).await
+    }
+
+    async fn record_attempt(&self, attempt: &DeliveryAttempt, disable_after: u32) -> StoreResult<bool> {
+        self.inner.record_attempt(attempt, disable_after).await
+    }
+
+    async fn deliveries_for_webhook(&self, webhook_id: &str, limit: i64) -> StoreResult<Vec<WebhookDelivery>> {
+        self.inner.deliveries_for_webhook(webhook_id, limit).await
+    }
+
+    async fn purge_deliveries(&self, finished_before: i64, limit: i64) -> StoreResult<u64> {
+        self.inner.purge_deliveries(finished_before, limit).await
+    }
+
+    async fn raw_webhook_secrets(&self, after: Option<&str>, limit: i64) -> StoreResult<Vec<(String, String)>> {
+        self.inner.raw_webhook_secrets(after, limit).await
+    }
+
+    async fn set_raw_webhook_secret(&self, webhook_id: &str, secret: &str) -> StoreResult<()> {
+        self.inner.set_raw_webhook_secret(webhook_id, secret).await
+    }
+}
+
+#[async_trait]
+impl InviteStore for EncryptedStore {
+    async fn create_invite(&self, invite: &Invite) -> StoreResult<()> {
+        self.inner.create_invite(invite).await
+    }
+
+    async fn invites_for_user(&self, user_id: &str) -> StoreResult<Vec<Invite>> {
+        self.inner.invites_for_user(user_id).await
+    }
+
+    async fn delete_invite(&self, invite_id: &str) -> StoreResult<bool> {
+        self.inner.delete_invite(invite_id).await
+    }
+}
+
+#[async_trait]
+impl ReactionStore for EncryptedStore {
+    async fn add_reaction(&self, message_id: &str, user_id: &str, emoji: &str, created_at: &str) -> StoreResult<bool> {
+        self.inner.add_reaction(message_id, user_id, emoji, created_at).await
+    }
+
+    async fn remove_reaction(&self, message_id: &str, user_id: &str, emoji: &str) -> StoreResult<bool> {
+        self.inner.remove_reaction(message_id, user_id, emoji).await
+    }
+
+    async fn reactions_for(&self, message_ids: &[String]) -> StoreResult<Vec<(String, String, String)>> {
+        self.inner.reactions_for(message_ids).await
+    }
+}
+
+#[async_trait]
+impl MentionStore for EncryptedStore {
+    async fn add_mentions(&self, message_id: &str, user_ids: &[String]) -> StoreResult<()> {
+        self.inner.add_mentions(message_id, user_ids).await
+    }
+
+    async fn mentions_for(&self, message_ids: &[String]) -> StoreResult<Vec<(String, String)>> {
+        self.inner.mentions_for(message_ids).await
+    }
+
+    async fn mention_inbox(&self, user_id: &str, before: Option<&str>, limit: i64) -> StoreResult<Vec<(Message, String)>> {
+        self.inner
+            .mention_inbox(user_id, before, limit)
+            .await?
+            .into_iter()
+            .map(|(m, group_name)| Ok((self.open(m)?, group_name)))
+            .collect()
+    }
+}
+
+#[async_trait]
+impl PinStore for EncryptedStore {
+    async fn pin_message(&self, message_id: &str, group_id: &str, user_id: &str, pinned_at: &str) -> StoreResult<bool> {
+        self.inner.pin_message(message_id, group_id, user_id, pinned_at).await
+    }
+
+    async fn unpin_message(&self, message_id: &str) -> StoreResult<bool> {
+        self.inner.unpin_message(message_id).await
+    }
+
+    async fn pins_for_group(&self, group_id: &str) -> StoreResult<Vec<PinRecord>> {
+        self.inner
+            .pins_for_group(group_id)
+            .await?
+            .into_iter()
+            .map(|p| Ok(PinRecord { message: self.open(p.message)?, ..p }))
+            .collect()
+    }
+}
+
+#[async_trait]
+impl KeyStore for EncryptedStore {
+    async fn put_public_key(&self, key: &PublicKey) -> StoreResult<()> {
+        self.inner.put_public_key(key).await
+    }
+
+    async fn public_key(&self, user_id: &str) -> StoreResult<Option<PublicKey>> {
+        self.inner.public_key(user_id).await
+    }
+
+    async fn group_public_keys(&self, group_id: &str) -> StoreResult<Vec<PublicKey>> {
+        self.inner.group_public_keys(group_id).await
+    }
+
+    async fn put_sender_keys(&self, keys: &[SenderKey]) -> StoreResult<()> {
+        self.inner.put_sender_keys(keys).await
+    }
+
+    async fn sender_keys_for(&self, group_id: &str, recipient_id: &str) -> StoreResult<Vec<SenderKey>> {
+        self.inner.sender_keys_for(group_id, recipient_id).await
+    }
+}
+
+#[async_trait]
+impl AdminStore for EncryptedStore {
+    async fn list_users(&self, now: i64) -> StoreResult<Vec<UserSummary>> {
+        self.inner.list_users(now).await
+    }
+
+    async fn set_password_hash(&self, user_id: &str, password_hash: &str) -> StoreResult<bool> {
+        self.inner.set_password_hash(user_id, password_hash).await
+    }
+
+    async fn set_user_disabled(&self, user_id: &str, disabled: bool) -> StoreResult<bool> {
+        self.inner.set_user_disabled(user_id, disabled).await
+    }
+
+    async fn revoke_sessions(&self, user_id: Option<&str>) -> StoreResult<u64> {
+        self.inner.revoke_sessions(user_id).await
+    }
+
+    async fn list_groups(&self) -> StoreResult<Vec<GroupSummary>> {
+        self.inner.list_groups().await
+    }
+
+    async fn delete_group(&self, group_id: &str) -> StoreResult<bool> {
+        self.inner.delete_group(group_id).await
+    }
+
+    async fn stats(&self, now: i64) -> StoreResult<StoreStats> {
+        self.inner.stats(now).await
+    }
+}
+
+#[async_trait]
+impl Store for EncryptedStore {
+    fn backend(&self) -> &'static str {
+        self.inner.backend()
+    }
+
+    async fn migrate(&self) -> anyhow::Result<()> {
+        self.inner.migrate().await
+    }
+
+    async fn ping(&self) -> StoreResult<()> {
+        self.inner.ping().await
+    }
+
+    async fn schema_version(&self) -> StoreResult<i64> {
+        self.inner.schema_version().await
+    }
+
+    async fn data_dir(&self) -> StoreResult<Option<PathBuf>> {
+        self.inner.data_dir().await
+    }
+
+    // la copia contiene i contenuti così come sono salvati, quindi cifrati
+    async fn backup_to(&self, path: &Path) -> anyhow::Result<()> {
+        self.inner.backup_to(path).await
+    }
+
+    fn pool_stats(&self) -> PoolStats {
+        self.inner.pool_stats()
+    }
+
+    async fn close(&self) {
+        self.inner.close().await
+    }
+}
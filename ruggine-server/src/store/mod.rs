@@ -0,0 +1,417 @@
+/* Livello di persistenza: un trait per ogni insieme di tabelle (utenti, sessioni, gruppi, membership,
+   messaggi, messaggi programmati, inviti, reazioni, menzioni, pin, chiavi E2EE, webhook), le operazioni dei comandi di amministrazione e
+   `Store` che li riunisce insieme alle operazioni di servizio (migrazioni, ping, chiusura). Handler HTTP e sessioni WS usano solo `Arc<dyn Store>`;
+   il backend concreto (SQLite o PostgreSQL) viene scelto dallo schema di database_url. */
+use async_trait::async_trait;
+use ruggine_core::{Group, Message, PublicKey, ScheduledMessage, SenderKey, User, Webhook, WebhookDelivery};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::config::Config;
+use crate::encryption::Keyring;
+
+pub mod encrypted;
+pub mod postgres;
+pub mod sqlite;
+
+pub use encrypted::EncryptedStore;
+pub use postgres::PostgresStore;
+pub use sqlite::SqliteStore;
+
+/// Entrambi i backend sono basati su sqlx: gli errori restano sqlx::Error.
+pub type StoreResult<T> = Result<T, sqlx::Error>;
+
+/// Credenziali salvate per un utente, usate dal login.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Credentials {
+    pub user: User,
+    pub password_hash: String,
+    /// Account disabilitato da un amministratore: il login va rifiutato.
+    pub disabled: bool,
+}
+
+/// Invito pendente di un utente in un gruppo.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Invite {
+    pub invite_id: String,
+    pub group_id: String,
+    /// user_id dell'invitato
+    pub invited: String,
+    pub created_at: String,
+}
+
+/// Ruolo di un membro nel gruppo, salvato come testo ("admin" / "member").
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    Admin,
+    Member,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Admin => "admin",
+            Role::Member => "member",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "admin" => Some(Role::Admin),
+            "member" => Some(Role::Member),
+            _ => None,
+        }
+    }
+}
+
+/// Messaggio fissato come letto dal DB (senza reazioni né menzioni).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PinRecord {
+    pub message: Message,
+    pub pinned_by: String,
+    pub pinned_at: String,
+}
+
+#[async_trait]
+pub trait UserStore {
+    async fn create_user(&self, user: &User, password_hash: &str) -> StoreResult<()>;
+    async fn find_user(&self, user_id: &str) -> StoreResult<Option<User>>;
+    async fn username_exists(&self, username: &str) -> StoreResult<bool>;
+    async fn credentials(&self, username: &str) -> StoreResult<Option<Credentials>>;
+}
+
+/// Token di sessione (uno per utente; scadenza in secondi unix, None = senza scadenza).
+#[async_trait]
+pub trait SessionStore {
+    async fn set_token(&self, user_id: &str, token: &str, expires_at: Option<i64>) -> StoreResult<()>;
+    /// user_id del token, se esiste e non è scaduto rispetto a `now`.
+    async fn user_id_for_token(&self, token: &str, now: i64) -> StoreResult<Option<String>>;
+}
+
+#[async_trait]
+pub trait GroupStore {
+    /// Crea il gruppo e le membership indicate in un'unica transazione.
+    async fn create_group(&self, group: &Group, members: &[(String, Role)]) -> StoreResult<()>;
+    async fn find_group(&self, group_id: &str) -> StoreResult<Option<Group>>;
+    /// Salva nome, pin_policy, retention e message_ttl_secs (encrypted non cambia dopo la creazione).
+    async fn update_group(&self, group: &Group) -> StoreResult<()>;
+    /// Gruppi di cui l'utente è membro, dal meno recente.
+    async fn groups_for_user(&self, user_id: &str) -> StoreResult<Vec<Group>>;
+}
+
+#[async_trait]
+pub trait MembershipStore {
+    async fn member_role(&self, group_id: &str, user_id: &str) -> StoreResult<Option<Role>>;
+    async fn member_ids(&self, group_id: &str) -> StoreResult<Vec<String>>;
+    /// user_id dei membri del gruppo con gli username indicati, escluso `exclude`.
+    /// Ritorna coppie (username, user_id) in ordine qualsiasi.
+    async fn find_members_by_username(
+        &self,
+        group_id: &str,
+        exclude: &str,
+        usernames: &[String],
+    ) -> StoreResult<Vec<(String, String)>>;
+    /// Silenzia o riattiva il gruppo per il membro; ritorna false se l'utente non è membro del gruppo.
+    async fn set_muted(&self, group_id: &str, user_id: &str, muted: bool) -> StoreResult<bool>;
+    /// user_id dei membri che hanno silenziato il gruppo.
+    async fn muted_member_ids(&self, group_id: &str) -> StoreResult<Vec<String>>;
+    /// user_id degli admin del gruppo.
+    async fn admin_ids(&self, group_id: &str) -> StoreResult<Vec<String>>;
+    /// Aggiunge l'utente al gruppo con il ruolo indicato; ritorna false se era già membro.
+    async fn add_member(&self, group_id: &str, user_id: &str, role: Role, joined_at: &str) -> StoreResult<bool>;
+    /// Toglie l'utente dal gruppo; ritorna false se non era membro.
+    async fn remove_member(&self, group_id: &str, user_id: &str) -> StoreResult<bool>;
+
+    async fn is_member(&self, group_id: &str, user_id: &str) -> StoreResult<bool> {
+        Ok(self.member_role(group_id, user_id).await?.is_some())
+    }
+}
+
+/// Le letture di messaggi (anche tramite menzioni e pin) ignorano quelli effimeri già scaduti secondo
+/// l'orologio del database, prima ancora che purge_expired li elimini.
+#[async_trait]
+pub trait MessageStore {
+    /// Salva il messaggio assegnandogli il seq successivo del suo gruppo (message.seq è ignorato)
+    /// e ritorna il seq assegnato.
+    async fn insert_message(&self, message: &Message) -> StoreResult<u64>;
+    async fn find_message(&self, message_id: &str) -> StoreResult<Option<Message>>;
+    /// Al più `limit` messaggi del gruppo creati prima di `before`, in ordine cronologico
+    /// (a parità di timestamp nell'ordine di inserimento).
+    async fn list_messages(&self, group_id: &str, before: Option<&str>, limit: i64) -> StoreResult<Vec<Message>>;
+    /// Al più `limit` messaggi del gruppo con seq maggiore di `after_seq`, in ordine di seq.
+    async fn messages_after(&self, group_id: &str, after_seq: u64, limit: i64) -> StoreResult<Vec<Message>>;
+    /// seq dell'ultimo messaggio del gruppo (0 se non ce ne sono).
+    async fn latest_seq(&self, group_id: &str) -> StoreResult<u64>;
+    /// seq del messaggio più recente che non rientra tra i `keep` più recenti del gruppo: eliminando i
+    /// messaggi fino a questo seq ne restano `keep`. None se il gruppo ne ha al più `keep`.
+    async fn seq_beyond_newest(&self, group_id: &str, keep: u64) -> StoreResult<Option<u64>>;
+    /// Elimina al più `limit` messaggi del gruppo con seq fino a `through_seq`, dal più vecchio,
+    /// insieme a reazioni, menzioni e pin; ritorna il numero di messaggi eliminati.
+    async fn purge_messages(&self, group_id: &str, through_seq: u64, limit: i64) -> StoreResult<u64>;
+    /// Elimina al più `limit` messaggi effimeri scaduti entro `now` (secondi unix), dal primo a scadere,
+    /// insieme a reazioni, menzioni e pin; ritorna le coppie (group_id, message_id) eliminate.
+    async fn purge_expired(&self, now: i64, limit: i64) -> StoreResult<Vec<(String, String)>>;
+    /// Coppie (message_id, content) con il contenuto così come è salvato, per message_id crescente
+    /// a partire dopo `after`; usato dalla ricifratura a riposo.
+    async fn raw_contents(&self, after: Option<&str>, limit: i64) -> StoreResult<Vec<(String, String)>>;
+    /// Sostituisce il contenuto salvato del messaggio.
+    async fn set_raw_content(&self, message_id: &str, content: &str) -> StoreResult<()>;
+}
+
+/// Messaggi programmati, trattenuti finché non arriva il loro send_at (secondi unix).
+#[async_trait]
+pub trait ScheduleStore {
+    async fn schedule_message(&self, scheduled: &ScheduledMessage) -> StoreResult<()>;
+    /// Messaggi programmati dall'utente e non ancora pubblicati, dal primo da inviare.
+    async fn scheduled_for_sender(&self, sender_id: &str) -> StoreResult<Vec<ScheduledMessage>>;
+    /// Annulla il messaggio programmato se appartiene a `sender_id`; ritorna false se non esisteva
+    /// (o era già stato pubblicato).
+    async fn cancel_scheduled(&self, message_id: &str, sender_id: &str) -> StoreResult<bool>;
+    /// Al più `limit` messaggi programmati con send_at entro `now`, dal primo da inviare.
+    async fn due_scheduled(&self, now: i64, limit: i64) -> StoreResult<Vec<ScheduledMessage>>;
+    /// In un'unica transazione toglie il messaggio programmato con lo stesso message_id e lo salva come
+    /// insert_message, ritornando il seq assegnato. None se non era più programmato (annullato o già
+    /// pubblicato da un'altra istanza): in quel caso non salva nulla.
+    async fn publish_scheduled(&self, message: &Message) -> StoreResult<Option<u64>>;
+    /// Come raw_contents, per i messaggi programmati.
+    async fn raw_scheduled_contents(&self, after: Option<&str>, limit: i64) -> StoreResult<Vec<(String, String)>>;
+    /// Sostituisce il contenuto salvato del messaggio programmato.
+    async fn set_raw_scheduled_content(&self, message_id: &str, content: &str) -> StoreResult<()>;
+}
+
+#[async_trait]
+pub trait InviteStore {
+    async fn create_invite(&self, invite: &Invite) -> StoreResult<()>;
+    /// Inviti pendenti dell'utente, dal meno recente.
+    async fn invites_for_user(&self, user_id: &str) -> StoreResult<Vec<Invite>>;
+    /// Ritorna false se l'invito non esisteva.
+    async fn delete_invite(&self, invite_id: &str) -> StoreResult<bool>;
+}
+
+#[async_trait]
+pub trait ReactionStore {
+    /// Ritorna false se la reazione esisteva già.
+    async fn add_reaction(&self, message_id: &str, user_id: &str, emoji: &str, created_at: &str) -> StoreResult<bool>;
+    /// Ritorna false se la reazione non esisteva.
+    async fn remove_reaction(&self, message_id: &str, user_id: &str, emoji: &str) -> StoreResult<bool>;
+    /// Terne (message_id, user_id, emoji) nell'ordine di inserimento.
+    async fn reactions_for(&self, message_ids: &[String]) -> StoreResult<Vec<(String, String, String)>>;
+}
+
+#[async_trait]
+pub trait MentionStore {
+    async fn add_mentions(&self, message_id: &str, user_ids: &[String]) -> StoreResult<()>;
+    /// Coppie (message_id, user_id) nell'ordine di inserimento.
+    async fn mentions_for(&self, message_ids: &[String]) -> StoreResult<Vec<(String, String)>>;
+    /// Messaggi che menzionano l'utente nei gruppi di cui è ancora membro, dal più recente,
+    /// con il nome del gruppo.
+    async fn mention_inbox(&self, user_id: &str, before: Option<&str>, limit: i64) -> StoreResult<Vec<(Message, String)>>;
+}
+
+#[async_trait]
+pub trait PinStore {
+    /// Ritorna false se il messaggio era già fissato.
+    async fn pin_message(&self, message_id: &str, group_id: &str, user_id: &str, pinned_at: &str) -> StoreResult<bool>;
+    /// Ritorna false se il messaggio non era fissato.
+    async fn unpin_message(&self, message_id: &str) -> StoreResult<bool>;
+    /// Messaggi fissati del gruppo, dal più recente.
+    async fn pins_for_group(&self, group_id: &str) -> StoreResult<Vec<PinRecord>>;
+}
+
+/// Key directory e sender key dei gruppi cifrati: il server salva solo materiale pubblico o già cifrato.
+#[async_trait]
+pub trait KeyStore {
+    /// Salva la chiave pubblica dell'utente, sostituendo la precedente.
+    async fn put_public_key(&self, key: &PublicKey) -> StoreResult<()>;
+    async fn public_key(&self, user_id: &str) -> StoreResult<Option<PublicKey>>;
+    /// Chiavi pubbliche dei membri del gruppo che ne hanno pubblicata una, per user_id.
+    async fn group_public_keys(&self, group_id: &str) -> StoreResult<Vec<PublicKey>>;
+    /// Salva le sender key in un'unica transazione, sostituendo quelle con stesso gruppo, mittente,
+    /// sender_key_id e destinatario.
+    async fn put_sender_keys(&self, keys: &[SenderKey]) -> StoreResult<()>;
+    /// Sender key del gruppo destinate all'utente, dalla meno recente.
+    async fn sender_keys_for(&self, group_id: &str, recipient_id: &str) -> StoreResult<Vec<SenderKey>>;
+}
+
+/// Webhook come salvato, con il segreto usato per firmare le consegne.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WebhookRecord {
+    pub webhook: Webhook,
+    pub secret: String,
+}
+
+/// Esito di un tentativo di consegna, registrato da record_attempt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttemptOutcome {
+    Delivered,
+    /// Fallito, da ritentare all'istante indicato (secondi unix)
+    Retry(i64),
+    /// Fallito, tentativi esauriti
+    Failed,
+    /// Abbandonato senza contattare il webhook (es. messaggio non più disponibile): non conta tra i
+    /// tentativi né tra i fallimenti del webhook
+    Dropped,
+}
+
+/// Tentativo di consegna concluso all'istante `at` (secondi unix).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeliveryAttempt {
+    pub delivery_id: String,
+    pub webhook_id: String,
+    pub outcome: AttemptOutcome,
+    pub status_code: Option<u16>,
+    pub error: Option<String>,
+    pub at: i64,
+}
+
+/// Webhook dei gruppi e coda persistente delle loro consegne (istanti in secondi unix).
+#[async_trait]
+pub trait WebhookStore {
+    async fn create_webhook(&self, webhook: &WebhookRecord) -> StoreResult<()>;
+    async fn find_webhook(&self, webhook_id: &str) -> StoreResult<Option<WebhookRecord>>;
+    /// Webhook del gruppo, dal meno recente.
+    async fn webhooks_for_group(&self, group_id: &str) -> StoreResult<Vec<WebhookRecord>>;
+    /// Abilita (azzerando i fallimenti consecutivi) o disabilita il webhook; disabilitandolo le consegne
+    /// in coda falliscono. Ritorna false se il webhook non esiste.
+    async fn set_webhook_enabled(&self, webhook_id: &str, enabled: bool, now: i64) -> StoreResult<bool>;
+    /// Elimina il webhook con le sue consegne; ritorna false se non esisteva.
+    async fn delete_webhook(&self, webhook_id: &str) -> StoreResult<bool>;
+    /// Mette in coda le consegne (status pending, next_attempt_at già impostato) in un'unica transazione.
+    async fn enqueue_deliveries(&self, deliveries: &[WebhookDelivery]) -> StoreResult<()>;
+    /// Prende al più `limit` consegne in coda con next_attempt_at entro `now`, dalla più vecchia, e sposta
+    /// il loro prossimo tentativo a `lease_until`: con più istanze ogni consegna è presa da una sola, e
+    /// se l'istanza si ferma durante il tentativo la consegna torna disponibile dopo `lease_until`.
+    async fn claim_deliveries(&self, now: i64, lease_until: i64, limit: i64) -> StoreResult<Vec<WebhookDelivery>>;
+    /// Registra l'esito di un tentativo e aggiorna i fallimenti consecutivi del webhook; arrivati a
+    /// `disable_after` il webhook viene disabilitato e le sue consegne in coda falliscono. Ritorna true
+    /// se questo tentativo ha disabilitato il webhook.
+    async fn record_attempt(&self, attempt: &DeliveryAttempt, disable_after: u32) -> StoreResult<bool>;
+    /// Registro delle consegne del webhook, dalla più recente.
+    async fn deliveries_for_webhook(&self, webhook_id: &str, limit: i64) -> StoreResult<Vec<WebhookDelivery>>;
+    /// Elimina al più `limit` consegne concluse entro `finished_before`; ritorna quante ne ha eliminate.
+    async fn purge_deliveries(&self, finished_before: i64, limit: i64) -> StoreResult<u64>;
+    /// Coppie (webhook_id, secret) con il segreto così come è salvato, per webhook_id crescente a partire
+    /// dopo `after`; usato dalla ricifratura a riposo.
+    async fn raw_webhook_secrets(&self, after: Option<&str>, limit: i64) -> StoreResult<Vec<(String, String)>>;
+    /// Sostituisce il segreto salvato del webhook.
+    async fn set_raw_webhook_secret(&self, webhook_id: &str, secret: &str) -> StoreResult<()>;
+}
+
+/// Utente come mostrato dai comandi di amministrazione.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UserSummary {
+    pub user: User,
+    pub disabled: bool,
+    /// Ha un token di sessione non scaduto.
+    pub active_session: bool,
+}
+
+/// Gruppo con il numero di membri e di messaggi.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GroupSummary {
+    pub group: Group,
+    pub members: u64,
+    pub messages: u64,
+}
+
+/// Conteggi complessivi del database.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StoreStats {
+    pub users: u64,
+    pub disabled_users: u64,
+    pub active_sessions: u64,
+    pub groups: u64,
+    pub messages: u64,
+    pub pending_invites: u64,
+}
+
+/// Operazioni dei comandi di amministrazione (`ruggine-server <comando>`); `now` in secondi unix decide
+/// quali sessioni sono ancora valide.
+#[async_trait]
+pub trait AdminStore {
+    /// Tutti gli utenti, dal meno recente.
+    async fn list_users(&self, now: i64) -> StoreResult<Vec<UserSummary>>;
+    /// Ritorna false se l'utente non esiste.
+    async fn set_password_hash(&self, user_id: &str, password_hash: &str) -> StoreResult<bool>;
+    /// Ritorna false se l'utente non esiste.
+    async fn set_user_disabled(&self, user_id: &str, disabled: bool) -> StoreResult<bool>;
+    /// Cancella il token di sessione dell'utente indicato (di tutti con None) e ritorna quanti
+    /// token sono stati cancellati.
+    async fn revoke_sessions(&self, user_id: Option<&str>) -> StoreResult<u64>;
+    /// Tutti i gruppi, dal meno recente.
+    async fn list_groups(&self) -> StoreResult<Vec<GroupSummary>>;
+    /// Elimina in un'unica transazione il gruppo con messaggi, messaggi programmati, reazioni, menzioni,
+    /// pin, membership, inviti, sender key e webhook (con le loro consegne). Ritorna false se il gruppo non esisteva.
+    async fn delete_group(&self, group_id: &str) -> StoreResult<bool>;
+    async fn stats(&self, now: i64) -> StoreResult<StoreStats>;
+}
+
+/// Stato del pool di connessioni, per le metriche.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoolStats {
+    pub size: u32,
+    pub idle: usize,
+}
+
+/// Backend completo usato dall'applicazione.
+#[async_trait]
+pub trait Store:
+    UserStore
+    + SessionStore
+    + GroupStore
+    + MembershipStore
+    + MessageStore
+    + ScheduleStore
+    + InviteStore
+    + ReactionStore
+    + MentionStore
+    + PinStore
+    + KeyStore
+    + WebhookStore
+    + AdminStore
+    + Send
+    + Sync
+{
+    /// Nome del backend ("sqlite", "postgres").
+    fn backend(&self) -> &'static str;
+    /// Crea o aggiorna lo schema fino a SCHEMA_VERSION.
+    async fn migrate(&self) -> anyhow::Result<()>;
+    /// Esegue una query banale.
+    async fn ping(&self) -> StoreResult<()>;
+    /// Versione dello schema registrata nel database.
+    async fn schema_version(&self) -> StoreResult<i64>;
+    /// Directory locale dei file del database (None per DB in memoria o remoti).
+    async fn data_dir(&self) -> StoreResult<Option<PathBuf>>;
+    /// Scrive in `path` (che non deve esistere) una copia coerente del database senza fermare le
+    /// altre operazioni; non supportato da tutti i backend.
+    async fn backup_to(&self, path: &Path) -> anyhow::Result<()>;
+    fn pool_stats(&self) -> PoolStats;
+    async fn close(&self);
+}
+
+/// true se l'URL indica un database PostgreSQL.
+pub fn is_postgres_url(url: &str) -> bool {
+    url.starts_with("postgres://") || url.starts_with("postgresql://")
+}
+
+/// Apre il backend indicato da database_url (postgres:// o postgresql:// per PostgreSQL,
+/// altrimenti un percorso/URL SQLite) ed esegue le migrazioni. Con encryption_key_file il backend
+/// è avvolto da un EncryptedStore.
+pub async fn connect(config: &Config) -> anyhow::Result<Arc<dyn Store>> {
+    let store = open(config).await?;
+    Ok(match &config.encryption_key_file {
+        Some(path) => Arc::new(EncryptedStore::new(store, Arc::new(Keyring::load(path)?))),
+        None => store,
+    })
+}
+
+/// Come connect, ma senza cifratura a riposo: i contenuti sono letti e scritti così come sono salvati.
+pub async fn open(config: &Config) -> anyhow::Result<Arc<dyn Store>> {
+    let store: Arc<dyn Store> = if is_postgres_url(&config.database_url) {
+        Arc::new(PostgresStore::connect(&config.database_url, config.db_pool_size).await?)
+    } else {
+        Arc::new(SqliteStore::connect(&config.database_url, config.db_pool_size).await?)
+    };
+    store.migrate().await?;
+    Ok(store)
+}
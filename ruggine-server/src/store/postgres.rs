@@ -0,0 +1,1600 @@
+/* Backend PostgreSQL: stesso modello dati del backend SQLite. L'ordine di inserimento, che in SQLite
+   viene dal rowid, qui è dato da una colonna `seq` BIGSERIAL (da non confondere con group_seq, il
+   numero del messaggio nel suo gruppo); la versione dello schema è salvata
+   nella tabella schema_version. */
+use anyhow::Context;
+use async_trait::async_trait;
+use ruggine_core::{
+    timestamp_from_unix, unix_from_timestamp, DeliveryStatus, Group, KeyAlgorithm, Message, PinPolicy, PublicKey,
+    RetentionPolicy, ScheduledMessage, SenderKey, User, Webhook, WebhookDelivery, WebhookEvent,
+};
+use sqlx::{
+    postgres::{PgPoolOptions, PgRow},
+    PgPool, Postgres, Row, Transaction,
+};
+use std::path::{Path, PathBuf};
+
+use super::{
+    AdminStore, AttemptOutcome, Credentials, DeliveryAttempt, GroupStore, GroupSummary, Invite, InviteStore, KeyStore,
+    MembershipStore, MentionStore, MessageStore, PinRecord, PinStore, PoolStats, ReactionStore, Role, ScheduleStore,
+    SessionStore, Store, StoreResult, StoreStats, UserStore, UserSummary, WebhookRecord, WebhookStore,
+};
+use crate::{metrics, SCHEMA_VERSION};
+
+#[derive(Clone)]
+pub struct PostgresStore {
+    pool: PgPool,
+}
+
+impl PostgresStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn connect(database_url: &str, max_connections: u32) -> anyhow::Result<Self> {
+        let pool = PgPoolOptions::new()
+            .max_connections(max_connections)
+            .connect(database_url)
+            .await
+            .context("connect to postgres")?;
+        Ok(Self::new(pool))
+    }
+
+    pub fn pool(&self) -> &PgPool {
+        &self.pool
+    }
+}
+
+const MIGRATIONS: &[&str] = &[
+    r#"
+    CREATE TABLE IF NOT EXISTS users (
+        user_id          TEXT PRIMARY KEY,
+        username         TEXT NOT NULL UNIQUE,
+        password_hash    TEXT NOT NULL,
+        token            TEXT,
+        created_at       TEXT NOT NULL,
+        token_expires_at BIGINT
+    )"#,
+    r#"
+    CREATE TABLE IF NOT EXISTS groups (
+        group_id   TEXT PRIMARY KEY,
+        name       TEXT NOT NULL,
+        created_at TEXT NOT NULL,
+        pin_policy TEXT NOT NULL DEFAULT 'members',
+        seq        BIGSERIAL
+    )"#,
+    r#"
+    CREATE TABLE IF NOT EXISTS messages (
+        message_id TEXT PRIMARY KEY,
+        group_id   TEXT NOT NULL REFERENCES groups(group_id),
+        sender_id  TEXT NOT NULL REFERENCES users(user_id),
+        content    TEXT NOT NULL,
+        created_at TEXT NOT NULL,
+        seq        BIGSERIAL
+    )"#,
+    r#"
+    CREATE TABLE IF NOT EXISTS memberships (
+        membership_id TEXT PRIMARY KEY,
+        group_id      TEXT NOT NULL REFERENCES groups(group_id),
+        user_id       TEXT NOT NULL REFERENCES users(user_id),
+        joined_at     TEXT NOT NULL,
+        role          TEXT NOT NULL DEFAULT 'member',
+        muted         BOOLEAN NOT NULL DEFAULT FALSE
+    )"#,
+    r#"
+    CREATE TABLE IF NOT EXISTS invites (
+        invite_id  TEXT PRIMARY KEY,
+        group_id   TEXT NOT NULL REFERENCES groups(group_id),
+        invited    TEXT NOT NULL,
+        created_at TEXT NOT NULL,
+        seq        BIGSERIAL
+    )"#,
+    r#"
+    CREATE TABLE IF NOT EXISTS reactions (
+        message_id TEXT NOT NULL REFERENCES messages(message_id) ON DELETE CASCADE,
+        user_id    TEXT NOT NULL REFERENCES users(user_id),
+        emoji      TEXT NOT NULL,
+        created_at TEXT NOT NULL,
+        seq        BIGSERIAL,
+        PRIMARY KEY(message_id, user_id, emoji)
+    )"#,
+    r#"
+    CREATE TABLE IF NOT EXISTS mentions (
+        message_id TEXT NOT NULL REFERENCES messages(message_id) ON DELETE CASCADE,
+        user_id    TEXT NOT NULL REFERENCES users(user_id),
+        seq        BIGSERIAL,
+        PRIMARY KEY(message_id, user_id)
+    )"#,
+    r#"
+    CREATE TABLE IF NOT EXISTS pinned_messages (
+        message_id TEXT PRIMARY KEY REFERENCES messages(message_id) ON DELETE CASCADE,
+        group_id   TEXT NOT NULL REFERENCES groups(group_id),
+        pinned_by  TEXT NOT NULL REFERENCES users(user_id),
+        pinned_at  TEXT NOT NULL,
+        seq        BIGSERIAL
+    )"#,
+    // versione 2: numerazione per gruppo dei messaggi, groups.last_seq è l'ultimo seq assegnato
+    "ALTER TABLE groups ADD COLUMN IF NOT EXISTS last_seq BIGINT NOT NULL DEFAULT 0",
+    "ALTER TABLE messages ADD COLUMN IF NOT EXISTS group_seq BIGINT NOT NULL DEFAULT 0",
+    // versione 3: gruppi cifrati end-to-end, key directory e sender key
+    "ALTER TABLE groups ADD COLUMN IF NOT EXISTS encrypted BOOLEAN NOT NULL DEFAULT FALSE",
+    r#"
+    CREATE TABLE IF NOT EXISTS public_keys (
+        user_id    TEXT PRIMARY KEY REFERENCES users(user_id),
+        key_id     TEXT NOT NULL,
+        alg        TEXT NOT NULL,
+        public_key TEXT NOT NULL,
+        created_at TEXT NOT NULL
+    )"#,
+    r#"
+    CREATE TABLE IF NOT EXISTS sender_keys (
+        group_id         TEXT NOT NULL REFERENCES groups(group_id),
+        sender_id        TEXT NOT NULL REFERENCES users(user_id),
+        sender_key_id    TEXT NOT NULL,
+        recipient_id     TEXT NOT NULL REFERENCES users(user_id),
+        recipient_key_id TEXT NOT NULL,
+        sealed_key       TEXT NOT NULL,
+        created_at       TEXT NOT NULL,
+        seq              BIGSERIAL,
+        PRIMARY KEY(group_id, sender_id, sender_key_id, recipient_id)
+    )"#,
+    // versione 4: account disabilitati dai comandi di amministrazione
+    "ALTER TABLE users ADD COLUMN IF NOT EXISTS disabled BOOLEAN NOT NULL DEFAULT FALSE",
+    // versione 5: limiti di conservazione per gruppo, NULL = default del server
+    "ALTER TABLE groups ADD COLUMN IF NOT EXISTS retention_max_age_secs BIGINT",
+    "ALTER TABLE groups ADD COLUMN IF NOT EXISTS retention_max_count BIGINT",
+    // versione 6: messaggi effimeri, scadenza in secondi unix e durata di default per gruppo
+    "ALTER TABLE messages ADD COLUMN IF NOT EXISTS expires_at BIGINT",
+    "ALTER TABLE groups ADD COLUMN IF NOT EXISTS message_ttl_secs BIGINT",
+    "CREATE INDEX IF NOT EXISTS messages_expires_at ON messages(expires_at) WHERE expires_at IS NOT NULL",
+    // versione 7: messaggi programmati, send_at in secondi unix, spostati in messages alla pubblicazione
+    r#"
+    CREATE TABLE IF NOT EXISTS scheduled_messages (
+        message_id TEXT PRIMARY KEY,
+        group_id   TEXT NOT NULL REFERENCES groups(group_id),
+        sender_id  TEXT NOT NULL REFERENCES users(user_id),
+        content    TEXT NOT NULL,
+        send_at    BIGINT NOT NULL,
+        ttl_secs   BIGINT,
+        created_at TEXT NOT NULL,
+        seq        BIGSERIAL
+    )"#,
+    "CREATE INDEX IF NOT EXISTS scheduled_messages_send_at ON scheduled_messages(send_at)",
+    // versione 8: webhook in uscita dei gruppi e coda delle consegne, events separati da virgole
+    r#"
+    CREATE TABLE IF NOT EXISTS webhooks (
+        webhook_id TEXT PRIMARY KEY,
+        group_id   TEXT NOT NULL REFERENCES groups(group_id),
+        url        TEXT NOT NULL,
+        events     TEXT NOT NULL,
+        secret     TEXT NOT NULL,
+        enabled    BOOLEAN NOT NULL DEFAULT TRUE,
+        created_by TEXT NOT NULL REFERENCES users(user_id),
+        created_at TEXT NOT NULL,
+        consecutive_failures BIGINT NOT NULL DEFAULT 0,
+        seq        BIGSERIAL
+    )"#,
+    r#"
+    CREATE TABLE IF NOT EXISTS webhook_deliveries (
+        delivery_id      TEXT PRIMARY KEY,
+        webhook_id       TEXT NOT NULL REFERENCES webhooks(webhook_id) ON DELETE CASCADE,
+        group_id         TEXT NOT NULL,
+        event            TEXT NOT NULL,
+        message_id       TEXT,
+        user_id          TEXT,
+        status           TEXT NOT NULL,
+        attempts         BIGINT NOT NULL DEFAULT 0,
+        next_attempt_at  BIGINT,
+        last_status_code INTEGER,
+        last_error       TEXT,
+        created_at       TEXT NOT NULL,
+        finished_at      BIGINT,
+        seq              BIGSERIAL
+    )"#,
+    "CREATE INDEX IF NOT EXISTS webhooks_group ON webhooks(group_id)",
+    "CREATE INDEX IF NOT EXISTS webhook_deliveries_due ON webhook_deliveries(status, next_attempt_at)",
+    "CREATE INDEX IF NOT EXISTS webhook_deliveries_webhook ON webhook_deliveries(webhook_id)",
+    "CREATE INDEX IF NOT EXISTS webhook_deliveries_finished ON webhook_deliveries(finished_at) WHERE finished_at IS NOT NULL",
+    // versione 9: gruppi silenziati dal singolo membro
+    "ALTER TABLE memberships ADD COLUMN IF NOT EXISTS muted BOOLEAN NOT NULL DEFAULT FALSE",
+    // versione 10: consegne degli eventi member.joined/member.left, senza messaggio
+    "ALTER TABLE webhook_deliveries ADD COLUMN IF NOT EXISTS user_id TEXT",
+    "ALTER TABLE webhook_deliveries ALTER COLUMN message_id DROP NOT NULL",
+    r#"
+    CREATE TABLE IF NOT EXISTS schema_version (
+        id      INTEGER PRIMARY KEY CHECK (id = 1),
+        version BIGINT NOT NULL
+    )"#,
+];
+
+const GROUP_COLUMNS: &str = "group_id, name, created_at, pin_policy, encrypted, retention_max_age_secs, \
+     retention_max_count, message_ttl_secs";
+
+const MESSAGE_COLUMNS: &str = "message_id, group_id, sender_id, content, created_at, group_seq, expires_at";
+
+const SCHEDULED_COLUMNS: &str = "message_id, group_id, sender_id, content, send_at, ttl_secs, created_at";
+
+const WEBHOOK_COLUMNS: &str =
+    "webhook_id, group_id, url, events, secret, enabled, created_by, created_at, consecutive_failures";
+
+const DELIVERY_COLUMNS: &str = "delivery_id, webhook_id, group_id, event, message_id, user_id, status, attempts, \
+     next_attempt_at, last_status_code, last_error, created_at, finished_at";
+
+// Condizione sui messaggi dell'alias non ancora scaduti, secondo l'orologio del database
+fn not_expired(alias: &str) -> String {
+    format!("({0}.expires_at IS NULL OR {0}.expires_at > EXTRACT(EPOCH FROM now())::BIGINT)", alias)
+}
+
+fn qualified_message_columns(alias: &str) -> String {
+    MESSAGE_COLUMNS
+        .split(", ")
+        .map(|c| format!("{}.{}", alias, c))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn scheduled_from_row(row: &PgRow) -> Result<ScheduledMessage, sqlx::Error> {
+    Ok(ScheduledMessage {
+        message_id: row.try_get("message_id")?,
+        group_id: row.try_get("group_id")?,
+        sender_id: row.try_get("sender_id")?,
+        content: row.try_get("content")?,
+        send_at: timestamp_from_unix(row.try_get("send_at")?),
+        created_at: row.try_get("created_at")?,
+        ttl_secs: row.try_get::<Option<i64>, _>("ttl_secs")?.map(|v| v as u64),
+    })
+}
+
+fn webhook_from_row(row: &PgRow) -> Result<WebhookRecord, sqlx::Error> {
+    let events: String = row.try_get("events")?;
+    Ok(WebhookRecord {
+        webhook: Webhook {
+            webhook_id: row.try_get("webhook_id")?,
+            group_id: row.try_get("group_id")?,
+            url: row.try_get("url")?,
+            events: events.split(',').filter_map(WebhookEvent::parse).collect(),
+            enabled: row.try_get("enabled")?,
+            created_by: row.try_get("created_by")?,
+            created_at: row.try_get("created_at")?,
+            consecutive_failures: row.try_get::<i64, _>("consecutive_failures")? as u32,
+        },
+        secret: row.try_get("secret")?,
+    })
+}
+
+fn delivery_from_row(row: &PgRow) -> Result<WebhookDelivery, sqlx::Error> {
+    let event: String = row.try_get("event")?;
+    let status: String = row.try_get("status")?;
+    Ok(WebhookDelivery {
+        delivery_id: row.try_get("delivery_id")?,
+        webhook_id: row.try_get("webhook_id")?,
+        group_id: row.try_get("group_id")?,
+        event: WebhookEvent::parse(&event).ok_or_else(|| sqlx::Error::Decode(format!("unknown webhook event {:?}", event).into()))?,
+        message_id: row.try_get("message_id")?,
+        user_id: row.try_get("user_id")?,
+        status: DeliveryStatus::parse(&status)
+            .ok_or_else(|| sqlx::Error::Decode(format!("unknown delivery status {:?}", status).into()))?,
+        attempts: row.try_get::<i64, _>("attempts")? as u32,
+        next_attempt_at: row.try_get::<Option<i64>, _>("next_attempt_at")?.map(timestamp_from_unix),
+        last_status_code: row.try_get::<Option<i32>, _>("last_status_code")?.map(|v| v as u16),
+        last_error: row.try_get("last_error")?,
+        created_at: row.try_get("created_at")?,
+        finished_at: row.try_get::<Option<i64>, _>("finished_at")?.map(timestamp_from_unix),
+    })
+}
+
+// Fa fallire le consegne in coda del webhook (disabilitato)
+async fn fail_pending_deliveries(
+    tx: &mut Transaction<'_, Postgres>,
+    webhook_id: &str,
+    error: &str,
+    now: i64,
+) -> StoreResult<()> {
+    sqlx::query(
+        "UPDATE webhook_deliveries SET status = 'failed', next_attempt_at = NULL, last_error = $1, finished_at = $2 \
+         WHERE webhook_id = $3 AND status = 'pending'",
+    )
+    .bind(error)
+    .bind(now)
+    .bind(webhook_id)
+    .execute(&mut *tx)
+    .await?;
+    Ok(())
+}
+
+// Salva il messaggio con il seq successivo del gruppo; il lock sulla riga del gruppo serializza gli
+// invii concorrenti nello stesso gruppo
+async fn insert_message_in(tx: &mut Transaction<'_, Postgres>, message: &Message) -> StoreResult<u64> {
+    let seq: i64 = sqlx::query_scalar("UPDATE groups SET last_seq = last_seq + 1 WHERE group_id = $1 RETURNING last_seq")
+        .bind(&message.group_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or(sqlx::Error::RowNotFound)?;
+    sqlx::query(
+        "INSERT INTO messages (message_id, group_id, sender_id, content, created_at, group_seq, expires_at) \
+         VALUES ($1, $2, $3, $4, $5, $6, $7)",
+    )
+    .bind(&message.message_id)
+    .bind(&message.group_id)
+    .bind(&message.sender_id)
+    .bind(&message.content)
+    .bind(&message.created_at)
+    .bind(seq)
+    .bind(message.expires_at.as_deref().and_then(unix_from_timestamp))
+    .execute(&mut *tx)
+    .await?;
+    Ok(seq as u64)
+}
+
+fn group_from_row(row: &PgRow) -> Result<Group, sqlx::Error> {
+    let pin_policy: String = row.try_get("pin_policy")?;
+    Ok(Group {
+        group_id: row.try_get("group_id")?,
+        name: row.try_get("name")?,
+        created_at: row.try_get("created_at")?,
+        pin_policy: PinPolicy::parse(&pin_policy).unwrap_or_default(),
+        encrypted: row.try_get("encrypted")?,
+        retention: RetentionPolicy {
+            max_age_secs: row.try_get::<Option<i64>, _>("retention_max_age_secs")?.map(|v| v as u64),
+            max_count: row.try_get::<Option<i64>, _>("retention_max_count")?.map(|v| v as u64),
+        },
+        message_ttl_secs: row.try_get::<Option<i64>, _>("message_ttl_secs")?.map(|v| v as u64),
+    })
+}
+
+fn message_from_row(row: &PgRow) -> Result<Message, sqlx::Error> {
+    Ok(Message {
+        message_id: row.try_get("message_id")?,
+        group_id: row.try_get("group_id")?,
+        sender_id: row.try_get("sender_id")?,
+        content: row.try_get("content")?,
+        created_at: row.try_get("created_at")?,
+        seq: row.try_get::<i64, _>("group_seq")? as u64,
+        reactions: Vec::new(),
+        mentions: Vec::new(),
+        expires_at: row.try_get::<Option<i64>, _>("expires_at")?.map(timestamp_from_unix),
+    })
+}
+
+fn public_key_from_row(row: &PgRow) -> Result<PublicKey, sqlx::Error> {
+    let alg: String = row.try_get("alg")?;
+    Ok(PublicKey {
+        user_id: row.try_get("user_id")?,
+        key_id: row.try_get("key_id")?,
+        alg: KeyAlgorithm::parse(&alg).ok_or_else(|| sqlx::Error::Decode(format!("unknown key algorithm {:?}", alg).into()))?,
+        public_key: row.try_get("public_key")?,
+        created_at: row.try_get("created_at")?,
+    })
+}
+
+fn sender_key_from_row(row: &PgRow) -> Result<SenderKey, sqlx::Error> {
+    Ok(SenderKey {
+        group_id: row.try_get("group_id")?,
+        sender_id: row.try_get("sender_id")?,
+        sender_key_id: row.try_get("sender_key_id")?,
+        recipient_id: row.try_get("recipient_id")?,
+        recipient_key_id: row.try_get("recipient_key_id")?,
+        sealed_key: row.try_get("sealed_key")?,
+        created_at: row.try_get("created_at")?,
+    })
+}
+
+fn invite_from_row(row: &PgRow) -> Result<Invite, sqlx::Error> {
+    Ok(Invite {
+        invite_id: row.try_get("invite_id")?,
+        group_id: row.try_get("group_id")?,
+        invited: row.try_get("invited")?,
+        created_at: row.try_get("created_at")?,
+    })
+}
+
+#[async_trait]
+impl UserStore for PostgresStore {
+    #[tracing::instrument(level = "debug", skip(self, user, password_hash), fields(user_id = %user.user_id), err)]
+    async fn create_user(&self, user: &User, password_hash: &str) -> StoreResult<()> {
+        let _timer = metrics::db_timer("create_user");
+        sqlx::query("INSERT INTO users (user_id, username, password_hash, created_at) VALUES ($1, $2, $3, $4)")
+            .bind(&user.user_id)
+            .bind(&user.username)
+            .bind(password_hash)
+            .bind(&user.created_at)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn find_user(&self, user_id: &str) -> StoreResult<Option<User>> {
+        let _timer = metrics::db_timer("find_user");
+        let row = sqlx::query("SELECT user_id, username, created_at FROM users WHERE user_id = $1")
+            .bind(user_id)
+            .fetch_optional(&self.pool)
+            .await?;
+        row.map(|r| {
+            Ok(User { user_id: r.try_get("user_id")?, username: r.try_get("username")?, created_at: r.try_get("created_at")? })
+        })
+        .transpose()
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn username_exists(&self, username: &str) -> StoreResult<bool> {
+        let _timer = metrics::db_timer("username_exists");
+        sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM users WHERE username = $1)")
+            .bind(username)
+            .fetch_one(&self.pool)
+            .await
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn credentials(&self, username: &str) -> StoreResult<Option<Credentials>> {
+        let _timer = metrics::db_timer("credentials");
+        let row = sqlx::query("SELECT user_id, username, password_hash, created_at, disabled FROM users WHERE username = $1")
+            .bind(username)
+            .fetch_optional(&self.pool)
+            .await?;
+        row.map(|r| {
+            Ok(Credentials {
+                user: User {
+                    user_id: r.try_get("user_id")?,
+                    username: r.try_get("username")?,
+                    created_at: r.try_get("created_at")?,
+                },
+                password_hash: r.try_get("password_hash")?,
+                disabled: r.try_get("disabled")?,
+            })
+        })
+        .transpose()
+    }
+}
+
+#[async_trait]
+impl SessionStore for PostgresStore {
+    #[tracing::instrument(level = "debug", skip(self, token), err)]
+    async fn set_token(&self, user_id: &str, token: &str, expires_at: Option<i64>) -> StoreResult<()> {
+        let _timer = metrics::db_timer("set_token");
+        sqlx::query("UPDATE users SET token = $1, token_expires_at = $2 WHERE user_id = $3")
+            .bind(token)
+            .bind(expires_at)
+            .bind(user_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    #[tracing::instrument(level = "debug", skip_all, err)]
+    async fn user_id_for_token(&self, token: &str, now: i64) -> StoreResult<Option<String>> {
+        let _timer = metrics::db_timer("user_id_for_token");
+        sqlx::query_scalar(
+            "SELECT user_id FROM users WHERE token = $1 AND (token_expires_at IS NULL OR token_expires_at > $2) AND NOT disabled",
+        )
+            .bind(token)
+            .bind(now)
+            .fetch_optional(&self.pool)
+            .await
+    }
+}
+
+#[async_trait]
+impl GroupStore for PostgresStore {
+    #[tracing::instrument(level = "debug", skip(self, group, members), fields(group_id = %group.group_id), err)]
+    async fn create_group(&self, group: &Group, members: &[(String, Role)]) -> StoreResult<()> {
+        let _timer = metrics::db_timer("create_group");
+        let mut tx = self.pool.begin().await?;
+        sqlx::query(
+            "INSERT INTO groups (group_id, name, created_at, pin_policy, encrypted, retention_max_age_secs, \
+             retention_max_count, message_ttl_secs) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+        )
+        .bind(&group.group_id)
+        .bind(&group.name)
+        .bind(&group.created_at)
+        .bind(group.pin_policy.as_str())
+        .bind(group.encrypted)
+        .bind(group.retention.max_age_secs.map(|v| v as i64))
+        .bind(group.retention.max_count.map(|v| v as i64))
+        .bind(group.message_ttl_secs.map(|v| v as i64))
+            .execute(&mut tx)
+            .await?;
+        for (user_id, role) in members {
+            sqlx::query("INSERT INTO memberships (membership_id, group_id, user_id, joined_at, role) VALUES ($1, $2, $3, $4, $5)")
+                .bind(uuid::Uuid::new_v4().to_string())
+                .bind(&group.group_id)
+                .bind(user_id)
+                .bind(&group.created_at)
+                .bind(role.as_str())
+                .execute(&mut tx)
+                .await?;
+        }
+        tx.commit().await
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn find_group(&self, group_id: &str) -> StoreResult<Option<Group>> {
+        let _timer = metrics::db_timer("find_group");
+        let row = sqlx::query(&format!("SELECT {} FROM groups WHERE group_id = $1", GROUP_COLUMNS))
+            .bind(group_id)
+            .fetch_optional(&self.pool)
+            .await?;
+        row.as_ref().map(group_from_row).transpose()
+    }
+
+    #[tracing::instrument(level = "debug", skip(self, group), fields(group_id = %group.group_id), err)]
+    async fn update_group(&self, group: &Group) -> StoreResult<()> {
+        let _timer = metrics::db_timer("update_group");
+        sqlx::query(
+            "UPDATE groups SET name = $1, pin_policy = $2, retention_max_age_secs = $3, retention_max_count = $4, \
+             message_ttl_secs = $5 WHERE group_id = $6",
+        )
+        .bind(&group.name)
+        .bind(group.pin_policy.as_str())
+        .bind(group.retention.max_age_secs.map(|v| v as i64))
+        .bind(group.retention.max_count.map(|v| v as i64))
+        .bind(group.message_ttl_secs.map(|v| v as i64))
+        .bind(&group.group_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn groups_for_user(&self, user_id: &str) -> StoreResult<Vec<Group>> {
+        let _timer = metrics::db_timer("groups_for_user");
+        let sql = format!(
+            "SELECT {} FROM groups WHERE group_id IN (SELECT group_id FROM memberships WHERE user_id = $1) \
+             ORDER BY created_at, seq",
+            GROUP_COLUMNS
+        );
+        let rows = sqlx::query(&sql).bind(user_id).fetch_all(&self.pool).await?;
+        rows.iter().map(group_from_row).collect()
+    }
+}
+
+#[async_trait]
+impl MembershipStore for PostgresStore {
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn member_role(&self, group_id: &str, user_id: &str) -> StoreResult<Option<Role>> {
+        let _timer = metrics::db_timer("member_role");
+        let role: Option<String> = sqlx::query_scalar("SELECT role FROM memberships WHERE group_id = $1 AND user_id = $2")
+            .bind(group_id)
+            .bind(user_id)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(role.map(|r| Role::parse(&r).unwrap_or(Role::Member)))
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn member_ids(&self, group_id: &str) -> StoreResult<Vec<String>> {
+        let _timer = metrics::db_timer("member_ids");
+        sqlx::query_scalar("SELECT user_id FROM memberships WHERE group_id = $1")
+            .bind(group_id)
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    #[tracing::instrument(level = "debug", skip(self, usernames), err)]
+    async fn find_members_by_username(
+        &self,
+        group_id: &str,
+        exclude: &str,
+        usernames: &[String],
+    ) -> StoreResult<Vec<(String, String)>> {
+        let _timer = metrics::db_timer("find_members_by_username");
+        if usernames.is_empty() {
+            return Ok(Vec::new());
+        }
+        sqlx::query(
+            "SELECT u.username, u.user_id FROM users u JOIN memberships m ON m.user_id = u.user_id \
+             WHERE m.group_id = $1 AND u.user_id <> $2 AND u.username = ANY($3)",
+        )
+        .bind(group_id)
+        .bind(exclude)
+        .bind(usernames)
+        .fetch_all(&self.pool)
+        .await?
+        .iter()
+        .map(|r| Ok((r.try_get("username")?, r.try_get("user_id")?)))
+        .collect()
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn set_muted(&self, group_id: &str, user_id: &str, muted: bool) -> StoreResult<bool> {
+        let _timer = metrics::db_timer("set_muted");
+        let res = sqlx::query("UPDATE memberships SET muted = $1 WHERE group_id = $2 AND user_id = $3")
+            .bind(muted)
+            .bind(group_id)
+            .bind(user_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn muted_member_ids(&self, group_id: &str) -> StoreResult<Vec<String>> {
+        let _timer = metrics::db_timer("muted_member_ids");
+        sqlx::query_scalar("SELECT user_id FROM memberships WHERE group_id = $1 AND muted")
+            .bind(group_id)
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn admin_ids(&self, group_id: &str) -> StoreResult<Vec<String>> {
+        let _timer = metrics::db_timer("admin_ids");
+        sqlx::query_scalar("SELECT user_id FROM memberships WHERE group_id = $1 AND role = 'admin'")
+            .bind(group_id)
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn add_member(&self, group_id: &str, user_id: &str, role: Role, joined_at: &str) -> StoreResult<bool> {
+        let _timer = metrics::db_timer("add_member");
+        let res = sqlx::query(
+            "INSERT INTO memberships (membership_id, group_id, user_id, joined_at, role) SELECT $1, $2, $3, $4, $5 \
+             WHERE NOT EXISTS (SELECT 1 FROM memberships WHERE group_id = $2 AND user_id = $3)",
+        )
+        .bind(uuid::Uuid::new_v4().to_string())
+        .bind(group_id)
+        .bind(user_id)
+        .bind(joined_at)
+        .bind(role.as_str())
+        .execute(&self.pool)
+        .await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn remove_member(&self, group_id: &str, user_id: &str) -> StoreResult<bool> {
+        let _timer = metrics::db_timer("remove_member");
+        let res = sqlx::query("DELETE FROM memberships WHERE group_id = $1 AND user_id = $2")
+            .bind(group_id)
+            .bind(user_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(res.rows_affected() > 0)
+    }
+}
+
+#[async_trait]
+impl MessageStore for PostgresStore {
+    #[tracing::instrument(level = "debug", skip(self, message), fields(message_id = %message.message_id), err)]
+    async fn insert_message(&self, message: &Message) -> StoreResult<u64> {
+        let _timer = metrics::db_timer("insert_message");
+        let mut tx = self.pool.begin().await?;
+        let seq = insert_message_in(&mut tx, message).await?;
+        tx.commit().await?;
+        Ok(seq)
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn find_message(&self, message_id: &str) -> StoreResult<Option<Message>> {
+        let _timer = metrics::db_timer("find_message");
+        let row = sqlx::query(&format!(
+            "SELECT {} FROM messages WHERE message_id = $1 AND {}",
+            MESSAGE_COLUMNS,
+            not_expired("messages")
+        ))
+            .bind(message_id)
+            .fetch_optional(&self.pool)
+            .await?;
+        row.as_ref().map(message_from_row).transpose()
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn list_messages(&self, group_id: &str, before: Option<&str>, limit: i64) -> StoreResult<Vec<Message>> {
+        let _timer = metrics::db_timer("list_messages");
+        let sql = format!(
+            "SELECT {} FROM messages WHERE group_id = $1 AND ($2::TEXT IS NULL OR created_at < $2) AND {} \
+             ORDER BY created_at DESC, seq DESC LIMIT $3",
+            MESSAGE_COLUMNS,
+            not_expired("messages")
+        );
+        let rows = sqlx::query(&sql)
+            .bind(group_id)
+            .bind(before)
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await?;
+        rows.iter().rev().map(message_from_row).collect()
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn messages_after(&self, group_id: &str, after_seq: u64, limit: i64) -> StoreResult<Vec<Message>> {
+        let _timer = metrics::db_timer("messages_after");
+        let sql = format!(
+            "SELECT {} FROM messages WHERE group_id = $1 AND group_seq > $2 AND {} ORDER BY group_seq LIMIT $3",
+            MESSAGE_COLUMNS,
+            not_expired("messages")
+        );
+        let rows = sqlx::query(&sql)
+            .bind(group_id)
+            .bind(after_seq as i64)
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await?;
+        rows.iter().map(message_from_row).collect()
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn latest_seq(&self, group_id: &str) -> StoreResult<u64> {
+        let _timer = metrics::db_timer("latest_seq");
+        let seq: Option<i64> = sqlx::query_scalar("SELECT last_seq FROM groups WHERE group_id = $1")
+            .bind(group_id)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(seq.unwrap_or(0) as u64)
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn seq_beyond_newest(&self, group_id: &str, keep: u64) -> StoreResult<Option<u64>> {
+        let _timer = metrics::db_timer("seq_beyond_newest");
+        let seq: Option<i64> = sqlx::query_scalar(
+            "SELECT group_seq FROM messages WHERE group_id = $1 ORDER BY group_seq DESC LIMIT 1 OFFSET $2",
+        )
+        .bind(group_id)
+        .bind(keep as i64)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(seq.map(|s| s as u64))
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn purge_messages(&self, group_id: &str, through_seq: u64, limit: i64) -> StoreResult<u64> {
+        let _timer = metrics::db_timer("purge_messages");
+        // reazioni, menzioni e pin dei messaggi seguono con ON DELETE CASCADE
+        let res = sqlx::query(
+            "DELETE FROM messages WHERE message_id IN (SELECT message_id FROM messages \
+             WHERE group_id = $1 AND group_seq <= $2 ORDER BY group_seq LIMIT $3)",
+        )
+        .bind(group_id)
+        .bind(through_seq as i64)
+        .bind(limit)
+        .execute(&self.pool)
+        .await?;
+        Ok(res.rows_affected())
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn purge_expired(&self, now: i64, limit: i64) -> StoreResult<Vec<(String, String)>> {
+        let _timer = metrics::db_timer("purge_expired");
+        // SKIP LOCKED: istanze concorrenti eliminano lotti diversi; i dipendenti seguono con ON DELETE CASCADE
+        sqlx::query_as(
+            "DELETE FROM messages WHERE message_id IN (SELECT message_id FROM messages \
+             WHERE expires_at <= $1 ORDER BY expires_at LIMIT $2 FOR UPDATE SKIP LOCKED) \
+             RETURNING group_id, message_id",
+        )
+        .bind(now)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn raw_contents(&self, after: Option<&str>, limit: i64) -> StoreResult<Vec<(String, String)>> {
+        let _timer = metrics::db_timer("raw_contents");
+        sqlx::query_as(
+            "SELECT message_id, content FROM messages WHERE ($1::TEXT IS NULL OR message_id > $1) ORDER BY message_id LIMIT $2",
+        )
+        .bind(after)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    #[tracing::instrument(level = "debug", skip(self, content), err)]
+    async fn set_raw_content(&self, message_id: &str, content: &str) -> StoreResult<()> {
+        let _timer = metrics::db_timer("set_raw_content");
+        sqlx::query("UPDATE messages SET content = $1 WHERE message_id = $2")
+            .bind(content)
+            .bind(message_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ScheduleStore for PostgresStore {
+    #[tracing::instrument(level = "debug", skip(self, scheduled), fields(message_id = %scheduled.message_id), err)]
+    async fn schedule_message(&self, scheduled: &ScheduledMessage) -> StoreResult<()> {
+        let _timer = metrics::db_timer("schedule_message");
+        let send_at = unix_from_timestamp(&scheduled.send_at)
+            .ok_or_else(|| sqlx::Error::Protocol(format!("invalid send_at {:?}", scheduled.send_at)))?;
+        sqlx::query(&format!(
+            "INSERT INTO scheduled_messages ({}) VALUES ($1, $2, $3, $4, $5, $6, $7)",
+            SCHEDULED_COLUMNS
+        ))
+        .bind(&scheduled.message_id)
+        .bind(&scheduled.group_id)
+        .bind(&scheduled.sender_id)
+        .bind(&scheduled.content)
+        .bind(send_at)
+        .bind(scheduled.ttl_secs.map(|v| v as i64))
+        .bind(&scheduled.created_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn scheduled_for_sender(&self, sender_id: &str) -> StoreResult<Vec<ScheduledMessage>> {
+        let _timer = metrics::db_timer("scheduled_for_sender");
+        let rows = sqlx::query(&format!(
+            "SELECT {} FROM scheduled_messages WHERE sender_id = $1 ORDER BY send_at, seq",
+            SCHEDULED_COLUMNS
+        ))
+        .bind(sender_id)
+        .fetch_all(&self.pool)
+        .await?;
+        rows.iter().map(scheduled_from_row).collect()
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn cancel_scheduled(&self, message_id: &str, sender_id: &str) -> StoreResult<bool> {
+        let _timer = metrics::db_timer("cancel_scheduled");
+        let res = sqlx::query("DELETE FROM scheduled_messages WHERE message_id = $1 AND sender_id = $2")
+            .bind(message_id)
+            .bind(sender_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn due_scheduled(&self, now: i64, limit: i64) -> StoreResult<Vec<ScheduledMessage>> {
+        let _timer = metrics::db_timer("due_scheduled");
+        let rows = sqlx::query(&format!(
+            "SELECT {} FROM scheduled_messages WHERE send_at <= $1 ORDER BY send_at, seq LIMIT $2",
+            SCHEDULED_COLUMNS
+        ))
+        .bind(now)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+        rows.iter().map(scheduled_from_row).collect()
+    }
+
+    #[tracing::instrument(level = "debug", skip(self, message), fields(message_id = %message.message_id), err)]
+    async fn publish_scheduled(&self, message: &Message) -> StoreResult<Option<u64>> {
+        let _timer = metrics::db_timer("publish_scheduled");
+        // il DELETE blocca la riga: con più istanze una sola pubblica il messaggio
+        let mut tx = self.pool.begin().await?;
+        let res = sqlx::query("DELETE FROM scheduled_messages WHERE message_id = $1")
+            .bind(&message.message_id)
+            .execute(&mut tx)
+            .await?;
+        if res.rows_affected() == 0 {
+            return Ok(None);
+        }
+        let seq = insert_message_in(&mut tx, message).await?;
+        tx.commit().await?;
+        Ok(Some(seq))
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn raw_scheduled_contents(&self, after: Option<&str>, limit: i64) -> StoreResult<Vec<(String, String)>> {
+        let _timer = metrics::db_timer("raw_scheduled_contents");
+        sqlx::query_as(
+            "SELECT message_id, content FROM scheduled_messages WHERE ($1::TEXT IS NULL OR message_id > $1) ORDER BY message_id LIMIT $2",
+        )
+        .bind(after)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    #[tracing::instrument(level = "debug", skip(self, content), err)]
+    async fn set_raw_scheduled_content(&self, message_id: &str, content: &str) -> StoreResult<()> {
+        let _timer = metrics::db_timer("set_raw_scheduled_content");
+        sqlx::query("UPDATE scheduled_messages SET content = $1 WHERE message_id = $2")
+            .bind(content)
+            .bind(message_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl InviteStore for PostgresStore {
+    #[tracing::instrument(level = "debug", skip(self, invite), fields(invite_id = %invite.invite_id), err)]
+    async fn create_invite(&self, invite: &Invite) -> StoreResult<()> {
+        let _timer = metrics::db_timer("create_invite");
+        sqlx::query("INSERT INTO invites (invite_id, group_id, invited, created_at) VALUES ($1, $2, $3, $4)")
+            .bind(&invite.invite_id)
+            .bind(&invite.group_id)
+            .bind(&invite.invited)
+            .bind(&invite.created_at)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn invites_for_user(&self, user_id: &str) -> StoreResult<Vec<Invite>> {
+        let _timer = metrics::db_timer("invites_for_user");
+        let rows = sqlx::query(
+            "SELECT invite_id, group_id, invited, created_at FROM invites WHERE invited = $1 ORDER BY created_at, seq",
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+        rows.iter().map(invite_from_row).collect()
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn delete_invite(&self, invite_id: &str) -> StoreResult<bool> {
+        let _timer = metrics::db_timer("delete_invite");
+        let res = sqlx::query("DELETE FROM invites WHERE invite_id = $1").bind(invite_id).execute(&self.pool).await?;
+        Ok(res.rows_affected() > 0)
+    }
+}
+
+#[async_trait]
+impl ReactionStore for PostgresStore {
+    #[tracing::instrument(level = "debug", skip(self, created_at), err)]
+    async fn add_reaction(&self, message_id: &str, user_id: &str, emoji: &str, created_at: &str) -> StoreResult<bool> {
+        let _timer = metrics::db_timer("add_reaction");
+        let res = sqlx::query(
+            "INSERT INTO reactions (message_id, user_id, emoji, created_at) VALUES ($1, $2, $3, $4) ON CONFLICT DO NOTHING",
+        )
+        .bind(message_id)
+        .bind(user_id)
+        .bind(emoji)
+        .bind(created_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn remove_reaction(&self, message_id: &str, user_id: &str, emoji: &str) -> StoreResult<bool> {
+        let _timer = metrics::db_timer("remove_reaction");
+        let res = sqlx::query("DELETE FROM reactions WHERE message_id = $1 AND user_id = $2 AND emoji = $3")
+            .bind(message_id)
+            .bind(user_id)
+            .bind(emoji)
+            .execute(&self.pool)
+            .await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    #[tracing::instrument(level = "debug", skip_all, fields(count = message_ids.len()), err)]
+    async fn reactions_for(&self, message_ids: &[String]) -> StoreResult<Vec<(String, String, String)>> {
+        let _timer = metrics::db_timer("reactions_for");
+        if message_ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        sqlx::query("SELECT message_id, user_id, emoji FROM reactions WHERE message_id = ANY($1) ORDER BY seq")
+            .bind(message_ids)
+            .fetch_all(&self.pool)
+            .await?
+            .iter()
+            .map(|r| Ok((r.try_get("message_id")?, r.try_get("user_id")?, r.try_get("emoji")?)))
+            .collect()
+    }
+}
+
+#[async_trait]
+impl MentionStore for PostgresStore {
+    #[tracing::instrument(level = "debug", skip(self, user_ids), err)]
+    async fn add_mentions(&self, message_id: &str, user_ids: &[String]) -> StoreResult<()> {
+        let _timer = metrics::db_timer("add_mentions");
+        for user_id in user_ids {
+            sqlx::query("INSERT INTO mentions (message_id, user_id) VALUES ($1, $2) ON CONFLICT DO NOTHING")
+                .bind(message_id)
+                .bind(user_id)
+                .execute(&self.pool)
+                .await?;
+        }
+        Ok(())
+    }
+
+    #[tracing::instrument(level = "debug", skip_all, fields(count = message_ids.len()), err)]
+    async fn mentions_for(&self, message_ids: &[String]) -> StoreResult<Vec<(String, String)>> {
+        let _timer = metrics::db_timer("mentions_for");
+        if message_ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        sqlx::query("SELECT message_id, user_id FROM mentions WHERE message_id = ANY($1) ORDER BY seq")
+            .bind(message_ids)
+            .fetch_all(&self.pool)
+            .await?
+            .iter()
+            .map(|r| Ok((r.try_get("message_id")?, r.try_get("user_id")?)))
+            .collect()
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn mention_inbox(&self, user_id: &str, before: Option<&str>, limit: i64) -> StoreResult<Vec<(Message, String)>> {
+        let _timer = metrics::db_timer("mention_inbox");
+        let sql = format!(
+            "SELECT {}, g.name AS group_name FROM mentions x \
+             JOIN messages ON messages.message_id = x.message_id \
+             JOIN groups g ON g.group_id = messages.group_id \
+             WHERE x.user_id = $1 AND ($2::TEXT IS NULL OR messages.created_at < $2) AND {} \
+             AND EXISTS (SELECT 1 FROM memberships m WHERE m.group_id = messages.group_id AND m.user_id = x.user_id) \
+             ORDER BY messages.created_at DESC, messages.seq DESC LIMIT $3",
+            qualified_message_columns("messages"),
+            not_expired("messages")
+        );
+        let rows = sqlx::query(&sql)
+            .bind(user_id)
+            .bind(before)
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await?;
+        rows.iter().map(|r| Ok((message_from_row(r)?, r.try_get("group_name")?))).collect()
+    }
+}
+
+#[async_trait]
+impl PinStore for PostgresStore {
+    #[tracing::instrument(level = "debug", skip(self, pinned_at), err)]
+    async fn pin_message(&self, message_id: &str, group_id: &str, user_id: &str, pinned_at: &str) -> StoreResult<bool> {
+        let _timer = metrics::db_timer("pin_message");
+        let res = sqlx::query(
+            "INSERT INTO pinned_messages (message_id, group_id, pinned_by, pinned_at) VALUES ($1, $2, $3, $4) \
+             ON CONFLICT DO NOTHING",
+        )
+        .bind(message_id)
+        .bind(group_id)
+        .bind(user_id)
+        .bind(pinned_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn unpin_message(&self, message_id: &str) -> StoreResult<bool> {
+        let _timer = metrics::db_timer("unpin_message");
+        let res = sqlx::query("DELETE FROM pinned_messages WHERE message_id = $1")
+            .bind(message_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn pins_for_group(&self, group_id: &str) -> StoreResult<Vec<PinRecord>> {
+        let _timer = metrics::db_timer("pins_for_group");
+        let sql = format!(
+            "SELECT {}, p.pinned_by, p.pinned_at FROM pinned_messages p \
+             JOIN messages ON messages.message_id = p.message_id \
+             WHERE p.group_id = $1 AND {} ORDER BY p.pinned_at DESC, p.seq DESC",
+            qualified_message_columns("messages"),
+            not_expired("messages")
+        );
+        let rows = sqlx::query(&sql).bind(group_id).fetch_all(&self.pool).await?;
+        rows.iter()
+            .map(|r| Ok(PinRecord { message: message_from_row(r)?, pinned_by: r.try_get("pinned_by")?, pinned_at: r.try_get("pinned_at")? }))
+            .collect()
+    }
+}
+
+#[async_trait]
+impl KeyStore for PostgresStore {
+    #[tracing::instrument(level = "debug", skip(self, key), fields(user_id = %key.user_id), err)]
+    async fn put_public_key(&self, key: &PublicKey) -> StoreResult<()> {
+        let _timer = metrics::db_timer("put_public_key");
+        sqlx::query(
+            "INSERT INTO public_keys (user_id, key_id, alg, public_key, created_at) VALUES ($1, $2, $3, $4, $5) \
+             ON CONFLICT (user_id) DO UPDATE SET key_id = EXCLUDED.key_id, alg = EXCLUDED.alg, \
+             public_key = EXCLUDED.public_key, created_at = EXCLUDED.created_at",
+        )
+        .bind(&key.user_id)
+        .bind(&key.key_id)
+        .bind(key.alg.as_str())
+        .bind(&key.public_key)
+        .bind(&key.created_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn public_key(&self, user_id: &str) -> StoreResult<Option<PublicKey>> {
+        let _timer = metrics::db_timer("public_key");
+        let row = sqlx::query("SELECT user_id, key_id, alg, public_key, created_at FROM public_keys WHERE user_id = $1")
+            .bind(user_id)
+            .fetch_optional(&self.pool)
+            .await?;
+        row.as_ref().map(public_key_from_row).transpose()
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn group_public_keys(&self, group_id: &str) -> StoreResult<Vec<PublicKey>> {
+        let _timer = metrics::db_timer("group_public_keys");
+        let rows = sqlx::query(
+            "SELECT k.user_id, k.key_id, k.alg, k.public_key, k.created_at FROM public_keys k \
+             JOIN memberships m ON m.user_id = k.user_id WHERE m.group_id = $1 ORDER BY k.user_id",
+        )
+        .bind(group_id)
+        .fetch_all(&self.pool)
+        .await?;
+        rows.iter().map(public_key_from_row).collect()
+    }
+
+    #[tracing::instrument(level = "debug", skip(self, keys), fields(count = keys.len()), err)]
+    async fn put_sender_keys(&self, keys: &[SenderKey]) -> StoreResult<()> {
+        let _timer = metrics::db_timer("put_sender_keys");
+        let mut tx = self.pool.begin().await?;
+        for key in keys {
+            sqlx::query(
+                "INSERT INTO sender_keys \
+                 (group_id, sender_id, sender_key_id, recipient_id, recipient_key_id, sealed_key, created_at) \
+                 VALUES ($1, $2, $3, $4, $5, $6, $7) \
+                 ON CONFLICT (group_id, sender_id, sender_key_id, recipient_id) DO UPDATE SET \
+                 recipient_key_id = EXCLUDED.recipient_key_id, sealed_key = EXCLUDED.sealed_key, \
+                 created_at = EXCLUDED.created_at",
+            )
+            .bind(&key.group_id)
+            .bind(&key.sender_id)
+            .bind(&key.sender_key_id)
+            .bind(&key.recipient_id)
+            .bind(&key.recipient_key_id)
+            .bind(&key.sealed_key)
+            .bind(&key.created_at)
+            .execute(&mut tx)
+            .await?;
+        }
+        tx.commit().await
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn sender_keys_for(&self, group_id: &str, recipient_id: &str) -> StoreResult<Vec<SenderKey>> {
+        let _timer = metrics::db_timer("sender_keys_for");
+        let rows = sqlx::query(
+            "SELECT group_id, sender_id, sender_key_id, recipient_id, recipient_key_id, sealed_key, created_at \
+             FROM sender_keys WHERE group_id = $1 AND recipient_id = $2 ORDER BY created_at, seq",
+        )
+        .bind(group_id)
+        .bind(recipient_id)
+        .fetch_all(&self.pool)
+        .await?;
+        rows.iter().map(sender_key_from_row).collect()
+    }
+}
+
+#[async_trait]
+impl WebhookStore for PostgresStore {
+    #[tracing::instrument(level = "debug", skip(self, record), fields(webhook_id = %record.webhook.webhook_id), err)]
+    async fn create_webhook(&self, record: &WebhookRecord) -> StoreResult<()> {
+        let _timer = metrics::db_timer("create_webhook");
+        let webhook = &record.webhook;
+        let events: Vec<&str> = webhook.events.iter().map(|e| e.as_str()).collect();
+        sqlx::query(&format!(
+            "INSERT INTO webhooks ({}) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)",
+            WEBHOOK_COLUMNS
+        ))
+        .bind(&webhook.webhook_id)
+        .bind(&webhook.group_id)
+        .bind(&webhook.url)
+        .bind(events.join(","))
+        .bind(&record.secret)
+        .bind(webhook.enabled)
+        .bind(&webhook.created_by)
+        .bind(&webhook.created_at)
+        .bind(i64::from(webhook.consecutive_failures))
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn find_webhook(&self, webhook_id: &str) -> StoreResult<Option<WebhookRecord>> {
+        let _timer = metrics::db_timer("find_webhook");
+        let row = sqlx::query(&format!("SELECT {} FROM webhooks WHERE webhook_id = $1", WEBHOOK_COLUMNS))
+            .bind(webhook_id)
+            .fetch_optional(&self.pool)
+            .await?;
+        row.as_ref().map(webhook_from_row).transpose()
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn webhooks_for_group(&self, group_id: &str) -> StoreResult<Vec<WebhookRecord>> {
+        let _timer = metrics::db_timer("webhooks_for_group");
+        let rows = sqlx::query(&format!(
+            "SELECT {} FROM webhooks WHERE group_id = $1 ORDER BY created_at, seq",
+            WEBHOOK_COLUMNS
+        ))
+        .bind(group_id)
+        .fetch_all(&self.pool)
+        .await?;
+        rows.iter().map(webhook_from_row).collect()
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn set_webhook_enabled(&self, webhook_id: &str, enabled: bool, now: i64) -> StoreResult<bool> {
+        let _timer = metrics::db_timer("set_webhook_enabled");
+        let mut tx = self.pool.begin().await?;
+        let res = if enabled {
+            sqlx::query("UPDATE webhooks SET enabled = TRUE, consecutive_failures = 0 WHERE webhook_id = $1")
+        } else {
+            sqlx::query("UPDATE webhooks SET enabled = FALSE WHERE webhook_id = $1")
+        }
+        .bind(webhook_id)
+        .execute(&mut tx)
+        .await?;
+        if !enabled {
+            fail_pending_deliveries(&mut tx, webhook_id, "webhook disabled", now).await?;
+        }
+        tx.commit().await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn delete_webhook(&self, webhook_id: &str) -> StoreResult<bool> {
+        let _timer = metrics::db_timer("delete_webhook");
+        // le consegne sono eliminate dal vincolo ON DELETE CASCADE
+        let res = sqlx::query("DELETE FROM webhooks WHERE webhook_id = $1").bind(webhook_id).execute(&self.pool).await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    #[tracing::instrument(level = "debug", skip(self, deliveries), fields(count = deliveries.len()), err)]
+    async fn enqueue_deliveries(&self, deliveries: &[WebhookDelivery]) -> StoreResult<()> {
+        let _timer = metrics::db_timer("enqueue_deliveries");
+        let mut tx = self.pool.begin().await?;
+        for d in deliveries {
+            sqlx::query(&format!(
+                "INSERT INTO webhook_deliveries ({}) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)",
+                DELIVERY_COLUMNS
+            ))
+            .bind(&d.delivery_id)
+            .bind(&d.webhook_id)
+            .bind(&d.group_id)
+            .bind(d.event.as_str())
+            .bind(&d.message_id)
+            .bind(&d.user_id)
+            .bind(d.status.as_str())
+            .bind(i64::from(d.attempts))
+            .bind(d.next_attempt_at.as_deref().and_then(unix_from_timestamp))
+            .bind(d.last_status_code.map(i32::from))
+            .bind(&d.last_error)
+            .bind(&d.created_at)
+            .bind(d.finished_at.as_deref().and_then(unix_from_timestamp))
+            .execute(&mut tx)
+            .await?;
+        }
+        tx.commit().await
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn claim_deliveries(&self, now: i64, lease_until: i64, limit: i64) -> StoreResult<Vec<WebhookDelivery>> {
+        let _timer = metrics::db_timer("claim_deliveries");
+        // SKIP LOCKED: le istanze concorrenti si dividono le consegne invece di aspettarsi
+        let rows = sqlx::query(&format!(
+            "UPDATE webhook_deliveries SET next_attempt_at = $1 WHERE delivery_id IN (\
+             SELECT d.delivery_id FROM webhook_deliveries d JOIN webhooks w ON w.webhook_id = d.webhook_id \
+             WHERE d.status = 'pending' AND d.next_attempt_at <= $2 AND w.enabled \
+             ORDER BY d.next_attempt_at, d.seq LIMIT $3 FOR UPDATE OF d SKIP LOCKED) RETURNING {}",
+            DELIVERY_COLUMNS
+        ))
+        .bind(lease_until)
+        .bind(now)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+        let mut claimed = rows.iter().map(delivery_from_row).collect::<StoreResult<Vec<_>>>()?;
+        claimed.sort_by(|a, b| (&a.created_at, &a.delivery_id).cmp(&(&b.created_at, &b.delivery_id)));
+        Ok(claimed)
+    }
+
+    #[tracing::instrument(level = "debug", skip(self, attempt), fields(delivery_id = %attempt.delivery_id), err)]
+    async fn record_attempt(&self, attempt: &DeliveryAttempt, disable_after: u32) -> StoreResult<bool> {
+        let _timer = metrics::db_timer("record_attempt");
+        let (status, next_attempt_at, attempts) = match attempt.outcome {
+            AttemptOutcome::Delivered => (DeliveryStatus::Delivered, None, 1i64),
+            AttemptOutcome::Retry(at) => (DeliveryStatus::Pending, Some(at), 1),
+            AttemptOutcome::Failed => (DeliveryStatus::Failed, None, 1),
+            AttemptOutcome::Dropped => (DeliveryStatus::Failed, None, 0),
+        };
+        let finished_at = next_attempt_at.is_none().then_some(attempt.at);
+        let mut tx = self.pool.begin().await?;
+        // una consegna già fallita perché il webhook è stato disabilitato nel frattempo resta fallita
+        sqlx::query(
+            "UPDATE webhook_deliveries SET status = $1, attempts = attempts + $2, next_attempt_at = $3, \
+             last_status_code = $4, last_error = $5, finished_at = $6 WHERE delivery_id = $7 AND status = 'pending'",
+        )
+        .bind(status.as_str())
+        .bind(attempts)
+        .bind(next_attempt_at)
+        .bind(attempt.status_code.map(i32::from))
+        .bind(&attempt.error)
+        .bind(finished_at)
+        .bind(&attempt.delivery_id)
+        .execute(&mut tx)
+        .await?;
+        let mut disabled = false;
+        match attempt.outcome {
+            AttemptOutcome::Delivered => {
+                sqlx::query("UPDATE webhooks SET consecutive_failures = 0 WHERE webhook_id = $1")
+                    .bind(&attempt.webhook_id)
+                    .execute(&mut tx)
+                    .await?;
+            }
+            AttemptOutcome::Retry(_) | AttemptOutcome::Failed => {
+                let failures: Option<(i64, bool)> = sqlx::query_as(
+                    "UPDATE webhooks SET consecutive_failures = consecutive_failures + 1 WHERE webhook_id = $1 \
+                     RETURNING consecutive_failures, enabled",
+                )
+                .bind(&attempt.webhook_id)
+                .fetch_optional(&mut tx)
+                .await?;
+                if let Some((failures, true)) = failures.filter(|&(f, _)| f >= i64::from(disable_after)) {
+                    sqlx::query("UPDATE webhooks SET enabled = FALSE WHERE webhook_id = $1")
+                        .bind(&attempt.webhook_id)
+                        .execute(&mut tx)
+                        .await?;
+                    let error = format!("webhook disabled after {} consecutive failures", failures);
+                    fail_pending_deliveries(&mut tx, &attempt.webhook_id, &error, attempt.at).await?;
+                    disabled = true;
+                }
+            }
+            AttemptOutcome::Dropped => {}
+        }
+        tx.commit().await?;
+        Ok(disabled)
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn deliveries_for_webhook(&self, webhook_id: &str, limit: i64) -> StoreResult<Vec<WebhookDelivery>> {
+        let _timer = metrics::db_timer("deliveries_for_webhook");
+        let rows = sqlx::query(&format!(
+            "SELECT {} FROM webhook_deliveries WHERE webhook_id = $1 ORDER BY created_at DESC, seq DESC LIMIT $2",
+            DELIVERY_COLUMNS
+        ))
+        .bind(webhook_id)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+        rows.iter().map(delivery_from_row).collect()
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn purge_deliveries(&self, finished_before: i64, limit: i64) -> StoreResult<u64> {
+        let _timer = metrics::db_timer("purge_deliveries");
+        let res = sqlx::query(
+            "DELETE FROM webhook_deliveries WHERE delivery_id IN (SELECT delivery_id FROM webhook_deliveries \
+             WHERE finished_at <= $1 ORDER BY finished_at LIMIT $2)",
+        )
+        .bind(finished_before)
+        .bind(limit)
+        .execute(&self.pool)
+        .await?;
+        Ok(res.rows_affected())
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn raw_webhook_secrets(&self, after: Option<&str>, limit: i64) -> StoreResult<Vec<(String, String)>> {
+        let _timer = metrics::db_timer("raw_webhook_secrets");
+        sqlx::query_as(
+            "SELECT webhook_id, secret FROM webhooks WHERE ($1::TEXT IS NULL OR webhook_id > $1) ORDER BY webhook_id LIMIT $2",
+        )
+        .bind(after)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    #[tracing::instrument(level = "debug", skip(self, secret), err)]
+    async fn set_raw_webhook_secret(&self, webhook_id: &str, secret: &str) -> StoreResult<()> {
+        let _timer = metrics::db_timer("set_raw_webhook_secret");
+        sqlx::query("UPDATE webhooks SET secret = $1 WHERE webhook_id = $2")
+            .bind(secret)
+            .bind(webhook_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl AdminStore for PostgresStore {
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn list_users(&self, now: i64) -> StoreResult<Vec<UserSummary>> {
+        let _timer = metrics::db_timer("list_users");
+        let rows = sqlx::query(
+            "SELECT user_id, username, created_at, disabled, \
+             (token IS NOT NULL AND (token_expires_at IS NULL OR token_expires_at > $1)) AS active_session \
+             FROM users ORDER BY created_at, username",
+        )
+        .bind(now)
+        .fetch_all(&self.pool)
+        .await?;
+        rows.iter()
+            .map(|r| {
+                Ok(UserSummary {
+                    user: User {
+                        user_id: r.try_get("user_id")?,
+                        username: r.try_get("username")?,
+                        created_at: r.try_get("created_at")?,
+                    },
+                    disabled: r.try_get("disabled")?,
+                    active_session: r.try_get("active_session")?,
+                })
+            })
+            .collect()
+    }
+
+    #[tracing::instrument(level = "debug", skip(self, password_hash), err)]
+    async fn set_password_hash(&self, user_id: &str, password_hash: &str) -> StoreResult<bool> {
+        let _timer = metrics::db_timer("set_password_hash");
+        let res = sqlx::query("UPDATE users SET password_hash = $1 WHERE user_id = $2")
+            .bind(password_hash)
+            .bind(user_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn set_user_disabled(&self, user_id: &str, disabled: bool) -> StoreResult<bool> {
+        let _timer = metrics::db_timer("set_user_disabled");
+        let res = sqlx::query("UPDATE users SET disabled = $1 WHERE user_id = $2")
+            .bind(disabled)
+            .bind(user_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn revoke_sessions(&self, user_id: Option<&str>) -> StoreResult<u64> {
+        let _timer = metrics::db_timer("revoke_sessions");
+        let res = sqlx::query(
+            "UPDATE users SET token = NULL, token_expires_at = NULL \
+             WHERE token IS NOT NULL AND ($1::TEXT IS NULL OR user_id = $1)",
+        )
+        .bind(user_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(res.rows_affected())
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn list_groups(&self) -> StoreResult<Vec<GroupSummary>> {
+        let _timer = metrics::db_timer("list_groups");
+        let rows = sqlx::query(&format!(
+            "SELECT {}, \
+             (SELECT COUNT(*) FROM memberships m WHERE m.group_id = g.group_id) AS members, \
+             (SELECT COUNT(*) FROM messages m WHERE m.group_id = g.group_id) AS messages \
+             FROM groups g ORDER BY created_at, seq",
+            GROUP_COLUMNS
+        ))
+        .fetch_all(&self.pool)
+        .await?;
+        rows.iter()
+            .map(|r| {
+                Ok(GroupSummary {
+                    group: group_from_row(r)?,
+                    members: r.try_get::<i64, _>("members")? as u64,
+                    messages: r.try_get::<i64, _>("messages")? as u64,
+                })
+            })
+            .collect()
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn delete_group(&self, group_id: &str) -> StoreResult<bool> {
+        let _timer = metrics::db_timer("delete_group");
+        let mut tx = self.pool.begin().await?;
+        // reazioni, menzioni e pin dei messaggi seguono con ON DELETE CASCADE
+        for stmt in [
+            "DELETE FROM messages WHERE group_id = $1",
+            "DELETE FROM scheduled_messages WHERE group_id = $1",
+            "DELETE FROM webhook_deliveries WHERE group_id = $1",
+            "DELETE FROM webhooks WHERE group_id = $1",
+            "DELETE FROM memberships WHERE group_id = $1",
+            "DELETE FROM invites WHERE group_id = $1",
+            "DELETE FROM sender_keys WHERE group_id = $1",
+        ] {
+            sqlx::query(stmt).bind(group_id).execute(&mut tx).await?;
+        }
+        let res = sqlx::query("DELETE FROM groups WHERE group_id = $1").bind(group_id).execute(&mut tx).await?;
+        tx.commit().await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn stats(&self, now: i64) -> StoreResult<StoreStats> {
+        let _timer = metrics::db_timer("stats");
+        let row = sqlx::query(
+            "SELECT \
+             (SELECT COUNT(*) FROM users) AS users, \
+             (SELECT COUNT(*) FROM users WHERE disabled) AS disabled_users, \
+             (SELECT COUNT(*) FROM users WHERE token IS NOT NULL AND (token_expires_at IS NULL OR token_expires_at > $1)) \
+                AS active_sessions, \
+             (SELECT COUNT(*) FROM groups) AS groups, \
+             (SELECT COUNT(*) FROM messages) AS messages, \
+             (SELECT COUNT(*) FROM invites) AS pending_invites",
+        )
+        .bind(now)
+        .fetch_one(&self.pool)
+        .await?;
+        let count = |column: &str| row.try_get::<i64, _>(column).map(|n| n as u64);
+        Ok(StoreStats {
+            users: count("users")?,
+            disabled_users: count("disabled_users")?,
+            active_sessions: count("active_sessions")?,
+            groups: count("groups")?,
+            messages: count("messages")?,
+            pending_invites: count("pending_invites")?,
+        })
+    }
+}
+
+#[async_trait]
+impl Store for PostgresStore {
+    fn backend(&self) -> &'static str {
+        "postgres"
+    }
+
+    async fn migrate(&self) -> anyhow::Result<()> {
+        for s in MIGRATIONS {
+            sqlx::query(s)
+                .execute(&self.pool)
+                .await
+                .with_context(|| format!("apply migration: {}", s.trim().lines().next().unwrap_or_default()))?;
+        }
+        let previous = self.schema_version().await.context("read schema version")?;
+        if previous < 2 {
+            // i messaggi esistenti vengono numerati nell'ordine di inserimento
+            sqlx::query(
+                "UPDATE messages m SET group_seq = n.rn FROM \
+                 (SELECT message_id, ROW_NUMBER() OVER (PARTITION BY group_id ORDER BY seq) AS rn FROM messages) n \
+                 WHERE m.message_id = n.message_id",
+            )
+            .execute(&self.pool)
+            .await
+            .context("number existing messages")?;
+            sqlx::query(
+                "UPDATE groups g SET last_seq = COALESCE((SELECT MAX(group_seq) FROM messages WHERE group_id = g.group_id), 0)",
+            )
+            .execute(&self.pool)
+            .await
+            .context("initialize group sequences")?;
+        }
+        sqlx::query("CREATE UNIQUE INDEX IF NOT EXISTS messages_group_seq ON messages(group_id, group_seq)")
+            .execute(&self.pool)
+            .await
+            .context("create messages_group_seq index")?;
+        sqlx::query(
+            "INSERT INTO schema_version (id, version) VALUES (1, $1) \
+             ON CONFLICT (id) DO UPDATE SET version = EXCLUDED.version",
+        )
+        .bind(SCHEMA_VERSION)
+        .execute(&self.pool)
+        .await
+        .context("record schema version")?;
+        Ok(())
+    }
+
+    async fn ping(&self) -> StoreResult<()> {
+        sqlx::query_scalar::<_, i32>("SELECT 1").fetch_one(&self.pool).await.map(|_| ())
+    }
+
+    async fn schema_version(&self) -> StoreResult<i64> {
+        let v: Option<i64> = sqlx::query_scalar("SELECT version FROM schema_version WHERE id = 1")
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(v.unwrap_or(0))
+    }
+
+    async fn data_dir(&self) -> StoreResult<Option<PathBuf>> {
+        // i file sono sul server PostgreSQL, non controllabili da qui
+        Ok(None)
+    }
+
+    async fn backup_to(&self, _path: &Path) -> anyhow::Result<()> {
+        anyhow::bail!("online backup is only supported for sqlite databases: use pg_dump for postgres")
+    }
+
+    fn pool_stats(&self) -> PoolStats {
+        PoolStats { size: self.pool.size(), idle: self.pool.num_idle() }
+    }
+
+    async fn close(&self) {
+        self.pool.close().await
+    }
+}
@@ -0,0 +1,1450 @@
+/* Backend SQLite: lo schema è quello di run_migrations; l'ordine di inserimento usa il rowid. */
+use anyhow::Context;
+use async_trait::async_trait;
+use ruggine_core::{
+    timestamp_from_unix, unix_from_timestamp, DeliveryStatus, Group, KeyAlgorithm, Message, PinPolicy, PublicKey,
+    RetentionPolicy, ScheduledMessage, SenderKey, User, Webhook, WebhookDelivery, WebhookEvent,
+};
+use sqlx::{sqlite::SqliteRow, Row, Sqlite, SqlitePool, Transaction};
+use std::path::{Path, PathBuf};
+
+use super::{
+    AdminStore, AttemptOutcome, Credentials, DeliveryAttempt, GroupStore, GroupSummary, Invite, InviteStore, KeyStore,
+    MembershipStore, MentionStore, MessageStore, PinRecord, PinStore, PoolStats, ReactionStore, Role, ScheduleStore,
+    SessionStore, Store, StoreResult, StoreStats, UserStore, UserSummary, WebhookRecord, WebhookStore,
+};
+use crate::{connect_pool, connect_pool_sized, metrics, run_migrations, sqlite_url_from, SQLITE_MEMORY_URL};
+
+#[derive(Clone)]
+pub struct SqliteStore {
+    pool: SqlitePool,
+}
+
+impl SqliteStore {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    /// Apre il database da un percorso o URL sqlite://... (creando il file se manca).
+    pub async fn connect(database_url: &str, max_connections: u32) -> anyhow::Result<Self> {
+        let url = sqlite_url_from(database_url)?;
+        Ok(Self::new(connect_pool_sized(&url, max_connections).await?))
+    }
+
+    /// Database in memoria, vuoto e già migrato: stato condiviso da tutte le connessioni del pool,
+    /// perso alla chiusura dello store. Pensato per test e istanze dimostrative.
+    pub async fn in_memory() -> anyhow::Result<Self> {
+        let store = Self::new(connect_pool(SQLITE_MEMORY_URL).await?);
+        run_migrations(&store.pool).await?;
+        Ok(store)
+    }
+
+    pub fn pool(&self) -> &SqlitePool {
+        &self.pool
+    }
+}
+
+const GROUP_COLUMNS: &str = "group_id, name, created_at, pin_policy, encrypted, retention_max_age_secs, \
+     retention_max_count, message_ttl_secs";
+
+const MESSAGE_COLUMNS: &str = "message_id, group_id, sender_id, content, created_at, group_seq, expires_at";
+
+const SCHEDULED_COLUMNS: &str = "message_id, group_id, sender_id, content, send_at, ttl_secs, created_at";
+
+const WEBHOOK_COLUMNS: &str =
+    "webhook_id, group_id, url, events, secret, enabled, created_by, created_at, consecutive_failures";
+
+const DELIVERY_COLUMNS: &str = "delivery_id, webhook_id, group_id, event, message_id, user_id, status, attempts, \
+     next_attempt_at, last_status_code, last_error, created_at, finished_at";
+
+// Condizione sui messaggi dell'alias non ancora scaduti, secondo l'orologio del database
+fn not_expired(alias: &str) -> String {
+    format!("({0}.expires_at IS NULL OR {0}.expires_at > CAST(strftime('%s', 'now') AS INTEGER))", alias)
+}
+
+// MESSAGE_COLUMNS qualificate con l'alias della tabella messages, per le query con JOIN
+fn qualified_message_columns(alias: &str) -> String {
+    MESSAGE_COLUMNS
+        .split(", ")
+        .map(|c| format!("{}.{}", alias, c))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn group_from_row(row: &SqliteRow) -> Result<Group, sqlx::Error> {
+    let pin_policy: String = row.try_get("pin_policy")?;
+    Ok(Group {
+        group_id: row.try_get("group_id")?,
+        name: row.try_get("name")?,
+        created_at: row.try_get("created_at")?,
+        pin_policy: PinPolicy::parse(&pin_policy).unwrap_or_default(),
+        encrypted: row.try_get("encrypted")?,
+        retention: RetentionPolicy {
+            max_age_secs: row.try_get::<Option<i64>, _>("retention_max_age_secs")?.map(|v| v as u64),
+            max_count: row.try_get::<Option<i64>, _>("retention_max_count")?.map(|v| v as u64),
+        },
+        message_ttl_secs: row.try_get::<Option<i64>, _>("message_ttl_secs")?.map(|v| v as u64),
+    })
+}
+
+// Message senza reazioni né menzioni
+fn message_from_row(row: &SqliteRow) -> Result<Message, sqlx::Error> {
+    Ok(Message {
+        message_id: row.try_get("message_id")?,
+        group_id: row.try_get("group_id")?,
+        sender_id: row.try_get("sender_id")?,
+        content: row.try_get("content")?,
+        created_at: row.try_get("created_at")?,
+        seq: row.try_get::<i64, _>("group_seq")? as u64,
+        reactions: Vec::new(),
+        mentions: Vec::new(),
+        expires_at: row.try_get::<Option<i64>, _>("expires_at")?.map(timestamp_from_unix),
+    })
+}
+
+fn scheduled_from_row(row: &SqliteRow) -> Result<ScheduledMessage, sqlx::Error> {
+    Ok(ScheduledMessage {
+        message_id: row.try_get("message_id")?,
+        group_id: row.try_get("group_id")?,
+        sender_id: row.try_get("sender_id")?,
+        content: row.try_get("content")?,
+        send_at: timestamp_from_unix(row.try_get("send_at")?),
+        created_at: row.try_get("created_at")?,
+        ttl_secs: row.try_get::<Option<i64>, _>("ttl_secs")?.map(|v| v as u64),
+    })
+}
+
+fn webhook_from_row(row: &SqliteRow) -> Result<WebhookRecord, sqlx::Error> {
+    let events: String = row.try_get("events")?;
+    Ok(WebhookRecord {
+        webhook: Webhook {
+            webhook_id: row.try_get("webhook_id")?,
+            group_id: row.try_get("group_id")?,
+            url: row.try_get("url")?,
+            events: events.split(',').filter_map(WebhookEvent::parse).collect(),
+            enabled: row.try_get("enabled")?,
+            created_by: row.try_get("created_by")?,
+            created_at: row.try_get("created_at")?,
+            consecutive_failures: row.try_get::<i64, _>("consecutive_failures")? as u32,
+        },
+        secret: row.try_get("secret")?,
+    })
+}
+
+fn delivery_from_row(row: &SqliteRow) -> Result<WebhookDelivery, sqlx::Error> {
+    let event: String = row.try_get("event")?;
+    let status: String = row.try_get("status")?;
+    Ok(WebhookDelivery {
+        delivery_id: row.try_get("delivery_id")?,
+        webhook_id: row.try_get("webhook_id")?,
+        group_id: row.try_get("group_id")?,
+        event: WebhookEvent::parse(&event).ok_or_else(|| sqlx::Error::Decode(format!("unknown webhook event {:?}", event).into()))?,
+        message_id: row.try_get("message_id")?,
+        user_id: row.try_get("user_id")?,
+        status: DeliveryStatus::parse(&status)
+            .ok_or_else(|| sqlx::Error::Decode(format!("unknown delivery status {:?}", status).into()))?,
+        attempts: row.try_get::<i64, _>("attempts")? as u32,
+        next_attempt_at: row.try_get::<Option<i64>, _>("next_attempt_at")?.map(timestamp_from_unix),
+        last_status_code: row.try_get::<Option<i64>, _>("last_status_code")?.map(|v| v as u16),
+        last_error: row.try_get("last_error")?,
+        created_at: row.try_get("created_at")?,
+        finished_at: row.try_get::<Option<i64>, _>("finished_at")?.map(timestamp_from_unix),
+    })
+}
+
+// Fa fallire le consegne in coda del webhook (disabilitato)
+async fn fail_pending_deliveries(
+    tx: &mut Transaction<'_, Sqlite>,
+    webhook_id: &str,
+    error: &str,
+    now: i64,
+) -> StoreResult<()> {
+    sqlx::query(
+        "UPDATE webhook_deliveries SET status = 'failed', next_attempt_at = NULL, last_error = ?, finished_at = ? \
+         WHERE webhook_id = ? AND status = 'pending'",
+    )
+    .bind(error)
+    .bind(now)
+    .bind(webhook_id)
+    .execute(&mut *tx)
+    .await?;
+    Ok(())
+}
+
+// Salva il messaggio con il seq successivo del gruppo: il contatore e il messaggio cambiano insieme,
+// nessun seq saltato o ripetuto
+async fn insert_message_in(tx: &mut Transaction<'_, Sqlite>, message: &Message) -> StoreResult<u64> {
+    let seq: i64 = sqlx::query_scalar("UPDATE groups SET last_seq = last_seq + 1 WHERE group_id = ? RETURNING last_seq")
+        .bind(&message.group_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or(sqlx::Error::RowNotFound)?;
+    sqlx::query(
+        "INSERT INTO messages (message_id, group_id, sender_id, content, created_at, group_seq, expires_at) \
+         VALUES (?, ?, ?, ?, ?, ?, ?)",
+    )
+    .bind(&message.message_id)
+    .bind(&message.group_id)
+    .bind(&message.sender_id)
+    .bind(&message.content)
+    .bind(&message.created_at)
+    .bind(seq)
+    .bind(message.expires_at.as_deref().and_then(unix_from_timestamp))
+    .execute(&mut *tx)
+    .await?;
+    Ok(seq as u64)
+}
+
+fn public_key_from_row(row: &SqliteRow) -> Result<PublicKey, sqlx::Error> {
+    let alg: String = row.try_get("alg")?;
+    Ok(PublicKey {
+        user_id: row.try_get("user_id")?,
+        key_id: row.try_get("key_id")?,
+        alg: KeyAlgorithm::parse(&alg).ok_or_else(|| sqlx::Error::Decode(format!("unknown key algorithm {:?}", alg).into()))?,
+        public_key: row.try_get("public_key")?,
+        created_at: row.try_get("created_at")?,
+    })
+}
+
+fn sender_key_from_row(row: &SqliteRow) -> Result<SenderKey, sqlx::Error> {
+    Ok(SenderKey {
+        group_id: row.try_get("group_id")?,
+        sender_id: row.try_get("sender_id")?,
+        sender_key_id: row.try_get("sender_key_id")?,
+        recipient_id: row.try_get("recipient_id")?,
+        recipient_key_id: row.try_get("recipient_key_id")?,
+        sealed_key: row.try_get("sealed_key")?,
+        created_at: row.try_get("created_at")?,
+    })
+}
+
+fn invite_from_row(row: &SqliteRow) -> Result<Invite, sqlx::Error> {
+    Ok(Invite {
+        invite_id: row.try_get("invite_id")?,
+        group_id: row.try_get("group_id")?,
+        invited: row.try_get("invited")?,
+        created_at: row.try_get("created_at")?,
+    })
+}
+
+fn placeholders(n: usize) -> String {
+    vec!["?"; n].join(", ")
+}
+
+#[async_trait]
+impl UserStore for SqliteStore {
+    #[tracing::instrument(level = "debug", skip(self, user, password_hash), fields(user_id = %user.user_id), err)]
+    async fn create_user(&self, user: &User, password_hash: &str) -> StoreResult<()> {
+        let _timer = metrics::db_timer("create_user");
+        sqlx::query("INSERT INTO users (user_id, username, password_hash, created_at) VALUES (?, ?, ?, ?)")
+            .bind(&user.user_id)
+            .bind(&user.username)
+            .bind(password_hash)
+            .bind(&user.created_at)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn find_user(&self, user_id: &str) -> StoreResult<Option<User>> {
+        let _timer = metrics::db_timer("find_user");
+        let row = sqlx::query("SELECT user_id, username, created_at FROM users WHERE user_id = ?")
+            .bind(user_id)
+            .fetch_optional(&self.pool)
+            .await?;
+        row.map(|r| {
+            Ok(User { user_id: r.try_get("user_id")?, username: r.try_get("username")?, created_at: r.try_get("created_at")? })
+        })
+        .transpose()
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn username_exists(&self, username: &str) -> StoreResult<bool> {
+        let _timer = metrics::db_timer("username_exists");
+        let n: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM users WHERE username = ?")
+            .bind(username)
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(n > 0)
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn credentials(&self, username: &str) -> StoreResult<Option<Credentials>> {
+        let _timer = metrics::db_timer("credentials");
+        let row = sqlx::query("SELECT user_id, username, password_hash, created_at, disabled FROM users WHERE username = ?")
+            .bind(username)
+            .fetch_optional(&self.pool)
+            .await?;
+        row.map(|r| {
+            Ok(Credentials {
+                user: User {
+                    user_id: r.try_get("user_id")?,
+                    username: r.try_get("username")?,
+                    created_at: r.try_get("created_at")?,
+                },
+                password_hash: r.try_get("password_hash")?,
+                disabled: r.try_get("disabled")?,
+            })
+        })
+        .transpose()
+    }
+}
+
+#[async_trait]
+impl SessionStore for SqliteStore {
+    #[tracing::instrument(level = "debug", skip(self, token), err)]
+    async fn set_token(&self, user_id: &str, token: &str, expires_at: Option<i64>) -> StoreResult<()> {
+        let _timer = metrics::db_timer("set_token");
+        sqlx::query("UPDATE users SET token = ?, token_expires_at = ? WHERE user_id = ?")
+            .bind(token)
+            .bind(expires_at)
+            .bind(user_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    #[tracing::instrument(level = "debug", skip_all, err)]
+    async fn user_id_for_token(&self, token: &str, now: i64) -> StoreResult<Option<String>> {
+        let _timer = metrics::db_timer("user_id_for_token");
+        sqlx::query_scalar(
+            "SELECT user_id FROM users WHERE token = ? AND (token_expires_at IS NULL OR token_expires_at > ?) AND disabled = 0",
+        )
+            .bind(token)
+            .bind(now)
+            .fetch_optional(&self.pool)
+            .await
+    }
+}
+
+#[async_trait]
+impl GroupStore for SqliteStore {
+    #[tracing::instrument(level = "debug", skip(self, group, members), fields(group_id = %group.group_id), err)]
+    async fn create_group(&self, group: &Group, members: &[(String, Role)]) -> StoreResult<()> {
+        let _timer = metrics::db_timer("create_group");
+        let mut tx = self.pool.begin().await?;
+        sqlx::query(
+            "INSERT INTO groups (group_id, name, created_at, pin_policy, encrypted, retention_max_age_secs, \
+             retention_max_count, message_ttl_secs) VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(&group.group_id)
+        .bind(&group.name)
+        .bind(&group.created_at)
+        .bind(group.pin_policy.as_str())
+        .bind(group.encrypted)
+        .bind(group.retention.max_age_secs.map(|v| v as i64))
+        .bind(group.retention.max_count.map(|v| v as i64))
+        .bind(group.message_ttl_secs.map(|v| v as i64))
+            .execute(&mut tx)
+            .await?;
+        for (user_id, role) in members {
+            sqlx::query("INSERT INTO memberships (membership_id, group_id, user_id, joined_at, role) VALUES (?, ?, ?, ?, ?)")
+                .bind(uuid::Uuid::new_v4().to_string())
+                .bind(&group.group_id)
+                .bind(user_id)
+                .bind(&group.created_at)
+                .bind(role.as_str())
+                .execute(&mut tx)
+                .await?;
+        }
+        tx.commit().await
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn find_group(&self, group_id: &str) -> StoreResult<Option<Group>> {
+        let _timer = metrics::db_timer("find_group");
+        let row = sqlx::query(&format!("SELECT {} FROM groups WHERE group_id = ?", GROUP_COLUMNS))
+            .bind(group_id)
+            .fetch_optional(&self.pool)
+            .await?;
+        row.as_ref().map(group_from_row).transpose()
+    }
+
+    #[tracing::instrument(level = "debug", skip(self, group), fields(group_id = %group.group_id), err)]
+    async fn update_group(&self, group: &Group) -> StoreResult<()> {
+        let _timer = metrics::db_timer("update_group");
+        sqlx::query(
+            "UPDATE groups SET name = ?, pin_policy = ?, retention_max_age_secs = ?, retention_max_count = ?, \
+             message_ttl_secs = ? WHERE group_id = ?",
+        )
+        .bind(&group.name)
+        .bind(group.pin_policy.as_str())
+        .bind(group.retention.max_age_secs.map(|v| v as i64))
+        .bind(group.retention.max_count.map(|v| v as i64))
+        .bind(group.message_ttl_secs.map(|v| v as i64))
+        .bind(&group.group_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn groups_for_user(&self, user_id: &str) -> StoreResult<Vec<Group>> {
+        let _timer = metrics::db_timer("groups_for_user");
+        let sql = format!(
+            "SELECT {} FROM groups WHERE group_id IN (SELECT group_id FROM memberships WHERE user_id = ?) \
+             ORDER BY created_at, rowid",
+            GROUP_COLUMNS
+        );
+        let rows = sqlx::query(&sql).bind(user_id).fetch_all(&self.pool).await?;
+        rows.iter().map(group_from_row).collect()
+    }
+}
+
+#[async_trait]
+impl MembershipStore for SqliteStore {
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn member_role(&self, group_id: &str, user_id: &str) -> StoreResult<Option<Role>> {
+        let _timer = metrics::db_timer("member_role");
+        let role: Option<String> = sqlx::query_scalar("SELECT role FROM memberships WHERE group_id = ? AND user_id = ?")
+            .bind(group_id)
+            .bind(user_id)
+            .fetch_optional(&self.pool)
+            .await?;
+        // ruoli sconosciuti (scritti da versioni future) contano come membro semplice
+        Ok(role.map(|r| Role::parse(&r).unwrap_or(Role::Member)))
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn member_ids(&self, group_id: &str) -> StoreResult<Vec<String>> {
+        let _timer = metrics::db_timer("member_ids");
+        sqlx::query_scalar("SELECT user_id FROM memberships WHERE group_id = ?")
+            .bind(group_id)
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    #[tracing::instrument(level = "debug", skip(self, usernames), err)]
+    async fn find_members_by_username(
+        &self,
+        group_id: &str,
+        exclude: &str,
+        usernames: &[String],
+    ) -> StoreResult<Vec<(String, String)>> {
+        let _timer = metrics::db_timer("find_members_by_username");
+        if usernames.is_empty() {
+            return Ok(Vec::new());
+        }
+        let sql = format!(
+            "SELECT u.username, u.user_id FROM users u JOIN memberships m ON m.user_id = u.user_id \
+             WHERE m.group_id = ? AND u.user_id <> ? AND u.username IN ({})",
+            placeholders(usernames.len())
+        );
+        let mut q = sqlx::query(&sql).bind(group_id).bind(exclude);
+        for name in usernames {
+            q = q.bind(name);
+        }
+        q.fetch_all(&self.pool)
+            .await?
+            .iter()
+            .map(|r| Ok((r.try_get("username")?, r.try_get("user_id")?)))
+            .collect()
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn set_muted(&self, group_id: &str, user_id: &str, muted: bool) -> StoreResult<bool> {
+        let _timer = metrics::db_timer("set_muted");
+        let res = sqlx::query("UPDATE memberships SET muted = ? WHERE group_id = ? AND user_id = ?")
+            .bind(muted)
+            .bind(group_id)
+            .bind(user_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn muted_member_ids(&self, group_id: &str) -> StoreResult<Vec<String>> {
+        let _timer = metrics::db_timer("muted_member_ids");
+        sqlx::query_scalar("SELECT user_id FROM memberships WHERE group_id = ? AND muted <> 0")
+            .bind(group_id)
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn admin_ids(&self, group_id: &str) -> StoreResult<Vec<String>> {
+        let _timer = metrics::db_timer("admin_ids");
+        sqlx::query_scalar("SELECT user_id FROM memberships WHERE group_id = ? AND role = 'admin'")
+            .bind(group_id)
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn add_member(&self, group_id: &str, user_id: &str, role: Role, joined_at: &str) -> StoreResult<bool> {
+        let _timer = metrics::db_timer("add_member");
+        let res = sqlx::query(
+            "INSERT INTO memberships (membership_id, group_id, user_id, joined_at, role) SELECT ?, ?, ?, ?, ? \
+             WHERE NOT EXISTS (SELECT 1 FROM memberships WHERE group_id = ? AND user_id = ?)",
+        )
+        .bind(uuid::Uuid::new_v4().to_string())
+        .bind(group_id)
+        .bind(user_id)
+        .bind(joined_at)
+        .bind(role.as_str())
+        .bind(group_id)
+        .bind(user_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn remove_member(&self, group_id: &str, user_id: &str) -> StoreResult<bool> {
+        let _timer = metrics::db_timer("remove_member");
+        let res = sqlx::query("DELETE FROM memberships WHERE group_id = ? AND user_id = ?")
+            .bind(group_id)
+            .bind(user_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(res.rows_affected() > 0)
+    }
+}
+
+#[async_trait]
+impl MessageStore for SqliteStore {
+    #[tracing::instrument(level = "debug", skip(self, message), fields(message_id = %message.message_id), err)]
+    async fn insert_message(&self, message: &Message) -> StoreResult<u64> {
+        let _timer = metrics::db_timer("insert_message");
+        let mut tx = self.pool.begin().await?;
+        let seq = insert_message_in(&mut tx, message).await?;
+        tx.commit().await?;
+        Ok(seq)
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn find_message(&self, message_id: &str) -> StoreResult<Option<Message>> {
+        let _timer = metrics::db_timer("find_message");
+        let row = sqlx::query(&format!(
+            "SELECT {} FROM messages WHERE message_id = ? AND {}",
+            MESSAGE_COLUMNS,
+            not_expired("messages")
+        ))
+            .bind(message_id)
+            .fetch_optional(&self.pool)
+            .await?;
+        row.as_ref().map(message_from_row).transpose()
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn list_messages(&self, group_id: &str, before: Option<&str>, limit: i64) -> StoreResult<Vec<Message>> {
+        let _timer = metrics::db_timer("list_messages");
+        let sql = format!(
+            "SELECT {} FROM messages WHERE group_id = ? AND (? IS NULL OR created_at < ?) AND {} \
+             ORDER BY created_at DESC, rowid DESC LIMIT ?",
+            MESSAGE_COLUMNS,
+            not_expired("messages")
+        );
+        let rows = sqlx::query(&sql)
+            .bind(group_id)
+            .bind(before)
+            .bind(before)
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await?;
+        // le righe arrivano dalla più recente: le riportiamo in ordine cronologico
+        rows.iter().rev().map(message_from_row).collect()
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn messages_after(&self, group_id: &str, after_seq: u64, limit: i64) -> StoreResult<Vec<Message>> {
+        let _timer = metrics::db_timer("messages_after");
+        let sql = format!(
+            "SELECT {} FROM messages WHERE group_id = ? AND group_seq > ? AND {} ORDER BY group_seq LIMIT ?",
+            MESSAGE_COLUMNS,
+            not_expired("messages")
+        );
+        let rows = sqlx::query(&sql)
+            .bind(group_id)
+            .bind(after_seq as i64)
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await?;
+        rows.iter().map(message_from_row).collect()
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn latest_seq(&self, group_id: &str) -> StoreResult<u64> {
+        let _timer = metrics::db_timer("latest_seq");
+        let seq: Option<i64> = sqlx::query_scalar("SELECT last_seq FROM groups WHERE group_id = ?")
+            .bind(group_id)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(seq.unwrap_or(0) as u64)
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn seq_beyond_newest(&self, group_id: &str, keep: u64) -> StoreResult<Option<u64>> {
+        let _timer = metrics::db_timer("seq_beyond_newest");
+        let seq: Option<i64> =
+            sqlx::query_scalar("SELECT group_seq FROM messages WHERE group_id = ? ORDER BY group_seq DESC LIMIT 1 OFFSET ?")
+                .bind(group_id)
+                .bind(keep as i64)
+                .fetch_optional(&self.pool)
+                .await?;
+        Ok(seq.map(|s| s as u64))
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn purge_messages(&self, group_id: &str, through_seq: u64, limit: i64) -> StoreResult<u64> {
+        let _timer = metrics::db_timer("purge_messages");
+        let batch = "SELECT message_id FROM messages WHERE group_id = ? AND group_seq <= ? ORDER BY group_seq LIMIT ?";
+        let mut tx = self.pool.begin().await?;
+        // foreign_keys non è attivo su tutte le connessioni del pool: i dipendenti sono eliminati esplicitamente
+        for table in ["reactions", "mentions", "pinned_messages"] {
+            sqlx::query(&format!("DELETE FROM {} WHERE message_id IN ({})", table, batch))
+                .bind(group_id)
+                .bind(through_seq as i64)
+                .bind(limit)
+                .execute(&mut tx)
+                .await?;
+        }
+        let res = sqlx::query(&format!("DELETE FROM messages WHERE message_id IN ({})", batch))
+            .bind(group_id)
+            .bind(through_seq as i64)
+            .bind(limit)
+            .execute(&mut tx)
+            .await?;
+        tx.commit().await?;
+        Ok(res.rows_affected())
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn purge_expired(&self, now: i64, limit: i64) -> StoreResult<Vec<(String, String)>> {
+        let _timer = metrics::db_timer("purge_expired");
+        let mut tx = self.pool.begin().await?;
+        let expired: Vec<(String, String)> = sqlx::query_as(
+            "SELECT group_id, message_id FROM messages WHERE expires_at <= ? ORDER BY expires_at, rowid LIMIT ?",
+        )
+        .bind(now)
+        .bind(limit)
+        .fetch_all(&mut tx)
+        .await?;
+        if expired.is_empty() {
+            return Ok(expired);
+        }
+        // foreign_keys non è attivo su tutte le connessioni del pool: i dipendenti sono eliminati esplicitamente
+        let ids = placeholders(expired.len());
+        for table in ["reactions", "mentions", "pinned_messages", "messages"] {
+            let sql = format!("DELETE FROM {} WHERE message_id IN ({})", table, ids);
+            let mut q = sqlx::query(&sql);
+            for (_, message_id) in &expired {
+                q = q.bind(message_id);
+            }
+            q.execute(&mut tx).await?;
+        }
+        tx.commit().await?;
+        Ok(expired)
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn raw_contents(&self, after: Option<&str>, limit: i64) -> StoreResult<Vec<(String, String)>> {
+        let _timer = metrics::db_timer("raw_contents");
+        sqlx::query_as(
+            "SELECT message_id, content FROM messages WHERE (? IS NULL OR message_id > ?) ORDER BY message_id LIMIT ?",
+        )
+        .bind(after)
+        .bind(after)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    #[tracing::instrument(level = "debug", skip(self, content), err)]
+    async fn set_raw_content(&self, message_id: &str, content: &str) -> StoreResult<()> {
+        let _timer = metrics::db_timer("set_raw_content");
+        sqlx::query("UPDATE messages SET content = ? WHERE message_id = ?")
+            .bind(content)
+            .bind(message_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ScheduleStore for SqliteStore {
+    #[tracing::instrument(level = "debug", skip(self, scheduled), fields(message_id = %scheduled.message_id), err)]
+    async fn schedule_message(&self, scheduled: &ScheduledMessage) -> StoreResult<()> {
+        let _timer = metrics::db_timer("schedule_message");
+        let send_at = unix_from_timestamp(&scheduled.send_at)
+            .ok_or_else(|| sqlx::Error::Protocol(format!("invalid send_at {:?}", scheduled.send_at)))?;
+        sqlx::query(&format!("INSERT INTO scheduled_messages ({}) VALUES (?, ?, ?, ?, ?, ?, ?)", SCHEDULED_COLUMNS))
+            .bind(&scheduled.message_id)
+            .bind(&scheduled.group_id)
+            .bind(&scheduled.sender_id)
+            .bind(&scheduled.content)
+            .bind(send_at)
+            .bind(scheduled.ttl_secs.map(|v| v as i64))
+            .bind(&scheduled.created_at)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn scheduled_for_sender(&self, sender_id: &str) -> StoreResult<Vec<ScheduledMessage>> {
+        let _timer = metrics::db_timer("scheduled_for_sender");
+        let rows = sqlx::query(&format!(
+            "SELECT {} FROM scheduled_messages WHERE sender_id = ? ORDER BY send_at, rowid",
+            SCHEDULED_COLUMNS
+        ))
+        .bind(sender_id)
+        .fetch_all(&self.pool)
+        .await?;
+        rows.iter().map(scheduled_from_row).collect()
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn cancel_scheduled(&self, message_id: &str, sender_id: &str) -> StoreResult<bool> {
+        let _timer = metrics::db_timer("cancel_scheduled");
+        let res = sqlx::query("DELETE FROM scheduled_messages WHERE message_id = ? AND sender_id = ?")
+            .bind(message_id)
+            .bind(sender_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn due_scheduled(&self, now: i64, limit: i64) -> StoreResult<Vec<ScheduledMessage>> {
+        let _timer = metrics::db_timer("due_scheduled");
+        let rows = sqlx::query(&format!(
+            "SELECT {} FROM scheduled_messages WHERE send_at <= ? ORDER BY send_at, rowid LIMIT ?",
+            SCHEDULED_COLUMNS
+        ))
+        .bind(now)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+        rows.iter().map(scheduled_from_row).collect()
+    }
+
+    #[tracing::instrument(level = "debug", skip(self, message), fields(message_id = %message.message_id), err)]
+    async fn publish_scheduled(&self, message: &Message) -> StoreResult<Option<u64>> {
+        let _timer = metrics::db_timer("publish_scheduled");
+        let mut tx = self.pool.begin().await?;
+        let res = sqlx::query("DELETE FROM scheduled_messages WHERE message_id = ?")
+            .bind(&message.message_id)
+            .execute(&mut tx)
+            .await?;
+        if res.rows_affected() == 0 {
+            return Ok(None);
+        }
+        let seq = insert_message_in(&mut tx, message).await?;
+        tx.commit().await?;
+        Ok(Some(seq))
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn raw_scheduled_contents(&self, after: Option<&str>, limit: i64) -> StoreResult<Vec<(String, String)>> {
+        let _timer = metrics::db_timer("raw_scheduled_contents");
+        sqlx::query_as(
+            "SELECT message_id, content FROM scheduled_messages WHERE (? IS NULL OR message_id > ?) ORDER BY message_id LIMIT ?",
+        )
+        .bind(after)
+        .bind(after)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    #[tracing::instrument(level = "debug", skip(self, content), err)]
+    async fn set_raw_scheduled_content(&self, message_id: &str, content: &str) -> StoreResult<()> {
+        let _timer = metrics::db_timer("set_raw_scheduled_content");
+        sqlx::query("UPDATE scheduled_messages SET content = ? WHERE message_id = ?")
+            .bind(content)
+            .bind(message_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl InviteStore for SqliteStore {
+    #[tracing::instrument(level = "debug", skip(self, invite), fields(invite_id = %invite.invite_id), err)]
+    async fn create_invite(&self, invite: &Invite) -> StoreResult<()> {
+        let _timer = metrics::db_timer("create_invite");
+        sqlx::query("INSERT INTO invites (invite_id, group_id, invited, created_at) VALUES (?, ?, ?, ?)")
+            .bind(&invite.invite_id)
+            .bind(&invite.group_id)
+            .bind(&invite.invited)
+            .bind(&invite.created_at)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn invites_for_user(&self, user_id: &str) -> StoreResult<Vec<Invite>> {
+        let _timer = metrics::db_timer("invites_for_user");
+        let rows = sqlx::query(
+            "SELECT invite_id, group_id, invited, created_at FROM invites WHERE invited = ? ORDER BY created_at, rowid",
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+        rows.iter().map(invite_from_row).collect()
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn delete_invite(&self, invite_id: &str) -> StoreResult<bool> {
+        let _timer = metrics::db_timer("delete_invite");
+        let res = sqlx::query("DELETE FROM invites WHERE invite_id = ?").bind(invite_id).execute(&self.pool).await?;
+        Ok(res.rows_affected() > 0)
+    }
+}
+
+#[async_trait]
+impl ReactionStore for SqliteStore {
+    #[tracing::instrument(level = "debug", skip(self, created_at), err)]
+    async fn add_reaction(&self, message_id: &str, user_id: &str, emoji: &str, created_at: &str) -> StoreResult<bool> {
+        let _timer = metrics::db_timer("add_reaction");
+        let res = sqlx::query("INSERT OR IGNORE INTO reactions (message_id, user_id, emoji, created_at) VALUES (?, ?, ?, ?)")
+            .bind(message_id)
+            .bind(user_id)
+            .bind(emoji)
+            .bind(created_at)
+            .execute(&self.pool)
+            .await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn remove_reaction(&self, message_id: &str, user_id: &str, emoji: &str) -> StoreResult<bool> {
+        let _timer = metrics::db_timer("remove_reaction");
+        let res = sqlx::query("DELETE FROM reactions WHERE message_id = ? AND user_id = ? AND emoji = ?")
+            .bind(message_id)
+            .bind(user_id)
+            .bind(emoji)
+            .execute(&self.pool)
+            .await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    #[tracing::instrument(level = "debug", skip_all, fields(count = message_ids.len()), err)]
+    async fn reactions_for(&self, message_ids: &[String]) -> StoreResult<Vec<(String, String, String)>> {
+        let _timer = metrics::db_timer("reactions_for");
+        if message_ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        let sql = format!(
+            "SELECT message_id, user_id, emoji FROM reactions WHERE message_id IN ({}) ORDER BY rowid",
+            placeholders(message_ids.len())
+        );
+        let mut q = sqlx::query(&sql);
+        for id in message_ids {
+            q = q.bind(id);
+        }
+        q.fetch_all(&self.pool)
+            .await?
+            .iter()
+            .map(|r| Ok((r.try_get("message_id")?, r.try_get("user_id")?, r.try_get("emoji")?)))
+            .collect()
+    }
+}
+
+#[async_trait]
+impl MentionStore for SqliteStore {
+    #[tracing::instrument(level = "debug", skip(self, user_ids), err)]
+    async fn add_mentions(&self, message_id: &str, user_ids: &[String]) -> StoreResult<()> {
+        let _timer = metrics::db_timer("add_mentions");
+        for user_id in user_ids {
+            sqlx::query("INSERT OR IGNORE INTO mentions (message_id, user_id) VALUES (?, ?)")
+                .bind(message_id)
+                .bind(user_id)
+                .execute(&self.pool)
+                .await?;
+        }
+        Ok(())
+    }
+
+    #[tracing::instrument(level = "debug", skip_all, fields(count = message_ids.len()), err)]
+    async fn mentions_for(&self, message_ids: &[String]) -> StoreResult<Vec<(String, String)>> {
+        let _timer = metrics::db_timer("mentions_for");
+        if message_ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        let sql = format!(
+            "SELECT message_id, user_id FROM mentions WHERE message_id IN ({}) ORDER BY rowid",
+            placeholders(message_ids.len())
+        );
+        let mut q = sqlx::query(&sql);
+        for id in message_ids {
+            q = q.bind(id);
+        }
+        q.fetch_all(&self.pool)
+            .await?
+            .iter()
+            .map(|r| Ok((r.try_get("message_id")?, r.try_get("user_id")?)))
+            .collect()
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn mention_inbox(&self, user_id: &str, before: Option<&str>, limit: i64) -> StoreResult<Vec<(Message, String)>> {
+        let _timer = metrics::db_timer("mention_inbox");
+        let sql = format!(
+            "SELECT {}, g.name AS group_name FROM mentions x \
+             JOIN messages ON messages.message_id = x.message_id \
+             JOIN groups g ON g.group_id = messages.group_id \
+             WHERE x.user_id = ? AND (? IS NULL OR messages.created_at < ?) AND {} \
+             AND EXISTS (SELECT 1 FROM memberships m WHERE m.group_id = messages.group_id AND m.user_id = x.user_id) \
+             ORDER BY messages.created_at DESC, messages.rowid DESC LIMIT ?",
+            qualified_message_columns("messages"),
+            not_expired("messages")
+        );
+        let rows = sqlx::query(&sql)
+            .bind(user_id)
+            .bind(before)
+            .bind(before)
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await?;
+        rows.iter().map(|r| Ok((message_from_row(r)?, r.try_get("group_name")?))).collect()
+    }
+}
+
+#[async_trait]
+impl PinStore for SqliteStore {
+    #[tracing::instrument(level = "debug", skip(self, pinned_at), err)]
+    async fn pin_message(&self, message_id: &str, group_id: &str, user_id: &str, pinned_at: &str) -> StoreResult<bool> {
+        let _timer = metrics::db_timer("pin_message");
+        let res = sqlx::query("INSERT OR IGNORE INTO pinned_messages (message_id, group_id, pinned_by, pinned_at) VALUES (?, ?, ?, ?)")
+            .bind(message_id)
+            .bind(group_id)
+            .bind(user_id)
+            .bind(pinned_at)
+            .execute(&self.pool)
+            .await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn unpin_message(&self, message_id: &str) -> StoreResult<bool> {
+        let _timer = metrics::db_timer("unpin_message");
+        let res = sqlx::query("DELETE FROM pinned_messages WHERE message_id = ?")
+            .bind(message_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn pins_for_group(&self, group_id: &str) -> StoreResult<Vec<PinRecord>> {
+        let _timer = metrics::db_timer("pins_for_group");
+        let sql = format!(
+            "SELECT {}, p.pinned_by, p.pinned_at FROM pinned_messages p \
+             JOIN messages ON messages.message_id = p.message_id \
+             WHERE p.group_id = ? AND {} ORDER BY p.pinned_at DESC, p.rowid DESC",
+            qualified_message_columns("messages"),
+            not_expired("messages")
+        );
+        let rows = sqlx::query(&sql).bind(group_id).fetch_all(&self.pool).await?;
+        rows.iter()
+            .map(|r| Ok(PinRecord { message: message_from_row(r)?, pinned_by: r.try_get("pinned_by")?, pinned_at: r.try_get("pinned_at")? }))
+            .collect()
+    }
+}
+
+#[async_trait]
+impl KeyStore for SqliteStore {
+    #[tracing::instrument(level = "debug", skip(self, key), fields(user_id = %key.user_id), err)]
+    async fn put_public_key(&self, key: &PublicKey) -> StoreResult<()> {
+        let _timer = metrics::db_timer("put_public_key");
+        sqlx::query("INSERT OR REPLACE INTO public_keys (user_id, key_id, alg, public_key, created_at) VALUES (?, ?, ?, ?, ?)")
+            .bind(&key.user_id)
+            .bind(&key.key_id)
+            .bind(key.alg.as_str())
+            .bind(&key.public_key)
+            .bind(&key.created_at)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn public_key(&self, user_id: &str) -> StoreResult<Option<PublicKey>> {
+        let _timer = metrics::db_timer("public_key");
+        let row = sqlx::query("SELECT user_id, key_id, alg, public_key, created_at FROM public_keys WHERE user_id = ?")
+            .bind(user_id)
+            .fetch_optional(&self.pool)
+            .await?;
+        row.as_ref().map(public_key_from_row).transpose()
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn group_public_keys(&self, group_id: &str) -> StoreResult<Vec<PublicKey>> {
+        let _timer = metrics::db_timer("group_public_keys");
+        let rows = sqlx::query(
+            "SELECT k.user_id, k.key_id, k.alg, k.public_key, k.created_at FROM public_keys k \
+             JOIN memberships m ON m.user_id = k.user_id WHERE m.group_id = ? ORDER BY k.user_id",
+        )
+        .bind(group_id)
+        .fetch_all(&self.pool)
+        .await?;
+        rows.iter().map(public_key_from_row).collect()
+    }
+
+    #[tracing::instrument(level = "debug", skip(self, keys), fields(count = keys.len()), err)]
+    async fn put_sender_keys(&self, keys: &[SenderKey]) -> StoreResult<()> {
+        let _timer = metrics::db_timer("put_sender_keys");
+        let mut tx = self.pool.begin().await?;
+        for key in keys {
+            sqlx::query(
+                "INSERT OR REPLACE INTO sender_keys \
+                 (group_id, sender_id, sender_key_id, recipient_id, recipient_key_id, sealed_key, created_at) \
+                 VALUES (?, ?, ?, ?, ?, ?, ?)",
+            )
+            .bind(&key.group_id)
+            .bind(&key.sender_id)
+            .bind(&key.sender_key_id)
+            .bind(&key.recipient_id)
+            .bind(&key.recipient_key_id)
+            .bind(&key.sealed_key)
+            .bind(&key.created_at)
+            .execute(&mut tx)
+            .await?;
+        }
+        tx.commit().await
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn sender_keys_for(&self, group_id: &str, recipient_id: &str) -> StoreResult<Vec<SenderKey>> {
+        let _timer = metrics::db_timer("sender_keys_for");
+        let rows = sqlx::query(
+            "SELECT group_id, sender_id, sender_key_id, recipient_id, recipient_key_id, sealed_key, created_at \
+             FROM sender_keys WHERE group_id = ? AND recipient_id = ? ORDER BY created_at, rowid",
+        )
+        .bind(group_id)
+        .bind(recipient_id)
+        .fetch_all(&self.pool)
+        .await?;
+        rows.iter().map(sender_key_from_row).collect()
+    }
+}
+
+#[async_trait]
+impl WebhookStore for SqliteStore {
+    #[tracing::instrument(level = "debug", skip(self, record), fields(webhook_id = %record.webhook.webhook_id), err)]
+    async fn create_webhook(&self, record: &WebhookRecord) -> StoreResult<()> {
+        let _timer = metrics::db_timer("create_webhook");
+        let webhook = &record.webhook;
+        let events: Vec<&str> = webhook.events.iter().map(|e| e.as_str()).collect();
+        sqlx::query(&format!("INSERT INTO webhooks ({}) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)", WEBHOOK_COLUMNS))
+            .bind(&webhook.webhook_id)
+            .bind(&webhook.group_id)
+            .bind(&webhook.url)
+            .bind(events.join(","))
+            .bind(&record.secret)
+            .bind(webhook.enabled)
+            .bind(&webhook.created_by)
+            .bind(&webhook.created_at)
+            .bind(i64::from(webhook.consecutive_failures))
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn find_webhook(&self, webhook_id: &str) -> StoreResult<Option<WebhookRecord>> {
+        let _timer = metrics::db_timer("find_webhook");
+        let row = sqlx::query(&format!("SELECT {} FROM webhooks WHERE webhook_id = ?", WEBHOOK_COLUMNS))
+            .bind(webhook_id)
+            .fetch_optional(&self.pool)
+            .await?;
+        row.as_ref().map(webhook_from_row).transpose()
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn webhooks_for_group(&self, group_id: &str) -> StoreResult<Vec<WebhookRecord>> {
+        let _timer = metrics::db_timer("webhooks_for_group");
+        let rows = sqlx::query(&format!(
+            "SELECT {} FROM webhooks WHERE group_id = ? ORDER BY created_at, rowid",
+            WEBHOOK_COLUMNS
+        ))
+        .bind(group_id)
+        .fetch_all(&self.pool)
+        .await?;
+        rows.iter().map(webhook_from_row).collect()
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn set_webhook_enabled(&self, webhook_id: &str, enabled: bool, now: i64) -> StoreResult<bool> {
+        let _timer = metrics::db_timer("set_webhook_enabled");
+        let mut tx = self.pool.begin().await?;
+        let res = if enabled {
+            sqlx::query("UPDATE webhooks SET enabled = 1, consecutive_failures = 0 WHERE webhook_id = ?")
+        } else {
+            sqlx::query("UPDATE webhooks SET enabled = 0 WHERE webhook_id = ?")
+        }
+        .bind(webhook_id)
+        .execute(&mut tx)
+        .await?;
+        if !enabled {
+            fail_pending_deliveries(&mut tx, webhook_id, "webhook disabled", now).await?;
+        }
+        tx.commit().await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn delete_webhook(&self, webhook_id: &str) -> StoreResult<bool> {
+        let _timer = metrics::db_timer("delete_webhook");
+        let mut tx = self.pool.begin().await?;
+        // foreign_keys non è attivo su tutte le connessioni del pool: le consegne sono eliminate esplicitamente
+        sqlx::query("DELETE FROM webhook_deliveries WHERE webhook_id = ?").bind(webhook_id).execute(&mut tx).await?;
+        let res = sqlx::query("DELETE FROM webhooks WHERE webhook_id = ?").bind(webhook_id).execute(&mut tx).await?;
+        tx.commit().await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    #[tracing::instrument(level = "debug", skip(self, deliveries), fields(count = deliveries.len()), err)]
+    async fn enqueue_deliveries(&self, deliveries: &[WebhookDelivery]) -> StoreResult<()> {
+        let _timer = metrics::db_timer("enqueue_deliveries");
+        let mut tx = self.pool.begin().await?;
+        for d in deliveries {
+            sqlx::query(&format!(
+                "INSERT INTO webhook_deliveries ({}) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+                DELIVERY_COLUMNS
+            ))
+            .bind(&d.delivery_id)
+            .bind(&d.webhook_id)
+            .bind(&d.group_id)
+            .bind(d.event.as_str())
+            .bind(&d.message_id)
+            .bind(&d.user_id)
+            .bind(d.status.as_str())
+            .bind(i64::from(d.attempts))
+            .bind(d.next_attempt_at.as_deref().and_then(unix_from_timestamp))
+            .bind(d.last_status_code.map(i64::from))
+            .bind(&d.last_error)
+            .bind(&d.created_at)
+            .bind(d.finished_at.as_deref().and_then(unix_from_timestamp))
+            .execute(&mut tx)
+            .await?;
+        }
+        tx.commit().await
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn claim_deliveries(&self, now: i64, lease_until: i64, limit: i64) -> StoreResult<Vec<WebhookDelivery>> {
+        let _timer = metrics::db_timer("claim_deliveries");
+        // un solo statement: due istanze non possono prendere la stessa consegna
+        let rows = sqlx::query(&format!(
+            "UPDATE webhook_deliveries SET next_attempt_at = ? WHERE delivery_id IN (\
+             SELECT d.delivery_id FROM webhook_deliveries d JOIN webhooks w ON w.webhook_id = d.webhook_id \
+             WHERE d.status = 'pending' AND d.next_attempt_at <= ? AND w.enabled <> 0 \
+             ORDER BY d.next_attempt_at, d.rowid LIMIT ?) RETURNING {}",
+            DELIVERY_COLUMNS
+        ))
+        .bind(lease_until)
+        .bind(now)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+        let mut claimed = rows.iter().map(delivery_from_row).collect::<StoreResult<Vec<_>>>()?;
+        claimed.sort_by(|a, b| (&a.created_at, &a.delivery_id).cmp(&(&b.created_at, &b.delivery_id)));
+        Ok(claimed)
+    }
+
+    #[tracing::instrument(level = "debug", skip(self, attempt), fields(delivery_id = %attempt.delivery_id), err)]
+    async fn record_attempt(&self, attempt: &DeliveryAttempt, disable_after: u32) -> StoreResult<bool> {
+        let _timer = metrics::db_timer("record_attempt");
+        let (status, next_attempt_at, attempts) = match attempt.outcome {
+            AttemptOutcome::Delivered => (DeliveryStatus::Delivered, None, 1),
+            AttemptOutcome::Retry(at) => (DeliveryStatus::Pending, Some(at), 1),
+            AttemptOutcome::Failed => (DeliveryStatus::Failed, None, 1),
+            AttemptOutcome::Dropped => (DeliveryStatus::Failed, None, 0),
+        };
+        let finished_at = next_attempt_at.is_none().then_some(attempt.at);
+        let mut tx = self.pool.begin().await?;
+        // una consegna già fallita perché il webhook è stato disabilitato nel frattempo resta fallita
+        sqlx::query(
+            "UPDATE webhook_deliveries SET status = ?, attempts = attempts + ?, next_attempt_at = ?, \
+             last_status_code = ?, last_error = ?, finished_at = ? WHERE delivery_id = ? AND status = 'pending'",
+        )
+        .bind(status.as_str())
+        .bind(attempts)
+        .bind(next_attempt_at)
+        .bind(attempt.status_code.map(i64::from))
+        .bind(&attempt.error)
+        .bind(finished_at)
+        .bind(&attempt.delivery_id)
+        .execute(&mut tx)
+        .await?;
+        let mut disabled = false;
+        match attempt.outcome {
+            AttemptOutcome::Delivered => {
+                sqlx::query("UPDATE webhooks SET consecutive_failures = 0 WHERE webhook_id = ?")
+                    .bind(&attempt.webhook_id)
+                    .execute(&mut tx)
+                    .await?;
+            }
+            AttemptOutcome::Retry(_) | AttemptOutcome::Failed => {
+                let failures: Option<(i64, bool)> = sqlx::query_as(
+                    "UPDATE webhooks SET consecutive_failures = consecutive_failures + 1 WHERE webhook_id = ? \
+                     RETURNING consecutive_failures, enabled",
+                )
+                .bind(&attempt.webhook_id)
+                .fetch_optional(&mut tx)
+                .await?;
+                if let Some((failures, true)) = failures.filter(|&(f, _)| f >= i64::from(disable_after)) {
+                    sqlx::query("UPDATE webhooks SET enabled = 0 WHERE webhook_id = ?")
+                        .bind(&attempt.webhook_id)
+                        .execute(&mut tx)
+                        .await?;
+                    let error = format!("webhook disabled after {} consecutive failures", failures);
+                    fail_pending_deliveries(&mut tx, &attempt.webhook_id, &error, attempt.at).await?;
+                    disabled = true;
+                }
+            }
+            AttemptOutcome::Dropped => {}
+        }
+        tx.commit().await?;
+        Ok(disabled)
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn deliveries_for_webhook(&self, webhook_id: &str, limit: i64) -> StoreResult<Vec<WebhookDelivery>> {
+        let _timer = metrics::db_timer("deliveries_for_webhook");
+        let rows = sqlx::query(&format!(
+            "SELECT {} FROM webhook_deliveries WHERE webhook_id = ? ORDER BY created_at DESC, rowid DESC LIMIT ?",
+            DELIVERY_COLUMNS
+        ))
+        .bind(webhook_id)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+        rows.iter().map(delivery_from_row).collect()
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn purge_deliveries(&self, finished_before: i64, limit: i64) -> StoreResult<u64> {
+        let _timer = metrics::db_timer("purge_deliveries");
+        let res = sqlx::query(
+            "DELETE FROM webhook_deliveries WHERE delivery_id IN (SELECT delivery_id FROM webhook_deliveries \
+             WHERE finished_at <= ? ORDER BY finished_at LIMIT ?)",
+        )
+        .bind(finished_before)
+        .bind(limit)
+        .execute(&self.pool)
+        .await?;
+        Ok(res.rows_affected())
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn raw_webhook_secrets(&self, after: Option<&str>, limit: i64) -> StoreResult<Vec<(String, String)>> {
+        let _timer = metrics::db_timer("raw_webhook_secrets");
+        sqlx::query_as(
+            "SELECT webhook_id, secret FROM webhooks WHERE (? IS NULL OR webhook_id > ?) ORDER BY webhook_id LIMIT ?",
+        )
+        .bind(after)
+        .bind(after)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    #[tracing::instrument(level = "debug", skip(self, secret), err)]
+    async fn set_raw_webhook_secret(&self, webhook_id: &str, secret: &str) -> StoreResult<()> {
+        let _timer = metrics::db_timer("set_raw_webhook_secret");
+        sqlx::query("UPDATE webhooks SET secret = ? WHERE webhook_id = ?")
+            .bind(secret)
+            .bind(webhook_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl AdminStore for SqliteStore {
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn list_users(&self, now: i64) -> StoreResult<Vec<UserSummary>> {
+        let _timer = metrics::db_timer("list_users");
+        let rows = sqlx::query(
+            "SELECT user_id, username, created_at, disabled, \
+             (token IS NOT NULL AND (token_expires_at IS NULL OR token_expires_at > ?)) AS active_session \
+             FROM users ORDER BY created_at, rowid",
+        )
+        .bind(now)
+        .fetch_all(&self.pool)
+        .await?;
+        rows.iter()
+            .map(|r| {
+                Ok(UserSummary {
+                    user: User {
+                        user_id: r.try_get("user_id")?,
+                        username: r.try_get("username")?,
+                        created_at: r.try_get("created_at")?,
+                    },
+                    disabled: r.try_get("disabled")?,
+                    active_session: r.try_get("active_session")?,
+                })
+            })
+            .collect()
+    }
+
+    #[tracing::instrument(level = "debug", skip(self, password_hash), err)]
+    async fn set_password_hash(&self, user_id: &str, password_hash: &str) -> StoreResult<bool> {
+        let _timer = metrics::db_timer("set_password_hash");
+        let res = sqlx::query("UPDATE users SET password_hash = ? WHERE user_id = ?")
+            .bind(password_hash)
+            .bind(user_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn set_user_disabled(&self, user_id: &str, disabled: bool) -> StoreResult<bool> {
+        let _timer = metrics::db_timer("set_user_disabled");
+        let res = sqlx::query("UPDATE users SET disabled = ? WHERE user_id = ?")
+            .bind(disabled)
+            .bind(user_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn revoke_sessions(&self, user_id: Option<&str>) -> StoreResult<u64> {
+        let _timer = metrics::db_timer("revoke_sessions");
+        let res = sqlx::query(
+            "UPDATE users SET token = NULL, token_expires_at = NULL \
+             WHERE token IS NOT NULL AND (? IS NULL OR user_id = ?)",
+        )
+        .bind(user_id)
+        .bind(user_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(res.rows_affected())
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn list_groups(&self) -> StoreResult<Vec<GroupSummary>> {
+        let _timer = metrics::db_timer("list_groups");
+        let rows = sqlx::query(&format!(
+            "SELECT {}, \
+             (SELECT COUNT(*) FROM memberships WHERE group_id = groups.group_id) AS members, \
+             (SELECT COUNT(*) FROM messages WHERE group_id = groups.group_id) AS messages \
+             FROM groups ORDER BY created_at, rowid",
+            GROUP_COLUMNS
+        ))
+        .fetch_all(&self.pool)
+        .await?;
+        rows.iter()
+            .map(|r| {
+                Ok(GroupSummary {
+                    group: group_from_row(r)?,
+                    members: r.try_get::<i64, _>("members")? as u64,
+                    messages: r.try_get::<i64, _>("messages")? as u64,
+                })
+            })
+            .collect()
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn delete_group(&self, group_id: &str) -> StoreResult<bool> {
+        let _timer = metrics::db_timer("delete_group");
+        let mut tx = self.pool.begin().await?;
+        // foreign_keys non è attivo su tutte le connessioni del pool: i dipendenti sono eliminati esplicitamente
+        for stmt in [
+            "DELETE FROM reactions WHERE message_id IN (SELECT message_id FROM messages WHERE group_id = ?)",
+            "DELETE FROM mentions WHERE message_id IN (SELECT message_id FROM messages WHERE group_id = ?)",
+            "DELETE FROM pinned_messages WHERE group_id = ?",
+            "DELETE FROM messages WHERE group_id = ?",
+            "DELETE FROM scheduled_messages WHERE group_id = ?",
+            "DELETE FROM webhook_deliveries WHERE group_id = ?",
+            "DELETE FROM webhooks WHERE group_id = ?",
+            "DELETE FROM memberships WHERE group_id = ?",
+            "DELETE FROM invites WHERE group_id = ?",
+            "DELETE FROM sender_keys WHERE group_id = ?",
+        ] {
+            sqlx::query(stmt).bind(group_id).execute(&mut tx).await?;
+        }
+        let res = sqlx::query("DELETE FROM groups WHERE group_id = ?").bind(group_id).execute(&mut tx).await?;
+        tx.commit().await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn stats(&self, now: i64) -> StoreResult<StoreStats> {
+        let _timer = metrics::db_timer("stats");
+        let row = sqlx::query(
+            "SELECT \
+             (SELECT COUNT(*) FROM users) AS users, \
+             (SELECT COUNT(*) FROM users WHERE disabled <> 0) AS disabled_users, \
+             (SELECT COUNT(*) FROM users WHERE token IS NOT NULL AND (token_expires_at IS NULL OR token_expires_at > ?)) \
+                AS active_sessions, \
+             (SELECT COUNT(*) FROM groups) AS groups, \
+             (SELECT COUNT(*) FROM messages) AS messages, \
+             (SELECT COUNT(*) FROM invites) AS pending_invites",
+        )
+        .bind(now)
+        .fetch_one(&self.pool)
+        .await?;
+        let count = |column: &str| row.try_get::<i64, _>(column).map(|n| n as u64);
+        Ok(StoreStats {
+            users: count("users")?,
+            disabled_users: count("disabled_users")?,
+            active_sessions: count("active_sessions")?,
+            groups: count("groups")?,
+            messages: count("messages")?,
+            pending_invites: count("pending_invites")?,
+        })
+    }
+}
+
+#[async_trait]
+impl Store for SqliteStore {
+    fn backend(&self) -> &'static str {
+        "sqlite"
+    }
+
+    async fn migrate(&self) -> anyhow::Result<()> {
+        run_migrations(&self.pool).await
+    }
+
+    async fn ping(&self) -> StoreResult<()> {
+        sqlx::query_scalar::<_, i64>("SELECT 1").fetch_one(&self.pool).await.map(|_| ())
+    }
+
+    async fn schema_version(&self) -> StoreResult<i64> {
+        sqlx::query_scalar("PRAGMA user_version").fetch_one(&self.pool).await
+    }
+
+    async fn data_dir(&self) -> StoreResult<Option<PathBuf>> {
+        let file: Option<String> = sqlx::query_scalar("SELECT file FROM pragma_database_list WHERE name = 'main'")
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(file
+            .filter(|f| !f.is_empty())
+            .map(|f| Path::new(&f).parent().map(Path::to_path_buf).unwrap_or_default()))
+    }
+
+    // VACUUM INTO legge il database in un'unica transazione: le scritture concorrenti restano possibili
+    // e la copia è compatta e già coerente (senza file -wal)
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn backup_to(&self, path: &Path) -> anyhow::Result<()> {
+        let _timer = metrics::db_timer("backup_to");
+        let target = path.to_str().with_context(|| format!("backup path {:?} is not valid utf-8", path))?;
+        sqlx::query("VACUUM INTO ?")
+            .bind(target)
+            .execute(&self.pool)
+            .await
+            .with_context(|| format!("write backup {:?}", path))?;
+        Ok(())
+    }
+
+    fn pool_stats(&self) -> PoolStats {
+        PoolStats { size: self.pool.size(), idle: self.pool.num_idle() }
+    }
+
+    async fn close(&self) {
+        self.pool.close().await
+    }
+}
@@ -0,0 +1,113 @@
+/* Log strutturati e tracciamento delle richieste: ogni richiesta HTTP (e ogni sessione WS, che nasce
+   da una richiesta di upgrade) riceve un request id, registrato nello span e restituito al client
+   nell'header X-Request-Id e nei dettagli degli errori JSON. */
+use axum::{
+    body::{to_bytes, Body},
+    extract::Request,
+    http::{header, HeaderName, HeaderValue},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use ruggine_core::{error::REQUEST_ID_HEADER, Error};
+use std::time::Instant;
+use tracing::{field, Instrument};
+use uuid::Uuid;
+
+use crate::config::{Config, LogFormat};
+
+/// Lunghezza massima di un request id ricevuto dal client; oltre viene ignorato e rigenerato.
+pub const MAX_REQUEST_ID_LEN: usize = 128;
+
+// Corpo massimo di una risposta di errore che viene riscritto per aggiungere il request id
+const MAX_ERROR_BODY: usize = 64 * 1024;
+
+/// Identificativo della richiesta corrente, disponibile agli handler come estensione.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestId(pub String);
+
+/// Installa il subscriber globale secondo log_level e log_format della configurazione.
+pub fn init(config: &Config) {
+    let filter = tracing_subscriber::EnvFilter::new(&config.log_level);
+    let builder = tracing_subscriber::fmt().with_env_filter(filter);
+    match config.log_format {
+        LogFormat::Text => builder.init(),
+        LogFormat::Json => builder.json().with_current_span(true).with_span_list(false).init(),
+    }
+}
+
+// Riusa l'id inviato dal client (es. da un proxy) se ragionevole, altrimenti ne genera uno nuovo
+fn request_id_from(req: &Request) -> String {
+    req.headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .filter(|v| !v.is_empty() && v.len() <= MAX_REQUEST_ID_LEN && v.bytes().all(|b| b.is_ascii_graphic()))
+        .map(str::to_string)
+        .unwrap_or_else(|| Uuid::new_v4().to_string())
+}
+
+/// Middleware applicato a tutte le rotte: apre lo span `http_request` (con request_id, metodo, percorso
+/// e, una volta autenticato, user_id), registra esito e durata, ed espone il request id nella risposta.
+pub async fn trace_request(mut req: Request, next: Next) -> Response {
+    let request_id = request_id_from(&req);
+    let span = tracing::info_span!(
+        "http_request",
+        request_id = %request_id,
+        method = %req.method(),
+        path = %req.uri().path(),
+        user_id = field::Empty,
+        status = field::Empty,
+    );
+    req.extensions_mut().insert(RequestId(request_id.clone()));
+
+    let started = Instant::now();
+    let response = next.run(req).instrument(span.clone()).await;
+    let status = response.status();
+    span.record("status", status.as_u16());
+    span.in_scope(|| {
+        let elapsed_ms = started.elapsed().as_millis() as u64;
+        if status.is_server_error() {
+            tracing::error!(elapsed_ms, "request failed");
+        } else {
+            tracing::info!(elapsed_ms, "request completed");
+        }
+    });
+
+    let mut response = if status.is_client_error() || status.is_server_error() {
+        tag_error_body(response, &request_id).await
+    } else {
+        response
+    };
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        response.headers_mut().insert(HeaderName::from_static(REQUEST_ID_HEADER), value);
+    }
+    response
+}
+
+// Se il corpo è un ruggine_core::Error in JSON, aggiunge details.requestId; altrimenti lo lascia invariato
+async fn tag_error_body(response: Response, request_id: &str) -> Response {
+    let is_json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("application/json"));
+    if !is_json {
+        return response;
+    }
+    let (mut parts, body) = response.into_parts();
+    let Ok(bytes) = to_bytes(body, MAX_ERROR_BODY).await else {
+        return parts.into_response();
+    };
+    match serde_json::from_slice::<Error>(&bytes) {
+        Ok(error) => {
+            parts.headers.remove(header::CONTENT_LENGTH);
+            (parts, Json(error.with_request_id(request_id))).into_response()
+        }
+        Err(_) => Response::from_parts(parts, Body::from(bytes)),
+    }
+}
+
+/// Registra l'utente autenticato nello span della richiesta corrente.
+pub fn record_user(user_id: &str) {
+    tracing::Span::current().record("user_id", user_id);
+}
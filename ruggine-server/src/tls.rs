@@ -0,0 +1,203 @@
+/* Terminazione TLS integrata (rustls): certificato e chiave sono letti da file PEM indicati in
+   configurazione e ricaricati a caldo quando cambiano (il file viene riletto ogni
+   tls_reload_interval_secs), senza interrompere le connessioni aperte. Le nuove connessioni usano
+   subito il certificato aggiornato; se i nuovi file non sono validi resta in uso quello precedente.
+   Opzionalmente un secondo listener in chiaro risponde a ogni richiesta con un redirect a https://. */
+use anyhow::{bail, Context};
+use axum::{
+    extract::Request,
+    http::{header, StatusCode, Uri},
+    response::{IntoResponse, Redirect, Response},
+    Router,
+};
+use rustls::server::{ClientHello, ResolvesServerCert};
+use rustls::sign::CertifiedKey;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use crate::shutdown::Shutdown;
+
+/// Tempo massimo per completare l'handshake TLS di una nuova connessione.
+pub const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Certificato corrente letto da file, sostituibile a caldo.
+pub struct ReloadingCert {
+    cert_path: PathBuf,
+    key_path: PathBuf,
+    // contenuto dei file da cui è stato caricato il certificato in uso
+    loaded: RwLock<(Vec<u8>, Vec<u8>)>,
+    current: RwLock<Arc<CertifiedKey>>,
+}
+
+impl std::fmt::Debug for ReloadingCert {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ReloadingCert")
+            .field("cert_path", &self.cert_path)
+            .field("key_path", &self.key_path)
+            .finish()
+    }
+}
+
+impl ReloadingCert {
+    /// Carica certificato (catena PEM) e chiave privata (PKCS#8, RSA o EC in PEM).
+    pub fn load(cert_path: &Path, key_path: &Path) -> anyhow::Result<Self> {
+        let (cert_pem, key_pem) = read_pair(cert_path, key_path)?;
+        let key = certified_key(&cert_pem, &key_pem)
+            .with_context(|| format!("load tls certificate {:?} with key {:?}", cert_path, key_path))?;
+        Ok(Self {
+            cert_path: cert_path.to_path_buf(),
+            key_path: key_path.to_path_buf(),
+            loaded: RwLock::new((cert_pem, key_pem)),
+            current: RwLock::new(Arc::new(key)),
+        })
+    }
+
+    /// Rilegge i file e, se sono cambiati, sostituisce il certificato in uso.
+    /// Ritorna true se il certificato è stato sostituito; in caso di errore resta quello precedente.
+    pub fn reload_if_changed(&self) -> anyhow::Result<bool> {
+        let (cert_pem, key_pem) = read_pair(&self.cert_path, &self.key_path)?;
+        self.apply(cert_pem, key_pem)
+    }
+
+    fn apply(&self, cert_pem: Vec<u8>, key_pem: Vec<u8>) -> anyhow::Result<bool> {
+        {
+            let loaded = self.loaded.read().unwrap();
+            if loaded.0 == cert_pem && loaded.1 == key_pem {
+                return Ok(false);
+            }
+        }
+        let key = certified_key(&cert_pem, &key_pem)?;
+        *self.current.write().unwrap() = Arc::new(key);
+        *self.loaded.write().unwrap() = (cert_pem, key_pem);
+        Ok(true)
+    }
+
+    /// Configurazione rustls per le nuove connessioni, sempre con il certificato corrente.
+    pub fn server_config(self: &Arc<Self>) -> rustls::ServerConfig {
+        let mut config = rustls::ServerConfig::builder()
+            .with_safe_defaults()
+            .with_no_client_auth()
+            .with_cert_resolver(self.clone());
+        config.alpn_protocols = vec![b"http/1.1".to_vec()];
+        config
+    }
+
+    pub fn acceptor(self: &Arc<Self>) -> tokio_rustls::TlsAcceptor {
+        tokio_rustls::TlsAcceptor::from(Arc::new(self.server_config()))
+    }
+
+    /// Controlla periodicamente i file finché non viene avviato l'arresto. Un cambiamento viene
+    /// applicato solo quando i file risultano uguali in due controlli consecutivi, così un
+    /// certificato scritto prima della sua chiave non viene caricato con la chiave vecchia.
+    pub fn spawn_reloader(self: &Arc<Self>, interval: Duration, shutdown: Arc<Shutdown>) -> tokio::task::JoinHandle<()> {
+        let cert = self.clone();
+        tokio::spawn(async move {
+            let mut ticks = tokio::time::interval(interval);
+            ticks.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            ticks.tick().await;
+            // ultimo contenuto letto e se è già stato applicato (evita di ripetere lo stesso errore)
+            let mut previous = None;
+            let mut settled = true;
+            loop {
+                tokio::select! {
+                    _ = ticks.tick() => {}
+                    _ = shutdown.triggered() => return,
+                }
+                let seen = match read_pair(&cert.cert_path, &cert.key_path) {
+                    Ok(pair) => pair,
+                    Err(e) => {
+                        tracing::warn!(error = %format!("{:#}", e), "tls certificate files not readable");
+                        continue;
+                    }
+                };
+                if previous.as_ref() != Some(&seen) {
+                    previous = Some(seen);
+                    settled = false;
+                    continue;
+                }
+                if settled {
+                    continue;
+                }
+                settled = true;
+                let (cert_pem, key_pem) = seen;
+                match cert.apply(cert_pem, key_pem) {
+                    Ok(true) => tracing::info!(cert = ?cert.cert_path, "tls certificate reloaded"),
+                    Ok(false) => {}
+                    Err(e) => tracing::warn!(
+                        cert = ?cert.cert_path,
+                        error = %format!("{:#}", e),
+                        "tls certificate reload failed, keeping the previous one"
+                    ),
+                }
+            }
+        })
+    }
+}
+
+impl ResolvesServerCert for ReloadingCert {
+    fn resolve(&self, _client_hello: ClientHello) -> Option<Arc<CertifiedKey>> {
+        Some(self.current.read().unwrap().clone())
+    }
+}
+
+fn read_pair(cert_path: &Path, key_path: &Path) -> anyhow::Result<(Vec<u8>, Vec<u8>)> {
+    let cert = std::fs::read(cert_path).with_context(|| format!("read tls certificate {:?}", cert_path))?;
+    let key = std::fs::read(key_path).with_context(|| format!("read tls key {:?}", key_path))?;
+    Ok((cert, key))
+}
+
+fn certified_key(cert_pem: &[u8], key_pem: &[u8]) -> anyhow::Result<CertifiedKey> {
+    let certs: Vec<rustls::Certificate> = rustls_pemfile::certs(&mut &*cert_pem)
+        .context("parse certificate pem")?
+        .into_iter()
+        .map(rustls::Certificate)
+        .collect();
+    if certs.is_empty() {
+        bail!("no certificate found in pem");
+    }
+    let mut reader = key_pem;
+    let key = loop {
+        match rustls_pemfile::read_one(&mut reader).context("parse key pem")? {
+            Some(rustls_pemfile::Item::PKCS8Key(k) | rustls_pemfile::Item::RSAKey(k) | rustls_pemfile::Item::ECKey(k)) => {
+                break rustls::PrivateKey(k)
+            }
+            Some(_) => continue,
+            None => bail!("no private key found in pem"),
+        }
+    };
+    let signing_key = rustls::sign::any_supported_type(&key).map_err(|_| anyhow::anyhow!("unsupported private key type"))?;
+    Ok(CertifiedKey::new(certs, signing_key))
+}
+
+/// Serve il listener in chiaro che reindirizza ogni richiesta allo stesso host e percorso su https://
+/// (porta `https_port`), finché non viene avviato l'arresto.
+pub async fn serve_redirect(
+    listener: tokio::net::TcpListener,
+    https_port: u16,
+    shutdown: Arc<Shutdown>,
+) -> anyhow::Result<()> {
+    let app = Router::new().fallback(move |req: Request| async move { redirect_to_https(req, https_port) });
+    axum::serve(listener, app)
+        .with_graceful_shutdown(async move { shutdown.triggered().await })
+        .await
+        .context("redirect listener")
+}
+
+fn redirect_to_https(req: Request, https_port: u16) -> Response {
+    let Some(host) = req.headers().get(header::HOST).and_then(|h| h.to_str().ok()) else {
+        return (StatusCode::BAD_REQUEST, "missing Host header").into_response();
+    };
+    // la porta del Host è quella del listener in chiaro: va sostituita con quella TLS
+    let authority = match host.parse::<axum::http::uri::Authority>() {
+        Ok(a) => a,
+        Err(_) => return (StatusCode::BAD_REQUEST, "invalid Host header").into_response(),
+    };
+    let host = authority.host();
+    let authority = if https_port == 443 { host.to_string() } else { format!("{}:{}", host, https_port) };
+    let path = req.uri().path_and_query().map(|p| p.as_str()).unwrap_or("/");
+    match Uri::builder().scheme("https").authority(authority).path_and_query(path).build() {
+        Ok(uri) => Redirect::permanent(&uri.to_string()).into_response(),
+        Err(_) => (StatusCode::BAD_REQUEST, "invalid redirect target").into_response(),
+    }
+}
@@ -0,0 +1,297 @@
+/* Client web servito dal server stesso, sulla stessa origine delle API (niente CORS): i file della
+   build di ruggine-client-web (`trunk build --release`, cartella dist/) sono letti da web_dist_dir
+   oppure, se il server è compilato con la feature `embed-web-client`, incorporati nel binario.
+   Le rotte /api, /ws, /health e /metrics hanno la precedenza.
+
+   - SPA: i percorsi senza estensione che non corrispondono a un file ricevono index.html, così il
+     routing lato client funziona anche ricaricando la pagina; i file mancanti con estensione sono 404.
+   - Compressione: se accanto a un file c'è la variante precompressa (`file.br`, `file.gz`) ed è
+     accettata dal client (Accept-Encoding) viene servita quella, con Content-Encoding.
+   - Cache: i file con hash nel nome generati da Trunk (es. `app-1a2b3c4d5e6f7a8b.js`) non cambiano
+     mai e sono cacheabili per un anno (immutable); gli altri (index.html in testa) vanno
+     rivalidati a ogni uso (no-cache) tramite ETag e If-None-Match. */
+use axum::{
+    body::Body,
+    extract::Extension,
+    http::{header, HeaderMap, Method, StatusCode, Uri},
+    response::{IntoResponse, Response},
+};
+use sha2::{Digest, Sha256};
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::config::Config;
+use crate::AppState;
+
+/// Documento servito per "/", per le directory e per le rotte del client (fallback SPA).
+pub const INDEX_FILE: &str = "index.html";
+
+/// true se il binario contiene la build del client web (feature `embed-web-client`).
+pub const EMBEDDED: bool = cfg!(feature = "embed-web-client");
+
+/// Cache-Control dei file con hash nel nome.
+pub const IMMUTABLE_CACHE: &str = "public, max-age=31536000, immutable";
+
+/// Cache-Control degli altri file: il browser li conserva ma li rivalida ogni volta.
+pub const REVALIDATE_CACHE: &str = "no-cache";
+
+// Varianti precompresse in ordine di preferenza: (Content-Encoding, estensione del file)
+const ENCODINGS: [(&str, &str); 2] = [("br", "br"), ("gzip", "gz")];
+
+#[cfg(feature = "embed-web-client")]
+static EMBEDDED_DIST: include_dir::Dir<'static> = include_dir::include_dir!("$CARGO_MANIFEST_DIR/../ruggine-client-web/dist");
+
+/// Origine dei file del client web.
+#[derive(Debug, Clone)]
+pub enum WebAssets {
+    /// Directory sul filesystem (web_dist_dir)
+    Dir(PathBuf),
+    /// Build incorporata nel binario
+    #[cfg(feature = "embed-web-client")]
+    Embedded(&'static include_dir::Dir<'static>),
+}
+
+impl WebAssets {
+    /// Client da servire secondo la configurazione: web_dist_dir se indicata (ha la precedenza sulla
+    /// build incorporata, utile in sviluppo), altrimenti quella incorporata se presente.
+    pub fn from_config(config: &Config) -> Option<Self> {
+        if let Some(dir) = &config.web_dist_dir {
+            return Some(WebAssets::Dir(dir.clone()));
+        }
+        #[cfg(feature = "embed-web-client")]
+        return Some(WebAssets::Embedded(&EMBEDDED_DIST));
+        #[cfg(not(feature = "embed-web-client"))]
+        None
+    }
+
+    /// Contenuto del file al percorso relativo (già normalizzato, senza ".."); None se non esiste.
+    pub async fn read(&self, rel: &str) -> std::io::Result<Option<Cow<'static, [u8]>>> {
+        match self {
+            WebAssets::Dir(dir) => {
+                let path = dir.join(rel);
+                match tokio::fs::metadata(&path).await {
+                    Ok(meta) if meta.is_file() => Ok(Some(Cow::Owned(tokio::fs::read(&path).await?))),
+                    Ok(_) => Ok(None),
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+                    Err(e) => Err(e),
+                }
+            }
+            #[cfg(feature = "embed-web-client")]
+            WebAssets::Embedded(dir) => Ok(dir.get_file(rel).map(|f| Cow::Borrowed(f.contents()))),
+        }
+    }
+
+    async fn is_dir(&self, rel: &str) -> bool {
+        match self {
+            WebAssets::Dir(dir) => tokio::fs::metadata(dir.join(rel)).await.is_ok_and(|m| m.is_dir()),
+            #[cfg(feature = "embed-web-client")]
+            WebAssets::Embedded(dir) => rel.is_empty() || dir.get_dir(rel).is_some(),
+        }
+    }
+}
+
+/// Fallback del router: serve un file del client web se configurato, altrimenti 404.
+pub async fn serve_client(
+    Extension(state): Extension<Arc<AppState>>,
+    method: Method,
+    uri: Uri,
+    headers: HeaderMap,
+) -> Response {
+    let Some(assets) = WebAssets::from_config(&state.config) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    if method != Method::GET && method != Method::HEAD {
+        return StatusCode::METHOD_NOT_ALLOWED.into_response();
+    }
+    // le rotte API inesistenti restano 404 anche con il client attivo
+    if uri.path().starts_with("/api/") {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    let Some(mut rel) = normalize(uri.path()) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    if assets.is_dir(&rel).await {
+        rel = if rel.is_empty() { INDEX_FILE.to_string() } else { format!("{}/{}", rel, INDEX_FILE) };
+    }
+    match respond(&assets, &rel, &method, &headers).await {
+        Ok(Some(response)) => response,
+        // rotta del client (nessuna estensione): index.html, il routing lo fa l'applicazione
+        Ok(None) if is_client_route(&rel) && accepts_html(&headers) => {
+            match respond(&assets, INDEX_FILE, &method, &headers).await {
+                Ok(Some(response)) => response,
+                Ok(None) => StatusCode::NOT_FOUND.into_response(),
+                Err(e) => read_failed(INDEX_FILE, e),
+            }
+        }
+        Ok(None) => StatusCode::NOT_FOUND.into_response(),
+        Err(e) => read_failed(&rel, e),
+    }
+}
+
+fn read_failed(rel: &str, e: std::io::Error) -> Response {
+    tracing::error!(path = %rel, error = %e, "read web client file");
+    StatusCode::INTERNAL_SERVER_ERROR.into_response()
+}
+
+// Risposta per il file `rel` (variante precompressa se possibile); None se il file non esiste
+async fn respond(assets: &WebAssets, rel: &str, method: &Method, headers: &HeaderMap) -> std::io::Result<Option<Response>> {
+    let mut found = None;
+    for (encoding, ext) in ENCODINGS {
+        if accepts_encoding(headers, encoding)
+            && let Some(bytes) = assets.read(&format!("{}.{}", rel, ext)).await?
+        {
+            found = Some((bytes, Some(encoding)));
+            break;
+        }
+    }
+    let (bytes, encoding) = match found {
+        Some(found) => found,
+        None => match assets.read(rel).await? {
+            Some(bytes) => (bytes, None),
+            None => return Ok(None),
+        },
+    };
+
+    let mut response = Response::builder()
+        .header(header::CONTENT_TYPE, content_type(Path::new(rel)))
+        .header(header::VARY, "accept-encoding");
+    if let Some(encoding) = encoding {
+        response = response.header(header::CONTENT_ENCODING, encoding);
+    }
+    if is_hashed_name(rel) {
+        response = response.header(header::CACHE_CONTROL, IMMUTABLE_CACHE);
+    } else {
+        let tag = etag(&bytes, encoding);
+        response = response.header(header::CACHE_CONTROL, REVALIDATE_CACHE).header(header::ETAG, &tag);
+        if if_none_match(headers, &tag) {
+            let response = response.status(StatusCode::NOT_MODIFIED).body(Body::empty());
+            return Ok(Some(response.unwrap_or_else(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response())));
+        }
+    }
+    let body = if *method == Method::HEAD {
+        response = response.header(header::CONTENT_LENGTH, bytes.len());
+        Body::empty()
+    } else {
+        match bytes {
+            Cow::Borrowed(b) => Body::from(b),
+            Cow::Owned(b) => Body::from(b),
+        }
+    };
+    Ok(Some(response.body(body).unwrap_or_else(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response())))
+}
+
+/// Percorso relativo normalizzato ("" per la radice); None se il percorso esce dalla directory
+/// (segmenti "..", separatori di Windows, byte nulli) o la codifica non è valida.
+pub fn normalize(request_path: &str) -> Option<String> {
+    let decoded = percent_decode(request_path)?;
+    let mut segments = Vec::new();
+    for segment in decoded.split('/').filter(|s| !s.is_empty() && *s != ".") {
+        if segment == ".." || segment.contains('\\') || segment.contains('\0') {
+            return None;
+        }
+        segments.push(segment);
+    }
+    Some(segments.join("/"))
+}
+
+// Decodifica %XX nel percorso; None se la codifica non è valida o il risultato non è UTF-8
+fn percent_decode(s: &str) -> Option<String> {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = std::str::from_utf8(bytes.get(i + 1..i + 3)?).ok()?;
+            out.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(out).ok()
+}
+
+// Ultimo segmento senza estensione: una rotta dell'applicazione, non un file
+fn is_client_route(rel: &str) -> bool {
+    !rel.rsplit('/').next().unwrap_or("").contains('.')
+}
+
+// I browser chiedono text/html navigando; fetch e script di solito no
+fn accepts_html(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::ACCEPT)
+        .and_then(|v| v.to_str().ok())
+        .is_none_or(|accept| accept.contains("text/html") || accept.contains("*/*"))
+}
+
+// Accept-Encoding contiene la codifica con q diverso da 0
+fn accepts_encoding(headers: &HeaderMap, encoding: &str) -> bool {
+    headers
+        .get_all(header::ACCEPT_ENCODING)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .any(|item| {
+            let mut parts = item.split(';').map(str::trim);
+            let name = parts.next().unwrap_or("");
+            let q = parts
+                .find_map(|p| p.strip_prefix("q="))
+                .and_then(|q| q.parse::<f32>().ok())
+                .unwrap_or(1.0);
+            name.eq_ignore_ascii_case(encoding) && q > 0.0
+        })
+}
+
+fn if_none_match(headers: &HeaderMap, tag: &str) -> bool {
+    headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.split(',').map(str::trim).any(|t| t == tag || t == "*"))
+}
+
+// ETag forte dal contenuto, distinto per ogni variante di codifica
+fn etag(bytes: &[u8], encoding: Option<&str>) -> String {
+    let digest = Sha256::digest(bytes);
+    let hex: String = digest[..12].iter().map(|b| format!("{:02x}", b)).collect();
+    match encoding {
+        Some(encoding) => format!("\"{}-{}\"", hex, encoding),
+        None => format!("\"{}\"", hex),
+    }
+}
+
+/// true per i nomi con hash di contenuto generati da Trunk: l'ultimo segmento del nome (prima
+/// dell'estensione e dell'eventuale suffisso "_bg") dopo un '-' è di almeno 8 cifre esadecimali.
+pub fn is_hashed_name(rel: &str) -> bool {
+    let name = rel.rsplit('/').next().unwrap_or("");
+    let stem = name.split('.').next().unwrap_or("");
+    let stem = stem.strip_suffix("_bg").unwrap_or(stem);
+    stem.rsplit_once('-')
+        .is_some_and(|(_, hash)| hash.len() >= 8 && hash.bytes().all(|b| b.is_ascii_hexdigit()))
+}
+
+/// Content-Type in base all'estensione del file (quelle prodotte da Trunk e le più comuni).
+pub fn content_type(path: &Path) -> &'static str {
+    let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("").to_ascii_lowercase();
+    match ext.as_str() {
+        "html" | "htm" => "text/html; charset=utf-8",
+        "js" | "mjs" => "text/javascript; charset=utf-8",
+        "css" => "text/css; charset=utf-8",
+        "wasm" => "application/wasm",
+        "json" | "map" => "application/json",
+        "webmanifest" => "application/manifest+json",
+        "svg" => "image/svg+xml",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "ico" => "image/x-icon",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        "ttf" => "font/ttf",
+        "txt" => "text/plain; charset=utf-8",
+        _ => "application/octet-stream",
+    }
+}
+
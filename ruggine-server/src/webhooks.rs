@@ -0,0 +1,423 @@
+/* Webhook in uscita dei gruppi: gli admin di un gruppo registrano URL che ricevono una POST JSON
+   (WebhookPayload) per ogni evento a cui sono iscritti, firmata con HMAC-SHA256 del corpo e il segreto
+   del webhook (header X-Ruggine-Signature: sha256=<hex>). Alla pubblicazione di un messaggio e quando
+   un utente entra o esce dal gruppo viene messa in coda nel database una consegna per ogni webhook
+   abilitato e iscritto all'evento; un task in background
+   prende ogni interval_secs le consegne arrivate al loro prossimo tentativo e le invia. Una risposta 2xx
+   conclude la consegna, altrimenti viene ritentata con attesa esponenziale fino a max_attempts. Dopo
+   disable_after_failures tentativi falliti di fila il webhook viene disabilitato (un admin può
+   riabilitarlo). Essendo nel database la coda sopravvive ai riavvii; il registro delle consegne concluse
+   è conservato per log_retention_secs.
+   Per non far arrivare le richieste del server a servizi interni, sia alla registrazione sia a ogni
+   tentativo l'host dell'URL viene risolto e rifiutato se anche uno solo degli indirizzi è loopback,
+   privato, link-local o comunque non pubblico (salvo allow_private_targets); la connessione usa poi gli
+   stessi indirizzi controllati, senza proxy. */
+use futures_util::future::join_all;
+use ring::hmac;
+use ring::rand::{SecureRandom, SystemRandom};
+use ruggine_core::{
+    timestamp_from_unix, utils::now_timestamp, DeliveryStatus, Message, WebhookDelivery, WebhookEvent, WebhookPayload,
+};
+use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use crate::store::{AttemptOutcome, DeliveryAttempt, StoreResult};
+use crate::{mentions, AppState};
+
+/// Lunghezza massima dell'URL di un webhook
+pub const MAX_URL_LEN: usize = 2048;
+
+/// Sezione [webhooks] della configurazione.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WebhooksConfig {
+    /// Tempo massimo per ogni POST (connessione e risposta)
+    pub timeout_secs: u64,
+    /// Tentativi per consegna prima di abbandonarla
+    pub max_attempts: u32,
+    /// Attesa prima del secondo tentativo; raddoppia a ogni tentativo successivo
+    pub retry_base_secs: u64,
+    /// Attesa massima tra due tentativi
+    pub retry_max_secs: u64,
+    /// Tentativi falliti consecutivi dopo cui il webhook viene disabilitato
+    pub disable_after_failures: u32,
+    /// Webhook registrabili per gruppo
+    pub max_per_group: u32,
+    /// Intervallo tra due controlli della coda delle consegne
+    pub interval_secs: u64,
+    /// Consegne inviate in parallelo per ogni controllo
+    pub batch_size: u32,
+    /// Per quanto restano nel registro le consegne concluse
+    pub log_retention_secs: u64,
+    /// Ammette URL che puntano a indirizzi loopback, privati o link-local (solo per reti fidate e test)
+    pub allow_private_targets: bool,
+}
+
+impl Default for WebhooksConfig {
+    fn default() -> Self {
+        Self {
+            timeout_secs: 10,
+            max_attempts: 8,
+            retry_base_secs: 10,
+            retry_max_secs: 60 * 60,
+            disable_after_failures: 20,
+            max_per_group: 10,
+            interval_secs: 1,
+            batch_size: 50,
+            log_retention_secs: 7 * 24 * 60 * 60,
+            allow_private_targets: false,
+        }
+    }
+}
+
+impl WebhooksConfig {
+    /// Attesa prima del tentativo successivo a `attempts` tentativi falliti.
+    pub fn retry_delay(&self, attempts: u32) -> u64 {
+        let factor = 1u64.checked_shl(attempts.saturating_sub(1)).unwrap_or(u64::MAX);
+        self.retry_base_secs.saturating_mul(factor).min(self.retry_max_secs)
+    }
+
+    pub fn problems(&self) -> Vec<String> {
+        let mut out = Vec::new();
+        if self.timeout_secs == 0 {
+            out.push("webhooks.timeout_secs must be positive".to_string());
+        }
+        if self.max_attempts == 0 {
+            out.push("webhooks.max_attempts must be at least 1".to_string());
+        }
+        if self.retry_base_secs == 0 {
+            out.push("webhooks.retry_base_secs must be positive".to_string());
+        }
+        if self.retry_max_secs < self.retry_base_secs {
+            out.push("webhooks.retry_max_secs must be at least webhooks.retry_base_secs".to_string());
+        }
+        if self.disable_after_failures == 0 {
+            out.push("webhooks.disable_after_failures must be at least 1".to_string());
+        }
+        if self.interval_secs == 0 {
+            out.push("webhooks.interval_secs must be positive".to_string());
+        }
+        if self.batch_size == 0 {
+            out.push("webhooks.batch_size must be at least 1".to_string());
+        }
+        out
+    }
+}
+
+/// Controlla l'URL chiesto per un webhook: assoluto, http o https, senza credenziali, con un host che
+/// risolve solo a indirizzi ammessi (vedi resolve_target).
+pub async fn validate_url(url: &str, config: &WebhooksConfig) -> Result<(), String> {
+    if url.len() > MAX_URL_LEN {
+        return Err(format!("url must be at most {} characters", MAX_URL_LEN));
+    }
+    let parsed = reqwest::Url::parse(url).map_err(|e| format!("invalid url: {}", e))?;
+    if !matches!(parsed.scheme(), "http" | "https") || parsed.host_str().is_none() {
+        return Err("url must be an http:// or https:// address".to_string());
+    }
+    if !parsed.username().is_empty() || parsed.password().is_some() {
+        return Err("url must not contain credentials".to_string());
+    }
+    resolve_target(&parsed, config).await.map(|_| ())
+}
+
+/// Indirizzo non pubblico: loopback, reti private, link-local (compreso il metadata service dei cloud),
+/// CGNAT, multicast, riservati e non specificati; per IPv6 anche unique local, link-local e le forme
+/// che incapsulano un indirizzo IPv4 (mapped, compatibili, NAT64, 6to4).
+pub fn is_private_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => is_private_v4(v4),
+        IpAddr::V6(v6) => is_private_v6(v6),
+    }
+}
+
+fn is_private_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, c, _] = ip.octets();
+    ip.is_private()
+        || ip.is_loopback()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        || ip.is_multicast()
+        || ip.is_documentation()
+        || a == 0
+        || a >= 240
+        || (a == 100 && (b & 0xc0) == 64)
+        || (a == 192 && b == 0 && c == 0)
+        || (a == 198 && (b & 0xfe) == 18)
+}
+
+fn is_private_v6(ip: Ipv6Addr) -> bool {
+    let s = ip.segments();
+    let embedded = |hi: u16, lo: u16| is_private_v4(Ipv4Addr::from((u32::from(hi) << 16) | u32::from(lo)));
+    if let Some(v4) = ip.to_ipv4_mapped() {
+        return is_private_v4(v4);
+    }
+    ip.is_loopback()
+        || ip.is_unspecified()
+        || ip.is_multicast()
+        || (s[0] & 0xfe00) == 0xfc00
+        || (s[0] & 0xffc0) == 0xfe80
+        || (s[0] & 0xffc0) == 0xfec0
+        || (s[0] == 0x2001 && s[1] == 0x0db8)
+        || (s[..6] == [0; 6] && embedded(s[6], s[7]))
+        || (s[0] == 0x0064 && s[1] == 0xff9b && embedded(s[6], s[7]))
+        || (s[0] == 0x2002 && embedded(s[1], s[2]))
+}
+
+/// Risolve l'host dell'URL; ritorna gli indirizzi a cui collegarsi, o un errore se l'host non risolve
+/// o (senza allow_private_targets) se anche uno degli indirizzi non è pubblico.
+pub async fn resolve_target(url: &reqwest::Url, config: &WebhooksConfig) -> Result<Vec<SocketAddr>, String> {
+    let host = url.host_str().ok_or_else(|| "url has no host".to_string())?;
+    let port = url.port_or_known_default().ok_or_else(|| "url has no port".to_string())?;
+    let addrs: Vec<SocketAddr> = match host.trim_start_matches('[').trim_end_matches(']').parse::<IpAddr>() {
+        Ok(ip) => vec![SocketAddr::new(ip, port)],
+        Err(_) => {
+            let lookup = tokio::net::lookup_host((host, port));
+            match tokio::time::timeout(Duration::from_secs(config.timeout_secs), lookup).await {
+                Ok(Ok(addrs)) => addrs.collect(),
+                Ok(Err(e)) => return Err(format!("cannot resolve {}: {}", host, e)),
+                Err(_) => return Err(format!("cannot resolve {}: timed out", host)),
+            }
+        }
+    };
+    if addrs.is_empty() {
+        return Err(format!("cannot resolve {}: no addresses", host));
+    }
+    if let Some(addr) = addrs.iter().find(|a| !config.allow_private_targets && is_private_ip(a.ip())) {
+        return Err(format!("url must not point to a private or local address ({})", addr.ip()));
+    }
+    Ok(addrs)
+}
+
+/// Nuovo segreto di firma: 32 byte casuali in esadecimale.
+pub fn new_secret() -> anyhow::Result<String> {
+    let mut bytes = [0u8; 32];
+    SystemRandom::new().fill(&mut bytes).map_err(|_| anyhow::anyhow!("random generator failure"))?;
+    Ok(bytes.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// Valore dell'header X-Ruggine-Signature per il corpo `body`: "sha256=" seguito dall'HMAC-SHA256 in esadecimale.
+pub fn signature(secret: &str, body: &[u8]) -> String {
+    let key = hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes());
+    let tag = hmac::sign(&key, body);
+    let hex: String = tag.as_ref().iter().map(|b| format!("{:02x}", b)).collect();
+    format!("sha256={}", hex)
+}
+
+/// Mette in coda l'evento message.created per i webhook abilitati del gruppo del messaggio.
+pub async fn enqueue_message(state: &AppState, message: &Message) -> StoreResult<()> {
+    enqueue(state, &message.group_id, WebhookEvent::MessageCreated, Some(&message.message_id), None).await
+}
+
+/// Mette in coda l'evento member.joined o member.left dell'utente per i webhook abilitati del gruppo.
+pub async fn enqueue_member(state: &AppState, group_id: &str, event: WebhookEvent, user_id: &str) -> StoreResult<()> {
+    enqueue(state, group_id, event, None, Some(user_id)).await
+}
+
+async fn enqueue(
+    state: &AppState,
+    group_id: &str,
+    event: WebhookEvent,
+    message_id: Option<&str>,
+    user_id: Option<&str>,
+) -> StoreResult<()> {
+    let webhooks = state.store.webhooks_for_group(group_id).await?;
+    let now = OffsetDateTime::now_utc().unix_timestamp();
+    let created_at = now_timestamp();
+    let deliveries: Vec<WebhookDelivery> = webhooks
+        .iter()
+        .filter(|r| r.webhook.enabled && r.webhook.events.contains(&event))
+        .map(|r| WebhookDelivery {
+            delivery_id: Uuid::new_v4().to_string(),
+            webhook_id: r.webhook.webhook_id.clone(),
+            group_id: group_id.to_string(),
+            event,
+            message_id: message_id.map(str::to_string),
+            user_id: user_id.map(str::to_string),
+            status: DeliveryStatus::Pending,
+            attempts: 0,
+            next_attempt_at: Some(timestamp_from_unix(now)),
+            last_status_code: None,
+            last_error: None,
+            created_at: created_at.clone(),
+            finished_at: None,
+        })
+        .collect();
+    if deliveries.is_empty() {
+        return Ok(());
+    }
+    state.store.enqueue_deliveries(&deliveries).await
+}
+
+// Client per un tentativo, con l'host dell'URL fissato agli indirizzi appena controllati: un DNS che
+// cambia risposta tra il controllo e la connessione non porta la richiesta altrove. Niente redirect (la
+// risposta che conta è quella dell'URL registrato) e niente proxy (collegherebbe a un altro indirizzo).
+async fn client_for(url: &str, config: &WebhooksConfig) -> Result<reqwest::Client, String> {
+    let parsed = reqwest::Url::parse(url).map_err(|e| format!("invalid url: {}", e))?;
+    let addrs = resolve_target(&parsed, config).await?;
+    let mut builder = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .no_proxy()
+        .user_agent(concat!("ruggine-webhooks/", env!("CARGO_PKG_VERSION")));
+    if let Some(host) = parsed.domain() {
+        builder = builder.resolve_to_addrs(host, &addrs);
+    }
+    builder.build().map_err(|e| format!("build http client: {}", e))
+}
+
+/// Invia tutte le consegne arrivate al loro prossimo tentativo; ritorna il numero di tentativi eseguiti.
+pub async fn run_once(state: &AppState) -> StoreResult<u64> {
+    let config = &state.config.webhooks;
+    let batch = i64::from(config.batch_size.max(1));
+    let mut total = 0;
+    loop {
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        // se l'istanza si ferma a metà tentativo la consegna riparte dopo il lease
+        let lease_until = now + 2 * config.timeout_secs as i64;
+        let claimed = state.store.claim_deliveries(now, lease_until, batch).await?;
+        let results = join_all(claimed.iter().map(|d| deliver(state, d))).await;
+        for result in results {
+            total += u64::from(result?);
+        }
+        if claimed.len() < batch as usize {
+            break;
+        }
+    }
+    let now = OffsetDateTime::now_utc().unix_timestamp();
+    let purged = state.store.purge_deliveries(now - config.log_retention_secs as i64, batch).await?;
+    if purged > 0 {
+        tracing::debug!(purged, "webhook deliveries log pruned");
+    }
+    Ok(total)
+}
+
+// Esegue un tentativo della consegna e ne registra l'esito; ritorna false se non c'era nulla da inviare
+async fn deliver(state: &AppState, delivery: &WebhookDelivery) -> StoreResult<bool> {
+    let config = &state.config.webhooks;
+    // eliminato o disabilitato dopo che la consegna è stata presa
+    let Some(record) = state.store.find_webhook(&delivery.webhook_id).await? else { return Ok(false) };
+    if !record.webhook.enabled {
+        return Ok(false);
+    }
+    let mut attempt = DeliveryAttempt {
+        delivery_id: delivery.delivery_id.clone(),
+        webhook_id: delivery.webhook_id.clone(),
+        outcome: AttemptOutcome::Dropped,
+        status_code: None,
+        error: None,
+        at: 0,
+    };
+    let mut payload = WebhookPayload {
+        delivery_id: delivery.delivery_id.clone(),
+        webhook_id: delivery.webhook_id.clone(),
+        event: delivery.event,
+        group_id: delivery.group_id.clone(),
+        created_at: delivery.created_at.clone(),
+        message: None,
+        member: None,
+    };
+    let missing = match delivery.event {
+        WebhookEvent::MessageCreated => {
+            let message = match &delivery.message_id {
+                Some(id) => state.store.find_message(id).await?,
+                None => None,
+            };
+            if let Some(message) = message {
+                let mut messages = [message];
+                mentions::attach(state.store.as_ref(), &mut messages).await?;
+                let [message] = messages;
+                payload.message = Some(message);
+            }
+            payload.message.is_none().then_some("message no longer available")
+        }
+        WebhookEvent::MemberJoined | WebhookEvent::MemberLeft => {
+            payload.member = match &delivery.user_id {
+                Some(id) => state.store.find_user(id).await?,
+                None => None,
+            };
+            payload.member.is_none().then_some("user no longer available")
+        }
+    };
+    if let Some(error) = missing {
+        attempt.error = Some(error.to_string());
+        attempt.at = OffsetDateTime::now_utc().unix_timestamp();
+        state.store.record_attempt(&attempt, config.disable_after_failures).await?;
+        return Ok(false);
+    }
+    let body = serde_json::to_vec(&payload).map_err(|e| sqlx::Error::Protocol(format!("encode webhook payload: {}", e)))?;
+
+    let response = match client_for(&record.webhook.url, config).await {
+        Ok(client) => client
+            .post(&record.webhook.url)
+            .timeout(Duration::from_secs(config.timeout_secs))
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header("X-Ruggine-Event", delivery.event.as_str())
+            .header("X-Ruggine-Delivery", &delivery.delivery_id)
+            .header("X-Ruggine-Signature", signature(&record.secret, &body))
+            .body(body)
+            .send()
+            .await
+            .map_err(|e| e.without_url().to_string()),
+        Err(e) => Err(e),
+    };
+    attempt.at = OffsetDateTime::now_utc().unix_timestamp();
+    let attempts = delivery.attempts + 1;
+    match response {
+        Ok(res) if res.status().is_success() => {
+            attempt.outcome = AttemptOutcome::Delivered;
+            attempt.status_code = Some(res.status().as_u16());
+        }
+        failed => {
+            match failed {
+                Ok(res) => {
+                    attempt.status_code = Some(res.status().as_u16());
+                    attempt.error = Some(format!("HTTP {}", res.status()));
+                }
+                Err(e) => attempt.error = Some(e),
+            }
+            attempt.outcome = if attempts < config.max_attempts {
+                AttemptOutcome::Retry(attempt.at + config.retry_delay(attempts) as i64)
+            } else {
+                AttemptOutcome::Failed
+            };
+        }
+    }
+    tracing::debug!(
+        delivery_id = %delivery.delivery_id,
+        webhook_id = %delivery.webhook_id,
+        attempts,
+        status_code = ?attempt.status_code,
+        error = ?attempt.error,
+        "webhook delivery attempted"
+    );
+    if state.store.record_attempt(&attempt, config.disable_after_failures).await? {
+        tracing::warn!(
+            webhook_id = %delivery.webhook_id,
+            group_id = %delivery.group_id,
+            failures = config.disable_after_failures,
+            "webhook disabled after repeated delivery failures"
+        );
+    }
+    Ok(true)
+}
+
+/// Avvia l'invio periodico delle consegne dei webhook, che termina all'arresto del server.
+pub fn spawn(state: Arc<AppState>) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticks = tokio::time::interval(Duration::from_secs(state.config.webhooks.interval_secs));
+        ticks.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            tokio::select! {
+                _ = ticks.tick() => {}
+                _ = state.shutdown.triggered() => return,
+            }
+            if let Err(e) = run_once(&state).await {
+                tracing::warn!(error = %e, "webhook deliveries failed");
+            }
+        }
+    })
+}
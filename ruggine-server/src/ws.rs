@@ -0,0 +1,515 @@
+/* Endpoint WebSocket: autenticazione via token in query string, poi ogni frame di testo
+   è un WsMessage (envelope { type, payload }). I comandi del client ricevono sempre un Ack
+   sulla stessa connessione; gli eventi (nuovi messaggi, reazioni, menzioni, pin) passano dall'Hub.
+   Con il parametro since (ultimo seq visto per gruppo) la connessione riceve prima i messaggi persi,
+   poi caughtUp, poi gli eventi live. */
+use axum::{
+    extract::{
+        ws::{close_code, CloseFrame, Message as WsFrame, WebSocket, WebSocketUpgrade},
+        Extension, Query,
+    },
+    http::StatusCode,
+    response::Response,
+};
+use futures_util::{SinkExt, StreamExt};
+use ruggine_core::{
+    e2ee::EncryptedContent, error::codes, parse_mentions, parse_since, timestamp_from_unix, utils::now_timestamp, Ack, AckStatus,
+    CaughtUp, Error, Mention, Message, Pin, PinChanged, PinCommand, ReactionChanged, ReactionCommand, ResyncRequired,
+    ScheduledMessage, SendMessage, ServerGoingAway, WsMessage,
+};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{field, Instrument};
+use uuid::Uuid;
+
+use crate::telemetry::{self, RequestId};
+use crate::{db, ephemeral, mentions, pins, ratelimit, reactions, shutdown, webhooks, AppState};
+
+#[derive(Debug, Deserialize)]
+pub struct WsParams {
+    pub token: String,
+    /// Ultimo seq visto per gruppo, "group_id:seq,group_id:seq" (vedi ruggine_core::encode_since)
+    pub since: Option<String>,
+}
+
+/// Handler per GET /ws?token=...[&since=...]
+pub async fn ws_handler(
+    ws: WebSocketUpgrade,
+    Query(params): Query<WsParams>,
+    Extension(state): Extension<Arc<AppState>>,
+    Extension(RequestId(request_id)): Extension<RequestId>,
+) -> Result<Response, (StatusCode, String)> {
+    if state.shutdown.is_triggered() {
+        return Err((StatusCode::SERVICE_UNAVAILABLE, "server is shutting down".to_string()));
+    }
+    let since = params
+        .since
+        .as_deref()
+        .map(parse_since)
+        .transpose()
+        .map_err(|e| (StatusCode::BAD_REQUEST, format!("invalid since: {}", e)))?;
+    let user_id = db::user_id_for_token(state.store.as_ref(), &params.token)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("db error: {}", e)))?
+        .ok_or((StatusCode::UNAUTHORIZED, "invalid token".to_string()))?;
+    telemetry::record_user(&user_id);
+    // la sessione sopravvive alla richiesta di upgrade: ha un proprio span con lo stesso request id
+    let span = tracing::info_span!("ws_session", request_id = %request_id, user_id = %user_id, conn_id = field::Empty);
+    Ok(ws.on_upgrade(move |socket| session(socket, state, user_id, request_id, since).instrument(span)))
+}
+
+/// Ciclo di vita di una connessione: un task scrive sul socket ciò che arriva dall'Hub,
+/// mentre il ciclo principale legge ed esegue i comandi del client.
+/// All'arresto del server il comando in corso viene completato, poi il client riceve
+/// serverGoingAway e un close frame 1001 (going away).
+/// Gli errori inviati al client riportano il request id della sessione in `details.requestId`.
+/// Il recupero dei messaggi persi avviene dopo la registrazione nell'Hub: gli eventi live arrivati
+/// nel frattempo restano in coda e quelli già recuperati vengono scartati.
+async fn session(
+    socket: WebSocket,
+    state: Arc<AppState>,
+    user_id: String,
+    request_id: String,
+    since: Option<BTreeMap<String, u64>>,
+) {
+    let _guard = state.shutdown.track_session();
+    let (conn_id, tx, mut rx) = state.hub.register(&user_id);
+    tracing::Span::current().record("conn_id", conn_id);
+    tracing::info!("websocket session opened");
+    let (mut sink, mut stream) = socket.split();
+
+    let mut replayed = BTreeMap::new();
+    if let Some(since) = since {
+        let events = match catch_up(&state, &user_id, &since).await {
+            Ok((events, marks)) => {
+                replayed = marks;
+                events
+            }
+            Err(e) => {
+                tracing::error!(error = %e, "catch-up failed");
+                vec![tag_request_id(WsMessage::Error(internal(e)), &request_id)]
+            }
+        };
+        for event in events {
+            let Ok(text) = serde_json::to_string(&event) else { continue };
+            if sink.send(WsFrame::Text(text)).await.is_err() {
+                break;
+            }
+        }
+    }
+
+    let writer = tokio::spawn(async move {
+        while let Some(msg) = rx.recv().await {
+            if already_replayed(&msg, &replayed) {
+                continue;
+            }
+            let Ok(text) = serde_json::to_string(&msg) else { continue };
+            if sink.send(WsFrame::Text(text)).await.is_err() {
+                break;
+            }
+        }
+        sink
+    });
+
+    let mut going_away = false;
+    loop {
+        let frame = tokio::select! {
+            frame = stream.next() => frame,
+            _ = state.shutdown.triggered() => {
+                going_away = true;
+                break;
+            }
+        };
+        let Some(Ok(frame)) = frame else { break };
+        match frame {
+            WsFrame::Text(text) => {
+                let reply = match serde_json::from_str::<WsMessage>(&text) {
+                    Ok(cmd) => match state.limits.ws.check(&user_id) {
+                        Ok(()) => handle_command(&state, &user_id, cmd).await,
+                        Err(retry_after) => rate_limited(cmd, retry_after),
+                    },
+                    Err(e) => WsMessage::Error(Error::new(codes::BAD_REQUEST, format!("invalid message: {}", e))),
+                };
+                let _ = tx.send(tag_request_id(reply, &request_id));
+            }
+            WsFrame::Close(_) => break,
+            _ => {}
+        }
+    }
+
+    if going_away {
+        let _ = tx.send(WsMessage::ServerGoingAway(ServerGoingAway {
+            reason: "server shutting down".to_string(),
+            reconnect_after_ms: shutdown::RECONNECT_AFTER_MS,
+        }));
+    }
+    // chiudendo tutti i sender il writer svuota la coda e termina
+    state.hub.unregister(&user_id, conn_id);
+    drop(tx);
+    let sink = writer.await;
+    if let (true, Ok(mut sink)) = (going_away, sink) {
+        let close = CloseFrame { code: close_code::AWAY, reason: "server shutting down".into() };
+        let _ = sink.send(WsFrame::Close(Some(close))).await;
+    }
+    tracing::info!(going_away, "websocket session closed");
+}
+
+/// Messaggi persi nei gruppi di `since` (di cui l'utente è membro), seguiti da caughtUp. Un gruppo
+/// con più di max_replay_messages messaggi persi, o con un seq più alto dell'ultimo assegnato, riceve
+/// resyncRequired al posto dei messaggi. Ritorna anche l'ultimo seq recuperato per gruppo.
+#[tracing::instrument(skip_all, fields(groups = since.len()))]
+async fn catch_up(
+    state: &AppState,
+    user_id: &str,
+    since: &BTreeMap<String, u64>,
+) -> Result<(Vec<WsMessage>, BTreeMap<String, u64>), sqlx::Error> {
+    let limit = state.config.max_replay_messages as usize;
+    let mut events = Vec::new();
+    let mut replayed = BTreeMap::new();
+    let mut latest = BTreeMap::new();
+    for (group_id, &last_seen_seq) in since {
+        if !state.store.is_member(group_id, user_id).await? {
+            continue;
+        }
+        let latest_seq = state.store.latest_seq(group_id).await?;
+        let mut missed = state.store.messages_after(group_id, last_seen_seq, limit as i64 + 1).await?;
+        if missed.len() > limit || last_seen_seq > latest_seq {
+            events.push(WsMessage::ResyncRequired(ResyncRequired {
+                group_id: group_id.clone(),
+                last_seen_seq,
+                latest_seq,
+            }));
+            latest.insert(group_id.clone(), latest_seq);
+            continue;
+        }
+        db::hydrate(state.store.as_ref(), &mut missed, user_id).await?;
+        let caught_up = missed.last().map_or(last_seen_seq, |m| m.seq);
+        events.extend(missed.into_iter().map(WsMessage::Message));
+        replayed.insert(group_id.clone(), caught_up);
+        latest.insert(group_id.clone(), caught_up);
+    }
+    tracing::debug!(events = events.len(), "catch-up ready");
+    events.push(WsMessage::CaughtUp(CaughtUp { latest_seq: latest }));
+    Ok((events, replayed))
+}
+
+// Evento live per un messaggio già inviato durante il recupero
+fn already_replayed(event: &WsMessage, replayed: &BTreeMap<String, u64>) -> bool {
+    match event {
+        WsMessage::Message(m) => replayed.get(&m.group_id).is_some_and(|&seq| m.seq <= seq),
+        _ => false,
+    }
+}
+
+// Aggiunge il request id della sessione agli errori destinati al client
+fn tag_request_id(reply: WsMessage, request_id: &str) -> WsMessage {
+    match reply {
+        WsMessage::Error(error) => WsMessage::Error(error.with_request_id(request_id)),
+        WsMessage::Ack(ack @ Ack { error: Some(_), .. }) => {
+            WsMessage::Ack(Ack { error: ack.error.map(|e| e.with_request_id(request_id)), ..ack })
+        }
+        other => other,
+    }
+}
+
+/// Esegue un comando del client e ritorna la risposta da inviare sulla stessa connessione.
+async fn handle_command(state: &AppState, user_id: &str, cmd: WsMessage) -> WsMessage {
+    let (in_reply_to, result) = match cmd {
+        WsMessage::SendMessage(sm) => (sm.client_msg_id.clone(), send_message(state, user_id, sm).await),
+        WsMessage::AddReaction(rc) => (rc.client_msg_id.clone(), react(state, user_id, rc, true).await),
+        WsMessage::RemoveReaction(rc) => (rc.client_msg_id.clone(), react(state, user_id, rc, false).await),
+        WsMessage::PinMessage(pc) => (pc.client_msg_id.clone(), pin(state, user_id, pc, true).await),
+        WsMessage::UnpinMessage(pc) => (pc.client_msg_id.clone(), pin(state, user_id, pc, false).await),
+        _ => {
+            return WsMessage::Error(Error::new(codes::BAD_REQUEST, "unsupported message type"));
+        }
+    };
+    WsMessage::Ack(result.unwrap_or_else(|error| {
+        if error.code == codes::INTERNAL {
+            tracing::error!(code = %error.code, message = %error.message, "command failed");
+        } else {
+            tracing::debug!(code = %error.code, message = %error.message, "command rejected");
+        }
+        error_ack(in_reply_to, error)
+    }))
+}
+
+/// Risposta ad un comando scartato dal rate limiting per utente.
+fn rate_limited(cmd: WsMessage, retry_after: Duration) -> WsMessage {
+    let error = ratelimit::limited_error(codes::RATE_LIMITED, "too many messages", retry_after);
+    let in_reply_to = match cmd {
+        WsMessage::SendMessage(sm) => sm.client_msg_id,
+        WsMessage::AddReaction(rc) | WsMessage::RemoveReaction(rc) => rc.client_msg_id,
+        WsMessage::PinMessage(pc) | WsMessage::UnpinMessage(pc) => pc.client_msg_id,
+        _ => return WsMessage::Error(error),
+    };
+    WsMessage::Ack(error_ack(in_reply_to, error))
+}
+
+fn error_ack(in_reply_to: String, error: Error) -> Ack {
+    Ack {
+        in_reply_to,
+        status: AckStatus::Error,
+        message_id: None,
+        created_at: None,
+        group_id: None,
+        content: None,
+        send_at: None,
+        error: Some(error),
+    }
+}
+
+fn internal(e: sqlx::Error) -> Error {
+    Error::new(codes::INTERNAL, format!("db error: {}", e))
+}
+
+async fn require_member(state: &AppState, group_id: &str, user_id: &str) -> Result<(), Error> {
+    if state.store.is_member(group_id, user_id).await.map_err(internal)? {
+        Ok(())
+    } else {
+        Err(Error::new(codes::FORBIDDEN, "not a member of the group"))
+    }
+}
+
+/// sendMessage: persiste il messaggio e lo notifica a tutti i membri del gruppo (mittente incluso).
+/// Gli utenti menzionati ricevono in più un evento mention dedicato. Nei gruppi cifrati il contenuto
+/// deve essere un EncryptedContent e le menzioni non vengono cercate (il server non legge il testo).
+/// Con ttl_secs (o un message_ttl_secs del gruppo) il messaggio è effimero e porta la sua scadenza.
+/// Con send_at nel futuro il messaggio viene solo programmato (vedi crate::scheduled).
+#[tracing::instrument(skip_all, fields(group_id = %sm.group_id))]
+async fn send_message(state: &AppState, user_id: &str, sm: SendMessage) -> Result<Ack, Error> {
+    if sm.content.trim().is_empty() {
+        return Err(Error::new(codes::BAD_REQUEST, "content must not be empty"));
+    }
+    require_member(state, &sm.group_id, user_id).await?;
+    let group = state.store.find_group(&sm.group_id)
+        .await
+        .map_err(internal)?
+        .ok_or_else(|| Error::new(codes::NOT_FOUND, "group not found"))?;
+    if group.encrypted {
+        EncryptedContent::parse(&sm.content).map_err(|e| Error::new(codes::BAD_REQUEST, e.to_string()))?;
+    }
+    if let Some(ttl) = sm.ttl_secs {
+        state.config.ephemeral.validate_ttl(ttl).map_err(|e| Error::new(codes::BAD_REQUEST, e))?;
+    }
+    let send_at = match &sm.send_at {
+        Some(send_at) => state.config.scheduled.send_at(send_at).map_err(|e| Error::new(codes::BAD_REQUEST, e))?,
+        None => None,
+    };
+    if let Some(send_at) = send_at {
+        return schedule(state, user_id, sm, send_at).await;
+    }
+    let ttl_secs = sm.ttl_secs.or(group.message_ttl_secs);
+
+    let mut message = Message {
+        message_id: Uuid::new_v4().to_string(),
+        group_id: sm.group_id,
+        sender_id: user_id.to_string(),
+        content: sm.content,
+        created_at: now_timestamp(),
+        seq: 0,
+        reactions: Vec::new(),
+        mentions: Vec::new(),
+        expires_at: ttl_secs.map(ephemeral::expires_at),
+    };
+    message.seq = state.store.insert_message(&message).await.map_err(internal)?;
+    announce(state, &mut message, group.encrypted).await.map_err(internal)?;
+
+    Ok(Ack {
+        in_reply_to: sm.client_msg_id,
+        status: AckStatus::Ok,
+        message_id: Some(message.message_id),
+        created_at: Some(message.created_at),
+        group_id: Some(message.group_id),
+        content: Some(message.content),
+        send_at: None,
+        error: None,
+    })
+}
+
+// Salva un sendMessage da pubblicare a send_at (secondi unix); il ttl del gruppo si applica alla pubblicazione
+async fn schedule(state: &AppState, user_id: &str, sm: SendMessage, send_at: i64) -> Result<Ack, Error> {
+    let pending = state.store.scheduled_for_sender(user_id).await.map_err(internal)?.len();
+    if pending >= state.config.scheduled.max_pending_per_user as usize {
+        return Err(Error::new(codes::BAD_REQUEST, "too many scheduled messages"));
+    }
+    let scheduled = ScheduledMessage {
+        message_id: Uuid::new_v4().to_string(),
+        group_id: sm.group_id,
+        sender_id: user_id.to_string(),
+        content: sm.content,
+        send_at: timestamp_from_unix(send_at),
+        created_at: now_timestamp(),
+        ttl_secs: sm.ttl_secs,
+    };
+    state.store.schedule_message(&scheduled).await.map_err(internal)?;
+    tracing::debug!(message_id = %scheduled.message_id, send_at = %scheduled.send_at, "message scheduled");
+
+    Ok(Ack {
+        in_reply_to: sm.client_msg_id,
+        status: AckStatus::Ok,
+        message_id: Some(scheduled.message_id),
+        created_at: None,
+        group_id: Some(scheduled.group_id),
+        content: Some(scheduled.content),
+        send_at: Some(scheduled.send_at),
+        error: None,
+    })
+}
+
+/// Completa la pubblicazione di un messaggio già salvato: registra le menzioni (non nei gruppi cifrati)
+/// e notifica il messaggio ai membri che non hanno silenziato il gruppo (e al mittente), l'evento mention
+/// ai menzionati anche se lo hanno silenziato e message.created ai webhook del gruppo. Usata anche per i
+/// messaggi programmati.
+pub(crate) async fn announce(state: &AppState, message: &mut Message, encrypted: bool) -> Result<(), sqlx::Error> {
+    let mentioned = if encrypted { Vec::new() } else { parse_mentions(&message.content) };
+    message.mentions = mentions::resolve(state.store.as_ref(), &message.group_id, &message.sender_id, &mentioned).await?;
+    state.store.add_mentions(&message.message_id, &message.mentions).await?;
+    // il messaggio è già salvato: un problema con la coda dei webhook non deve far fallire l'invio
+    if let Err(e) = webhooks::enqueue_message(state, message).await {
+        tracing::warn!(error = %e, message_id = %message.message_id, "webhook deliveries not enqueued");
+    }
+
+    state.metrics.record_message_sent();
+
+    let members = unmuted_members(state, &message.group_id, &message.sender_id, &[]).await?;
+    let event = WsMessage::Message(message.clone());
+    state.metrics.time_fanout("message", || state.bus.publish(&members, &event));
+    if !message.mentions.is_empty() {
+        let group_name = db::group_name(state.store.as_ref(), &message.group_id).await?.unwrap_or_default();
+        let event = WsMessage::Mention(Mention { message: message.clone(), group_name });
+        state.metrics.time_fanout("mention", || state.bus.publish(&message.mentions, &event));
+    }
+    Ok(())
+}
+
+// Membri del gruppo a cui notificare un evento: chi ha silenziato il gruppo è escluso, salvo l'autore
+// dell'evento e gli utenti in `mentioned`
+async fn unmuted_members(
+    state: &AppState,
+    group_id: &str,
+    actor: &str,
+    mentioned: &[String],
+) -> Result<Vec<String>, sqlx::Error> {
+    let muted = state.store.muted_member_ids(group_id).await?;
+    let mut members = state.store.member_ids(group_id).await?;
+    members.retain(|m| m == actor || mentioned.contains(m) || !muted.contains(m));
+    Ok(members)
+}
+
+/// addReaction / removeReaction: idempotenti; l'evento reactionChanged parte solo se lo stato cambia,
+/// con reactedByMe calcolato per ciascun destinatario. Chi ha silenziato il gruppo lo riceve solo se
+/// è menzionato nel messaggio.
+#[tracing::instrument(skip_all, fields(message_id = %rc.message_id, added, group_id = field::Empty))]
+async fn react(state: &AppState, user_id: &str, rc: ReactionCommand, added: bool) -> Result<Ack, Error> {
+    if !reactions::is_valid_emoji(&rc.emoji) {
+        return Err(Error::new(codes::BAD_REQUEST, "invalid emoji"));
+    }
+    let mut message = state.store.find_message(&rc.message_id)
+        .await
+        .map_err(internal)?
+        .ok_or_else(|| Error::new(codes::NOT_FOUND, "message not found"))?;
+    tracing::Span::current().record("group_id", message.group_id.as_str());
+    require_member(state, &message.group_id, user_id).await?;
+
+    let changed = if added {
+        state.store.add_reaction(&message.message_id, user_id, &rc.emoji, &now_timestamp()).await
+    } else {
+        state.store.remove_reaction(&message.message_id, user_id, &rc.emoji).await
+    }
+    .map_err(internal)?;
+
+    if changed {
+        let tally = reactions::tally_for(state.store.as_ref(), &message.message_id).await.map_err(internal)?;
+        mentions::attach(state.store.as_ref(), std::slice::from_mut(&mut message)).await.map_err(internal)?;
+        let members = unmuted_members(state, &message.group_id, user_id, &message.mentions).await.map_err(internal)?;
+        state.metrics.time_fanout("reaction", || {
+            for member in &members {
+                let event = ReactionChanged {
+                    message_id: message.message_id.clone(),
+                    group_id: message.group_id.clone(),
+                    user_id: user_id.to_string(),
+                    emoji: rc.emoji.clone(),
+                    added,
+                    reactions: tally.view(member),
+                };
+                state.bus.publish(std::slice::from_ref(member), &WsMessage::ReactionChanged(event));
+            }
+        });
+    }
+
+    Ok(Ack {
+        in_reply_to: rc.client_msg_id,
+        status: AckStatus::Ok,
+        message_id: Some(message.message_id),
+        created_at: None,
+        group_id: Some(message.group_id),
+        content: None,
+        send_at: None,
+        error: None,
+    })
+}
+
+/// pinMessage / unpinMessage: consentiti secondo la pin_policy del gruppo, idempotenti;
+/// l'evento pinChanged parte solo se lo stato cambia e, come reactionChanged, non arriva a chi ha
+/// silenziato il gruppo salvo che sia menzionato nel messaggio.
+#[tracing::instrument(skip_all, fields(message_id = %pc.message_id, pinned, group_id = field::Empty))]
+async fn pin(state: &AppState, user_id: &str, pc: PinCommand, pinned: bool) -> Result<Ack, Error> {
+    let mut message = state.store.find_message(&pc.message_id)
+        .await
+        .map_err(internal)?
+        .ok_or_else(|| Error::new(codes::NOT_FOUND, "message not found"))?;
+    let group = state.store.find_group(&message.group_id)
+        .await
+        .map_err(internal)?
+        .ok_or_else(|| Error::new(codes::NOT_FOUND, "group not found"))?;
+    tracing::Span::current().record("group_id", group.group_id.as_str());
+    require_member(state, &group.group_id, user_id).await?;
+    if !pins::can_pin(state.store.as_ref(), &group, user_id).await.map_err(internal)? {
+        return Err(Error::new(codes::FORBIDDEN, "only group admins can pin messages"));
+    }
+
+    let pinned_at = now_timestamp();
+    let changed = if pinned {
+        state.store.pin_message(&message.message_id, &group.group_id, user_id, &pinned_at).await
+    } else {
+        state.store.unpin_message(&message.message_id).await
+    }
+    .map_err(internal)?;
+
+    if changed {
+        mentions::attach(state.store.as_ref(), std::slice::from_mut(&mut message)).await.map_err(internal)?;
+        let tally = reactions::tally_for(state.store.as_ref(), &message.message_id).await.map_err(internal)?;
+        let members = unmuted_members(state, &group.group_id, user_id, &message.mentions).await.map_err(internal)?;
+        state.metrics.time_fanout("pin", || {
+            for member in &members {
+                let pin = pinned.then(|| Pin {
+                    message: Message { reactions: tally.view(member), ..message.clone() },
+                    pinned_by: user_id.to_string(),
+                    pinned_at: pinned_at.clone(),
+                });
+                let event = PinChanged {
+                    group_id: group.group_id.clone(),
+                    message_id: message.message_id.clone(),
+                    user_id: user_id.to_string(),
+                    pinned,
+                    pin,
+                };
+                state.bus.publish(std::slice::from_ref(member), &WsMessage::PinChanged(event));
+            }
+        });
+    }
+
+    Ok(Ack {
+        in_reply_to: pc.client_msg_id,
+        status: AckStatus::Ok,
+        message_id: Some(message.message_id),
+        created_at: None,
+        group_id: Some(group.group_id),
+        content: None,
+        send_at: None,
+        error: None,
+    })
+}
@@ -0,0 +1,104 @@
+mod common;
+
+use assert_cmd::cargo::cargo_bin_cmd;
+use common::spawn_app_on_disk_with;
+use ruggine_core::LoginRequest;
+use ruggine_server::admin::{self, AdminCommand};
+use ruggine_server::store::{GroupStore, Role, SqliteStore, Store, UserStore};
+use ruggine_server::AppState;
+use tempfile::TempDir;
+
+async fn run(store: &dyn Store, command: AdminCommand) -> anyhow::Result<String> {
+    let mut out = Vec::new();
+    admin::run(store, command, std::io::empty(), &mut out).await?;
+    Ok(String::from_utf8(out).unwrap())
+}
+
+// Test che verifica che disable-user, reset-password e revoke-sessions abbiano effetto subito su un
+// server in esecuzione sullo stesso database
+#[tokio::test]
+async fn admin_commands_apply_to_running_server() {
+    let app = spawn_app_on_disk_with(AppState::new).await;
+    let (_, alice) = app.register("alice").await;
+    let (_, bob) = app.register("bob").await;
+    let store = SqliteStore::new(app.pool.clone());
+    let login = |username: &str, password: &str| {
+        let req = LoginRequest { username: username.into(), password: password.into() };
+        app.http.post(app.url("/api/login")).json(&req).send()
+    };
+    assert_eq!(app.get_json(&alice, "/api/groups").await.0, 200);
+
+    run(&store, AdminCommand::DisableUser { username: "alice".into() }).await.unwrap();
+    assert_eq!(app.get_json(&alice, "/api/groups").await.0, 401);
+    let resp = login("alice", "secret").await.unwrap();
+    assert_eq!(resp.status(), 403);
+    let listed = run(&store, AdminCommand::ListUsers).await.unwrap();
+    assert!(listed.lines().any(|l| l.starts_with("alice\t") && l.ends_with("\tdisabled\tno")), "{}", listed);
+    run(&store, AdminCommand::EnableUser { username: "alice".into() }).await.unwrap();
+    assert_eq!(login("alice", "secret").await.unwrap().status(), 200);
+
+    let reset = AdminCommand::ResetPassword { username: "bob".into(), password: Some("changed".into()) };
+    run(&store, reset).await.unwrap();
+    assert_eq!(app.get_json(&bob, "/api/groups").await.0, 401, "reset must close the session");
+    assert_eq!(login("bob", "secret").await.unwrap().status(), 401);
+    assert_eq!(login("bob", "changed").await.unwrap().status(), 200);
+
+    let revoked = run(&store, AdminCommand::RevokeSessions { username: None, all: true }).await.unwrap();
+    assert_eq!(revoked, "revoked 2 sessions\n");
+    let missing = run(&store, AdminCommand::DisableUser { username: "nobody".into() }).await;
+    assert_eq!(missing.unwrap_err().to_string(), "user \"nobody\" not found");
+}
+
+// Test che verifica i comandi del binario su un database su file: password da stdin, elenchi,
+// eliminazione di un gruppo, statistiche e rifiuto del database in memoria
+#[tokio::test]
+async fn binary_manages_users_and_groups() {
+    let td = TempDir::new().unwrap();
+    let db = td.path().join("ruggine.db");
+    let cli = |args: &[&str]| {
+        let mut cmd = cargo_bin_cmd!("ruggine-server");
+        cmd.current_dir(td.path()).args(["--database-url", db.to_str().unwrap()]).args(args);
+        cmd
+    };
+
+    let out = cli(&["create-user", "carol"]).write_stdin("s3cret\n").assert().success();
+    assert!(String::from_utf8_lossy(&out.get_output().stdout).starts_with("created user carol ("));
+    cli(&["create-user", "carol", "--password", "x"]).assert().failure();
+    cli(&["create-user", "dave", "--password", "pw"]).assert().success();
+    cli(&["revoke-sessions"]).assert().failure();
+
+    let store = SqliteStore::connect(db.to_str().unwrap(), 1).await.unwrap();
+    let creds = store.credentials("carol").await.unwrap().expect("carol");
+    assert_eq!(creds.password_hash, ruggine_server::db::hash_password("s3cret"));
+    let group = ruggine_core::Group {
+        group_id: "g1".into(),
+        name: "ops".into(),
+        created_at: "2024-01-01T00:00:00Z".into(),
+        pin_policy: Default::default(),
+        encrypted: false,
+        retention: Default::default(),
+        message_ttl_secs: None,
+    };
+    store.create_group(&group, &[(creds.user.user_id.clone(), Role::Admin)]).await.unwrap();
+    store.close().await;
+
+    let out = cli(&["list-users"]).assert().success();
+    let listed = String::from_utf8(out.get_output().stdout.clone()).unwrap();
+    let names: Vec<&str> = listed.lines().skip(1).map(|l| l.split('\t').next().unwrap()).collect();
+    assert_eq!(names, ["carol", "dave"]);
+    let out = cli(&["list-groups"]).assert().success();
+    let listed = String::from_utf8(out.get_output().stdout.clone()).unwrap();
+    assert_eq!(listed.lines().nth(1), Some("g1\tops\t2024-01-01T00:00:00Z\t1\t0"));
+
+    cli(&["delete-group", "g1"]).assert().success();
+    cli(&["delete-group", "g1"]).assert().failure();
+    let out = cli(&["stats"]).assert().success();
+    let stats = String::from_utf8(out.get_output().stdout.clone()).unwrap();
+    assert!(stats.contains("users: 2\n") && stats.contains("groups: 0\n"), "{}", stats);
+
+    cargo_bin_cmd!("ruggine-server")
+        .current_dir(td.path())
+        .args(["--database-url", ":memory:", "stats"])
+        .assert()
+        .failure();
+}
@@ -0,0 +1,160 @@
+mod common;
+
+use assert_cmd::cargo::cargo_bin_cmd;
+use common::{spawn_app_on_disk_with, spawn_app_with};
+use ruggine_core::{BackupResponse, GroupExport, Message};
+use ruggine_server::{backup, config::Config, connect_pool, sqlite_url_for_path, AppState, SCHEMA_VERSION};
+use std::fs;
+use tempfile::TempDir;
+
+fn message(id: &str, group_id: &str, sender: &str, content: &str) -> Message {
+    Message {
+        message_id: id.to_string(),
+        group_id: group_id.to_string(),
+        sender_id: sender.to_string(),
+        content: content.to_string(),
+        created_at: "2024-01-01T00:00:00Z".to_string(),
+        seq: 0,
+        reactions: Vec::new(),
+        mentions: Vec::new(),
+        expires_at: None,
+    }
+}
+
+// Test che verifica il backup online tramite POST /api/admin/backup: token di amministrazione,
+// file con data e ora nel nome e copia valida con la versione corrente dello schema
+#[tokio::test]
+async fn admin_endpoint_writes_online_backup() {
+    let td = TempDir::new().unwrap();
+    let token_file = td.path().join("admin-token");
+    fs::write(&token_file, "s3cret-admin-token\n").unwrap();
+    let backups = td.path().join("backups");
+    let config = Config {
+        admin_token_file: Some(token_file.clone()),
+        backup_dir: backups.clone(),
+        ..Config::default()
+    };
+    config.validate().unwrap();
+    let app = spawn_app_on_disk_with(move |pool| AppState::with_config(pool, config)).await;
+    let (alice, token) = app.register("alice").await;
+    let group_id = app.create_group(&token, "general", &[]).await;
+    app.state.store.insert_message(&message("m1", &group_id, &alice, "prima del backup")).await.unwrap();
+
+    let url = app.url("/api/admin/backup");
+    assert_eq!(app.http.post(&url).send().await.unwrap().status(), 401);
+    assert_eq!(app.http.post(&url).bearer_auth(&token).send().await.unwrap().status(), 401);
+    let resp = app.http.post(&url).bearer_auth("s3cret-admin-token").send().await.unwrap();
+    assert_eq!(resp.status(), 201);
+    let first: BackupResponse = resp.json().await.unwrap();
+    assert!(first.file.starts_with("ruggine-") && first.file.ends_with("Z.db"), "{}", first.file);
+    let resp = app.http.post(&url).bearer_auth("s3cret-admin-token").send().await.unwrap();
+    let second: BackupResponse = resp.json().await.unwrap();
+    assert_ne!(first.file, second.file, "backups must not overwrite each other");
+
+    let path = backups.join(&first.file);
+    assert_eq!(fs::metadata(&path).unwrap().len(), first.size_bytes);
+    assert_eq!(backup::validate_backup(&path).await.unwrap(), SCHEMA_VERSION);
+    let pool = connect_pool(&sqlite_url_for_path(&path).unwrap()).await.unwrap();
+    let content: String = sqlx::query_scalar("SELECT content FROM messages").fetch_one(&pool).await.unwrap();
+    assert_eq!(content, "prima del backup");
+
+    // il token viene riletto: cambiarlo invalida il precedente
+    fs::write(&token_file, "rotated-admin-token").unwrap();
+    assert_eq!(app.http.post(&url).bearer_auth("s3cret-admin-token").send().await.unwrap().status(), 401);
+
+    // senza admin_token_file le API di amministrazione sono disattivate
+    let plain = spawn_app_with(AppState::new).await;
+    let resp = plain.http.post(plain.url("/api/admin/backup")).bearer_auth("anything").send().await.unwrap();
+    assert_eq!(resp.status(), 403);
+}
+
+// Test che verifica i comandi backup e restore del binario: il ripristino sostituisce il database
+// conservando il precedente e rifiuta file non validi o con uno schema più recente
+#[tokio::test]
+async fn binary_backs_up_and_restores() {
+    let td = TempDir::new().unwrap();
+    let db = td.path().join("ruggine.db");
+    let cli = |args: &[&str]| {
+        let mut cmd = cargo_bin_cmd!("ruggine-server");
+        cmd.current_dir(td.path()).args(["--database-url", db.to_str().unwrap()]).args(args);
+        cmd
+    };
+    cli(&["create-user", "before", "--password", "pw"]).assert().success();
+    let out = cli(&["backup", "--dir", "snapshots"]).assert().success();
+    let printed = String::from_utf8(out.get_output().stdout.clone()).unwrap();
+    let backup_file = fs::read_dir(td.path().join("snapshots")).unwrap().next().unwrap().unwrap().path();
+    assert!(printed.starts_with(&format!("wrote backup {}", backup_file.display())), "{}", printed);
+    cli(&["create-user", "after", "--password", "pw"]).assert().success();
+
+    // file che non è un database e backup con schema più recente del server
+    let garbage = td.path().join("garbage.db");
+    fs::write(&garbage, "not a database").unwrap();
+    cli(&["restore", garbage.to_str().unwrap()]).assert().failure();
+    let newer = td.path().join("newer.db");
+    fs::copy(&backup_file, &newer).unwrap();
+    let pool = connect_pool(&sqlite_url_for_path(&newer).unwrap()).await.unwrap();
+    sqlx::query(&format!("PRAGMA user_version = {}", SCHEMA_VERSION + 1)).execute(&pool).await.unwrap();
+    pool.close().await;
+    let err = cli(&["restore", newer.to_str().unwrap()]).assert().failure().get_output().stderr.clone();
+    assert!(String::from_utf8(err).unwrap().contains("newer than"));
+    let out = cli(&["list-users"]).assert().success();
+    assert_eq!(String::from_utf8(out.get_output().stdout.clone()).unwrap().lines().count(), 3, "rejected restore must not touch the database");
+
+    let out = cli(&["restore", backup_file.to_str().unwrap()]).assert().success();
+    let printed = String::from_utf8(out.get_output().stdout.clone()).unwrap();
+    assert!(printed.contains("previous database moved to"), "{}", printed);
+    let out = cli(&["list-users"]).assert().success();
+    let listed = String::from_utf8(out.get_output().stdout.clone()).unwrap();
+    let names: Vec<&str> = listed.lines().skip(1).map(|l| l.split('\t').next().unwrap()).collect();
+    assert_eq!(names, ["before"]);
+    let kept = fs::read_dir(td.path())
+        .unwrap()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_name().to_string_lossy().starts_with("ruggine.db.pre-restore-"))
+        .count();
+    assert!(kept >= 1);
+}
+
+// Test che verifica l'esportazione di un gruppo: solo per gli admin via API, con membri, messaggi in
+// ordine, reazioni e menzioni; lo stesso formato dal comando export-group
+#[tokio::test]
+async fn group_export_contains_members_and_history() {
+    let app = spawn_app_on_disk_with(AppState::new).await;
+    let (alice, alice_token) = app.register("alice").await;
+    let (bob, bob_token) = app.register("bob").await;
+    let group_id = app.create_group(&alice_token, "archivio", &[&bob]).await;
+    let store = app.state.store.clone();
+    for i in 1..=3 {
+        store.insert_message(&message(&format!("m{}", i), &group_id, &alice, &format!("msg {}", i))).await.unwrap();
+    }
+    store.add_reaction("m2", &bob, "👍", "2024-01-01T00:00:01Z").await.unwrap();
+    store.add_mentions("m3", std::slice::from_ref(&bob)).await.unwrap();
+
+    let path = format!("/api/groups/{}/export", group_id);
+    assert_eq!(app.get_json(&bob_token, &path).await.0, 403);
+    let resp = app.http.get(app.url(&path)).bearer_auth(&alice_token).send().await.unwrap();
+    assert_eq!(resp.status(), 200);
+    let disposition = resp.headers()["content-disposition"].to_str().unwrap().to_string();
+    assert!(disposition.starts_with("attachment;"), "{}", disposition);
+    let export: GroupExport = resp.json().await.unwrap();
+    assert_eq!(export.group.name, "archivio");
+    let members: Vec<(&str, &str)> =
+        export.members.iter().map(|m| (m.user.username.as_str(), m.role.as_str())).collect();
+    assert_eq!(members, [("alice", "admin"), ("bob", "member")]);
+    let seqs: Vec<u64> = export.messages.iter().map(|m| m.seq).collect();
+    assert_eq!(seqs, [1, 2, 3]);
+    assert_eq!(export.messages[1].reactions[0].count, 1);
+    assert!(!export.messages[1].reactions[0].reacted_by_me);
+    assert_eq!(export.messages[2].mentions, [bob]);
+    assert_eq!(app.get_json(&alice_token, "/api/groups/missing/export").await.0, 403);
+
+    let db = store.data_dir().await.unwrap().unwrap().join("ruggine.db");
+    let file = db.with_file_name("export.json");
+    cargo_bin_cmd!("ruggine-server")
+        .args(["--database-url", db.to_str().unwrap(), "export-group", &group_id, "--output", file.to_str().unwrap()])
+        .assert()
+        .success();
+    let from_cli: GroupExport = serde_json::from_slice(&fs::read(&file).unwrap()).unwrap();
+    assert_eq!(from_cli.members, export.members);
+    assert_eq!(from_cli.messages, export.messages);
+}
@@ -0,0 +1,116 @@
+mod common;
+
+use common::{send_message, spawn_app, spawn_app_with, ws_expect_silence, ws_recv, ws_recv_until};
+use ruggine_core::{encode_since, WsMessage};
+use ruggine_server::{config::Config, AppState};
+use std::collections::BTreeMap;
+
+fn since(entries: &[(&str, u64)]) -> String {
+    encode_since(&entries.iter().map(|(g, s)| (g.to_string(), *s)).collect::<BTreeMap<_, _>>())
+}
+
+// Test che verifica seq crescenti per gruppo, il recupero in ordine dei messaggi persi alla
+// riconnessione, il caughtUp finale e il passaggio agli eventi live senza duplicati
+#[tokio::test]
+async fn reconnect_replays_missed_messages_then_goes_live() {
+    let app = spawn_app().await;
+    let (_, alice) = app.register("alice").await;
+    let (bob_id, bob) = app.register("bob").await;
+    let group_id = app.create_group(&alice, "general", &[&bob_id]).await;
+    let other_id = app.create_group(&alice, "random", &[&bob_id]).await;
+
+    let mut ws_alice = app.ws_connect(&alice).await;
+    let mut ws_bob = app.ws_connect(&bob).await;
+    send_message(&mut ws_alice, &group_id, "uno").await;
+    match ws_recv_until(&mut ws_bob, |m| matches!(m, WsMessage::Message(_))).await {
+        WsMessage::Message(m) => assert_eq!(m.seq, 1),
+        _ => unreachable!(),
+    }
+    drop(ws_bob);
+
+    for content in ["due", "tre"] {
+        send_message(&mut ws_alice, &group_id, content).await;
+    }
+    send_message(&mut ws_alice, &other_id, "altrove").await;
+
+    let mut ws_bob = app.ws_connect_since(&bob, &since(&[(&group_id, 1), (&other_id, 1)])).await;
+    for (seq, content) in [(2, "due"), (3, "tre")] {
+        match ws_recv(&mut ws_bob).await {
+            WsMessage::Message(m) => {
+                assert_eq!((m.seq, m.content.as_str(), m.group_id.as_str()), (seq, content, group_id.as_str()));
+            }
+            other => panic!("expected replayed message, got {:?}", other),
+        }
+    }
+    match ws_recv(&mut ws_bob).await {
+        WsMessage::CaughtUp(c) => {
+            assert_eq!(c.latest_seq, BTreeMap::from([(group_id.clone(), 3), (other_id.clone(), 1)]));
+        }
+        other => panic!("expected caughtUp, got {:?}", other),
+    }
+
+    send_message(&mut ws_alice, &group_id, "quattro").await;
+    match ws_recv(&mut ws_bob).await {
+        WsMessage::Message(m) => assert_eq!((m.seq, m.content.as_str()), (4, "quattro")),
+        other => panic!("expected live message, got {:?}", other),
+    }
+    ws_expect_silence(&mut ws_bob, 200).await;
+
+    // anche la cronologia HTTP riporta il seq
+    let (status, body) = app.get_json(&bob, &format!("/api/groups/{}/messages", group_id)).await;
+    assert_eq!(status, 200);
+    let seqs: Vec<u64> = body["messages"].as_array().unwrap().iter().map(|m| m["seq"].as_u64().unwrap()).collect();
+    assert_eq!(seqs, [1, 2, 3, 4]);
+}
+
+// Test che verifica resyncRequired quando i messaggi persi superano max_replay_messages
+// o il seq del client è più avanti del server, e che i gruppi di altri utenti siano ignorati
+#[tokio::test]
+async fn too_many_missed_messages_require_resync() {
+    let app =
+        spawn_app_with(|pool| AppState::with_config(pool, Config { max_replay_messages: 2, ..Config::default() })).await;
+    let (_, alice) = app.register("alice").await;
+    let (bob_id, bob) = app.register("bob").await;
+    let (_, carol) = app.register("carol").await;
+    let group_id = app.create_group(&alice, "general", &[&bob_id]).await;
+    let private_id = app.create_group(&carol, "private", &[]).await;
+
+    let mut ws_alice = app.ws_connect(&alice).await;
+    for content in ["a", "b", "c"] {
+        send_message(&mut ws_alice, &group_id, content).await;
+    }
+
+    let mut ws_bob = app.ws_connect_since(&bob, &since(&[(&group_id, 0), (&private_id, 0)])).await;
+    match ws_recv(&mut ws_bob).await {
+        WsMessage::ResyncRequired(r) => {
+            assert_eq!((r.group_id.as_str(), r.last_seen_seq, r.latest_seq), (group_id.as_str(), 0, 3));
+        }
+        other => panic!("expected resyncRequired, got {:?}", other),
+    }
+    match ws_recv(&mut ws_bob).await {
+        WsMessage::CaughtUp(c) => assert_eq!(c.latest_seq, BTreeMap::from([(group_id.clone(), 3)])),
+        other => panic!("expected caughtUp, got {:?}", other),
+    }
+
+    // seq più alto dell'ultimo assegnato (es. database ripristinato): serve una resync
+    let mut ws_bob = app.ws_connect_since(&bob, &since(&[(&group_id, 9)])).await;
+    assert!(matches!(ws_recv(&mut ws_bob).await, WsMessage::ResyncRequired(r) if r.latest_seq == 3));
+
+    // entro il limite i messaggi vengono recuperati
+    let mut ws_bob = app.ws_connect_since(&bob, &since(&[(&group_id, 1)])).await;
+    assert!(matches!(ws_recv(&mut ws_bob).await, WsMessage::Message(m) if m.seq == 2));
+    assert!(matches!(ws_recv(&mut ws_bob).await, WsMessage::Message(m) if m.seq == 3));
+    assert!(matches!(ws_recv(&mut ws_bob).await, WsMessage::CaughtUp(_)));
+}
+
+// Test che verifica il 400 per un parametro since non valido
+#[tokio::test]
+async fn invalid_since_is_rejected() {
+    let app = spawn_app().await;
+    let (_, alice) = app.register("alice").await;
+    let url = format!("ws://{}/ws?token={}&since=group-without-seq", app.addr, alice);
+    match tokio_tungstenite::connect_async(url).await {
+        Err(tokio_tungstenite::tungstenite::Error::Http(resp)) => assert_eq!(resp.status(), 400),
+        other => panic!("expected 400, got {:?}", other.map(|_| ())),
+    }
+}
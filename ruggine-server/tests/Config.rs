@@ -0,0 +1,155 @@
+mod common;
+
+use assert_cmd::cargo::cargo_bin_cmd;
+use ruggine_server::config::{Config, ConfigArgs};
+use std::collections::HashMap;
+use std::fs;
+use tempfile::TempDir;
+
+fn env_from(pairs: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+    let map: HashMap<String, String> = pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+    move |k| map.get(k).cloned()
+}
+
+// Test che verifica la precedenza dei livelli: default < file < ambiente < flag
+#[test]
+fn layers_override_each_other_in_order() {
+    let td = TempDir::new().unwrap();
+    let file = td.path().join("server.toml");
+    fs::write(
+        &file,
+        r#"
+        bind_addr = "0.0.0.0:4000"
+        db_pool_size = 3
+        log_level = "debug"
+
+        [rate_limits.ws_per_user]
+        burst = 7
+        refill_per_sec = 1.5
+        "#,
+    )
+    .unwrap();
+
+    let args = ConfigArgs { config: Some(file), bind_addr: Some("127.0.0.1:5000".to_string()), ..Default::default() };
+    let env = env_from(&[
+        ("RUGGINE_DB_POOL_SIZE", "4"),
+        ("DATABASE_URL", "legacy.db"),
+        ("RUGGINE_CORS_ORIGINS", "http://localhost:8080, https://chat.example.com"),
+    ]);
+    let config = Config::load_with_env(&args, env).unwrap();
+
+    assert_eq!(config.bind_addr, "127.0.0.1:5000"); // flag
+    assert_eq!(config.db_pool_size, 4); // ambiente
+    assert_eq!(config.database_url, "legacy.db"); // variabile storica
+    assert_eq!(config.log_level, "debug"); // file
+    assert_eq!(config.rate_limits.ws_per_user.burst, 7); // file, sezione annidata
+    assert_eq!(config.cors_origins, vec!["http://localhost:8080", "https://chat.example.com"]);
+    assert_eq!(config.token_lifetime_secs, Config::default().token_lifetime_secs); // default
+    config.validate().unwrap();
+}
+
+// Test che verifica che la validazione riporti tutti i problemi e che chiavi sconosciute nel file siano un errore
+#[test]
+fn invalid_values_are_reported_together() {
+    let config = Config {
+        bind_addr: "not-an-address".to_string(),
+        db_pool_size: 0,
+        cors_origins: vec!["localhost:8080".to_string(), "*".to_string()],
+        bus_url: Some("redis://localhost".to_string()),
+        tls_cert_file: Some("cert.pem".into()),
+        tls_redirect_addr: Some("0.0.0.0:80".to_string()),
+        cors_allow_credentials: true,
+        web_dist_dir: Some("missing-dist".into()),
+        admin_token_file: Some("missing-token".into()),
+        backup_dir: "".into(),
+        ..Config::default()
+    };
+    let msg = config.validate().unwrap_err().to_string();
+    assert!(msg.contains("bind_addr"), "{}", msg);
+    assert!(msg.contains("db_pool_size"), "{}", msg);
+    assert!(msg.contains("localhost:8080"), "{}", msg);
+    assert!(msg.contains("bus_url"), "{}", msg);
+    assert!(msg.contains("tls_cert_file requires tls_key_file"), "{}", msg);
+    assert!(msg.contains("tls_redirect_addr requires"), "{}", msg);
+    assert!(msg.contains("cors_allow_credentials"), "{}", msg);
+    assert!(msg.contains("web_dist_dir"), "{}", msg);
+    assert!(msg.contains("admin_token_file"), "{}", msg);
+    assert!(msg.contains("backup_dir"), "{}", msg);
+
+    let td = TempDir::new().unwrap();
+    let file = td.path().join("typo.toml");
+    fs::write(&file, "bind_adr = \"127.0.0.1:3000\"\n").unwrap();
+    let args = ConfigArgs { config: Some(file), ..Default::default() };
+    assert!(Config::load_with_env(&args, env_from(&[])).is_err());
+}
+
+// Test che verifica --print-config sul binario e l'uscita con errore per una configurazione non valida
+#[test]
+fn binary_prints_config_and_rejects_invalid_values() {
+    let td = TempDir::new().unwrap();
+    let out = cargo_bin_cmd!("ruggine-server")
+        .current_dir(td.path())
+        .env_remove("DATABASE_URL")
+        .env_remove("BIND_ADDR")
+        .args(["--print-config", "--db-pool-size", "7", "--cors-origin", "http://localhost:8080"])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let printed: Config = toml::from_str(&String::from_utf8(out).unwrap()).unwrap();
+    assert_eq!(printed.db_pool_size, 7);
+    assert_eq!(printed.cors_origins, vec!["http://localhost:8080"]);
+
+    let err = cargo_bin_cmd!("ruggine-server")
+        .current_dir(td.path())
+        .args(["--bind-addr", "nowhere"])
+        .assert()
+        .failure()
+        .get_output()
+        .stderr
+        .clone();
+    assert!(String::from_utf8(err).unwrap().contains("invalid configuration"));
+}
+
+// Test che verifica che i flag dei limiti di frequenza abbiano la precedenza sulle variabili d'ambiente
+#[test]
+fn rate_limit_flags_override_env() {
+    let td = TempDir::new().unwrap();
+    let out = cargo_bin_cmd!("ruggine-server")
+        .current_dir(td.path())
+        .env("RUGGINE_AUTH_BURST", "5")
+        .env("RUGGINE_AUTH_REFILL_PER_SEC", "0.5")
+        .env("RUGGINE_WS_BURST", "10")
+        .env("RUGGINE_WS_REFILL_PER_SEC", "2")
+        .args(["--print-config", "--auth-burst", "9", "--ws-refill-per-sec", "4.5"])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let printed: Config = toml::from_str(&String::from_utf8(out).unwrap()).unwrap();
+    let limits = printed.rate_limits;
+    assert_eq!((limits.auth_per_ip.burst, limits.auth_per_ip.refill_per_sec), (9, 0.5));
+    assert_eq!((limits.ws_per_user.burst, limits.ws_per_user.refill_per_sec), (10, 4.5));
+
+    let args = ConfigArgs { ws_burst: Some(3), auth_refill_per_sec: Some(0.25), ..Default::default() };
+    let env = env_from(&[("RUGGINE_WS_BURST", "10"), ("RUGGINE_AUTH_REFILL_PER_SEC", "0.5")]);
+    let config = Config::load_with_env(&args, env).unwrap();
+    assert_eq!((config.rate_limits.ws_per_user.burst, config.rate_limits.auth_per_ip.refill_per_sec), (3, 0.25));
+}
+
+// Test che verifica che un token scaduto non sia più accettato
+#[tokio::test]
+async fn expired_token_is_rejected() {
+    let app = common::spawn_app().await;
+    let (user_id, token) = app.register("alice").await;
+    assert_eq!(app.get_json(&token, "/api/groups").await.0, 200);
+
+    sqlx::query("UPDATE users SET token_expires_at = 1 WHERE user_id = ?")
+        .bind(&user_id)
+        .execute(&app.pool)
+        .await
+        .unwrap();
+    assert_eq!(app.get_json(&token, "/api/groups").await.0, 401);
+}
@@ -0,0 +1,224 @@
+mod common;
+
+use assert_cmd::cargo::cargo_bin_cmd;
+use common::{new_message, spawn_app_with, ws_recv_until, ws_send};
+use ruggine_core::{
+    Group, Message, PinPolicy, RetentionPolicy, ScheduledMessage, User, Webhook, WebhookEvent,
+    WsMessage,
+};
+use ruggine_server::config::Config;
+use ruggine_server::encryption::{self, field_key_id, Keyring, RotationReport};
+use ruggine_server::store::{
+    EncryptedStore, MentionStore, MessageStore, PinStore, Role, ScheduleStore, SqliteStore, Store, WebhookRecord, WebhookStore,
+};
+use ruggine_server::{connect_pool, run_migrations, sqlite_url_for_path, AppState};
+use std::sync::Arc;
+use tempfile::TempDir;
+
+const OLD_KEY: &str = "old AAECAwQFBgcICQoLDA0ODxAREhMUFRYXGBkaGxwdHh8=";
+const NEW_KEY: &str = "new HyAhIiMkJSYnKCkqKywtLi8wMTIzNDU2Nzg5Ojs8PT4=";
+
+fn keyring(lines: &[&str]) -> Arc<Keyring> {
+    Arc::new(Keyring::parse(&lines.join("\n")).expect("keyring"))
+}
+
+fn message(id: &str, content: &str) -> Message {
+    Message {
+        message_id: id.to_string(),
+        group_id: "g1".to_string(),
+        sender_id: "u1".to_string(),
+        content: content.to_string(),
+        created_at: "2024-01-01T00:00:00Z".to_string(),
+        seq: 0,
+        reactions: Vec::new(),
+        mentions: Vec::new(),
+        expires_at: None,
+    }
+}
+
+fn webhook(id: &str, secret: &str) -> WebhookRecord {
+    let webhook = Webhook {
+        webhook_id: id.into(),
+        group_id: "g1".into(),
+        url: "https://example.com/hook".into(),
+        events: vec![WebhookEvent::MessageCreated],
+        enabled: true,
+        created_by: "u1".into(),
+        created_at: "2024-01-01T00:00:00Z".into(),
+        consecutive_failures: 0,
+    };
+    WebhookRecord { webhook, secret: secret.into() }
+}
+
+async fn raw_content(store: &dyn Store, message_id: &str) -> String {
+    let rows = store.raw_contents(None, 100).await.unwrap();
+    rows.into_iter().find(|(id, _)| id == message_id).map(|(_, c)| c).expect("stored message")
+}
+
+// Store SQLite in memoria con un utente u1 e un gruppo g1
+async fn seeded_store() -> Arc<dyn Store> {
+    let store: Arc<dyn Store> = Arc::new(SqliteStore::in_memory().await.unwrap());
+    let user = User { user_id: "u1".into(), username: "alice".into(), created_at: "2024-01-01T00:00:00Z".into() };
+    store.create_user(&user, "hash").await.unwrap();
+    let group = Group {
+        group_id: "g1".into(),
+        name: "general".into(),
+        created_at: "2024-01-01T00:00:00Z".into(),
+        pin_policy: PinPolicy::Members,
+        encrypted: false,
+        retention: RetentionPolicy::default(),
+        message_ttl_secs: None,
+    };
+    store.create_group(&group, &[("u1".into(), Role::Admin)]).await.unwrap();
+    store
+}
+
+// Test che verifica il formato del file delle chiavi, la cifratura dei campi legata alla riga
+// e il passaggio invariato dei valori in chiaro
+#[test]
+fn keyring_encrypts_fields_bound_to_their_row() {
+    let keys = keyring(&["# chiave attiva per prima", NEW_KEY, "", OLD_KEY]);
+    assert_eq!(keys.active_key_id(), "new");
+    let sealed = keys.encrypt_field("ciao", "m1").unwrap();
+    assert_eq!(field_key_id(&sealed), Some("new"));
+    assert!(!sealed.contains("ciao"));
+    assert_ne!(keys.encrypt_field("ciao", "m1").unwrap(), sealed, "nonce must be random");
+    assert_eq!(keys.decrypt_field(&sealed, "m1").unwrap(), "ciao");
+    assert!(keys.decrypt_field(&sealed, "m2").is_err(), "ciphertext moved to another row");
+    assert_eq!(keys.decrypt_field("in chiaro", "m1").unwrap(), "in chiaro");
+    assert!(keyring(&[OLD_KEY]).decrypt_field(&sealed, "m1").is_err(), "unknown key");
+
+    for bad in ["", "solo-id", "k1 not-base64!", "k1 AAAA", "k1 AAECAwQFBgcICQoLDA0ODxAREhMUFRYXGBkaGxwdHh8=\nk1 AAECAwQFBgcICQoLDA0ODxAREhMUFRYXGBkaGxwdHh8="] {
+        assert!(Keyring::parse(bad).is_err(), "{:?}", bad);
+    }
+    let generated = Keyring::generate_key_line().unwrap();
+    assert!(Keyring::parse(&generated).is_ok(), "{}", generated);
+}
+
+// Test che verifica che l'EncryptedStore salvi il contenuto cifrato e lo restituisca in chiaro
+// da tutte le letture (messaggi, storico, pin, menzioni), e così il segreto dei webhook
+#[tokio::test]
+async fn encrypted_store_keeps_content_encrypted_on_disk() {
+    let raw = seeded_store().await;
+    let store = EncryptedStore::new(raw.clone(), keyring(&[NEW_KEY]));
+    store.insert_message(&message("m1", "segreto @alice")).await.unwrap();
+
+    let stored = raw_content(raw.as_ref(), "m1").await;
+    assert_eq!(field_key_id(&stored), Some("new"));
+    assert!(!stored.contains("segreto"));
+
+    assert_eq!(store.find_message("m1").await.unwrap().unwrap().content, "segreto @alice");
+    assert_eq!(store.list_messages("g1", None, 10).await.unwrap()[0].content, "segreto @alice");
+    assert_eq!(store.messages_after("g1", 0, 10).await.unwrap()[0].content, "segreto @alice");
+    store.pin_message("m1", "g1", "u1", "2024-01-02T00:00:00Z").await.unwrap();
+    assert_eq!(store.pins_for_group("g1").await.unwrap()[0].message.content, "segreto @alice");
+    store.add_mentions("m1", &["u1".into()]).await.unwrap();
+    assert_eq!(store.mention_inbox("u1", None, 10).await.unwrap()[0].0.content, "segreto @alice");
+    store.create_webhook(&webhook("w1", "firma")).await.unwrap();
+    let secrets = raw.raw_webhook_secrets(None, 10).await.unwrap();
+    assert_eq!(field_key_id(&secrets[0].1), Some("new"));
+    assert_eq!(store.find_webhook("w1").await.unwrap().unwrap().secret, "firma");
+    assert_eq!(store.webhooks_for_group("g1").await.unwrap()[0].secret, "firma");
+
+    // senza la chiave giusta la lettura fallisce invece di restituire dati sbagliati
+    let wrong = EncryptedStore::new(raw, keyring(&[OLD_KEY]));
+    assert!(wrong.find_message("m1").await.is_err());
+}
+
+// Test che verifica la rotazione: messaggi, messaggi programmati e segreti dei webhook cifrati con la
+// chiave precedente o in chiaro vengono ricifrati con la chiave attiva e contati separatamente;
+// una seconda rotazione non cambia nulla
+#[tokio::test]
+async fn rotation_reencrypts_rows() {
+    let raw = seeded_store().await;
+    raw.insert_message(&message("legacy", "salvato in chiaro")).await.unwrap();
+    let old = EncryptedStore::new(raw.clone(), keyring(&[OLD_KEY]));
+    old.insert_message(&message("m1", "vecchio")).await.unwrap();
+    let scheduled = ScheduledMessage {
+        message_id: "s1".into(),
+        group_id: "g1".into(),
+        sender_id: "u1".into(),
+        content: "programmato".into(),
+        send_at: "2400-01-01T00:00:00Z".into(),
+        created_at: "2024-01-01T00:00:00Z".into(),
+        ttl_secs: None,
+    };
+    old.schedule_message(&scheduled).await.unwrap();
+    raw.create_webhook(&webhook("w1", "firma in chiaro")).await.unwrap();
+
+    let rotating = keyring(&[NEW_KEY, OLD_KEY]);
+    let report = encryption::rotate(raw.as_ref(), &rotating).await.unwrap();
+    assert_eq!(report, RotationReport { messages: 2, scheduled: 1, webhook_secrets: 1 });
+    let report = encryption::rotate(raw.as_ref(), &rotating).await.unwrap();
+    assert_eq!(report, RotationReport::default());
+
+    // dopo la rotazione basta la chiave nuova
+    let only_new = keyring(&[NEW_KEY]);
+    let store = EncryptedStore::new(raw.clone(), only_new.clone());
+    assert_eq!(store.find_message("legacy").await.unwrap().unwrap().content, "salvato in chiaro");
+    assert_eq!(store.find_message("m1").await.unwrap().unwrap().content, "vecchio");
+    assert_eq!(field_key_id(&raw_content(raw.as_ref(), "legacy").await), Some("new"));
+    assert_eq!(store.scheduled_for_sender("u1").await.unwrap()[0].content, "programmato");
+    assert_eq!(store.find_webhook("w1").await.unwrap().unwrap().secret, "firma in chiaro");
+}
+
+// Test che verifica che i messaggi inviati via WS arrivino in chiaro ai client e siano cifrati nel DB
+#[tokio::test]
+async fn messages_sent_over_ws_are_encrypted_at_rest() {
+    let app = spawn_app_with(|pool| {
+        let store = Arc::new(EncryptedStore::new(Arc::new(SqliteStore::new(pool)), keyring(&[NEW_KEY])));
+        AppState::with_store(store, Config::default())
+    })
+    .await;
+    let (_, alice) = app.register("alice").await;
+    let group_id = app.create_group(&alice, "general", &[]).await;
+    let mut ws = app.ws_connect(&alice).await;
+    ws_send(&mut ws, &WsMessage::SendMessage(new_message(&group_id, "non leggibile dal disco"))).await;
+    match ws_recv_until(&mut ws, |m| matches!(m, WsMessage::Message(_))).await {
+        WsMessage::Message(m) => assert_eq!(m.content, "non leggibile dal disco"),
+        _ => unreachable!(),
+    }
+    let (_, body) = app.get_json(&alice, &format!("/api/groups/{}/messages", group_id)).await;
+    assert_eq!(body["messages"][0]["content"], "non leggibile dal disco");
+    let stored: String = sqlx::query_scalar("SELECT content FROM messages").fetch_one(&app.pool).await.unwrap();
+    assert!(stored.starts_with("enc:v1:new:"), "{}", stored);
+}
+
+// Test che verifica i comandi generate-key e rotate-key del binario su un database su file
+#[tokio::test]
+async fn binary_generates_keys_and_rotates() {
+    let td = TempDir::new().unwrap();
+    let db = td.path().join("ruggine.db");
+    let pool = connect_pool(&sqlite_url_for_path(&db).unwrap()).await.unwrap();
+    run_migrations(&pool).await.unwrap();
+    let raw: Arc<dyn Store> = Arc::new(SqliteStore::new(pool));
+    let user = User { user_id: "u1".into(), username: "alice".into(), created_at: "t".into() };
+    raw.create_user(&user, "hash").await.unwrap();
+    let group =
+        Group { group_id: "g1".into(), name: "g".into(), created_at: "t".into(), pin_policy: PinPolicy::Members, encrypted: false, retention: RetentionPolicy::default(), message_ttl_secs: None };
+    raw.create_group(&group, &[("u1".into(), Role::Admin)]).await.unwrap();
+    raw.insert_message(&message("m1", "in chiaro")).await.unwrap();
+    raw.close().await;
+
+    let out = cargo_bin_cmd!("ruggine-server").current_dir(td.path()).arg("generate-key").assert().success();
+    let line = String::from_utf8(out.get_output().stdout.clone()).unwrap();
+    let key_file = td.path().join("keys");
+    std::fs::write(&key_file, &line).unwrap();
+
+    let args = ["--database-url", db.to_str().unwrap()];
+    cargo_bin_cmd!("ruggine-server").current_dir(td.path()).args(args).arg("rotate-key").assert().failure();
+    let out = cargo_bin_cmd!("ruggine-server")
+        .current_dir(td.path())
+        .args(args)
+        .args(["--encryption-key-file", key_file.to_str().unwrap(), "rotate-key"])
+        .assert()
+        .success();
+    let printed = String::from_utf8(out.get_output().stdout.clone()).unwrap();
+    assert!(printed.starts_with("re-encrypted 1 messages, 0 scheduled messages and 0 webhook secrets"), "{}", printed);
+
+    let pool = connect_pool(&sqlite_url_for_path(&db).unwrap()).await.unwrap();
+    let stored: String = sqlx::query_scalar("SELECT content FROM messages").fetch_one(&pool).await.unwrap();
+    let keys = Keyring::parse(&line).unwrap();
+    assert_eq!(field_key_id(&stored), Some(keys.active_key_id()));
+    assert_eq!(keys.decrypt_field(&stored, "m1").unwrap(), "in chiaro");
+}
@@ -0,0 +1,122 @@
+mod common;
+
+use common::{new_message, send_and_ack, spawn_app, ws_expect_silence, ws_recv_until, TestApp};
+use ruggine_core::e2ee::{ContentAlgorithm, EncryptedContent, KeyAlgorithm};
+use ruggine_core::{
+    AckStatus, DistributeSenderKeyRequest, PublicKeyResponse, PublishKeyRequest, SealedSenderKey, WsMessage,
+};
+
+async fn publish_key(app: &TestApp, token: &str, key_id: &str) -> u16 {
+    let req = PublishKeyRequest { key_id: key_id.to_string(), alg: KeyAlgorithm::X25519, public_key: "cHVia2V5".to_string() };
+    let resp = app.http.put(app.url("/api/keys")).bearer_auth(token).json(&req).send().await.expect("publish key");
+    resp.status().as_u16()
+}
+
+async fn distribute(app: &TestApp, token: &str, group_id: &str, keys: Vec<SealedSenderKey>) -> u16 {
+    let req = DistributeSenderKeyRequest { sender_key_id: "sk-alice-1".to_string(), keys };
+    let resp = app
+        .http
+        .post(app.url(&format!("/api/groups/{}/sender-keys", group_id)))
+        .bearer_auth(token)
+        .json(&req)
+        .send()
+        .await
+        .expect("distribute sender key");
+    resp.status().as_u16()
+}
+
+fn sealed_for(recipient_id: &str, recipient_key_id: &str) -> SealedSenderKey {
+    SealedSenderKey {
+        recipient_id: recipient_id.to_string(),
+        recipient_key_id: recipient_key_id.to_string(),
+        sealed_key: "c2VhbGVk".to_string(),
+    }
+}
+
+// Test che verifica key directory e distribuzione delle sender key: pubblicazione e rotazione della
+// chiave, chiavi dei membri, evento senderKey al destinatario e rifiuto di chiavi non aggiornate
+#[tokio::test]
+async fn key_directory_and_sender_key_distribution() {
+    let app = spawn_app().await;
+    let (alice_id, alice) = app.register("alice").await;
+    let (bob_id, bob) = app.register("bob").await;
+    let (carol_id, carol) = app.register("carol").await;
+    let group_id = app.create_group_with(&alice, "segreto", &[&bob_id], true).await;
+    let plain_id = app.create_group(&alice, "pubblico", &[&bob_id]).await;
+
+    assert_eq!(app.get_json(&alice, &format!("/api/keys/{}", bob_id)).await.0, 404);
+    assert_eq!(publish_key(&app, &bob, "bob-1").await, 200);
+    assert_eq!(publish_key(&app, &bob, "bob-2").await, 200);
+    assert_eq!(publish_key(&app, &carol, "carol-1").await, 200);
+    assert_eq!(publish_key(&app, &alice, "not a key id").await, 400);
+    let (status, body) = app.get_json(&alice, &format!("/api/keys/{}", bob_id)).await;
+    assert_eq!(status, 200);
+    let key: PublicKeyResponse = serde_json::from_value(body).unwrap();
+    assert_eq!((key.key.key_id.as_str(), key.key.alg), ("bob-2", KeyAlgorithm::X25519));
+
+    // solo i membri con una chiave pubblicata, e solo per i membri del gruppo
+    let (status, body) = app.get_json(&alice, &format!("/api/groups/{}/keys", group_id)).await;
+    assert_eq!(status, 200);
+    assert_eq!(body["keys"].as_array().unwrap().len(), 1);
+    assert_eq!(body["keys"][0]["userId"], bob_id.as_str());
+    assert_eq!(app.get_json(&carol, &format!("/api/groups/{}/keys", group_id)).await.0, 403);
+
+    let mut ws_bob = app.ws_connect(&bob).await;
+    assert_eq!(distribute(&app, &alice, &group_id, vec![sealed_for(&bob_id, "bob-1")]).await, 409);
+    assert_eq!(distribute(&app, &alice, &group_id, vec![sealed_for(&carol_id, "carol-1")]).await, 400);
+    assert_eq!(distribute(&app, &alice, &plain_id, vec![sealed_for(&bob_id, "bob-2")]).await, 400);
+    assert_eq!(distribute(&app, &carol, &group_id, vec![sealed_for(&bob_id, "bob-2")]).await, 403);
+    assert_eq!(distribute(&app, &alice, &group_id, vec![sealed_for(&bob_id, "bob-2")]).await, 204);
+
+    match ws_recv_until(&mut ws_bob, |m| matches!(m, WsMessage::SenderKey(_))).await {
+        WsMessage::SenderKey(k) => {
+            assert_eq!((k.sender_id.as_str(), k.sender_key_id.as_str()), (alice_id.as_str(), "sk-alice-1"));
+            assert_eq!(k.sealed_key, "c2VhbGVk");
+        }
+        _ => unreachable!(),
+    }
+    let (status, body) = app.get_json(&bob, &format!("/api/groups/{}/sender-keys", group_id)).await;
+    assert_eq!(status, 200);
+    assert_eq!(body["senderKeys"].as_array().unwrap().len(), 1);
+    assert_eq!(body["senderKeys"][0]["recipientKeyId"], "bob-2");
+    let (_, body) = app.get_json(&alice, &format!("/api/groups/{}/sender-keys", group_id)).await;
+    assert!(body["senderKeys"].as_array().unwrap().is_empty());
+}
+
+// Test che verifica che un gruppo cifrato accetti solo contenuto cifrato, lo salvi così com'è
+// e non generi menzioni
+#[tokio::test]
+async fn encrypted_group_accepts_only_ciphertext() {
+    let app = spawn_app().await;
+    let (_, alice) = app.register("alice").await;
+    let (bob_id, bob) = app.register("bob").await;
+    let group_id = app.create_group_with(&alice, "segreto", &[&bob_id], true).await;
+    let (_, body) = app.get_json(&alice, "/api/groups").await;
+    assert_eq!(body["groups"][0]["encrypted"], true);
+
+    let mut ws_alice = app.ws_connect(&alice).await;
+    let mut ws_bob = app.ws_connect(&bob).await;
+    let ciphertext = EncryptedContent {
+        alg: ContentAlgorithm::XChaCha20Poly1305,
+        sender_key_id: "sk-alice-1".to_string(),
+        nonce: "bm9uY2U=".to_string(),
+        ciphertext: "QGJvYiBjaWFv".to_string(),
+    }
+    .encode();
+    for (content, expected) in [("@bob ciao", AckStatus::Error), (ciphertext.as_str(), AckStatus::Ok)] {
+        let ack = send_and_ack(&mut ws_alice, new_message(&group_id, content)).await;
+        assert_eq!(ack.status, expected, "{:?}", ack.error);
+    }
+
+    match ws_recv_until(&mut ws_bob, |m| matches!(m, WsMessage::Message(_))).await {
+        WsMessage::Message(m) => {
+            assert_eq!(m.content, ciphertext);
+            assert!(m.mentions.is_empty());
+        }
+        _ => unreachable!(),
+    }
+    ws_expect_silence(&mut ws_bob, 200).await;
+    let (_, body) = app.get_json(&bob, &format!("/api/groups/{}/messages", group_id)).await;
+    assert_eq!(body["messages"].as_array().unwrap().len(), 1);
+    assert_eq!(body["messages"][0]["content"], ciphertext.as_str());
+}
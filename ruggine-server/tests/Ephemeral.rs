@@ -0,0 +1,109 @@
+mod common;
+
+use common::{new_message, send_and_ack, spawn_app, spawn_app_with, spawn_node, ws_expect_silence, ws_recv_until, Ws};
+use ruggine_core::{error::codes, unix_from_timestamp, Ack, AckStatus, Message, SendMessage, WsMessage};
+use ruggine_server::config::Config;
+use ruggine_server::ephemeral::{self, EphemeralConfig};
+use ruggine_server::AppState;
+use serde_json::json;
+use std::time::Duration;
+
+async fn send(ws: &mut Ws, group_id: &str, content: &str, ttl_secs: Option<u64>) -> Ack {
+    send_and_ack(ws, SendMessage { ttl_secs, ..new_message(group_id, content) }).await
+}
+
+async fn recv_message(ws: &mut Ws, message_id: &str) -> Message {
+    match ws_recv_until(ws, |m| matches!(m, WsMessage::Message(msg) if msg.message_id == message_id)).await {
+        WsMessage::Message(msg) => msg,
+        _ => unreachable!(),
+    }
+}
+
+// Test che verifica un messaggio con ttl: porta la sua scadenza, sparisce dalla cronologia appena
+// scaduto (prima ancora della pulizia), poi viene eliminato con un avviso messageExpired ai membri
+#[tokio::test]
+async fn ephemeral_message_expires_and_members_are_notified() {
+    let app = spawn_app().await;
+    let (_, alice) = app.register("alice").await;
+    let (bob_id, bob) = app.register("bob").await;
+    let group_id = app.create_group(&alice, "g", &[bob_id.as_str()]).await;
+    let mut ws_alice = app.ws_connect(&alice).await;
+    let mut ws_bob = app.ws_connect(&bob).await;
+
+    let ack = send(&mut ws_alice, &group_id, "mai", Some(0)).await;
+    assert_eq!(ack.status, AckStatus::Error);
+    assert_eq!(ack.error.unwrap().code, codes::BAD_REQUEST);
+
+    let ephemeral_id = send(&mut ws_alice, &group_id, "sparisce", Some(1)).await.message_id.unwrap();
+    let msg = recv_message(&mut ws_bob, &ephemeral_id).await;
+    let expires_at = unix_from_timestamp(msg.expires_at.as_deref().expect("expiresAt")).unwrap();
+    assert!(expires_at - unix_from_timestamp(&msg.created_at).unwrap() >= 1);
+    let kept_id = send(&mut ws_alice, &group_id, "resta", None).await.message_id.unwrap();
+    assert_eq!(recv_message(&mut ws_bob, &kept_id).await.expires_at, None);
+
+    let path = format!("/api/groups/{}/messages", group_id);
+    let (_, body) = app.get_json(&bob, &path).await;
+    assert_eq!(body["messages"].as_array().unwrap().len(), 2);
+
+    tokio::time::sleep(Duration::from_millis(2100)).await;
+    let (_, body) = app.get_json(&bob, &path).await;
+    let ids: Vec<&str> = body["messages"].as_array().unwrap().iter().map(|m| m["messageId"].as_str().unwrap()).collect();
+    assert_eq!(ids, [kept_id.as_str()]);
+
+    assert_eq!(ephemeral::run_once(&app.state).await.unwrap(), 1);
+    match ws_recv_until(&mut ws_bob, |m| matches!(m, WsMessage::MessageExpired(_))).await {
+        WsMessage::MessageExpired(ev) => {
+            assert_eq!(ev.group_id, group_id);
+            assert_eq!(ev.message_ids, [ephemeral_id]);
+        }
+        _ => unreachable!(),
+    }
+    assert_eq!(ephemeral::run_once(&app.state).await.unwrap(), 0);
+    ws_expect_silence(&mut ws_bob, 300).await;
+}
+
+// Test che verifica la durata di default del gruppo (impostabile solo dall'admin, entro max_ttl_secs)
+// e che la scadenza salvata viene rispettata anche da un'istanza avviata dopo l'invio
+#[tokio::test]
+async fn group_default_ttl_is_persisted_with_messages() {
+    let config = Config { ephemeral: EphemeralConfig { max_ttl_secs: 3600, ..EphemeralConfig::default() }, ..Config::default() };
+    let app = spawn_app_with(move |pool| AppState::with_config(pool, config)).await;
+    let (_, alice) = app.register("alice").await;
+    let (bob_id, bob) = app.register("bob").await;
+    let group_id = app.create_group(&alice, "g", &[bob_id.as_str()]).await;
+
+    let url = app.url(&format!("/api/groups/{}", group_id));
+    let resp = app.http.patch(&url).bearer_auth(&bob).json(&json!({"messageTtlSecs": 60})).send().await.unwrap();
+    assert_eq!(resp.status(), 403);
+    let resp = app.http.patch(&url).bearer_auth(&alice).json(&json!({"messageTtlSecs": 7200})).send().await.unwrap();
+    assert_eq!(resp.status(), 400);
+    let resp = app.http.patch(&url).bearer_auth(&alice).json(&json!({"messageTtlSecs": 60})).send().await.unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["group"]["messageTtlSecs"], 60);
+
+    let mut ws = app.ws_connect(&alice).await;
+    let mut ws_bob = app.ws_connect(&bob).await;
+    let minute_id = send(&mut ws, &group_id, "un minuto", None).await.message_id.unwrap();
+    let msg = recv_message(&mut ws_bob, &minute_id).await;
+    let ttl = unix_from_timestamp(msg.expires_at.as_deref().expect("group default")).unwrap()
+        - unix_from_timestamp(&msg.created_at).unwrap();
+    assert!((60..=61).contains(&ttl), "ttl {}", ttl);
+    // il ttl del messaggio prevale sul default del gruppo
+    let second_id = send(&mut ws, &group_id, "un secondo", Some(1)).await.message_id.unwrap();
+
+    let resp = app.http.patch(&url).bearer_auth(&alice).json(&json!({"messageTtlSecs": 0})).send().await.unwrap();
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert!(body["group"].get("messageTtlSecs").is_none());
+    let plain_id = send(&mut ws, &group_id, "per sempre", None).await.message_id.unwrap();
+    assert_eq!(recv_message(&mut ws_bob, &plain_id).await.expires_at, None);
+
+    // un'altra istanza sullo stesso database (es. dopo un riavvio) elimina il messaggio scaduto
+    tokio::time::sleep(Duration::from_millis(2100)).await;
+    let node = spawn_node(&app, AppState::new).await;
+    assert_eq!(ephemeral::run_once(&node.state).await.unwrap(), 1);
+    assert_eq!(app.state.store.find_message(&second_id).await.unwrap(), None);
+    let left: Vec<String> =
+        app.state.store.messages_after(&group_id, 0, 10).await.unwrap().into_iter().map(|m| m.message_id).collect();
+    assert_eq!(left, [minute_id, plain_id]);
+}
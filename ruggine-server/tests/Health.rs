@@ -0,0 +1,65 @@
+mod common;
+
+use common::{spawn_app, spawn_app_on_disk_with};
+use ruggine_core::{HealthResponse, HealthStatus};
+use ruggine_server::{config::Config, AppState, SCHEMA_VERSION};
+
+async fn ready(app: &common::TestApp) -> (u16, HealthResponse) {
+    let resp = app.http.get(app.url("/health/ready")).send().await.expect("ready");
+    let status = resp.status().as_u16();
+    (status, resp.json().await.expect("health body"))
+}
+
+// Test che verifica liveness e readiness di un server appena avviato, con versione e componenti
+#[tokio::test]
+async fn live_and_ready_report_ok() {
+    let app = spawn_app().await;
+    let resp = app.http.get(app.url("/health/live")).send().await.expect("live");
+    assert_eq!(resp.status(), 200);
+    let live: HealthResponse = resp.json().await.expect("live body");
+    assert_eq!(live.status, HealthStatus::Ok);
+    assert_eq!(live.version, env!("CARGO_PKG_VERSION"));
+
+    let (status, report) = ready(&app).await;
+    assert_eq!(status, 200, "{:?}", report);
+    for name in ["database", "migrations", "diskDatabase", "diskUploads", "shutdown"] {
+        assert_eq!(report.components[name].status, HealthStatus::Ok, "{}", name);
+    }
+    assert_eq!(
+        report.components["migrations"].detail.as_deref(),
+        Some(format!("schema version {}", SCHEMA_VERSION).as_str())
+    );
+}
+
+// Test che verifica il 503 quando lo schema non è alla versione attesa o l'arresto è in corso
+#[tokio::test]
+async fn ready_fails_on_schema_mismatch_and_shutdown() {
+    let app = spawn_app().await;
+    sqlx::query("PRAGMA user_version = 0").execute(&app.pool).await.unwrap();
+    let (status, report) = ready(&app).await;
+    assert_eq!(status, 503);
+    assert_eq!(report.status, HealthStatus::Fail);
+    assert_eq!(report.components["migrations"].status, HealthStatus::Fail);
+    assert_eq!(report.components["database"].status, HealthStatus::Ok);
+
+    sqlx::query(&format!("PRAGMA user_version = {}", SCHEMA_VERSION)).execute(&app.pool).await.unwrap();
+    app.state.shutdown.trigger();
+    let (status, report) = ready(&app).await;
+    assert_eq!(status, 503);
+    assert_eq!(report.components["shutdown"].status, HealthStatus::Fail);
+    assert_eq!(report.components["migrations"].status, HealthStatus::Ok);
+}
+
+// Test che verifica il controllo dello spazio libero con una soglia irraggiungibile
+#[tokio::test]
+async fn ready_fails_when_disk_space_is_low() {
+    let app = spawn_app_on_disk_with(|pool| {
+        AppState::with_config(pool, Config { min_free_disk_mb: u64::MAX / (1024 * 1024), ..Config::default() })
+    })
+    .await;
+    let (status, report) = ready(&app).await;
+    assert_eq!(status, 503);
+    assert_eq!(report.components["diskDatabase"].status, HealthStatus::Fail);
+    assert_eq!(report.components["diskUploads"].status, HealthStatus::Fail);
+    assert!(report.components["diskUploads"].detail.as_deref().unwrap().contains("required"));
+}
@@ -0,0 +1,165 @@
+mod common;
+
+use assert_cmd::cargo::cargo_bin_cmd;
+use common::spawn_app_on_disk_with;
+use ruggine_server::import::{self, ImportFormat, ImportOptions, NO_PASSWORD_HASH};
+use ruggine_server::store::{GroupStore, MembershipStore, MessageStore, SqliteStore, Store, UserStore};
+use ruggine_server::AppState;
+use serde_json::json;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use tempfile::TempDir;
+
+fn write_slack_export(path: &Path) {
+    let files = [
+        (
+            "users.json",
+            json!([
+                {"id": "U1", "name": "alice"},
+                {"id": "U2", "name": "bob"},
+                {"id": "B1", "name": "deploybot", "is_bot": true}
+            ]),
+        ),
+        (
+            "channels.json",
+            json!([{"id": "C1", "name": "general", "created": 1700000000, "creator": "U2", "members": ["U1", "U2"]}]),
+        ),
+        ("dms.json", json!([{"id": "D1", "created": 1700000000, "members": ["U1", "U2"]}])),
+        (
+            "general/2023-11-15.json",
+            json!([{"type": "message", "user": "U2", "text": "risposta", "ts": "1700086400.000100"}]),
+        ),
+        (
+            "general/2023-11-14.json",
+            json!([
+                {"type": "message", "subtype": "channel_join", "user": "U1", "text": "<@U1> has joined", "ts": "1699999999.000100"},
+                {"type": "message", "user": "U1", "text": "<@U2> ciao &amp; benvenuto in <#C1|general>: <https://example.org|sito>", "ts": "1700000001.200300"},
+                {"type": "message", "subtype": "bot_message", "bot_id": "B1", "text": "deployed", "ts": "1700000002.000000"}
+            ]),
+        ),
+        ("D1/2023-11-14.json", json!([{"type": "message", "user": "U2", "text": "privato", "ts": "1700000005.000000"}])),
+    ];
+    let mut zip = zip::ZipWriter::new(fs::File::create(path).unwrap());
+    for (name, content) in files {
+        zip.start_file(name, zip::write::SimpleFileOptions::default()).unwrap();
+        zip.write_all(content.to_string().as_bytes()).unwrap();
+    }
+    zip.finish().unwrap();
+}
+
+// Test che verifica l'importazione di un export Slack: il dry run non scrive nulla, l'importazione
+// associa gli utenti esistenti, crea gli altri senza password e conserva mittenti, timestamp, ordine
+// e menzioni; una seconda importazione salta i canali già importati
+#[tokio::test]
+async fn slack_export_is_imported_once() {
+    let td = TempDir::new().unwrap();
+    let file = td.path().join("slack.zip");
+    write_slack_export(&file);
+    let app = spawn_app_on_disk_with(AppState::new).await;
+    let (alice, alice_token) = app.register("alice").await;
+    let store = app.state.store.clone();
+
+    let export = import::read(ImportFormat::Slack, &file).unwrap();
+    let dry = import::import(store.as_ref(), &export, &ImportOptions { dry_run: true, admin: None }).await.unwrap();
+    assert_eq!(dry.users_created, ["bob"]);
+    assert_eq!(dry.users_matched, ["alice"]);
+    let names: Vec<&str> = dry.groups.iter().map(|g| g.name.as_str()).collect();
+    assert_eq!(names, ["general", "dm-alice-bob"]);
+    assert_eq!((dry.groups[0].messages, dry.groups[0].skipped), (2, 2));
+    assert_eq!(dry.messages(), 3);
+    assert!(dry.to_string().starts_with("dry run: nothing was written\n"), "{}", dry);
+    assert!(store.credentials("bob").await.unwrap().is_none());
+    assert!(store.list_groups().await.unwrap().is_empty());
+
+    let report = import::import(store.as_ref(), &export, &ImportOptions::default()).await.unwrap();
+    assert_eq!(report.messages(), 3);
+    let bob = store.credentials("bob").await.unwrap().expect("bob created");
+    assert_eq!(bob.password_hash, NO_PASSWORD_HASH);
+    let general = &report.groups[0].group_id;
+    assert_eq!(store.find_group(general).await.unwrap().unwrap().created_at, "2023-11-14T22:13:20Z");
+    assert_eq!(store.member_role(general, &bob.user.user_id).await.unwrap().unwrap().as_str(), "admin");
+    assert_eq!(store.member_role(general, &alice).await.unwrap().unwrap().as_str(), "member");
+    let (status, groups) = app.get_json(&alice_token, "/api/groups").await;
+    assert_eq!(status, 200);
+    assert_eq!(groups["groups"].as_array().unwrap().len(), 2);
+
+    let mut messages = store.messages_after(general, 0, 10).await.unwrap();
+    ruggine_server::db::hydrate(store.as_ref(), &mut messages, "").await.unwrap();
+    let got: Vec<(u64, &str, &str, &str)> = messages
+        .iter()
+        .map(|m| (m.seq, m.sender_id.as_str(), m.created_at.as_str(), m.content.as_str()))
+        .collect();
+    assert_eq!(
+        got,
+        [
+            (1, alice.as_str(), "2023-11-14T22:13:21.2Z", "@bob ciao & benvenuto in #general: sito (https://example.org)"),
+            (2, bob.user.user_id.as_str(), "2023-11-15T22:13:20Z", "risposta"),
+        ]
+    );
+    assert_eq!(messages[0].mentions, std::slice::from_ref(&bob.user.user_id));
+
+    let again = import::import(store.as_ref(), &export, &ImportOptions::default()).await.unwrap();
+    assert!(again.users_created.is_empty());
+    assert!(again.groups.iter().all(|g| !g.imported));
+    assert_eq!(again.messages(), 0);
+    assert_eq!(store.messages_after(general, 0, 10).await.unwrap().len(), 2);
+}
+
+// Test che verifica il comando import del binario con un export di stanza Matrix: membri finali,
+// creatore e --admin come amministratori, messaggi di testo con il timestamp originale
+#[tokio::test]
+async fn binary_imports_matrix_room() {
+    let td = TempDir::new().unwrap();
+    let db = td.path().join("ruggine.db");
+    let cli = |args: &[&str]| {
+        let mut cmd = cargo_bin_cmd!("ruggine-server");
+        cmd.current_dir(td.path()).args(["--database-url", db.to_str().unwrap()]).args(args);
+        cmd
+    };
+    let room = json!({
+        "room_id": "!abc:example.org",
+        "room_name": "progetto",
+        "room_creator": "@alice:example.org",
+        "messages": [
+            {"type": "m.room.create", "sender": "@alice:example.org", "origin_server_ts": 1700000000000i64, "content": {}},
+            {"type": "m.room.member", "sender": "@alice:example.org", "state_key": "@alice:example.org", "origin_server_ts": 1700000000100i64, "content": {"membership": "join"}},
+            {"type": "m.room.member", "sender": "@bob:example.org", "state_key": "@bob:example.org", "origin_server_ts": 1700000000200i64, "content": {"membership": "join"}},
+            {"type": "m.room.message", "sender": "@bob:example.org", "event_id": "$1", "origin_server_ts": 1700000001000i64, "content": {"msgtype": "m.text", "body": "ciao @alice"}},
+            {"type": "m.room.message", "sender": "@alice:example.org", "event_id": "$2", "origin_server_ts": 1700000002000i64, "content": {"msgtype": "m.image", "body": "foto.png"}},
+            {"type": "m.room.message", "sender": "@alice:example.org", "event_id": "$3", "origin_server_ts": 1700000003000i64, "content": {"msgtype": "m.emote", "body": "saluta"}},
+            {"type": "m.room.member", "sender": "@bob:example.org", "state_key": "@bob:example.org", "origin_server_ts": 1700000004000i64, "content": {"membership": "leave"}}
+        ]
+    });
+    let file = td.path().join("room.json");
+    fs::write(&file, room.to_string()).unwrap();
+    cli(&["create-user", "carol", "--password", "pw"]).assert().success();
+    let path = file.to_str().unwrap();
+
+    let out = cli(&["import", path, "--format", "matrix", "--dry-run"]).assert().success();
+    let printed = String::from_utf8(out.get_output().stdout.clone()).unwrap();
+    assert!(printed.contains("progetto") && printed.contains("would import 2 messages"), "{}", printed);
+    let out = cli(&["list-groups"]).assert().success();
+    assert_eq!(String::from_utf8(out.get_output().stdout.clone()).unwrap().lines().count(), 1);
+
+    cli(&["import", path, "--format", "matrix", "--admin", "nobody"]).assert().failure();
+    let out = cli(&["import", path, "--format", "matrix", "--admin", "carol"]).assert().success();
+    let printed = String::from_utf8(out.get_output().stdout.clone()).unwrap();
+    assert!(printed.contains("users: 2 new, 0 matched"), "{}", printed);
+    let out = cli(&["list-groups"]).assert().success();
+    let listed = String::from_utf8(out.get_output().stdout.clone()).unwrap();
+    let row: Vec<&str> = listed.lines().nth(1).unwrap().split('\t').collect();
+    assert_eq!(&row[1..], ["progetto", "2023-11-14T22:13:20Z", "2", "2"]);
+
+    let store = SqliteStore::connect(db.to_str().unwrap(), 1).await.unwrap();
+    let alice = store.credentials("alice").await.unwrap().unwrap().user.user_id;
+    let carol = store.credentials("carol").await.unwrap().unwrap().user.user_id;
+    let group_id = row[0];
+    assert!(store.find_group(group_id).await.unwrap().is_some());
+    assert_eq!(store.member_role(group_id, &alice).await.unwrap().unwrap().as_str(), "admin");
+    assert_eq!(store.member_role(group_id, &carol).await.unwrap().unwrap().as_str(), "admin");
+    let messages = store.messages_after(group_id, 0, 10).await.unwrap();
+    let got: Vec<(&str, &str)> = messages.iter().map(|m| (m.created_at.as_str(), m.content.as_str())).collect();
+    assert_eq!(got, [("2023-11-14T22:13:21Z", "ciao @alice"), ("2023-11-14T22:13:23Z", "* alice saluta")]);
+    store.close().await;
+}
@@ -0,0 +1,135 @@
+mod common;
+
+use common::{new_message, send_message, spawn_app, ws_expect_silence, ws_recv_until, ws_send};
+use ruggine_core::{new_client_msg_id, PinCommand, ReactionCommand, WsMessage};
+
+// Test che verifica che solo i membri menzionati ricevano l'evento mention
+// e che la menzione compaia nel messaggio, nello storico e nell'inbox
+#[tokio::test]
+async fn mentioned_members_get_event_and_inbox_entry() {
+    let app = spawn_app().await;
+    let (_, alice) = app.register("alice").await;
+    let (bob_id, bob) = app.register("bob").await;
+    let (carol_id, carol) = app.register("carol").await;
+    let (_, dave) = app.register("dave").await; // non membro: la sua menzione viene ignorata
+    let group_id = app.create_group(&alice, "team", &[&bob_id, &carol_id]).await;
+
+    let mut ws_bob = app.ws_connect(&bob).await;
+    let mut ws_carol = app.ws_connect(&carol).await;
+    let mut ws_alice = app.ws_connect(&alice).await;
+    send_message(&mut ws_alice, &group_id, "@bob @dave @alice puoi controllare?").await;
+
+    match ws_recv_until(&mut ws_bob, |m| matches!(m, WsMessage::Mention(_))).await {
+        WsMessage::Mention(mention) => {
+            assert_eq!(mention.group_name, "team");
+            assert_eq!(mention.message.mentions, vec![bob_id.clone()]);
+        }
+        _ => unreachable!(),
+    }
+    // carol riceve il messaggio ma nessuna menzione
+    match ws_recv_until(&mut ws_carol, |m| matches!(m, WsMessage::Message(_))).await {
+        WsMessage::Message(m) => assert_eq!(m.mentions, vec![bob_id.clone()]),
+        _ => unreachable!(),
+    }
+    ws_expect_silence(&mut ws_carol, 200).await;
+
+    let (status, body) = app.get_json(&bob, "/api/mentions").await;
+    assert_eq!(status, 200);
+    assert_eq!(body["mentions"].as_array().expect("array").len(), 1);
+    assert_eq!(body["mentions"][0]["message"]["content"], "@bob @dave @alice puoi controllare?");
+
+    let (_, body) = app.get_json(&carol, &format!("/api/groups/{}/messages", group_id)).await;
+    assert_eq!(body["messages"][0]["mentions"][0], bob_id.as_str());
+
+    let (_, body) = app.get_json(&dave, "/api/mentions").await;
+    assert!(body["mentions"].as_array().expect("array").is_empty());
+}
+
+// Test che verifica che un membro che ha silenziato il gruppo non riceva più i messaggi sul WebSocket
+// ma continui a ricevere l'evento mention, e che riattivandolo torni a riceverli
+#[tokio::test]
+async fn muted_members_only_get_mentions() {
+    let app = spawn_app().await;
+    let (_, alice) = app.register("alice").await;
+    let (bob_id, bob) = app.register("bob").await;
+    let (_, carol) = app.register("carol").await;
+    let group_id = app.create_group(&alice, "team", &[&bob_id]).await;
+    let mute_url = app.url(&format!("/api/groups/{}/mute", group_id));
+
+    let res = app.http.put(&mute_url).bearer_auth(&carol).json(&serde_json::json!({ "muted": true })).send().await.unwrap();
+    assert_eq!(res.status(), 403);
+    let res = app.http.put(&mute_url).bearer_auth(&bob).json(&serde_json::json!({ "muted": true })).send().await.unwrap();
+    assert_eq!(res.status(), 204);
+
+    let mut ws_bob = app.ws_connect(&bob).await;
+    let mut ws_alice = app.ws_connect(&alice).await;
+    ws_send(&mut ws_alice, &WsMessage::SendMessage(new_message(&group_id, "nessuna menzione"))).await;
+    // il mittente riceve comunque il proprio messaggio
+    ws_recv_until(&mut ws_alice, |m| matches!(m, WsMessage::Message(_))).await;
+    ws_expect_silence(&mut ws_bob, 300).await;
+
+    send_message(&mut ws_alice, &group_id, "@bob urgente").await;
+    match ws_recv_until(&mut ws_bob, |m| matches!(m, WsMessage::Message(_) | WsMessage::Mention(_))).await {
+        WsMessage::Mention(mention) => assert_eq!(mention.message.content, "@bob urgente"),
+        other => panic!("expected only the mention, got {:?}", other),
+    }
+    ws_expect_silence(&mut ws_bob, 300).await;
+    // lo storico resta completo
+    let (_, body) = app.get_json(&bob, &format!("/api/groups/{}/messages", group_id)).await;
+    assert_eq!(body["messages"].as_array().expect("array").len(), 2);
+
+    let res = app.http.put(&mute_url).bearer_auth(&bob).json(&serde_json::json!({ "muted": false })).send().await.unwrap();
+    assert_eq!(res.status(), 204);
+    send_message(&mut ws_alice, &group_id, "di nuovo").await;
+    match ws_recv_until(&mut ws_bob, |m| matches!(m, WsMessage::Message(_))).await {
+        WsMessage::Message(m) => assert_eq!(m.content, "di nuovo"),
+        _ => unreachable!(),
+    }
+}
+
+// Test che verifica che chi ha silenziato il gruppo non riceva reazioni e pin, salvo quelli sui
+// messaggi in cui è menzionato
+#[tokio::test]
+async fn muted_members_skip_reactions_and_pins_unless_mentioned() {
+    let app = spawn_app().await;
+    let (_, alice) = app.register("alice").await;
+    let (bob_id, bob) = app.register("bob").await;
+    let group_id = app.create_group(&alice, "team", &[&bob_id]).await;
+    let res = app
+        .http
+        .put(app.url(&format!("/api/groups/{}/mute", group_id)))
+        .bearer_auth(&bob)
+        .json(&serde_json::json!({ "muted": true }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), 204);
+
+    let mut ws_bob = app.ws_connect(&bob).await;
+    let mut ws_alice = app.ws_connect(&alice).await;
+    let mut message_ids = Vec::new();
+    for content in ["nessuna menzione", "@bob guarda qui"] {
+        message_ids.push(send_message(&mut ws_alice, &group_id, content).await);
+    }
+    ws_recv_until(&mut ws_bob, |m| matches!(m, WsMessage::Mention(_))).await;
+
+    for (message_id, mentioned) in message_ids.iter().zip([false, true]) {
+        let reaction = ReactionCommand { client_msg_id: new_client_msg_id(), message_id: message_id.clone(), emoji: "👍".to_string() };
+        ws_send(&mut ws_alice, &WsMessage::AddReaction(reaction)).await;
+        ws_recv_until(&mut ws_alice, |m| matches!(m, WsMessage::ReactionChanged(_))).await;
+        let pin = PinCommand { client_msg_id: new_client_msg_id(), message_id: message_id.clone() };
+        ws_send(&mut ws_alice, &WsMessage::PinMessage(pin)).await;
+        ws_recv_until(&mut ws_alice, |m| matches!(m, WsMessage::PinChanged(_))).await;
+        if mentioned {
+            match ws_recv_until(&mut ws_bob, |m| matches!(m, WsMessage::ReactionChanged(_))).await {
+                WsMessage::ReactionChanged(r) => assert_eq!(&r.message_id, message_id),
+                _ => unreachable!(),
+            }
+            match ws_recv_until(&mut ws_bob, |m| matches!(m, WsMessage::PinChanged(_))).await {
+                WsMessage::PinChanged(p) => assert_eq!(&p.message_id, message_id),
+                _ => unreachable!(),
+            }
+        }
+        ws_expect_silence(&mut ws_bob, 300).await;
+    }
+}
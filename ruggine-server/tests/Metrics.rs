@@ -0,0 +1,59 @@
+mod common;
+
+use common::{send_message, spawn_app};
+use ruggine_core::{LoginRequest, LoginResponse};
+
+// Valore di una serie nel formato testuale di Prometheus (la riga che inizia con `series `)
+fn sample(body: &str, series: &str) -> Option<f64> {
+    body.lines()
+        .find_map(|l| l.strip_prefix(series).and_then(|rest| rest.strip_prefix(' ')))
+        .and_then(|v| v.trim().parse().ok())
+}
+
+// Test che verifica che /metrics esponga richieste per rotta (con il pattern, non l'id), login,
+// messaggi inviati, connessioni WS, fan-out, query SQLite e stato del pool
+#[tokio::test]
+async fn metrics_reflect_activity() {
+    let app = spawn_app().await;
+    let (_, alice) = app.register("alice").await;
+    let group_id = app.create_group(&alice, "general", &[]).await;
+    let (status, _) = app.get_json(&alice, &format!("/api/groups/{}/messages", group_id)).await;
+    assert_eq!(status, 200);
+
+    let mut alice = alice;
+    for password in ["wrong", "secret"] {
+        let resp = app
+            .http
+            .post(app.url("/api/login"))
+            .json(&LoginRequest { username: "alice".to_string(), password: password.to_string() })
+            .send()
+            .await
+            .expect("login");
+        if resp.status() == 200 {
+            // il login ruota il token
+            alice = resp.json::<LoginResponse>().await.expect("login body").token;
+        }
+    }
+    let (_, bob) = app.register("bob").await;
+    let ws_bob = app.ws_connect(&bob).await;
+    let mut ws_alice = app.ws_connect(&alice).await;
+    send_message(&mut ws_alice, &group_id, "ciao").await;
+
+    let resp = app.http.get(app.url("/metrics")).send().await.expect("metrics");
+    assert_eq!(resp.status(), 200);
+    assert!(resp.headers()["content-type"].to_str().unwrap().starts_with("text/plain"));
+    let body = resp.text().await.expect("body");
+
+    let route = r#"ruggine_http_requests_total{method="GET",route="/api/groups/:group_id/messages",status="200"}"#;
+    assert_eq!(sample(&body, route), Some(1.0), "{}", body);
+    assert!(!body.contains(&group_id), "ids must not appear in labels");
+    assert_eq!(sample(&body, r#"ruggine_logins_total{outcome="failure"}"#), Some(1.0));
+    assert_eq!(sample(&body, r#"ruggine_logins_total{outcome="success"}"#), Some(1.0));
+    assert_eq!(sample(&body, "ruggine_messages_sent_total"), Some(1.0));
+    assert_eq!(sample(&body, "ruggine_ws_connections"), Some(2.0));
+    assert_eq!(sample(&body, r#"ruggine_fanout_duration_seconds_count{event="message"}"#), Some(1.0));
+    assert!(sample(&body, r#"ruggine_db_query_duration_seconds_count{query="insert_message"}"#).unwrap_or(0.0) >= 1.0);
+    assert!(sample(&body, "ruggine_db_pool_connections").unwrap_or(0.0) >= 1.0);
+    assert_eq!(sample(&body, "ruggine_db_pool_max_connections"), Some(10.0));
+    drop(ws_bob);
+}
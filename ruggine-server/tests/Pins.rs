@@ -0,0 +1,100 @@
+mod common;
+
+use common::{send_message, spawn_app, ws_recv_until, ws_send, Ws};
+use ruggine_core::{new_client_msg_id, AckStatus, PinCommand, PinPolicy, UpdateGroupRequest, WsMessage};
+
+// Invia pin/unpin e ritorna lo stato dell'Ack con l'eventuale codice di errore
+async fn pin_cmd(ws: &mut Ws, message_id: &str, pinned: bool) -> (AckStatus, Option<String>) {
+    let pc = PinCommand { client_msg_id: new_client_msg_id(), message_id: message_id.to_string() };
+    let cmd = if pinned { WsMessage::PinMessage(pc.clone()) } else { WsMessage::UnpinMessage(pc.clone()) };
+    ws_send(ws, &cmd).await;
+    match ws_recv_until(ws, |m| matches!(m, WsMessage::Ack(a) if a.in_reply_to == pc.client_msg_id)).await {
+        WsMessage::Ack(ack) => (ack.status, ack.error.map(|e| e.code)),
+        _ => unreachable!(),
+    }
+}
+
+// Test che verifica pin da parte di un membro, evento pinChanged, elenco dei fissati
+// e restrizione agli admin tramite pinPolicy
+#[tokio::test]
+async fn pin_policy_controls_who_can_pin() {
+    let app = spawn_app().await;
+    let (_, alice) = app.register("alice").await;
+    let (bob_id, bob) = app.register("bob").await;
+    let group_id = app.create_group(&alice, "general", &[&bob_id]).await;
+
+    let mut ws_alice = app.ws_connect(&alice).await;
+    let mut ws_bob = app.ws_connect(&bob).await;
+    let message_id = send_message(&mut ws_alice, &group_id, "leggete le regole").await;
+
+    // policy di default: anche i membri possono fissare
+    assert_eq!(pin_cmd(&mut ws_bob, &message_id, true).await, (AckStatus::Ok, None));
+    match ws_recv_until(&mut ws_alice, |m| matches!(m, WsMessage::PinChanged(_))).await {
+        WsMessage::PinChanged(ev) => {
+            assert!(ev.pinned);
+            assert_eq!(ev.user_id, bob_id);
+            let pin = ev.pin.expect("pin");
+            assert_eq!(pin.message.content, "leggete le regole");
+            assert_eq!(pin.pinned_by, bob_id);
+        }
+        _ => unreachable!(),
+    }
+    let (status, body) = app.get_json(&bob, &format!("/api/groups/{}/pins", group_id)).await;
+    assert_eq!(status, 200);
+    assert_eq!(body["pins"][0]["message"]["messageId"], message_id.as_str());
+
+    // bob non è admin: non può cambiare le impostazioni
+    let update = UpdateGroupRequest { name: None, pin_policy: Some(PinPolicy::Admins), retention: None, message_ttl_secs: None };
+    let url = app.url(&format!("/api/groups/{}", group_id));
+    let resp = app.http.patch(&url).bearer_auth(&bob).json(&update).send().await.expect("patch");
+    assert_eq!(resp.status(), 403);
+    let resp = app.http.patch(&url).bearer_auth(&alice).json(&update).send().await.expect("patch");
+    assert_eq!(resp.status(), 200);
+    let body: serde_json::Value = resp.json().await.expect("body");
+    assert_eq!(body["group"]["pinPolicy"], "admins");
+
+    // ora solo l'admin può rimuovere il pin
+    assert_eq!(pin_cmd(&mut ws_bob, &message_id, false).await, (AckStatus::Error, Some("FORBIDDEN".to_string())));
+    assert_eq!(pin_cmd(&mut ws_alice, &message_id, false).await, (AckStatus::Ok, None));
+    match ws_recv_until(&mut ws_bob, |m| matches!(m, WsMessage::PinChanged(_))).await {
+        WsMessage::PinChanged(ev) => {
+            assert!(!ev.pinned);
+            assert!(ev.pin.is_none());
+        }
+        _ => unreachable!(),
+    }
+    let (_, body) = app.get_json(&bob, &format!("/api/groups/{}/pins", group_id)).await;
+    assert!(body["pins"].as_array().expect("array").is_empty());
+}
+
+// Test che verifica che il pin segua il contenuto aggiornato del messaggio e sparisca quando il messaggio viene cancellato
+#[tokio::test]
+async fn pin_survives_edit_and_disappears_with_message() {
+    let app = spawn_app().await;
+    let (_, alice) = app.register("alice").await;
+    let group_id = app.create_group(&alice, "notes", &[]).await;
+    let mut ws = app.ws_connect(&alice).await;
+    let message_id = send_message(&mut ws, &group_id, "bozza").await;
+    assert_eq!(pin_cmd(&mut ws, &message_id, true).await, (AckStatus::Ok, None));
+
+    sqlx::query("UPDATE messages SET content = 'versione finale' WHERE message_id = ?")
+        .bind(&message_id)
+        .execute(&app.pool)
+        .await
+        .expect("edit");
+    let (_, body) = app.get_json(&alice, &format!("/api/groups/{}/pins", group_id)).await;
+    assert_eq!(body["pins"][0]["message"]["content"], "versione finale");
+
+    sqlx::query("DELETE FROM messages WHERE message_id = ?")
+        .bind(&message_id)
+        .execute(&app.pool)
+        .await
+        .expect("delete");
+    let (_, body) = app.get_json(&alice, &format!("/api/groups/{}/pins", group_id)).await;
+    assert!(body["pins"].as_array().expect("array").is_empty());
+    let left: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM pinned_messages")
+        .fetch_one(&app.pool)
+        .await
+        .expect("count");
+    assert_eq!(left, 0);
+}
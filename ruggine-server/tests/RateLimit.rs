@@ -0,0 +1,111 @@
+mod common;
+
+use common::{new_message, send_and_ack, spawn_app_with};
+use ruggine_core::{AckStatus, LoginRequest, RegisterRequest};
+use ruggine_server::ratelimit::{BucketConfig, LockoutConfig, RateLimitConfig};
+use ruggine_server::{config::Config, AppState};
+use std::net::IpAddr;
+
+fn tight_limits() -> RateLimitConfig {
+    RateLimitConfig {
+        auth_per_ip: BucketConfig { burst: 3, refill_per_sec: 0.01 },
+        ws_per_user: BucketConfig { burst: 2, refill_per_sec: 0.01 },
+        login_lockout: LockoutConfig { max_failures: 2, window_secs: 60, lock_secs: 60 },
+    }
+}
+
+// Test che verifica il 429 con Retry-After e codice RATE_LIMITED quando un IP esaurisce il bucket di register/login
+#[tokio::test]
+async fn auth_routes_are_limited_per_ip() {
+    let app = spawn_app_with(|pool| AppState::with_config(pool, Config { rate_limits: tight_limits(), ..Config::default() })).await;
+    for name in ["u1", "u2", "u3"] {
+        app.register(name).await;
+    }
+    let resp = app
+        .http
+        .post(app.url("/api/register"))
+        .json(&RegisterRequest { username: "u4".to_string(), password: "secret".to_string() })
+        .send()
+        .await
+        .expect("register");
+    assert_eq!(resp.status(), 429);
+    let retry: u64 = resp.headers()["retry-after"].to_str().unwrap().parse().unwrap();
+    assert!(retry >= 1);
+    let body: serde_json::Value = resp.json().await.expect("json body");
+    assert_eq!(body["code"], "RATE_LIMITED");
+    assert_eq!(body["details"]["retryAfter"], retry);
+}
+
+// Test che verifica il blocco dell'account dopo i login falliti, anche con la password corretta
+#[tokio::test]
+async fn account_is_locked_after_repeated_failures() {
+    let limits = RateLimitConfig { auth_per_ip: BucketConfig { burst: 100, refill_per_sec: 1.0 }, ..tight_limits() };
+    let app = spawn_app_with(|pool| AppState::with_config(pool, Config { rate_limits: limits, ..Config::default() })).await;
+    app.register("alice").await;
+
+    let login = |password: &str| {
+        app.http
+            .post(app.url("/api/login"))
+            .json(&LoginRequest { username: "alice".to_string(), password: password.to_string() })
+            .send()
+    };
+    assert_eq!(login("wrong").await.expect("login").status(), 401);
+    assert_eq!(login("wrong").await.expect("login").status(), 401);
+    let resp = login("secret").await.expect("login");
+    assert_eq!(resp.status(), 429);
+    let body: serde_json::Value = resp.json().await.expect("json body");
+    assert_eq!(body["code"], "ACCOUNT_LOCKED");
+    assert!(body["details"]["retryAfter"].as_u64().unwrap() > 1);
+}
+
+// Test che verifica che il blocco valga solo per l'IP da cui arrivano i tentativi falliti: il
+// proprietario dell'account accede da un altro IP con la password corretta
+#[tokio::test]
+async fn lockout_does_not_block_other_ips() {
+    let limits = RateLimitConfig { auth_per_ip: BucketConfig { burst: 100, refill_per_sec: 1.0 }, ..tight_limits() };
+    let app = spawn_app_with(|pool| AppState::with_config(pool, Config { rate_limits: limits, ..Config::default() })).await;
+    app.register("alice").await;
+    // 127.0.0.2 è sempre un indirizzo di loopback: le connessioni arrivano al server da un IP diverso
+    let other = reqwest::Client::builder().local_address(IpAddr::from([127, 0, 0, 2])).build().unwrap();
+
+    let login = |client: &reqwest::Client, password: &str| {
+        client
+            .post(app.url("/api/login"))
+            .json(&LoginRequest { username: "alice".to_string(), password: password.to_string() })
+            .send()
+    };
+    assert_eq!(login(&app.http, "wrong").await.expect("login").status(), 401);
+    assert_eq!(login(&app.http, "wrong").await.expect("login").status(), 401);
+    assert_eq!(login(&app.http, "secret").await.expect("login").status(), 429);
+    assert_eq!(login(&other, "secret").await.expect("login").status(), 200);
+    assert_eq!(login(&other, "wrong").await.expect("login").status(), 401);
+    assert_eq!(login(&other, "secret").await.expect("login").status(), 200);
+    assert_eq!(login(&app.http, "secret").await.expect("login").status(), 429);
+}
+
+// Test che verifica l'Ack di errore RATE_LIMITED quando un utente supera il limite di comandi WS
+#[tokio::test]
+async fn ws_commands_are_limited_per_user() {
+    let app = spawn_app_with(|pool| AppState::with_config(pool, Config { rate_limits: tight_limits(), ..Config::default() })).await;
+    let (_, alice) = app.register("alice").await;
+    let group_id = app.create_group(&alice, "general", &[]).await;
+    let mut ws = app.ws_connect(&alice).await;
+
+    let mut statuses = Vec::new();
+    for _ in 0..3 {
+        let ack = send_and_ack(&mut ws, new_message(&group_id, "spam")).await;
+        statuses.push((ack.status, ack.error));
+    }
+    assert_eq!(statuses[0].0, AckStatus::Ok);
+    assert_eq!(statuses[1].0, AckStatus::Ok);
+    let (status, error) = &statuses[2];
+    assert_eq!(*status, AckStatus::Error);
+    let error = error.as_ref().expect("error");
+    assert_eq!(error.code, "RATE_LIMITED");
+    assert!(error.details.as_ref().expect("details")["retryAfter"].as_u64().unwrap() >= 1);
+
+    // il limite è per utente: una seconda connessione non riparte da zero
+    let mut ws2 = app.ws_connect(&alice).await;
+    let ack = send_and_ack(&mut ws2, new_message(&group_id, "ancora")).await;
+    assert_eq!(ack.error.expect("error").code, "RATE_LIMITED");
+}
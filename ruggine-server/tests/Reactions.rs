@@ -0,0 +1,99 @@
+mod common;
+
+use common::{send_message, spawn_app, ws_recv, ws_recv_until, ws_send};
+use ruggine_core::{new_client_msg_id, AckStatus, ReactionCommand, WsMessage};
+
+fn reaction(message_id: &str, emoji: &str) -> ReactionCommand {
+    ReactionCommand { client_msg_id: new_client_msg_id(), message_id: message_id.to_string(), emoji: emoji.to_string() }
+}
+
+// Test che verifica il ciclo add/remove: ack, evento reactionChanged con reactedByMe per destinatario,
+// e reazioni aggregate nello storico
+#[tokio::test]
+async fn reactions_are_broadcast_and_returned_in_history() {
+    let app = spawn_app().await;
+    let (_, alice) = app.register("alice").await;
+    let (bob_id, bob) = app.register("bob").await;
+    let group_id = app.create_group(&alice, "general", &[&bob_id]).await;
+
+    let mut ws_alice = app.ws_connect(&alice).await;
+    let mut ws_bob = app.ws_connect(&bob).await;
+    let message_id = send_message(&mut ws_alice, &group_id, "ciao").await;
+    ws_recv_until(&mut ws_bob, |m| matches!(m, WsMessage::Message(_))).await;
+
+    let cmd = reaction(&message_id, "👍");
+    ws_send(&mut ws_bob, &WsMessage::AddReaction(cmd.clone())).await;
+
+    // bob riceve l'ack e l'evento (in ordine non garantito), alice solo l'evento
+    let mut got_ack = false;
+    let mut got_event = false;
+    while !(got_ack && got_event) {
+        match ws_recv(&mut ws_bob).await {
+            WsMessage::Ack(ack) => {
+                assert_eq!(ack.in_reply_to, cmd.client_msg_id);
+                assert_eq!(ack.status, AckStatus::Ok);
+                got_ack = true;
+            }
+            WsMessage::ReactionChanged(ev) => {
+                assert!(ev.added);
+                assert_eq!(ev.user_id, bob_id);
+                assert_eq!(ev.reactions.len(), 1);
+                assert!(ev.reactions[0].reacted_by_me);
+                got_event = true;
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+    match ws_recv_until(&mut ws_alice, |m| matches!(m, WsMessage::ReactionChanged(_))).await {
+        WsMessage::ReactionChanged(ev) => {
+            assert_eq!(ev.message_id, message_id);
+            assert_eq!(ev.reactions[0].emoji, "👍");
+            assert_eq!(ev.reactions[0].count, 1);
+            assert!(!ev.reactions[0].reacted_by_me);
+        }
+        _ => unreachable!(),
+    }
+
+    // alice aggiunge la stessa emoji: lo storico la vede con count 2
+    ws_send(&mut ws_alice, &WsMessage::AddReaction(reaction(&message_id, "👍"))).await;
+    ws_recv_until(&mut ws_alice, |m| matches!(m, WsMessage::Ack(_))).await;
+    let (status, body) = app.get_json(&alice, &format!("/api/groups/{}/messages", group_id)).await;
+    assert_eq!(status, 200);
+    assert_eq!(body["messages"][0]["reactions"][0]["count"], 2);
+    assert_eq!(body["messages"][0]["reactions"][0]["reactedByMe"], true);
+
+    // bob rimuove la sua: resta solo quella di alice
+    ws_send(&mut ws_bob, &WsMessage::RemoveReaction(reaction(&message_id, "👍"))).await;
+    match ws_recv_until(&mut ws_alice, |m| matches!(m, WsMessage::ReactionChanged(ev) if !ev.added)).await {
+        WsMessage::ReactionChanged(ev) => {
+            assert_eq!(ev.reactions[0].count, 1);
+            assert!(ev.reactions[0].reacted_by_me);
+        }
+        _ => unreachable!(),
+    }
+    let (_, body) = app.get_json(&bob, &format!("/api/groups/{}/messages", group_id)).await;
+    assert_eq!(body["messages"][0]["reactions"][0]["count"], 1);
+    assert_eq!(body["messages"][0]["reactions"][0]["reactedByMe"], false);
+}
+
+// Test che verifica che chi non è membro del gruppo non possa reagire
+#[tokio::test]
+async fn reaction_from_non_member_is_rejected() {
+    let app = spawn_app().await;
+    let (_, alice) = app.register("alice").await;
+    let (_, mallory) = app.register("mallory").await;
+    let group_id = app.create_group(&alice, "private", &[]).await;
+
+    let mut ws_alice = app.ws_connect(&alice).await;
+    let message_id = send_message(&mut ws_alice, &group_id, "segreto").await;
+
+    let mut ws_mallory = app.ws_connect(&mallory).await;
+    ws_send(&mut ws_mallory, &WsMessage::AddReaction(reaction(&message_id, "😈"))).await;
+    match ws_recv(&mut ws_mallory).await {
+        WsMessage::Ack(ack) => {
+            assert_eq!(ack.status, AckStatus::Error);
+            assert_eq!(ack.error.expect("error").code, "FORBIDDEN");
+        }
+        other => panic!("unexpected {:?}", other),
+    }
+}
@@ -0,0 +1,66 @@
+mod common;
+
+use common::{new_message, send_and_ack, spawn_app, spawn_app_with};
+use ruggine_core::{AckStatus, RegisterRequest};
+use ruggine_server::ratelimit::{BucketConfig, RateLimitConfig};
+use ruggine_server::{config::Config, AppState};
+
+// Test che verifica che ogni risposta abbia un X-Request-Id, generato dal server o ripreso dalla richiesta
+#[tokio::test]
+async fn responses_carry_request_id() {
+    let app = spawn_app().await;
+
+    let resp = app.http.get(app.url("/health")).send().await.expect("health");
+    let generated = resp.headers()["x-request-id"].to_str().unwrap().to_string();
+    assert_eq!(generated.len(), 36, "uuid expected, got {}", generated);
+
+    let resp = app
+        .http
+        .get(app.url("/api/groups"))
+        .header("x-request-id", "proxy-42")
+        .send()
+        .await
+        .expect("groups");
+    assert_eq!(resp.status(), 401);
+    assert_eq!(resp.headers()["x-request-id"], "proxy-42");
+
+    // id non validi (spazi) vengono sostituiti
+    let resp = app.http.get(app.url("/health")).header("x-request-id", "a b").send().await.expect("health");
+    assert_ne!(resp.headers()["x-request-id"], "a b");
+}
+
+// Test che verifica che gli errori JSON (HTTP e WS) riportino il request id in details.requestId
+#[tokio::test]
+async fn errors_include_request_id_in_details() {
+    let limits = RateLimitConfig {
+        auth_per_ip: BucketConfig { burst: 1, refill_per_sec: 0.01 },
+        ws_per_user: BucketConfig { burst: 100, refill_per_sec: 1.0 },
+        ..RateLimitConfig::default()
+    };
+    let app = spawn_app_with(|pool| AppState::with_config(pool, Config { rate_limits: limits, ..Config::default() })).await;
+    let (_, alice) = app.register("alice").await;
+
+    let resp = app
+        .http
+        .post(app.url("/api/register"))
+        .header("x-request-id", "req-limited")
+        .json(&RegisterRequest { username: "bob".to_string(), password: "secret".to_string() })
+        .send()
+        .await
+        .expect("register");
+    assert_eq!(resp.status(), 429);
+    assert_eq!(resp.headers()["x-request-id"], "req-limited");
+    let body: serde_json::Value = resp.json().await.expect("json body");
+    assert_eq!(body["code"], "RATE_LIMITED");
+    assert_eq!(body["details"]["requestId"], "req-limited");
+    assert!(body["details"]["retryAfter"].as_u64().is_some());
+
+    // sessione WS: gli Ack di errore riportano il request id della richiesta di upgrade
+    let (mut ws, resp) = tokio_tungstenite::connect_async(format!("ws://{}/ws?token={}", app.addr, alice))
+        .await
+        .expect("ws connect");
+    let session_id = resp.headers()["x-request-id"].to_str().unwrap().to_string();
+    let ack = send_and_ack(&mut ws, new_message("missing", "ciao")).await;
+    assert_eq!(ack.status, AckStatus::Error);
+    assert_eq!(ack.error.expect("error").request_id(), Some(session_id.as_str()));
+}
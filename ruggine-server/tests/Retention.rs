@@ -0,0 +1,114 @@
+mod common;
+
+use common::{spawn_app_with, ws_expect_silence, ws_recv_until};
+use ruggine_core::{encode_since, now_timestamp, Message, WsMessage};
+use ruggine_server::config::Config;
+use ruggine_server::retention::{self, RetentionConfig};
+use ruggine_server::AppState;
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+fn message(id: &str, group_id: &str, sender: &str, created_at: &str) -> Message {
+    Message {
+        message_id: id.to_string(),
+        group_id: group_id.to_string(),
+        sender_id: sender.to_string(),
+        content: format!("content of {}", id),
+        created_at: created_at.to_string(),
+        seq: 0,
+        reactions: Vec::new(),
+        mentions: Vec::new(),
+        expires_at: None,
+    }
+}
+
+// Test che verifica la pulizia dei messaggi: il limite di età del server e il limite di numero del
+// gruppo (impostabile solo dall'admin) eliminano i messaggi più vecchi con reazioni e pin, i membri
+// ricevono historyPruned e un secondo passaggio non elimina né notifica nulla
+#[tokio::test]
+async fn expired_messages_are_purged_and_members_notified() {
+    let config = Config {
+        retention: RetentionConfig { max_age_secs: Some(3600), batch_size: 2, ..RetentionConfig::default() },
+        ..Config::default()
+    };
+    let app = spawn_app_with(move |pool| AppState::with_config(pool, config)).await;
+    let (alice_id, alice) = app.register("alice").await;
+    let (bob_id, bob) = app.register("bob").await;
+    let old = app.create_group(&alice, "old", &[bob_id.as_str()]).await;
+    let chatty = app.create_group(&alice, "chatty", &[bob_id.as_str()]).await;
+
+    let url = app.url(&format!("/api/groups/{}", chatty));
+    let resp = app.http.patch(&url).bearer_auth(&bob).json(&json!({"retention": {"maxCount": 2}})).send().await.unwrap();
+    assert_eq!(resp.status(), 403);
+    let resp = app.http.patch(&url).bearer_auth(&alice).json(&json!({"retention": {"maxCount": 0}})).send().await.unwrap();
+    assert_eq!(resp.status(), 400);
+    let resp = app.http.patch(&url).bearer_auth(&alice).json(&json!({"retention": {"maxCount": 2}})).send().await.unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["group"]["retention"], json!({"maxCount": 2}));
+
+    let store = app.state.store.clone();
+    for i in 1..=3 {
+        store.insert_message(&message(&format!("o{}", i), &old, &alice_id, &format!("2024-01-01T00:00:0{}Z", i))).await.unwrap();
+    }
+    store.insert_message(&message("o4", &old, &alice_id, &now_timestamp())).await.unwrap();
+    for i in 1..=5 {
+        store.insert_message(&message(&format!("c{}", i), &chatty, &alice_id, &now_timestamp())).await.unwrap();
+    }
+    store.add_reaction("o1", &alice_id, "👍", &now_timestamp()).await.unwrap();
+    store.pin_message("o2", &old, &alice_id, &now_timestamp()).await.unwrap();
+
+    let since = encode_since(&BTreeMap::from([(old.clone(), 4), (chatty.clone(), 5)]));
+    let mut ws = app.ws_connect_since(&alice, &since).await;
+    ws_recv_until(&mut ws, |m| matches!(m, WsMessage::CaughtUp(_))).await;
+
+    assert_eq!(retention::run_once(&app.state).await.unwrap(), 6);
+    let mut notices = BTreeMap::new();
+    for _ in 0..2 {
+        match ws_recv_until(&mut ws, |m| matches!(m, WsMessage::HistoryPruned(_))).await {
+            WsMessage::HistoryPruned(ev) => {
+                notices.insert(ev.group_id.clone(), (ev.up_to_seq, ev.deleted));
+            }
+            _ => unreachable!(),
+        }
+    }
+    assert_eq!(notices, BTreeMap::from([(old.clone(), (3, 3)), (chatty.clone(), (3, 3))]));
+
+    let left: Vec<String> = store.messages_after(&old, 0, 10).await.unwrap().into_iter().map(|m| m.message_id).collect();
+    assert_eq!(left, ["o4"]);
+    let left: Vec<String> = store.messages_after(&chatty, 0, 10).await.unwrap().into_iter().map(|m| m.message_id).collect();
+    assert_eq!(left, ["c4", "c5"]);
+    assert!(store.reactions_for(&["o1".to_string()]).await.unwrap().is_empty());
+    assert!(store.pins_for_group(&old).await.unwrap().is_empty());
+    // i numeri di sequenza non vengono riusati
+    assert_eq!(store.latest_seq(&old).await.unwrap(), 4);
+
+    assert_eq!(retention::run_once(&app.state).await.unwrap(), 0);
+    ws_expect_silence(&mut ws, 300).await;
+}
+
+// Test che verifica il task periodico: la prima pulizia avviene all'avvio e il task termina con lo
+// spegnimento del server
+#[tokio::test]
+async fn background_purge_stops_on_shutdown() {
+    let config = Config {
+        retention: RetentionConfig { max_count: Some(1), interval_secs: 1, ..RetentionConfig::default() },
+        ..Config::default()
+    };
+    let mut app = spawn_app_with(move |pool| AppState::with_config(pool, config)).await;
+    let (alice_id, alice) = app.register("alice").await;
+    let group = app.create_group(&alice, "g", &[]).await;
+    for i in 1..=3 {
+        app.state.store.insert_message(&message(&format!("m{}", i), &group, &alice_id, &now_timestamp())).await.unwrap();
+    }
+
+    let task = retention::spawn(app.state.clone());
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(5);
+    while app.state.store.messages_after(&group, 0, 10).await.unwrap().len() > 1 {
+        assert!(tokio::time::Instant::now() < deadline, "messages were not purged");
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    app.begin_shutdown();
+    tokio::time::timeout(Duration::from_secs(5), task).await.expect("task stopped").unwrap();
+}
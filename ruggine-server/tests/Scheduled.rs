@@ -0,0 +1,128 @@
+mod common;
+
+use common::{spawn_app, spawn_app_with, spawn_node, ws_expect_silence, ws_recv_until, ws_send, Ws};
+use ruggine_core::{
+    error::codes, new_client_msg_id, timestamp_from_unix, unix_from_timestamp, Ack, AckStatus, Message, SendMessage, WsMessage,
+};
+use ruggine_server::config::Config;
+use ruggine_server::scheduled::{self, ScheduledConfig};
+use ruggine_server::AppState;
+use std::time::Duration;
+use time::OffsetDateTime;
+
+async fn send(ws: &mut Ws, group_id: &str, content: &str, send_at: Option<String>, ttl_secs: Option<u64>) -> Ack {
+    let sm = SendMessage {
+        client_msg_id: new_client_msg_id(),
+        group_id: group_id.to_string(),
+        content: content.to_string(),
+        sent_at: None,
+        ttl_secs,
+        send_at,
+    };
+    ws_send(ws, &WsMessage::SendMessage(sm.clone())).await;
+    match ws_recv_until(ws, |m| matches!(m, WsMessage::Ack(a) if a.in_reply_to == sm.client_msg_id)).await {
+        WsMessage::Ack(ack) => ack,
+        _ => unreachable!(),
+    }
+}
+
+async fn recv_message(ws: &mut Ws, message_id: &str) -> Message {
+    match ws_recv_until(ws, |m| matches!(m, WsMessage::Message(msg) if msg.message_id == message_id)).await {
+        WsMessage::Message(msg) => msg,
+        _ => unreachable!(),
+    }
+}
+
+// Istante RFC3339 tra `secs` secondi (negativo = nel passato), a secondi interi
+fn in_secs(secs: i64) -> String {
+    timestamp_from_unix(OffsetDateTime::now_utc().unix_timestamp() + secs)
+}
+
+// Test che verifica un messaggio programmato: l'ack riporta sendAt ma il messaggio non è pubblicato,
+// il mittente lo vede in GET /api/scheduled e può annullarlo, e al suo send_at viene pubblicato con
+// l'id già comunicato e le sue menzioni
+#[tokio::test]
+async fn scheduled_message_is_listed_cancelled_and_published() {
+    let app = spawn_app().await;
+    let (_, alice) = app.register("alice").await;
+    let (bob_id, bob) = app.register("bob").await;
+    let group_id = app.create_group(&alice, "g", &[bob_id.as_str()]).await;
+    let mut ws_alice = app.ws_connect(&alice).await;
+    let mut ws_bob = app.ws_connect(&bob).await;
+
+    let ack = send(&mut ws_alice, &group_id, "quando?", Some("domani".to_string()), None).await;
+    assert_eq!(ack.status, AckStatus::Error);
+    assert_eq!(ack.error.unwrap().code, codes::BAD_REQUEST);
+    let ack = send(&mut ws_alice, &group_id, "fra due anni", Some(in_secs(2 * 366 * 24 * 60 * 60)), None).await;
+    assert_eq!(ack.error.unwrap().code, codes::BAD_REQUEST);
+    // un send_at già passato vale come invio immediato
+    let ack = send(&mut ws_alice, &group_id, "subito", Some(in_secs(-60)), None).await;
+    assert_eq!(ack.send_at, None);
+    recv_message(&mut ws_bob, &ack.message_id.unwrap()).await;
+
+    let soon_at = in_secs(2);
+    let soon = send(&mut ws_alice, &group_id, "ciao @bob", Some(soon_at.clone()), None).await;
+    assert_eq!(soon.status, AckStatus::Ok);
+    assert_eq!((soon.send_at.as_deref(), soon.created_at.as_deref()), (Some(soon_at.as_str()), None));
+    let later = send(&mut ws_alice, &group_id, "mai", Some(in_secs(3600)), None).await;
+    ws_expect_silence(&mut ws_bob, 300).await;
+    let (_, body) = app.get_json(&bob, &format!("/api/groups/{}/messages", group_id)).await;
+    assert_eq!(body["messages"].as_array().unwrap().len(), 1);
+
+    let (status, body) = app.get_json(&alice, "/api/scheduled").await;
+    assert_eq!(status, 200);
+    let listed: Vec<&str> = body["scheduled"].as_array().unwrap().iter().map(|s| s["messageId"].as_str().unwrap()).collect();
+    assert_eq!(listed, [soon.message_id.as_deref().unwrap(), later.message_id.as_deref().unwrap()]);
+    assert_eq!(body["scheduled"][0]["sendAt"], soon_at);
+    assert_eq!(body["scheduled"][0]["content"], "ciao @bob");
+    let (_, body) = app.get_json(&bob, "/api/scheduled").await;
+    assert!(body["scheduled"].as_array().unwrap().is_empty());
+
+    let url = app.url(&format!("/api/scheduled/{}", later.message_id.unwrap()));
+    assert_eq!(app.http.delete(&url).bearer_auth(&bob).send().await.unwrap().status(), 404);
+    assert_eq!(app.http.delete(&url).bearer_auth(&alice).send().await.unwrap().status(), 204);
+    assert_eq!(app.http.delete(&url).bearer_auth(&alice).send().await.unwrap().status(), 404);
+
+    assert_eq!(scheduled::run_once(&app.state).await.unwrap(), 0);
+    tokio::time::sleep(Duration::from_millis(2100)).await;
+    assert_eq!(scheduled::run_once(&app.state).await.unwrap(), 1);
+    let soon_id = soon.message_id.unwrap();
+    let msg = recv_message(&mut ws_bob, &soon_id).await;
+    assert_eq!((msg.content.as_str(), msg.seq), ("ciao @bob", 2));
+    assert_eq!(msg.mentions, [bob_id]);
+    assert!(unix_from_timestamp(&msg.created_at) >= unix_from_timestamp(&soon_at));
+    match ws_recv_until(&mut ws_bob, |m| matches!(m, WsMessage::Mention(_))).await {
+        WsMessage::Mention(mention) => assert_eq!(mention.message.message_id, soon_id),
+        _ => unreachable!(),
+    }
+    let (_, body) = app.get_json(&alice, "/api/scheduled").await;
+    assert!(body["scheduled"].as_array().unwrap().is_empty());
+    assert_eq!(scheduled::run_once(&app.state).await.unwrap(), 0);
+}
+
+// Test che verifica il limite di messaggi programmati per utente e che quelli salvati vengono pubblicati
+// da un'altra istanza sullo stesso database (es. dopo un riavvio), con il ttl richiesto all'invio
+#[tokio::test]
+async fn scheduled_messages_survive_restart() {
+    let config = Config { scheduled: ScheduledConfig { max_pending_per_user: 1, ..ScheduledConfig::default() }, ..Config::default() };
+    let app = spawn_app_with(move |pool| AppState::with_config(pool, config)).await;
+    let (_, alice) = app.register("alice").await;
+    let (bob_id, bob) = app.register("bob").await;
+    let group_id = app.create_group(&alice, "g", &[bob_id.as_str()]).await;
+
+    let mut ws = app.ws_connect(&alice).await;
+    let ack = send(&mut ws, &group_id, "dopo il riavvio", Some(in_secs(1)), Some(600)).await;
+    assert_eq!(ack.status, AckStatus::Ok);
+    let message_id = ack.message_id.unwrap();
+    let ack = send(&mut ws, &group_id, "troppi", Some(in_secs(1)), None).await;
+    assert_eq!(ack.error.unwrap().code, codes::BAD_REQUEST);
+    drop(ws);
+
+    let node = spawn_node(&app, AppState::new).await;
+    let mut ws_bob = node.ws_connect(&bob).await;
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    assert_eq!(scheduled::run_once(&node.state).await.unwrap(), 1);
+    let msg = recv_message(&mut ws_bob, &message_id).await;
+    assert_eq!(msg.content, "dopo il riavvio");
+    assert!(msg.expires_at.is_some(), "ttl requested with the scheduled message");
+}
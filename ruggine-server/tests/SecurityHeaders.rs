@@ -0,0 +1,142 @@
+mod common;
+
+use common::spawn_app_with;
+use ruggine_server::{config::Config, AppState};
+use std::fs;
+use tempfile::TempDir;
+
+fn with_config(config: Config) -> impl FnOnce(sqlx::SqlitePool) -> AppState {
+    move |pool| AppState::with_config(pool, config)
+}
+
+// Test che verifica le preflight CORS e gli header CORS per origini ammesse e non ammesse
+#[tokio::test]
+async fn cors_allows_only_configured_origins() {
+    let config = Config {
+        cors_origins: vec!["http://localhost:8080".to_string()],
+        cors_allow_credentials: true,
+        ..Config::default()
+    };
+    let app = spawn_app_with(with_config(config)).await;
+
+    let resp = app
+        .http
+        .request(reqwest::Method::OPTIONS, app.url("/api/groups"))
+        .header("origin", "http://localhost:8080")
+        .header("access-control-request-method", "POST")
+        .header("access-control-request-headers", "authorization, content-type")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 204);
+    let h = resp.headers();
+    assert_eq!(h["access-control-allow-origin"], "http://localhost:8080");
+    assert_eq!(h["access-control-allow-credentials"], "true");
+    assert_eq!(h["access-control-allow-headers"], "authorization, content-type");
+    assert!(h["access-control-allow-methods"].to_str().unwrap().contains("POST"));
+
+    let resp = app
+        .http
+        .request(reqwest::Method::OPTIONS, app.url("/api/groups"))
+        .header("origin", "http://evil.example")
+        .header("access-control-request-method", "POST")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 403);
+    assert!(resp.headers().get("access-control-allow-origin").is_none());
+
+    // richieste semplici: header CORS solo per l'origine ammessa, anche sulle risposte di errore
+    let resp = app.http.get(app.url("/api/groups")).header("origin", "http://localhost:8080").send().await.unwrap();
+    assert_eq!(resp.status(), 401);
+    assert_eq!(resp.headers()["access-control-allow-origin"], "http://localhost:8080");
+    assert_eq!(resp.headers()["access-control-expose-headers"], "x-request-id");
+    assert!(resp.headers()["vary"].to_str().unwrap().contains("origin"));
+    let resp = app.http.get(app.url("/health")).header("origin", "http://evil.example").send().await.unwrap();
+    assert_eq!(resp.status(), 200);
+    assert!(resp.headers().get("access-control-allow-origin").is_none());
+
+    // "*" senza credenziali: risposta con "*"
+    let config = Config { cors_origins: vec!["*".to_string()], ..Config::default() };
+    let open = spawn_app_with(with_config(config)).await;
+    let resp = open.http.get(open.url("/health")).header("origin", "https://any.example").send().await.unwrap();
+    assert_eq!(resp.headers()["access-control-allow-origin"], "*");
+    assert!(resp.headers().get("access-control-allow-credentials").is_none());
+}
+
+// Test che verifica CSP con frame-ancestors, nosniff e X-Frame-Options su ogni risposta, e che
+// la configurazione di frame_ancestors venga rispettata
+#[tokio::test]
+async fn security_headers_are_set_on_every_response() {
+    let app = spawn_app_with(AppState::new).await;
+    for path in ["/health", "/api/groups", "/does-not-exist"] {
+        let resp = app.http.get(app.url(path)).send().await.unwrap();
+        let h = resp.headers();
+        let csp = h["content-security-policy"].to_str().unwrap();
+        assert!(csp.starts_with("default-src 'self'"), "{}: {}", path, csp);
+        assert!(csp.ends_with("frame-ancestors 'none'"), "{}: {}", path, csp);
+        assert_eq!(h["x-content-type-options"], "nosniff", "{}", path);
+        assert_eq!(h["x-frame-options"], "DENY", "{}", path);
+        assert_eq!(h["referrer-policy"], "no-referrer", "{}", path);
+        assert!(h.get("strict-transport-security").is_none(), "{}", path);
+    }
+
+    let config = Config {
+        content_security_policy: "default-src 'none'".to_string(),
+        frame_ancestors: vec!["'self'".to_string(), "https://portal.example".to_string()],
+        ..Config::default()
+    };
+    let app = spawn_app_with(with_config(config)).await;
+    let resp = app.http.get(app.url("/health")).send().await.unwrap();
+    assert_eq!(
+        resp.headers()["content-security-policy"],
+        "default-src 'none'; frame-ancestors 'self' https://portal.example"
+    );
+    assert!(resp.headers().get("x-frame-options").is_none());
+}
+
+// Test che verifica il client web servito dalla directory dist: index, tipi MIME, percorsi fuori
+// dalla directory e rotte API inesistenti
+#[tokio::test]
+async fn serves_web_client_from_dist_dir() {
+    let dist = TempDir::new().unwrap();
+    fs::write(dist.path().join("index.html"), "<!DOCTYPE html><main id=\"app\"></main>").unwrap();
+    fs::write(dist.path().join("ruggine-client-web.js"), "export default function init() {}").unwrap();
+    fs::write(dist.path().join("ruggine-client-web_bg.wasm"), b"\0asm").unwrap();
+    fs::create_dir(dist.path().join("assets")).unwrap();
+    fs::write(dist.path().join("assets").join("logo name.svg"), "<svg/>").unwrap();
+    let outside = TempDir::new().unwrap();
+    fs::write(outside.path().join("secret.txt"), "secret").unwrap();
+
+    let config = Config { web_dist_dir: Some(dist.path().to_path_buf()), ..Config::default() };
+    config.validate().unwrap();
+    let app = spawn_app_with(with_config(config)).await;
+
+    let resp = app.http.get(app.url("/")).send().await.unwrap();
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.headers()["content-type"], "text/html; charset=utf-8");
+    assert!(resp.headers().contains_key("content-security-policy"));
+    assert!(resp.text().await.unwrap().contains("id=\"app\""));
+
+    for (path, mime) in [
+        ("/ruggine-client-web.js", "text/javascript; charset=utf-8"),
+        ("/ruggine-client-web_bg.wasm", "application/wasm"),
+        ("/assets/logo%20name.svg", "image/svg+xml"),
+    ] {
+        let resp = app.http.get(app.url(path)).send().await.unwrap();
+        assert_eq!(resp.status(), 200, "{}", path);
+        assert_eq!(resp.headers()["content-type"], mime, "{}", path);
+    }
+
+    let escape = format!("/..%2f{}/secret.txt", outside.path().file_name().unwrap().to_str().unwrap());
+    for path in ["/missing.js", "/api/nope", escape.as_str(), "/assets/..%2f..%2fetc/passwd"] {
+        let resp = app.http.get(app.url(path)).send().await.unwrap();
+        assert_eq!(resp.status(), 404, "{}", path);
+    }
+    let resp = app.http.post(app.url("/")).send().await.unwrap();
+    assert_eq!(resp.status(), 405);
+
+    // le API continuano a rispondere sulla stessa origine
+    let resp = app.http.get(app.url("/health")).send().await.unwrap();
+    assert_eq!(resp.status(), 200);
+}
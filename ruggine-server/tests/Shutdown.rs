@@ -0,0 +1,58 @@
+mod common;
+
+use common::{send_message, spawn_app, ws_recv_until};
+use futures_util::StreamExt;
+use ruggine_core::WsMessage;
+use std::time::Duration;
+use tokio_tungstenite::tungstenite::{protocol::frame::coding::CloseCode, Message as Frame};
+
+// Test che verifica l'arresto controllato: il messaggio già confermato resta salvato,
+// il client riceve serverGoingAway seguito da un close frame 1001, il pool viene chiuso
+// e il server non accetta più connessioni
+#[tokio::test]
+async fn shutdown_notifies_sessions_and_closes_pool() {
+    let mut app = spawn_app().await;
+    let (_, alice) = app.register("alice").await;
+    let group_id = app.create_group(&alice, "general", &[]).await;
+    let mut ws = app.ws_connect(&alice).await;
+
+    send_message(&mut ws, &group_id, "ultimo messaggio").await;
+
+    app.begin_shutdown();
+    match ws_recv_until(&mut ws, |m| matches!(m, WsMessage::ServerGoingAway(_))).await {
+        WsMessage::ServerGoingAway(notice) => assert!(notice.reconnect_after_ms > 0),
+        _ => unreachable!(),
+    }
+    let close = loop {
+        let frame = tokio::time::timeout(Duration::from_secs(5), ws.next())
+            .await
+            .expect("timeout waiting for close")
+            .expect("stream ended before close frame")
+            .expect("ws error");
+        if let Frame::Close(close) = frame {
+            break close.expect("close frame payload");
+        }
+    };
+    assert_eq!(close.code, CloseCode::Away);
+    drop(ws);
+
+    app.wait_stopped().await;
+    assert!(app.pool.is_closed());
+    assert_eq!(app.state.shutdown.active_sessions(), 0);
+    assert!(tokio_tungstenite::connect_async(format!("ws://{}/ws?token={}", app.addr, alice)).await.is_err());
+}
+
+// Test che verifica che, con l'arresto già avviato, l'apertura di nuove sessioni WS venga rifiutata
+#[tokio::test]
+async fn ws_upgrade_rejected_while_shutting_down() {
+    let app = spawn_app().await;
+    let (_, alice) = app.register("alice").await;
+    app.state.shutdown.trigger();
+    let err = tokio_tungstenite::connect_async(format!("ws://{}/ws?token={}", app.addr, alice))
+        .await
+        .expect_err("upgrade should fail");
+    match err {
+        tokio_tungstenite::tungstenite::Error::Http(resp) => assert_eq!(resp.status(), 503),
+        other => panic!("unexpected error: {}", other),
+    }
+}
@@ -71,4 +71,112 @@ async fn creating_db_file_and_parent_dirs_is_idempotent() -> Result<()> {
         .fetch_all(&pool).await?;
     assert!(!rows.is_empty());
     Ok(())
-}
\ No newline at end of file
+}
+
+// Test che verifica che le migrazioni aggiungano le colonne nuove ad un DB creato con lo schema precedente
+#[tokio::test]
+async fn run_migrations_upgrades_existing_tables() -> Result<()> {
+    let td = TempDir::new()?;
+    let url = sqlite_url_for(&td.path().join("old.db"));
+    let pool = connect_pool(&url).await?;
+    sqlx::query("CREATE TABLE groups (group_id TEXT PRIMARY KEY, name TEXT NOT NULL, created_at TEXT NOT NULL)")
+        .execute(&pool).await?;
+    sqlx::query("INSERT INTO groups (group_id, name, created_at) VALUES ('g1', 'old', '2025-11-02T10:00:00Z')")
+        .execute(&pool).await?;
+
+    run_migrations(&pool).await?;
+    // una seconda esecuzione non deve fallire
+    run_migrations(&pool).await?;
+
+    let policy: String = sqlx::query_scalar("SELECT pin_policy FROM groups WHERE group_id = 'g1'")
+        .fetch_one(&pool).await?;
+    assert_eq!(policy, "members");
+    let role_cols: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM pragma_table_info('memberships') WHERE name = 'role'")
+        .fetch_one(&pool).await?;
+    assert_eq!(role_cols, 1);
+    Ok(())
+}
+
+// Test che verifica la numerazione per gruppo dei messaggi già presenti in un DB con lo schema precedente
+#[tokio::test]
+async fn run_migrations_numbers_existing_messages() -> Result<()> {
+    let td = TempDir::new()?;
+    let url = sqlite_url_for(&td.path().join("old.db"));
+    let pool = connect_pool(&url).await?;
+    sqlx::query("CREATE TABLE groups (group_id TEXT PRIMARY KEY, name TEXT NOT NULL, created_at TEXT NOT NULL)")
+        .execute(&pool).await?;
+    sqlx::query(
+        "CREATE TABLE messages (message_id TEXT PRIMARY KEY, group_id TEXT NOT NULL, sender_id TEXT NOT NULL, \
+         content TEXT NOT NULL, created_at TEXT NOT NULL)",
+    )
+    .execute(&pool).await?;
+    sqlx::query("INSERT INTO groups (group_id, name, created_at) VALUES ('g1', 'a', 't'), ('g2', 'b', 't'), ('g3', 'c', 't')")
+        .execute(&pool).await?;
+    for (id, group) in [("m1", "g1"), ("m2", "g2"), ("m3", "g1"), ("m4", "g1")] {
+        sqlx::query("INSERT INTO messages VALUES (?, ?, 'u1', 'x', 't')").bind(id).bind(group).execute(&pool).await?;
+    }
+
+    run_migrations(&pool).await?;
+    run_migrations(&pool).await?;
+
+    let seqs: Vec<(String, i64)> = sqlx::query_as("SELECT message_id, group_seq FROM messages ORDER BY message_id")
+        .fetch_all(&pool).await?;
+    let expected = [("m1", 1), ("m2", 1), ("m3", 2), ("m4", 3)].map(|(id, seq)| (id.to_string(), seq));
+    assert_eq!(seqs, expected);
+    let last: Vec<i64> = sqlx::query_scalar("SELECT last_seq FROM groups ORDER BY group_id").fetch_all(&pool).await?;
+    assert_eq!(last, [3, 1, 0]);
+    Ok(())
+}
+
+// Test che verifica che le migrazioni rendano facoltativo message_id nelle consegne dei webhook di un DB
+// creato con la versione 8, mantenendo le consegne esistenti
+#[tokio::test]
+async fn run_migrations_relaxes_webhook_deliveries() -> Result<()> {
+    let td = TempDir::new()?;
+    let url = sqlite_url_for(&td.path().join("old.db"));
+    let pool = connect_pool(&url).await?;
+    run_migrations(&pool).await?;
+    sqlx::query("DROP TABLE webhook_deliveries").execute(&pool).await?;
+    sqlx::query(
+        "CREATE TABLE webhook_deliveries (delivery_id TEXT PRIMARY KEY, webhook_id TEXT NOT NULL, group_id TEXT NOT NULL, \
+         event TEXT NOT NULL, message_id TEXT NOT NULL, status TEXT NOT NULL, attempts INTEGER NOT NULL DEFAULT 0, \
+         next_attempt_at INTEGER, last_status_code INTEGER, last_error TEXT, created_at TEXT NOT NULL, finished_at INTEGER, \
+         FOREIGN KEY(webhook_id) REFERENCES webhooks(webhook_id) ON DELETE CASCADE)",
+    )
+    .execute(&pool).await?;
+    sqlx::query("INSERT INTO users (user_id, username, password_hash, created_at) VALUES ('u1', 'alice', 'x', 't')")
+        .execute(&pool).await?;
+    sqlx::query("INSERT INTO groups (group_id, name, created_at) VALUES ('g1', 'a', 't')").execute(&pool).await?;
+    sqlx::query(
+        "INSERT INTO webhooks (webhook_id, group_id, url, events, secret, created_by, created_at) \
+         VALUES ('w1', 'g1', 'http://example.com', 'message.created', 's', 'u1', 't')",
+    )
+    .execute(&pool).await?;
+    sqlx::query(
+        "INSERT INTO webhook_deliveries (delivery_id, webhook_id, group_id, event, message_id, status, created_at) \
+         VALUES ('d1', 'w1', 'g1', 'message.created', 'm1', 'pending', 't')",
+    )
+    .execute(&pool).await?;
+    sqlx::query("PRAGMA user_version = 9").execute(&pool).await?;
+
+    run_migrations(&pool).await?;
+    run_migrations(&pool).await?;
+
+    let not_null: bool = sqlx::query_scalar("SELECT \"notnull\" FROM pragma_table_info('webhook_deliveries') WHERE name = 'message_id'")
+        .fetch_one(&pool).await?;
+    assert!(!not_null);
+    let kept: (String, Option<String>) = sqlx::query_as("SELECT message_id, user_id FROM webhook_deliveries WHERE delivery_id = 'd1'")
+        .fetch_one(&pool).await?;
+    assert_eq!(kept, ("m1".to_string(), None));
+    sqlx::query(
+        "INSERT INTO webhook_deliveries (delivery_id, webhook_id, group_id, event, user_id, status, created_at) \
+         VALUES ('d2', 'w1', 'g1', 'member.joined', 'u1', 'pending', 't')",
+    )
+    .execute(&pool).await?;
+    let indexes: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM sqlite_master WHERE type = 'index' AND tbl_name = 'webhook_deliveries' AND sql IS NOT NULL")
+        .fetch_one(&pool).await?;
+    assert_eq!(indexes, 3);
+    let version: i64 = sqlx::query_scalar("PRAGMA user_version").fetch_one(&pool).await?;
+    assert_eq!(version, ruggine_server::SCHEMA_VERSION);
+    Ok(())
+}
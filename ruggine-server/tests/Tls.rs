@@ -0,0 +1,229 @@
+use futures_util::{SinkExt, StreamExt};
+use ruggine_core::{
+    new_client_msg_id, CreateGroupRequest, CreateGroupResponse, RegisterRequest, RegisterResponse, SendMessage, WsMessage,
+};
+use ruggine_server::config::Config;
+use ruggine_server::store::SqliteStore;
+use ruggine_server::tls::{serve_redirect, ReloadingCert};
+use ruggine_server::{serve_tls, AppState};
+use rustls::{Certificate, ClientConfig, RootCertStore, ServerName};
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use tempfile::TempDir;
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpStream;
+use tokio::sync::oneshot;
+use tokio_rustls::TlsConnector;
+use tokio_tungstenite::tungstenite::Message as Frame;
+
+// Certificato autofirmato per localhost: (pem del certificato, pem della chiave, der del certificato)
+fn self_signed() -> (String, String, Vec<u8>) {
+    let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).expect("generate cert");
+    // la firma ECDSA cambia a ogni serializzazione: il der va ricavato dal pem scritto su file
+    let pem = cert.serialize_pem().unwrap();
+    let der = rustls_pemfile::certs(&mut pem.as_bytes()).unwrap().remove(0);
+    (pem, cert.serialize_private_key_pem(), der)
+}
+
+fn write_pair(dir: &Path, cert_pem: &str, key_pem: &str) {
+    std::fs::write(dir.join("cert.pem"), cert_pem).unwrap();
+    std::fs::write(dir.join("key.pem"), key_pem).unwrap();
+}
+
+struct TlsApp {
+    addr: SocketAddr,
+    cert: Arc<ReloadingCert>,
+    state: Arc<AppState>,
+    stop: Option<oneshot::Sender<()>>,
+}
+
+async fn spawn_tls_app(dir: &Path) -> TlsApp {
+    let store = SqliteStore::in_memory().await.expect("in-memory store");
+    let state = Arc::new(AppState::new(store.pool().clone()));
+    let cert = Arc::new(ReloadingCert::load(&dir.join("cert.pem"), &dir.join("key.pem")).expect("load cert"));
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.expect("bind");
+    let addr = listener.local_addr().unwrap();
+    let (stop, stopped) = oneshot::channel::<()>();
+    tokio::spawn(serve_tls(listener, state.clone(), cert.clone(), async move {
+        let _ = stopped.await;
+    }));
+    TlsApp { addr, cert, state, stop: Some(stop) }
+}
+
+// Client che si fida solo dei certificati indicati
+fn connector(trusted: &[&[u8]]) -> TlsConnector {
+    let mut roots = RootCertStore::empty();
+    for der in trusted {
+        roots.add(&Certificate(der.to_vec())).unwrap();
+    }
+    let mut config = ClientConfig::builder().with_safe_defaults().with_root_certificates(roots).with_no_client_auth();
+    // senza resumption ogni handshake riceve il certificato corrente del server
+    config.resumption = rustls::client::Resumption::disabled();
+    TlsConnector::from(Arc::new(config))
+}
+
+// Certificato presentato dal server a una nuova connessione
+async fn served_cert(addr: SocketAddr, connector: &TlsConnector) -> Vec<u8> {
+    let tcp = TcpStream::connect(addr).await.unwrap();
+    let tls = connector.connect(ServerName::try_from("localhost").unwrap(), tcp).await.expect("tls handshake");
+    tls.get_ref().1.peer_certificates().expect("peer certificates")[0].0.clone()
+}
+
+// Test che verifica le API su https:// e l'invio di un messaggio su wss:// con un certificato autofirmato
+#[tokio::test]
+async fn https_and_wss_work_with_self_signed_certificate() {
+    let dir = TempDir::new().unwrap();
+    let (cert_pem, key_pem, cert_der) = self_signed();
+    write_pair(dir.path(), &cert_pem, &key_pem);
+    let mut app = spawn_tls_app(dir.path()).await;
+
+    let http = reqwest::Client::builder()
+        .add_root_certificate(reqwest::Certificate::from_pem(cert_pem.as_bytes()).unwrap())
+        .resolve("localhost", app.addr)
+        .build()
+        .unwrap();
+    let base = format!("https://localhost:{}", app.addr.port());
+    let resp = http.get(format!("{}/health", base)).send().await.expect("https request");
+    assert_eq!(resp.status(), 200);
+    let resp = http
+        .post(format!("{}/api/register", base))
+        .json(&RegisterRequest { username: "alice".to_string(), password: "secret".to_string() })
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+    let alice: RegisterResponse = resp.json().await.unwrap();
+    let resp = http
+        .post(format!("{}/api/groups", base))
+        .bearer_auth(&alice.token)
+        .json(&CreateGroupRequest { name: "general".to_string(), members: None, encrypted: false })
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+    let group_id = resp.json::<CreateGroupResponse>().await.unwrap().group.group_id;
+
+    // un client in chiaro non riesce a parlare con il listener TLS
+    assert!(reqwest::get(format!("http://{}/health", app.addr)).await.is_err());
+
+    let tcp = TcpStream::connect(app.addr).await.unwrap();
+    let tls = connector(&[&cert_der]).connect(ServerName::try_from("localhost").unwrap(), tcp).await.unwrap();
+    let url = format!("wss://localhost:{}/ws?token={}", app.addr.port(), alice.token);
+    let (mut ws, _) = tokio_tungstenite::client_async(url, tls).await.expect("wss handshake");
+    let msg = WsMessage::SendMessage(SendMessage {
+        client_msg_id: new_client_msg_id(),
+        group_id: group_id.clone(),
+        content: "ciao".to_string(),
+        sent_at: None,
+        ttl_secs: None,
+        send_at: None,
+    });
+    ws.send(Frame::Text(serde_json::to_string(&msg).unwrap())).await.unwrap();
+    loop {
+        let frame = tokio::time::timeout(Duration::from_secs(5), ws.next()).await.unwrap().unwrap().unwrap();
+        if let Frame::Text(text) = frame
+            && let WsMessage::Ack(_) = serde_json::from_str::<WsMessage>(&text).unwrap()
+        {
+            break;
+        }
+    }
+
+    // l'arresto controllato chiude anche le sessioni wss
+    app.stop.take().unwrap().send(()).unwrap();
+    let closed = tokio::time::timeout(Duration::from_secs(5), async {
+        while let Some(Ok(frame)) = ws.next().await {
+            if let Frame::Close(_) = frame {
+                return;
+            }
+        }
+    })
+    .await;
+    assert!(closed.is_ok(), "wss session not closed on shutdown");
+}
+
+// Test che verifica il ricaricamento a caldo del certificato quando i file cambiano e che file non
+// validi lascino in uso il certificato precedente
+#[tokio::test]
+async fn certificate_is_reloaded_when_files_change() {
+    let dir = TempDir::new().unwrap();
+    let (first_pem, first_key, first_der) = self_signed();
+    let (second_pem, second_key, second_der) = self_signed();
+    write_pair(dir.path(), &first_pem, &first_key);
+    let app = spawn_tls_app(dir.path()).await;
+    let client = connector(&[&first_der, &second_der]);
+    assert_eq!(served_cert(app.addr, &client).await, first_der);
+
+    // connessione aperta prima della rotazione: deve restare utilizzabile
+    let tcp = TcpStream::connect(app.addr).await.unwrap();
+    let mut open = client.connect(ServerName::try_from("localhost").unwrap(), tcp).await.unwrap();
+
+    assert!(!app.cert.reload_if_changed().unwrap(), "unchanged files must not reload");
+    app.cert.spawn_reloader(Duration::from_millis(50), app.state.shutdown.clone());
+    write_pair(dir.path(), &second_pem, &second_key);
+    let reloaded = tokio::time::timeout(Duration::from_secs(5), async {
+        while served_cert(app.addr, &client).await != second_der {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    })
+    .await;
+    assert!(reloaded.is_ok(), "certificate not reloaded");
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    open.write_all(b"GET /health HTTP/1.1\r\nHost: localhost\r\n\r\n").await.unwrap();
+    let mut buf = [0u8; 12];
+    open.read_exact(&mut buf).await.unwrap();
+    assert_eq!(&buf, b"HTTP/1.1 200");
+
+    std::fs::write(dir.path().join("key.pem"), "not a key").unwrap();
+    assert!(app.cert.reload_if_changed().is_err());
+    assert_eq!(served_cert(app.addr, &client).await, second_der);
+}
+
+// Test che verifica il listener di redirect: stesso host e percorso su https:// e porta TLS
+#[tokio::test]
+async fn redirect_listener_points_to_https() {
+    let state = AppState::new(SqliteStore::in_memory().await.unwrap().pool().clone());
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let redirect = tokio::spawn(serve_redirect(listener, 8443, state.shutdown.clone()));
+
+    let http = reqwest::Client::builder().redirect(reqwest::redirect::Policy::none()).build().unwrap();
+    let resp = http.get(format!("http://{}/api/groups?limit=5", addr)).send().await.unwrap();
+    assert_eq!(resp.status(), 308);
+    assert_eq!(resp.headers()["location"], "https://127.0.0.1:8443/api/groups?limit=5");
+
+    state.shutdown.trigger();
+    tokio::time::timeout(Duration::from_secs(5), redirect).await.unwrap().unwrap().unwrap();
+}
+
+// Test che verifica che l'arresto non attenda oltre shutdown_timeout_secs una connessione TLS con una
+// richiesta rimasta a metà
+#[tokio::test]
+async fn shutdown_does_not_wait_for_stalled_tls_connections() {
+    let dir = TempDir::new().unwrap();
+    let (cert_pem, key_pem, cert_der) = self_signed();
+    write_pair(dir.path(), &cert_pem, &key_pem);
+    let pool = SqliteStore::in_memory().await.unwrap().pool().clone();
+    let state = Arc::new(AppState::with_config(pool, Config { shutdown_timeout_secs: 1, ..Config::default() }));
+    let cert = Arc::new(ReloadingCert::load(&dir.path().join("cert.pem"), &dir.path().join("key.pem")).unwrap());
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (stop, stopped) = oneshot::channel::<()>();
+    let server = tokio::spawn(serve_tls(listener, state, cert, async move {
+        let _ = stopped.await;
+    }));
+
+    // il corpo annunciato non arriva mai: la connessione resta occupata dalla richiesta
+    let tcp = TcpStream::connect(addr).await.unwrap();
+    let mut tls = connector(&[&cert_der]).connect(ServerName::try_from("localhost").unwrap(), tcp).await.unwrap();
+    tls.write_all(b"POST /api/register HTTP/1.1\r\nhost: localhost\r\ncontent-type: application/json\r\ncontent-length: 100\r\n\r\n{")
+        .await
+        .unwrap();
+    tls.flush().await.unwrap();
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    stop.send(()).unwrap();
+    tokio::time::timeout(Duration::from_secs(5), server).await.expect("shutdown waited for the stalled connection").unwrap().unwrap();
+}
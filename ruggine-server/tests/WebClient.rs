@@ -0,0 +1,106 @@
+mod common;
+
+use common::spawn_app_with;
+use ruggine_server::web::{IMMUTABLE_CACHE, REVALIDATE_CACHE};
+use ruggine_server::{config::Config, AppState};
+use std::fs;
+use tempfile::TempDir;
+
+const INDEX: &str = "<!DOCTYPE html><main id=\"app\"></main>";
+const APP_JS: &str = "ruggine-client-web-1a2b3c4d5e6f7a8b.js";
+const APP_WASM: &str = "ruggine-client-web-1a2b3c4d5e6f7a8b_bg.wasm";
+
+// Build finta come quella prodotta da Trunk, con varianti precompresse per lo script
+fn dist() -> TempDir {
+    let dist = TempDir::new().unwrap();
+    fs::write(dist.path().join("index.html"), INDEX).unwrap();
+    fs::write(dist.path().join(APP_JS), "export default function init() {}").unwrap();
+    fs::write(dist.path().join(format!("{}.br", APP_JS)), b"brotli bytes").unwrap();
+    fs::write(dist.path().join(format!("{}.gz", APP_JS)), b"gzip bytes").unwrap();
+    fs::write(dist.path().join(APP_WASM), b"\0asm").unwrap();
+    fs::write(dist.path().join("favicon.ico"), b"ico").unwrap();
+    dist
+}
+
+fn serving(dist: &TempDir) -> impl FnOnce(sqlx::SqlitePool) -> AppState {
+    let config = Config { web_dist_dir: Some(dist.path().to_path_buf()), ..Config::default() };
+    move |pool| AppState::with_config(pool, config)
+}
+
+// Test che verifica il fallback SPA: le rotte del client ricevono index.html, i file mancanti 404
+#[tokio::test]
+async fn client_routes_fall_back_to_index() {
+    let dist = dist();
+    let app = spawn_app_with(serving(&dist)).await;
+
+    for path in ["/groups/42", "/settings", "/groups/42/"] {
+        let resp = app.http.get(app.url(path)).header("accept", "text/html,*/*;q=0.8").send().await.unwrap();
+        assert_eq!(resp.status(), 200, "{}", path);
+        assert_eq!(resp.headers()["content-type"], "text/html; charset=utf-8", "{}", path);
+        assert_eq!(resp.headers()["cache-control"], REVALIDATE_CACHE, "{}", path);
+        assert_eq!(resp.text().await.unwrap(), INDEX, "{}", path);
+    }
+    for path in ["/missing.js", "/groups/logo.png", "/api/unknown"] {
+        let resp = app.http.get(app.url(path)).header("accept", "text/html").send().await.unwrap();
+        assert_eq!(resp.status(), 404, "{}", path);
+    }
+    // una fetch di dati (niente text/html) non riceve la pagina
+    let resp = app.http.get(app.url("/settings")).header("accept", "application/json").send().await.unwrap();
+    assert_eq!(resp.status(), 404);
+}
+
+// Test che verifica la scelta della variante precompressa secondo Accept-Encoding
+#[tokio::test]
+async fn precompressed_variants_follow_accept_encoding() {
+    let dist = dist();
+    let app = spawn_app_with(serving(&dist)).await;
+    // reqwest senza le feature di decompressione non tocca il corpo
+    let path = format!("/{}", APP_JS);
+
+    for (accept, encoding, body) in [
+        ("gzip, deflate, br", Some("br"), "brotli bytes"),
+        ("gzip", Some("gzip"), "gzip bytes"),
+        ("br;q=0, gzip;q=0.5", Some("gzip"), "gzip bytes"),
+        ("identity", None, "export default function init() {}"),
+    ] {
+        let resp = app.http.get(app.url(&path)).header("accept-encoding", accept).send().await.unwrap();
+        assert_eq!(resp.status(), 200, "{}", accept);
+        let h = resp.headers();
+        assert_eq!(h["content-type"], "text/javascript; charset=utf-8", "{}", accept);
+        assert_eq!(h.get("content-encoding").map(|v| v.to_str().unwrap()), encoding, "{}", accept);
+        assert_eq!(h["vary"], "accept-encoding", "{}", accept);
+        assert_eq!(resp.text().await.unwrap(), body, "{}", accept);
+    }
+}
+
+// Test che verifica gli header di cache: immutable per i file con hash, ETag e 304 per gli altri
+#[tokio::test]
+async fn hashed_assets_are_immutable_and_others_revalidate() {
+    let dist = dist();
+    let app = spawn_app_with(serving(&dist)).await;
+
+    for name in [APP_JS, APP_WASM] {
+        let resp = app.http.get(app.url(&format!("/{}", name))).send().await.unwrap();
+        assert_eq!(resp.headers()["cache-control"], IMMUTABLE_CACHE, "{}", name);
+    }
+    let resp = app.http.get(app.url(&format!("/{}", APP_WASM))).send().await.unwrap();
+    assert_eq!(resp.headers()["content-type"], "application/wasm");
+
+    let resp = app.http.get(app.url("/favicon.ico")).send().await.unwrap();
+    assert_eq!(resp.headers()["cache-control"], REVALIDATE_CACHE);
+    assert_eq!(resp.headers()["content-type"], "image/x-icon");
+    let etag = resp.headers()["etag"].to_str().unwrap().to_string();
+    let resp = app.http.get(app.url("/favicon.ico")).header("if-none-match", &etag).send().await.unwrap();
+    assert_eq!(resp.status(), 304);
+    assert!(resp.bytes().await.unwrap().is_empty());
+
+    // se il file cambia cambia anche l'ETag
+    fs::write(dist.path().join("favicon.ico"), b"new ico").unwrap();
+    let resp = app.http.get(app.url("/favicon.ico")).header("if-none-match", &etag).send().await.unwrap();
+    assert_eq!(resp.status(), 200);
+    assert_ne!(resp.headers()["etag"], etag.as_str());
+
+    let resp = app.http.head(app.url("/")).send().await.unwrap();
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.headers()["content-length"], INDEX.len().to_string().as_str());
+}
@@ -0,0 +1,352 @@
+mod common;
+
+use axum::{body::Bytes, extract::State, http::{HeaderMap, StatusCode}, routing::post, Router};
+use common::{send_message, spawn_app_with, spawn_node, TestApp};
+use ring::hmac;
+use ruggine_core::{WebhookEvent, WebhookPayload};
+use ruggine_server::config::Config;
+use ruggine_server::webhooks::{self, WebhooksConfig};
+use ruggine_server::AppState;
+use serde_json::json;
+use std::sync::atomic::{AtomicU16, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+// Richiesta ricevuta dal finto destinatario dei webhook
+struct Received {
+    headers: HeaderMap,
+    body: Bytes,
+}
+
+// Finto destinatario dei webhook: registra le POST ricevute e risponde con lo status corrente
+struct Receiver {
+    url: String,
+    status: Arc<AtomicU16>,
+    requests: mpsc::UnboundedReceiver<Received>,
+}
+
+impl Receiver {
+    async fn start() -> Self {
+        let (tx, requests) = mpsc::unbounded_channel();
+        let status = Arc::new(AtomicU16::new(200));
+        let shared = (tx, status.clone());
+        let app = Router::new()
+            .route(
+                "/hook",
+                post(
+                    |State((tx, status)): State<(mpsc::UnboundedSender<Received>, Arc<AtomicU16>)>,
+                     headers: HeaderMap,
+                     body: Bytes| async move {
+                        let _ = tx.send(Received { headers, body });
+                        StatusCode::from_u16(status.load(Ordering::SeqCst)).unwrap()
+                    },
+                ),
+            )
+            .with_state(shared);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        Self { url, status, requests }
+    }
+
+    fn respond_with(&self, status: u16) {
+        self.status.store(status, Ordering::SeqCst);
+    }
+
+    async fn next(&mut self) -> Received {
+        tokio::time::timeout(Duration::from_secs(5), self.requests.recv()).await.expect("no webhook request").unwrap()
+    }
+}
+
+// Il finto destinatario ascolta su 127.0.0.1, che senza allow_private_targets viene rifiutato
+fn local_targets() -> WebhooksConfig {
+    WebhooksConfig { allow_private_targets: true, ..WebhooksConfig::default() }
+}
+
+async fn spawn_with(webhooks: WebhooksConfig) -> (TestApp, Config) {
+    let config = Config { webhooks, ..Config::default() };
+    let state_config = config.clone();
+    (spawn_app_with(move |pool| AppState::with_config(pool, state_config)).await, config)
+}
+
+async fn create_webhook(app: &TestApp, token: &str, group_id: &str, url: &str) -> (String, String) {
+    let res = app
+        .http
+        .post(app.url(&format!("/api/groups/{}/webhooks", group_id)))
+        .bearer_auth(token)
+        .json(&json!({ "url": url }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), 201);
+    let body: serde_json::Value = res.json().await.unwrap();
+    (body["webhook"]["webhookId"].as_str().unwrap().to_string(), body["secret"].as_str().unwrap().to_string())
+}
+
+async fn deliveries(app: &TestApp, token: &str, group_id: &str, webhook_id: &str) -> Vec<serde_json::Value> {
+    let (status, body) =
+        app.get_json(token, &format!("/api/groups/{}/webhooks/{}/deliveries", group_id, webhook_id)).await;
+    assert_eq!(status, 200);
+    body["deliveries"].as_array().unwrap().clone()
+}
+
+// Test che verifica la gestione dei webhook riservata agli admin del gruppo e che ogni messaggio arriva
+// al webhook come POST JSON firmata con HMAC-SHA256 del corpo e il segreto mostrato alla creazione
+#[tokio::test]
+async fn webhooks_are_admin_only_and_deliveries_are_signed() {
+    let (app, _) = spawn_with(local_targets()).await;
+    let mut receiver = Receiver::start().await;
+    let (alice_id, alice) = app.register("alice").await;
+    let (bob_id, bob) = app.register("bob").await;
+    let group_id = app.create_group(&alice, "g", &[bob_id.as_str()]).await;
+    let hooks_url = app.url(&format!("/api/groups/{}/webhooks", group_id));
+
+    let res = app.http.post(&hooks_url).bearer_auth(&bob).json(&json!({ "url": receiver.url })).send().await.unwrap();
+    assert_eq!(res.status(), 403);
+    assert_eq!(app.get_json(&bob, &format!("/api/groups/{}/webhooks", group_id)).await.0, 403);
+    let res = app.http.post(&hooks_url).bearer_auth(&alice).json(&json!({ "url": "ftp://example.com" })).send().await.unwrap();
+    assert_eq!(res.status(), 400);
+    let res = app.http.post(app.url("/api/groups/nope/webhooks")).bearer_auth(&alice).json(&json!({ "url": receiver.url }));
+    assert_eq!(res.send().await.unwrap().status(), 404);
+
+    let (webhook_id, secret) = create_webhook(&app, &alice, &group_id, &receiver.url).await;
+    let (status, body) = app.get_json(&alice, &format!("/api/groups/{}/webhooks", group_id)).await;
+    assert_eq!(status, 200);
+    let listed = &body["webhooks"][0];
+    assert_eq!((listed["webhookId"].as_str(), listed["enabled"].as_bool()), (Some(webhook_id.as_str()), Some(true)));
+    assert_eq!(listed["events"], json!(["message.created"]));
+    assert!(listed.get("secret").is_none(), "secret is only shown at creation");
+
+    let mut ws = app.ws_connect(&bob).await;
+    let message_id = send_message(&mut ws, &group_id, "ciao @alice").await;
+    assert_eq!(webhooks::run_once(&app.state).await.unwrap(), 1);
+    let request = receiver.next().await;
+    let signature = request.headers["x-ruggine-signature"].to_str().unwrap();
+    let tag: Vec<u8> = (0..64)
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&signature["sha256=".len()..][i..i + 2], 16).unwrap())
+        .collect();
+    let key = hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes());
+    hmac::verify(&key, &request.body, &tag).expect("valid signature");
+    assert_eq!(request.headers["x-ruggine-event"], "message.created");
+    assert_eq!(request.headers["content-type"], "application/json");
+
+    let payload: WebhookPayload = serde_json::from_slice(&request.body).unwrap();
+    assert_eq!((payload.webhook_id.as_str(), payload.event), (webhook_id.as_str(), WebhookEvent::MessageCreated));
+    assert_eq!(request.headers["x-ruggine-delivery"], payload.delivery_id.as_str());
+    let message = payload.message.unwrap();
+    assert_eq!((message.message_id.as_str(), message.content.as_str()), (message_id.as_str(), "ciao @alice"));
+    assert_eq!(message.mentions, [alice_id]);
+
+    let log = deliveries(&app, &alice, &group_id, &webhook_id).await;
+    assert_eq!(log.len(), 1);
+    assert_eq!((log[0]["status"].as_str(), log[0]["attempts"].as_u64()), (Some("delivered"), Some(1)));
+    assert_eq!(log[0]["lastStatusCode"], 200);
+    assert_eq!(webhooks::run_once(&app.state).await.unwrap(), 0);
+
+    let url = app.url(&format!("/api/groups/{}/webhooks/{}", group_id, webhook_id));
+    assert_eq!(app.http.delete(&url).bearer_auth(&bob).send().await.unwrap().status(), 403);
+    assert_eq!(app.http.delete(&url).bearer_auth(&alice).send().await.unwrap().status(), 204);
+    assert_eq!(app.http.delete(&url).bearer_auth(&alice).send().await.unwrap().status(), 404);
+    send_message(&mut ws, &group_id, "nessuno ascolta").await;
+    assert_eq!(webhooks::run_once(&app.state).await.unwrap(), 0);
+}
+
+// Test che verifica che una consegna fallita resta in coda e viene ritentata dopo l'attesa, anche da
+// un'altra istanza sullo stesso database, con lo stesso delivery id
+#[tokio::test]
+async fn failed_deliveries_are_retried_with_backoff() {
+    let (app, config) = spawn_with(WebhooksConfig { retry_base_secs: 1, ..local_targets() }).await;
+    let mut receiver = Receiver::start().await;
+    let (_, alice) = app.register("alice").await;
+    let group_id = app.create_group(&alice, "g", &[]).await;
+    let (webhook_id, _) = create_webhook(&app, &alice, &group_id, &receiver.url).await;
+
+    receiver.respond_with(500);
+    let mut ws = app.ws_connect(&alice).await;
+    send_message(&mut ws, &group_id, "riprova").await;
+    assert_eq!(webhooks::run_once(&app.state).await.unwrap(), 1);
+    let first = receiver.next().await;
+    let log = deliveries(&app, &alice, &group_id, &webhook_id).await;
+    assert_eq!((log[0]["status"].as_str(), log[0]["attempts"].as_u64()), (Some("pending"), Some(1)));
+    assert_eq!((log[0]["lastStatusCode"].as_u64(), log[0]["lastError"].as_str()), (Some(500), Some("HTTP 500 Internal Server Error")));
+    assert!(log[0]["nextAttemptAt"].is_string());
+    // non ancora arrivata al prossimo tentativo
+    assert_eq!(webhooks::run_once(&app.state).await.unwrap(), 0);
+
+    receiver.respond_with(204);
+    let node = spawn_node(&app, move |pool| AppState::with_config(pool, config)).await;
+    tokio::time::sleep(Duration::from_millis(2100)).await;
+    assert_eq!(webhooks::run_once(&node.state).await.unwrap(), 1);
+    let second = receiver.next().await;
+    assert_eq!(first.headers["x-ruggine-delivery"], second.headers["x-ruggine-delivery"]);
+    assert_eq!(first.body, second.body);
+    let log = deliveries(&node, &alice, &group_id, &webhook_id).await;
+    assert_eq!((log[0]["status"].as_str(), log[0]["attempts"].as_u64()), (Some("delivered"), Some(2)));
+    assert!(log[0].get("nextAttemptAt").is_none());
+    assert!(log[0]["finishedAt"].is_string());
+}
+
+// Test che verifica che dopo disable_after_failures tentativi falliti di fila il webhook viene
+// disabilitato e non riceve più eventi finché un admin non lo riabilita
+#[tokio::test]
+async fn webhook_is_disabled_after_repeated_failures() {
+    let (app, _) = spawn_with(WebhooksConfig { max_attempts: 1, disable_after_failures: 2, ..local_targets() }).await;
+    let mut receiver = Receiver::start().await;
+    let (_, alice) = app.register("alice").await;
+    let group_id = app.create_group(&alice, "g", &[]).await;
+    let (webhook_id, _) = create_webhook(&app, &alice, &group_id, &receiver.url).await;
+    let mut ws = app.ws_connect(&alice).await;
+
+    receiver.respond_with(503);
+    for content in ["uno", "due"] {
+        send_message(&mut ws, &group_id, content).await;
+        assert_eq!(webhooks::run_once(&app.state).await.unwrap(), 1);
+        receiver.next().await;
+    }
+    let log = deliveries(&app, &alice, &group_id, &webhook_id).await;
+    assert!(log.iter().all(|d| d["status"] == "failed" && d["attempts"] == 1), "{:?}", log);
+    let (_, body) = app.get_json(&alice, &format!("/api/groups/{}/webhooks", group_id)).await;
+    assert_eq!((body["webhooks"][0]["enabled"].as_bool(), body["webhooks"][0]["consecutiveFailures"].as_u64()), (Some(false), Some(2)));
+
+    send_message(&mut ws, &group_id, "tre").await;
+    assert_eq!(webhooks::run_once(&app.state).await.unwrap(), 0);
+    assert_eq!(deliveries(&app, &alice, &group_id, &webhook_id).await.len(), 2);
+
+    receiver.respond_with(200);
+    let url = app.url(&format!("/api/groups/{}/webhooks/{}", group_id, webhook_id));
+    let res = app.http.patch(&url).bearer_auth(&alice).json(&json!({ "enabled": true })).send().await.unwrap();
+    assert_eq!(res.status(), 200);
+    let body: serde_json::Value = res.json().await.unwrap();
+    assert_eq!((body["enabled"].as_bool(), body["consecutiveFailures"].as_u64()), (Some(true), Some(0)));
+    send_message(&mut ws, &group_id, "quattro").await;
+    assert_eq!(webhooks::run_once(&app.state).await.unwrap(), 1);
+    let payload: WebhookPayload = serde_json::from_slice(&receiver.next().await.body).unwrap();
+    assert_eq!(payload.message.unwrap().content, "quattro");
+}
+
+// Test che verifica che senza allow_private_targets gli URL verso indirizzi loopback, privati o
+// link-local sono rifiutati alla registrazione e, se l'host cambia indirizzo dopo, anche alla consegna
+#[tokio::test]
+async fn private_targets_are_rejected() {
+    for ip in ["127.0.0.1", "10.1.2.3", "172.16.0.1", "192.168.1.1", "169.254.169.254", "100.64.0.1", "0.0.0.0", "::1", "fe80::1", "fd00::1", "::ffff:127.0.0.1", "64:ff9b::a9fe:a9fe", "2002:7f00:1::"] {
+        assert!(webhooks::is_private_ip(ip.parse().unwrap()), "{}", ip);
+    }
+    for ip in ["93.184.216.34", "8.8.8.8", "2606:4700::1111", "::ffff:8.8.8.8"] {
+        assert!(!webhooks::is_private_ip(ip.parse().unwrap()), "{}", ip);
+    }
+
+    let (app, _) = spawn_with(WebhooksConfig::default()).await;
+    let mut receiver = Receiver::start().await;
+    let (_, alice) = app.register("alice").await;
+    let group_id = app.create_group(&alice, "g", &[]).await;
+    let hooks_url = app.url(&format!("/api/groups/{}/webhooks", group_id));
+    for url in [receiver.url.as_str(), "http://169.254.169.254/latest/meta-data", "http://[::1]:8080/hook", "http://localhost/hook"] {
+        let res = app.http.post(&hooks_url).bearer_auth(&alice).json(&json!({ "url": url })).send().await.unwrap();
+        assert_eq!(res.status(), 400, "{}", url);
+        assert!(res.text().await.unwrap().contains("private or local address"), "{}", url);
+    }
+
+    // registrato da un'istanza che ammette gli indirizzi locali, consegnato da una che non li ammette
+    let node = spawn_node(&app, |pool| AppState::with_config(pool, Config { webhooks: local_targets(), ..Config::default() })).await;
+    let (webhook_id, _) = create_webhook(&node, &alice, &group_id, &receiver.url).await;
+    let mut ws = app.ws_connect(&alice).await;
+    send_message(&mut ws, &group_id, "non deve uscire").await;
+    assert_eq!(webhooks::run_once(&app.state).await.unwrap(), 1);
+    let log = deliveries(&app, &alice, &group_id, &webhook_id).await;
+    assert_eq!((log[0]["status"].as_str(), log[0]["attempts"].as_u64()), (Some("pending"), Some(1)));
+    assert!(log[0]["lastError"].as_str().unwrap().contains("private or local address"), "{:?}", log[0]);
+    assert!(receiver.requests.try_recv().is_err(), "no request reaches the private address");
+}
+
+async fn create_member_webhook(app: &TestApp, token: &str, group_id: &str, url: &str) -> String {
+    let res = app
+        .http
+        .post(app.url(&format!("/api/groups/{}/webhooks", group_id)))
+        .bearer_auth(token)
+        .json(&json!({ "url": url, "events": ["member.joined", "member.left"] }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), 201);
+    let body: serde_json::Value = res.json().await.unwrap();
+    body["webhook"]["webhookId"].as_str().unwrap().to_string()
+}
+
+// Test che verifica che un utente aggiunto al gruppo da un admin arriva ai webhook iscritti a member.joined
+// con i suoi dati, e che i messaggi non arrivano ai webhook che non sono iscritti a message.created
+#[tokio::test]
+async fn member_joined_is_delivered() {
+    let (app, _) = spawn_with(local_targets()).await;
+    let mut receiver = Receiver::start().await;
+    let (_, alice) = app.register("alice").await;
+    let (bob_id, bob) = app.register("bob").await;
+    let (carol_id, _) = app.register("carol").await;
+    let group_id = app.create_group(&alice, "g", &[]).await;
+    let webhook_id = create_member_webhook(&app, &alice, &group_id, &receiver.url).await;
+    let members_url = app.url(&format!("/api/groups/{}/members", group_id));
+
+    let res = app.http.post(&members_url).bearer_auth(&bob).json(&json!({ "userId": carol_id })).send().await.unwrap();
+    assert_eq!(res.status(), 403);
+    let res = app.http.post(&members_url).bearer_auth(&alice).json(&json!({ "userId": "nope" })).send().await.unwrap();
+    assert_eq!(res.status(), 400);
+    let res = app.http.post(&members_url).bearer_auth(&alice).json(&json!({ "userId": bob_id })).send().await.unwrap();
+    assert_eq!(res.status(), 204);
+    let res = app.http.post(&members_url).bearer_auth(&alice).json(&json!({ "userId": bob_id })).send().await.unwrap();
+    assert_eq!(res.status(), 409);
+    assert_eq!(app.get_json(&bob, &format!("/api/groups/{}/messages", group_id)).await.0, 200);
+
+    assert_eq!(webhooks::run_once(&app.state).await.unwrap(), 1);
+    let request = receiver.next().await;
+    assert_eq!(request.headers["x-ruggine-event"], "member.joined");
+    let payload: WebhookPayload = serde_json::from_slice(&request.body).unwrap();
+    assert_eq!((payload.webhook_id.as_str(), payload.event), (webhook_id.as_str(), WebhookEvent::MemberJoined));
+    let member = payload.member.unwrap();
+    assert_eq!((member.user_id.as_str(), member.username.as_str()), (bob_id.as_str(), "bob"));
+    assert!(payload.message.is_none());
+    let log = deliveries(&app, &alice, &group_id, &webhook_id).await;
+    assert_eq!((log[0]["userId"].as_str(), log[0]["status"].as_str()), (Some(bob_id.as_str()), Some("delivered")));
+    assert!(log[0].get("messageId").is_none());
+
+    let mut ws = app.ws_connect(&bob).await;
+    send_message(&mut ws, &group_id, "non interessa al webhook").await;
+    assert_eq!(webhooks::run_once(&app.state).await.unwrap(), 0);
+}
+
+// Test che verifica che un membro che esce dal gruppo e uno rimosso da un admin arrivano ai webhook
+// iscritti a member.left, che solo gli admin possono rimuovere altri membri e che l'ultimo admin resta
+#[tokio::test]
+async fn member_left_is_delivered() {
+    let (app, _) = spawn_with(local_targets()).await;
+    let mut receiver = Receiver::start().await;
+    let (alice_id, alice) = app.register("alice").await;
+    let (bob_id, bob) = app.register("bob").await;
+    let (carol_id, _) = app.register("carol").await;
+    let group_id = app.create_group(&alice, "g", &[bob_id.as_str(), carol_id.as_str()]).await;
+    create_member_webhook(&app, &alice, &group_id, &receiver.url).await;
+    let member_url = |user_id: &str| app.url(&format!("/api/groups/{}/members/{}", group_id, user_id));
+
+    assert_eq!(app.http.delete(member_url(&carol_id)).bearer_auth(&bob).send().await.unwrap().status(), 403);
+    assert_eq!(app.http.delete(member_url(&bob_id)).bearer_auth(&bob).send().await.unwrap().status(), 204);
+    assert_eq!(app.get_json(&bob, &format!("/api/groups/{}/messages", group_id)).await.0, 403);
+    assert_eq!(app.http.delete(member_url(&carol_id)).bearer_auth(&alice).send().await.unwrap().status(), 204);
+    assert_eq!(app.http.delete(member_url(&carol_id)).bearer_auth(&alice).send().await.unwrap().status(), 404);
+    // l'unico admin non può lasciare il gruppo
+    assert_eq!(app.http.delete(member_url(&alice_id)).bearer_auth(&alice).send().await.unwrap().status(), 409);
+    assert_eq!(app.get_json(&alice, &format!("/api/groups/{}/messages", group_id)).await.0, 200);
+
+    assert_eq!(webhooks::run_once(&app.state).await.unwrap(), 2);
+    let mut left = Vec::new();
+    for _ in 0..2 {
+        let request = receiver.next().await;
+        assert_eq!(request.headers["x-ruggine-event"], "member.left");
+        let payload: WebhookPayload = serde_json::from_slice(&request.body).unwrap();
+        assert_eq!(payload.event, WebhookEvent::MemberLeft);
+        left.push(payload.member.unwrap().user_id);
+    }
+    left.sort();
+    let mut expected = vec![bob_id, carol_id];
+    expected.sort();
+    assert_eq!(left, expected);
+}
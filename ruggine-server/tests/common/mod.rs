@@ -0,0 +1,224 @@
+// Utilità condivise dai test di integrazione: avvio del server su una porta libera,
+// registrazione utenti, creazione gruppi e client WebSocket.
+#![allow(dead_code)]
+
+use futures_util::{SinkExt, StreamExt};
+use ruggine_core::{
+    new_client_msg_id, Ack, AckStatus, CreateGroupRequest, CreateGroupResponse, RegisterRequest, RegisterResponse, SendMessage,
+    WsMessage,
+};
+use ruggine_server::store::SqliteStore;
+use ruggine_server::{connect_pool, run_migrations, serve, sqlite_url_for_path, AppState};
+use sqlx::SqlitePool;
+use std::sync::Arc;
+use std::time::Duration;
+use tempfile::TempDir;
+use tokio::net::TcpStream;
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+use tokio_tungstenite::{tungstenite::Message as Frame, MaybeTlsStream, WebSocketStream};
+
+pub type Ws = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+pub struct TestApp {
+    pub addr: String,
+    pub pool: SqlitePool,
+    pub state: Arc<AppState>,
+    pub http: reqwest::Client,
+    stop: Option<oneshot::Sender<()>>,
+    server: Option<JoinHandle<anyhow::Result<()>>>,
+    _dir: Option<TempDir>,
+}
+
+/// Avvia il server su un DB in memoria e una porta scelta dal sistema operativo.
+pub async fn spawn_app() -> TestApp {
+    spawn_app_with(AppState::new).await
+}
+
+/// Come spawn_app, ma lo stato viene costruito dal chiamante (es. per configurare i limiti).
+pub async fn spawn_app_with(make_state: impl FnOnce(SqlitePool) -> AppState) -> TestApp {
+    let store = SqliteStore::in_memory().await.expect("in-memory store");
+    start(store.pool().clone(), None, make_state).await
+}
+
+/// Come spawn_app_with, ma su un file SQLite in una directory temporanea (per i test che
+/// riguardano il filesystem del database).
+pub async fn spawn_app_on_disk_with(make_state: impl FnOnce(SqlitePool) -> AppState) -> TestApp {
+    let dir = TempDir::new().expect("tempdir");
+    let url = sqlite_url_for_path(&dir.path().join("ruggine.db")).expect("sqlite url");
+    let pool = connect_pool(&url).await.expect("connect");
+    run_migrations(&pool).await.expect("migrations");
+    start(pool, Some(dir), make_state).await
+}
+
+/// Avvia un'altra istanza del server sullo stesso database di `app`, come un secondo nodo
+/// dietro un load balancer.
+pub async fn spawn_node(app: &TestApp, make_state: impl FnOnce(SqlitePool) -> AppState) -> TestApp {
+    start(app.pool.clone(), None, make_state).await
+}
+
+async fn start(pool: SqlitePool, dir: Option<TempDir>, make_state: impl FnOnce(SqlitePool) -> AppState) -> TestApp {
+    let state = Arc::new(make_state(pool.clone()));
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.expect("bind");
+    let addr = listener.local_addr().expect("local addr").to_string();
+    let (stop, stopped) = oneshot::channel::<()>();
+    let server = tokio::spawn(serve(listener, state.clone(), async move {
+        let _ = stopped.await;
+    }));
+    TestApp {
+        addr,
+        pool,
+        state,
+        http: reqwest::Client::new(),
+        stop: Some(stop),
+        server: Some(server),
+        _dir: dir,
+    }
+}
+
+impl TestApp {
+    /// Avvia l'arresto controllato (come alla ricezione di SIGTERM).
+    pub fn begin_shutdown(&mut self) {
+        if let Some(stop) = self.stop.take() {
+            let _ = stop.send(());
+        }
+    }
+
+    /// Attende che il server abbia completato l'arresto (fallisce dopo 10 secondi).
+    pub async fn wait_stopped(&mut self) {
+        let server = self.server.take().expect("server already stopped");
+        tokio::time::timeout(Duration::from_secs(10), server)
+            .await
+            .expect("timeout waiting for shutdown")
+            .expect("server task")
+            .expect("serve");
+    }
+
+    pub fn url(&self, path: &str) -> String {
+        format!("http://{}{}", self.addr, path)
+    }
+
+    /// Registra un utente e ritorna (user_id, token).
+    pub async fn register(&self, username: &str) -> (String, String) {
+        let resp = self
+            .http
+            .post(self.url("/api/register"))
+            .json(&RegisterRequest { username: username.to_string(), password: "secret".to_string() })
+            .send()
+            .await
+            .expect("register request");
+        assert_eq!(resp.status(), 201, "register {}", username);
+        let body: RegisterResponse = resp.json().await.expect("register body");
+        (body.user.user_id, body.token)
+    }
+
+    /// Crea un gruppo con i membri indicati (oltre al creatore) e ritorna il group_id.
+    pub async fn create_group(&self, token: &str, name: &str, members: &[&str]) -> String {
+        self.create_group_with(token, name, members, false).await
+    }
+
+    /// Come create_group, indicando se il gruppo è cifrato end-to-end.
+    pub async fn create_group_with(&self, token: &str, name: &str, members: &[&str], encrypted: bool) -> String {
+        let resp = self
+            .http
+            .post(self.url("/api/groups"))
+            .bearer_auth(token)
+            .json(&CreateGroupRequest {
+                name: name.to_string(),
+                members: Some(members.iter().map(|m| m.to_string()).collect()),
+                encrypted,
+            })
+            .send()
+            .await
+            .expect("create group request");
+        assert_eq!(resp.status(), 201, "create group {}", name);
+        let body: CreateGroupResponse = resp.json().await.expect("create group body");
+        body.group.group_id
+    }
+
+    /// GET autenticato, ritorna status e body JSON.
+    pub async fn get_json(&self, token: &str, path: &str) -> (u16, serde_json::Value) {
+        let resp = self.http.get(self.url(path)).bearer_auth(token).send().await.expect("get request");
+        let status = resp.status().as_u16();
+        (status, resp.json().await.unwrap_or(serde_json::Value::Null))
+    }
+
+    pub async fn ws_connect(&self, token: &str) -> Ws {
+        let (ws, _) = tokio_tungstenite::connect_async(format!("ws://{}/ws?token={}", self.addr, token))
+            .await
+            .expect("ws connect");
+        ws
+    }
+
+    /// Come ws_connect, indicando l'ultimo seq visto per gruppo (recupero dei messaggi persi).
+    pub async fn ws_connect_since(&self, token: &str, since: &str) -> Ws {
+        let url = format!("ws://{}/ws?token={}&{}={}", self.addr, token, ruggine_core::SINCE_PARAM, since);
+        let (ws, _) = tokio_tungstenite::connect_async(url).await.expect("ws connect");
+        ws
+    }
+}
+
+pub async fn ws_send(ws: &mut Ws, msg: &WsMessage) {
+    let text = serde_json::to_string(msg).expect("serialize");
+    ws.send(Frame::Text(text)).await.expect("ws send");
+}
+
+/// Attende il prossimo WsMessage (fallisce dopo 5 secondi).
+pub async fn ws_recv(ws: &mut Ws) -> WsMessage {
+    loop {
+        let frame = tokio::time::timeout(Duration::from_secs(5), ws.next())
+            .await
+            .expect("timeout waiting for ws message")
+            .expect("ws closed")
+            .expect("ws error");
+        if let Frame::Text(text) = frame {
+            return serde_json::from_str(&text).expect("valid WsMessage");
+        }
+    }
+}
+
+/// Attende il prossimo messaggio che soddisfa il predicato, scartando gli altri.
+pub async fn ws_recv_until(ws: &mut Ws, pred: impl Fn(&WsMessage) -> bool) -> WsMessage {
+    loop {
+        let msg = ws_recv(ws).await;
+        if pred(&msg) {
+            return msg;
+        }
+    }
+}
+
+/// SendMessage per il gruppo con un client_msg_id nuovo, senza ttl né invio programmato.
+pub fn new_message(group_id: &str, content: &str) -> SendMessage {
+    SendMessage {
+        client_msg_id: new_client_msg_id(),
+        group_id: group_id.to_string(),
+        content: content.to_string(),
+        sent_at: None,
+        ttl_secs: None,
+        send_at: None,
+    }
+}
+
+/// Invia il messaggio e attende il suo Ack, scartando gli altri eventi ricevuti nel frattempo.
+pub async fn send_and_ack(ws: &mut Ws, sm: SendMessage) -> Ack {
+    let client_msg_id = sm.client_msg_id.clone();
+    ws_send(ws, &WsMessage::SendMessage(sm)).await;
+    match ws_recv_until(ws, |m| matches!(m, WsMessage::Ack(a) if a.in_reply_to == client_msg_id)).await {
+        WsMessage::Ack(ack) => ack,
+        _ => unreachable!(),
+    }
+}
+
+/// Invia un messaggio nel gruppo, verifica che sia accettato e ritorna il suo message_id.
+pub async fn send_message(ws: &mut Ws, group_id: &str, content: &str) -> String {
+    let ack = send_and_ack(ws, new_message(group_id, content)).await;
+    assert_eq!(ack.status, AckStatus::Ok, "{:?}", ack.error);
+    ack.message_id.expect("message id")
+}
+
+/// Verifica che non arrivino messaggi entro il tempo indicato.
+pub async fn ws_expect_silence(ws: &mut Ws, millis: u64) {
+    if let Ok(Some(Ok(Frame::Text(text)))) = tokio::time::timeout(Duration::from_millis(millis), ws.next()).await {
+        panic!("unexpected ws message: {}", text);
+    }
+}